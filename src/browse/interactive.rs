@@ -2,10 +2,9 @@
 
 use anyhow::Result;
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEventKind},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
 };
+use futures::stream::{self, StreamExt, TryStreamExt};
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     prelude::*,
@@ -14,14 +13,31 @@ use ratatui::{
     widgets::{Block, Borders, Gauge, List, ListItem, ListState, Paragraph, Wrap},
     Frame, Terminal,
 };
-use std::collections::HashSet;
+use chrono::{DateTime, Utc};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 use std::io;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
-use tracing::debug;
-
-use crate::device::{Device, DeviceDetector, SyncManifest, UnmountedDevice};
-use crate::subsonic::{Album, Artist, Playlist, SubsonicClient, SyncSelection};
-use crate::sync::{DeletionSelection, SyncEngine, SyncProgress as SyncProgressEvent};
+use tracing::{debug, warn};
+
+use super::keys::{Action, KeyMap};
+use super::terminal::{interrupt, TerminalGuard};
+use crate::config::{LastSession, SessionView, Settings};
+use crate::device::layout::LayoutTemplate;
+use crate::device::{Device, DeviceDetector, SyncManifest, SyncedAlbum, SyncedPlaylist, UnmountedDevice};
+use crate::subsonic::{Album, AlbumSource, Artist, Genre, Playlist, SearchResult, Song, SubsonicClient, SyncSelection};
+use crate::sync::downloader::Transcode;
+use crate::sync::{DeletionSelection, InsufficientSpace, SyncEngine, SyncOptions, SyncProgress as SyncProgressEvent};
+use crate::utils::fuzzy::fuzzy_score;
+use crate::utils::size::estimated_size;
+use crate::utils::text::format_row;
+
+/// Maximum number of concurrent `getArtist` requests made by the album prefetch
+const PREFETCH_CONCURRENCY: usize = 4;
+
+/// Results of each type fetched by a server search
+const SERVER_SEARCH_LIMIT: usize = 100;
 
 /// Current view in the browser
 #[derive(Debug, Clone, PartialEq)]
@@ -31,17 +47,151 @@ pub enum BrowseView {
     AlbumTracks { album: Album },
     Playlists,
     PlaylistTracks { playlist: Playlist },
+    /// Genres in the library
+    Genres,
+    /// Albums tagged with a genre
+    GenreAlbums { genre: String },
+    /// Folder tree of a file-based library; `path` is the (id, name) of each
+    /// folder from the top level down, empty for the top-level folders
+    Folders { path: Vec<(String, String)> },
+    /// Everything currently selected, across artists, folders and playlists
+    Selection,
+    /// Artists, albums and songs found by a server-side search
+    SearchResults { query: String },
     DeviceSelection,
     SyncConfirmation,
     SyncProgress,
 }
 
+/// Subfolder or song shown in the folder view
+#[derive(Debug, Clone)]
+struct FolderEntry {
+    id: String,
+    name: String,
+    is_dir: bool,
+    duration: Option<u32>,
+}
+
+/// Album or playlist listed in the selection view, by ID
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SelectedItem {
+    Album(String),
+    Playlist(String),
+}
+
+/// Row of the server search results view
+#[derive(Debug, Clone)]
+enum SearchHit {
+    Artist(Artist),
+    Album(Album),
+    Song(Song),
+}
+
+/// View left by opening an item in it, to go back to
+#[derive(Debug, Clone)]
+struct NavEntry {
+    view: BrowseView,
+    /// Item the cursor was on (see `cursor_item_id`)
+    cursor: Option<String>,
+}
+
+/// Whether an album or playlist is on the active device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SyncStatus {
+    NotSynced,
+    Synced,
+    /// Synced with only some of the album's tracks picked
+    Partial,
+    /// Synced, but tracks are missing or the server's track count changed since
+    Outdated,
+}
+
+impl SyncStatus {
+    /// Status column text
+    fn label(self) -> &'static str {
+        match self {
+            Self::NotSynced => "",
+            Self::Synced => "[SYNCED]",
+            Self::Partial => "[PARTIAL]",
+            Self::Outdated => "[OUTDATED]",
+        }
+    }
+
+    /// Row color, unless the row is selected
+    fn color(self) -> Option<Color> {
+        match self {
+            Self::NotSynced => None,
+            Self::Synced | Self::Partial => Some(Color::Cyan),
+            Self::Outdated => Some(Color::Yellow),
+        }
+    }
+
+    /// On the device as selected, needing no sync
+    fn is_current(self) -> bool {
+        matches!(self, Self::Synced | Self::Partial)
+    }
+}
+
+/// Row of the selection view
+struct SelectionRow {
+    item: SelectedItem,
+    label: String,
+    duration: Option<u32>,
+}
+
+/// Order of the artists, albums and playlists lists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum SortMode {
+    /// As returned by the server
+    #[default]
+    Server,
+    Name,
+    /// Albums oldest first, undated ones last; artists and playlists by name
+    Year,
+    /// Most tracks first (most albums for artists)
+    TrackCount,
+    /// Newest on the server first; artists by name
+    RecentlyAdded,
+}
+
+impl SortMode {
+    /// Mode after this one when cycling with `o`
+    fn next(self) -> Self {
+        match self {
+            Self::Server | Self::RecentlyAdded => Self::Name,
+            Self::Name => Self::Year,
+            Self::Year => Self::TrackCount,
+            Self::TrackCount => Self::RecentlyAdded,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Server => "server",
+            Self::Name => "name",
+            Self::Year => "year",
+            Self::TrackCount => "tracks",
+            Self::RecentlyAdded => "recent",
+        }
+    }
+}
+
+/// Fresh library data fetched by a background refresh
+enum LibraryRefresh {
+    Artists(Vec<Artist>),
+    Albums { artist_id: String, albums: Vec<Album> },
+    Playlists(Vec<Playlist>),
+    Failed(String),
+}
+
 /// Progress info for syncing
 #[derive(Debug, Clone, Default)]
 pub struct SyncProgressInfo {
     pub current_album: String,
     pub current_artist: String,
+    /// Albums finished (synced or failed) out of `albums_total`
     pub albums_completed: usize,
+    /// Albums that need downloading; already-synced ones aren't counted
     pub albums_total: usize,
     pub tracks_completed: usize,
     pub tracks_total: usize,
@@ -49,13 +199,90 @@ pub struct SyncProgressInfo {
     pub is_complete: bool,
     pub error: Option<String>,
     pub log_messages: Vec<String>,
+    /// Albums that failed to sync (id, "artist - album")
+    pub failed_albums: Vec<(String, String)>,
+    /// Playlists that failed to sync (id, name)
+    pub failed_playlists: Vec<(String, String)>,
+    /// When the downloads started
+    pub started_at: Option<Instant>,
+    /// Expected download size, when the engine could size the selection
+    pub estimated_bytes: Option<u64>,
+    /// Recent (time, bytes downloaded) samples the current rate is taken from
+    rate_samples: VecDeque<(Instant, u64)>,
+}
+
+/// How far back the current download rate looks
+const RATE_WINDOW: Duration = Duration::from_secs(10);
+
+impl SyncProgressInfo {
+    fn failure_count(&self) -> usize {
+        self.failed_albums.len() + self.failed_playlists.len()
+    }
+
+    /// Start timing the downloads
+    fn start_clock(&mut self, now: Instant) {
+        self.started_at = Some(now);
+        self.bytes_downloaded = 0;
+        self.rate_samples = VecDeque::from([(now, 0)]);
+    }
+
+    /// Count a downloaded track towards the throughput
+    fn record_bytes(&mut self, bytes: u64, now: Instant) {
+        self.bytes_downloaded += bytes;
+        self.rate_samples.push_back((now, self.bytes_downloaded));
+        // Keep one sample from before the window to measure from
+        while self.rate_samples.len() > 1 && now.duration_since(self.rate_samples[1].0) > RATE_WINDOW {
+            self.rate_samples.pop_front();
+        }
+    }
+
+    /// Download rate over the last few seconds, in bytes per second
+    fn current_rate(&self, now: Instant) -> Option<f64> {
+        let &(since, base) = self.rate_samples.front()?;
+        let elapsed = now.duration_since(since).as_secs_f64();
+        (elapsed > 0.0).then(|| (self.bytes_downloaded - base) as f64 / elapsed)
+    }
+
+    /// Download rate since the sync started, in bytes per second
+    fn average_rate(&self, now: Instant) -> Option<f64> {
+        let elapsed = now.duration_since(self.started_at?).as_secs_f64();
+        (elapsed > 0.0).then(|| self.bytes_downloaded as f64 / elapsed)
+    }
+
+    /// Time left, from the bytes still to download or else the albums left
+    fn eta(&self, now: Instant) -> Option<Duration> {
+        if let Some(total) = self.estimated_bytes
+            && let Some(rate) = self.current_rate(now).filter(|&r| r > 0.0)
+        {
+            let remaining = total.saturating_sub(self.bytes_downloaded);
+            return Some(Duration::from_secs_f64(remaining as f64 / rate));
+        }
+        if self.albums_completed == 0 {
+            return None;
+        }
+        let elapsed = now.duration_since(self.started_at?);
+        let remaining = self.albums_total.saturating_sub(self.albums_completed);
+        Some(elapsed.mul_f64(remaining as f64 / self.albums_completed as f64))
+    }
+
+    /// Rate and time left for the sync header, e.g. `2.4 MB/s, ETA 3:10`
+    fn throughput_label(&self, now: Instant) -> Option<String> {
+        let rate = self.current_rate(now)?;
+        let mut label = format!("{:.1} MB/s", rate / 1_048_576.0);
+        if let Some(eta) = self.eta(now) {
+            label.push_str(&format!(", ETA {}", format_duration(eta.as_secs().min(u64::from(u32::MAX)) as u32)));
+        }
+        Some(label)
+    }
 }
 
 /// Result from the browser - either just a selection or a selection + device
 #[derive(Debug)]
 pub enum BrowseResult {
+    /// Everything selected, including what's already on a device, since
+    /// `nutune sync` deletes whatever the selection leaves out
     SelectionOnly(SyncSelection),
-    SyncToDevice { selection: SyncSelection, device: Device },
+    SyncToDevice { selection: SyncSelection, device: Box<Device> },
 }
 
 /// Browser state
@@ -68,6 +295,11 @@ struct BrowserState {
     unmounted_devices: Vec<UnmountedDevice>,
     list_state: ListState,
     selected_albums: HashSet<String>,
+    /// Songs picked from partly selected albums, by album ID; selected albums
+    /// without an entry are selected whole
+    picked_tracks: BTreeMap<String, BTreeSet<String>>,
+    /// Songs of the album shown in the album tracks view
+    album_songs: Vec<Song>,
     selected_playlists: HashSet<String>,
     /// Artists with all albums selected (for display purposes)
     selected_artists: HashSet<String>,
@@ -75,29 +307,84 @@ struct BrowserState {
     artist_album_ids: std::collections::HashMap<String, Vec<String>>,
     /// Cache of Album objects by ID for selection building
     album_cache: std::collections::HashMap<String, Album>,
+    /// Playlists from a device's saved selection, for when the playlists
+    /// haven't been loaded yet
+    playlist_cache: std::collections::HashMap<String, Playlist>,
+    /// Entries of the folder currently shown in the folder view
+    folder_entries: Vec<FolderEntry>,
+    /// Top-level folders, fetched once
+    folder_roots: Vec<FolderEntry>,
+    /// Album IDs found under each folder that has been selected (folder ID -> album IDs)
+    folder_album_ids: std::collections::HashMap<String, Vec<String>>,
+    /// Genres in the library, fetched once
+    genres: Vec<Genre>,
+    /// Album IDs of each genre that has been fetched (genre -> album IDs)
+    genre_album_ids: std::collections::HashMap<String, Vec<String>>,
+    /// View to go back to when leaving the genres view
+    genres_return: Option<BrowseView>,
     status_message: String,
     /// When the status message was set (for auto-clear timeout)
     status_message_time: Option<std::time::Instant>,
+    /// Whether the status message is an error (kept until a key is pressed)
+    status_is_error: bool,
+    /// How long informational status messages stay up (`None` keeps them)
+    status_timeout: Option<std::time::Duration>,
+    /// Albums the sync downloads at once (`browse --album-parallel`)
+    album_parallel: usize,
+    /// Key bindings (keys.toml or the defaults)
+    keys: KeyMap,
     sync_progress: SyncProgressInfo,
     selected_device: Option<Device>,
     /// Receiver for sync progress events
     progress_rx: Option<mpsc::Receiver<SyncProgressEvent>>,
+    /// Receiver for background album prefetch results (artist ID, albums)
+    prefetch_rx: Option<mpsc::Receiver<(String, Vec<Album>)>>,
+    /// Receiver for a background library refresh of the current view
+    refresh_rx: Option<mpsc::Receiver<LibraryRefresh>>,
     /// Selection being synced
     sync_selection: Option<SyncSelection>,
     /// Deletions pending for sync
     pending_deletions: Option<DeletionSelection>,
+    /// Confirmation plan entries toggled off (indices into `plan_entries`)
+    plan_excluded: HashSet<usize>,
+    /// Confirming the plan only reports what it would do, leaving the device untouched
+    plan_preview: bool,
     /// Albums already synced to device (from manifest)
     synced_album_ids: HashSet<String>,
+    /// Songs on the device of albums synced partly, by album ID (from manifest)
+    synced_picks: BTreeMap<String, BTreeSet<String>>,
     /// Playlists already synced to device (from manifest)
     synced_playlist_ids: HashSet<String>,
+    /// Manifest entries of the synced albums and playlists, by ID, for
+    /// spotting ones that changed on the server since
+    synced_album_entries: std::collections::HashMap<String, SyncedAlbum>,
+    synced_playlist_entries: std::collections::HashMap<String, SyncedPlaylist>,
     /// Active device for sync status display
     active_device: Option<Device>,
+    /// Estimated bytes the selection still needs on each mounted device, by UUID
+    space_needed: std::collections::HashMap<String, u64>,
     /// Search/filter mode
     search_mode: bool,
     /// Current search query
     search_query: String,
+    /// Match the search query as a plain substring instead of fuzzily
+    exact_search: bool,
+    /// The search prompt queries the server instead of filtering the current list
+    server_search: bool,
+    /// Results of the last server search
+    search_hits: Vec<SearchHit>,
+    /// View to go back to when leaving the search results
+    search_return: Option<BrowseView>,
     /// Filtered indices (maps display index to original index)
     filtered_indices: Vec<usize>,
+    /// Hide albums and playlists already on the device
+    hide_synced: bool,
+    /// View to go back to when leaving the selection view
+    selection_return: Option<BrowseView>,
+    /// Views that items were opened from, innermost last
+    nav_stack: Vec<NavEntry>,
+    /// Order of the artists, albums and playlists lists
+    sort_mode: SortMode,
     /// Show help overlay
     show_help: bool,
 }
@@ -116,46 +403,295 @@ impl BrowserState {
             unmounted_devices: Vec::new(),
             list_state,
             selected_albums: HashSet::new(),
+            picked_tracks: BTreeMap::new(),
+            album_songs: Vec::new(),
             selected_playlists: HashSet::new(),
             selected_artists: HashSet::new(),
             artist_album_ids: std::collections::HashMap::new(),
             album_cache: std::collections::HashMap::new(),
+            playlist_cache: std::collections::HashMap::new(),
+            folder_entries: Vec::new(),
+            folder_roots: Vec::new(),
+            folder_album_ids: std::collections::HashMap::new(),
+            genres: Vec::new(),
+            genre_album_ids: std::collections::HashMap::new(),
+            genres_return: None,
             status_message: String::new(),
             status_message_time: None,
+            status_is_error: false,
+            status_timeout: Settings::default().status_timeout(),
+            album_parallel: 1,
+            keys: KeyMap::default(),
             sync_progress: SyncProgressInfo::default(),
             selected_device: None,
             progress_rx: None,
+            prefetch_rx: None,
+            refresh_rx: None,
             sync_selection: None,
             pending_deletions: None,
+            plan_excluded: HashSet::new(),
+            plan_preview: false,
             synced_album_ids: HashSet::new(),
+            synced_picks: BTreeMap::new(),
             synced_playlist_ids: HashSet::new(),
+            synced_album_entries: std::collections::HashMap::new(),
+            synced_playlist_entries: std::collections::HashMap::new(),
             active_device: None,
+            space_needed: std::collections::HashMap::new(),
             search_mode: false,
+            exact_search: false,
+            server_search: false,
+            search_hits: Vec::new(),
+            search_return: None,
             search_query: String::new(),
             filtered_indices: Vec::new(),
+            hide_synced: false,
+            selection_return: None,
+            nav_stack: Vec::new(),
+            sort_mode: SortMode::default(),
             show_help: false,
         }
     }
 
+    /// Estimate how much space the current selection needs on each mounted device
+    ///
+    /// Content already in a device's manifest doesn't count against it. Sizes
+    /// are estimated from durations since the server doesn't report album sizes.
+    fn update_space_needed(&mut self) {
+        self.space_needed = self
+            .mounted_devices
+            .iter()
+            .map(|device| {
+                let manifest = SyncManifest::load(&device.mount_point).ok().flatten();
+                let albums: u64 = self
+                    .selected_albums
+                    .iter()
+                    .filter(|id| !manifest.as_ref().is_some_and(|m| m.is_album_synced(id)))
+                    .filter_map(|id| self.album_cache.get(id))
+                    .map(|a| estimated_size(a.duration.unwrap_or(0)))
+                    .sum();
+                let playlists: u64 = self
+                    .playlists
+                    .iter()
+                    .filter(|p| self.selected_playlists.contains(&p.id))
+                    .filter(|p| !manifest.as_ref().is_some_and(|m| m.is_playlist_synced(&p.id)))
+                    .map(|p| estimated_size(p.duration.unwrap_or(0)))
+                    .sum();
+                (device.uuid.clone(), albums + playlists)
+            })
+            .collect();
+    }
+
+    /// Whether the selection likely won't fit on a device
+    fn too_small(&self, device: &Device) -> bool {
+        self.space_needed
+            .get(&device.uuid)
+            .is_some_and(|&needed| needed > device.free_space)
+    }
+
+    /// Select all album folders under a folder, or deselect them if all are selected
+    fn toggle_folder_selection(&mut self, folder_id: &str) {
+        let Some(album_ids) = self.folder_album_ids.get(folder_id) else {
+            return;
+        };
+        if album_ids.is_empty() {
+            self.set_status("No tracks in this folder");
+            return;
+        }
+
+        if album_ids.iter().all(|id| self.selected_albums.contains(id)) {
+            for id in album_ids {
+                self.selected_albums.remove(id);
+            }
+        } else {
+            self.selected_albums.extend(album_ids.iter().cloned());
+        }
+    }
+
+    /// Record a genre's albums in the lookup caches used for selection
+    fn cache_genre_albums(&mut self, genre: &str, albums: &[Album]) {
+        let album_ids: Vec<String> = albums.iter().map(|a| a.id.clone()).collect();
+        self.genre_album_ids.insert(genre.to_string(), album_ids);
+        for album in albums {
+            self.album_cache.insert(album.id.clone(), album.clone());
+        }
+    }
+
+    /// Whether every album of a fetched genre is selected
+    fn is_genre_selected(&self, genre: &str) -> bool {
+        self.genre_album_ids
+            .get(genre)
+            .is_some_and(|ids| !ids.is_empty() && ids.iter().all(|id| self.selected_albums.contains(id)))
+    }
+
+    /// Select or deselect all albums of a genre whose albums have been fetched
+    fn set_genre_selected(&mut self, genre: &str, selected: bool) {
+        let Some(album_ids) = self.genre_album_ids.get(genre) else {
+            return;
+        };
+        if album_ids.is_empty() {
+            self.set_status(format!("No albums tagged {}", genre));
+            return;
+        }
+
+        if selected {
+            self.selected_albums.extend(album_ids.iter().cloned());
+        } else {
+            for id in album_ids {
+                self.selected_albums.remove(id);
+            }
+        }
+        self.update_artist_selection_status();
+    }
+
+    /// Genre under the cursor in the genres view
+    fn cursor_genre(&self) -> Option<String> {
+        let idx = self.get_actual_index(self.list_state.selected().unwrap_or(0));
+        match self.view {
+            BrowseView::Genres => self.genres.get(idx).map(|g| g.value.clone()),
+            _ => None,
+        }
+    }
+
+    /// Show the genres view, remembering the view to come back to
+    fn show_genres(&mut self) {
+        let previous = std::mem::replace(&mut self.view, BrowseView::Genres);
+        if !matches!(previous, BrowseView::Genres | BrowseView::GenreAlbums { .. }) {
+            self.genres_return = Some(previous);
+        }
+        self.clear_filter();
+        self.list_state.select(Some(0));
+    }
+
+    /// Leave the genres view for the view it was opened from
+    fn close_genres(&mut self) {
+        self.view = self.genres_return.take().unwrap_or(BrowseView::Artists);
+        self.clear_filter();
+        self.list_state.select(Some(0));
+    }
+
+    /// Show the albums of a fetched genre
+    fn show_genre_albums(&mut self, genre: String) {
+        self.albums = self.cached_albums(self.genre_album_ids.get(&genre));
+        self.view = BrowseView::GenreAlbums { genre };
+        self.sort_current_view();
+        self.clear_filter();
+        self.list_state.select(Some(0));
+    }
+
+    /// Cached albums with the given IDs, in that order
+    fn cached_albums(&self, ids: Option<&Vec<String>>) -> Vec<Album> {
+        ids.into_iter()
+            .flatten()
+            .filter_map(|id| self.album_cache.get(id).cloned())
+            .collect()
+    }
+
+    /// Remember the current view and cursor item before opening an item in it
+    fn push_nav(&mut self) {
+        let cursor = self.cursor_item_id();
+        self.nav_stack.push(NavEntry { view: self.view.clone(), cursor });
+    }
+
+    /// Take the view the current one was opened from, if `is_parent` accepts it
+    ///
+    /// A view that doesn't fit (say, after starting the browser in a nested
+    /// view) means the stack is stale, so it is dropped.
+    fn pop_nav(&mut self, is_parent: impl Fn(&BrowseView) -> bool) -> Option<NavEntry> {
+        let entry = self.nav_stack.pop()?;
+        if is_parent(&entry.view) {
+            Some(entry)
+        } else {
+            self.nav_stack.clear();
+            None
+        }
+    }
+
+    /// Show a view again with the cursor back on the item it was left on
+    fn restore_nav(&mut self, entry: NavEntry) {
+        self.view = entry.view;
+        // A genre may have replaced the album list in the meantime
+        match &self.view {
+            BrowseView::Albums { artist_id, .. } => {
+                self.albums = self.cached_albums(self.artist_album_ids.get(artist_id));
+            }
+            BrowseView::GenreAlbums { genre } => {
+                self.albums = self.cached_albums(self.genre_album_ids.get(genre));
+            }
+            _ => {}
+        }
+        self.sort_current_view();
+        self.clear_filter();
+        self.list_state.select(Some(0));
+        self.restore_cursor(entry.cursor.as_deref());
+    }
+
     /// Load synced content from a device's manifest
     fn load_synced_content(&mut self, device: &Device) {
         if let Ok(Some(manifest)) = crate::device::SyncManifest::load(&device.mount_point) {
-            self.synced_album_ids = manifest.synced_albums.iter().map(|a| a.id.clone()).collect();
-            self.synced_playlist_ids = manifest.synced_playlists.iter().map(|p| p.id.clone()).collect();
+            self.load_synced_ids(&manifest);
             self.active_device = Some(device.clone());
         }
     }
 
-    /// Load synced content from device and auto-select synced items
+    /// Remember which albums and playlists a device's manifest lists
+    fn load_synced_ids(&mut self, manifest: &SyncManifest) {
+        self.synced_album_ids = manifest.synced_albums.iter().map(|a| a.id.clone()).collect();
+        self.synced_playlist_ids = manifest.synced_playlists.iter().map(|p| p.id.clone()).collect();
+        self.synced_picks = manifest
+            .synced_albums
+            .iter()
+            .filter(|a| a.partial)
+            .map(|a| (a.id.clone(), a.synced_tracks.iter().cloned().collect()))
+            .collect();
+        self.synced_album_entries = manifest.synced_albums.iter().map(|a| (a.id.clone(), a.clone())).collect();
+        self.synced_playlist_entries = manifest.synced_playlists.iter().map(|p| (p.id.clone(), p.clone())).collect();
+    }
+
+    /// Sync status of an album the server now lists `song_count` songs for
+    fn album_status(&self, album_id: &str, song_count: Option<u32>) -> SyncStatus {
+        if !self.synced_album_ids.contains(album_id) {
+            SyncStatus::NotSynced
+        } else if self.synced_album_entries.get(album_id).is_some_and(|a| a.is_outdated(song_count)) {
+            SyncStatus::Outdated
+        } else if self.synced_picks.contains_key(album_id) {
+            SyncStatus::Partial
+        } else {
+            SyncStatus::Synced
+        }
+    }
+
+    /// Sync status of a playlist as the server lists it now
+    fn playlist_status(&self, playlist: &Playlist) -> SyncStatus {
+        if !self.synced_playlist_ids.contains(&playlist.id) {
+            SyncStatus::NotSynced
+        } else if self
+            .synced_playlist_entries
+            .get(&playlist.id)
+            .is_some_and(|p| p.is_outdated(playlist))
+        {
+            SyncStatus::Outdated
+        } else {
+            SyncStatus::Synced
+        }
+    }
+
+    /// Load synced content from device and auto-select synced items, along
+    /// with the selection last made for the device
     fn load_and_select_synced_content(&mut self, device: &Device) {
+        let saved = SyncSelection::load_for(&device.uuid).unwrap_or_else(|e| {
+            debug!("Failed to load saved selection for {}: {}", device.display_name(), e);
+            SyncSelection::new()
+        });
+
         if let Ok(Some(manifest)) = crate::device::SyncManifest::load(&device.mount_point) {
             // Load synced IDs
-            self.synced_album_ids = manifest.synced_albums.iter().map(|a| a.id.clone()).collect();
-            self.synced_playlist_ids = manifest.synced_playlists.iter().map(|p| p.id.clone()).collect();
+            self.load_synced_ids(&manifest);
             self.active_device = Some(device.clone());
 
-            // Auto-select synced items
+            // Auto-select synced items, keeping partly synced albums partial
             self.selected_albums = self.synced_album_ids.clone();
+            self.picked_tracks = self.synced_picks.clone();
             self.selected_playlists = self.synced_playlist_ids.clone();
 
             // Group synced albums by artist name
@@ -176,10 +712,13 @@ impl BrowserState {
                         artist: Some(synced.artist.clone()),
                         artist_id: None,
                         cover_art: None,
+                        cover_art_ids: Vec::new(),
                         song_count: Some(synced.track_count),
                         duration: None,
                         year: None,
                         genre: None,
+                        created: None,
+                        source: AlbumSource::Id3,
                     };
                     self.album_cache.insert(album.id.clone(), album);
                 }
@@ -191,11 +730,122 @@ impl BrowserState {
                     self.artist_album_ids.insert(artist.id.clone(), album_ids);
                 }
             }
+        }
+
+        self.merge_saved_selection(saved);
+        self.update_artist_selection_status();
+    }
 
-            self.update_artist_selection_status();
+    /// Add a device's saved selection to the current one
+    ///
+    /// Tracks picked in the saved selection replace what the manifest shows
+    /// on the device, since they're what was last chosen.
+    fn merge_saved_selection(&mut self, saved: SyncSelection) {
+        for album in saved.albums {
+            match saved.tracks.get(&album.id) {
+                Some(picked) => self.picked_tracks.insert(album.id.clone(), picked.clone()),
+                None => self.picked_tracks.remove(&album.id),
+            };
+            self.selected_albums.insert(album.id.clone());
+            if let Some(artist_id) = &album.artist_id {
+                let ids = self.artist_album_ids.entry(artist_id.clone()).or_default();
+                if !ids.contains(&album.id) {
+                    ids.push(album.id.clone());
+                }
+            }
+            self.album_cache.entry(album.id.clone()).or_insert(album);
+        }
+
+        for playlist in saved.playlists {
+            self.selected_playlists.insert(playlist.id.clone());
+            self.playlist_cache.insert(playlist.id.clone(), playlist);
         }
     }
 
+    /// Everything selected, including items already on the device
+    fn full_selection(&self) -> SyncSelection {
+        let mut selection = SyncSelection::new();
+        for album_id in &self.selected_albums {
+            if let Some(album) = self.album_cache.get(album_id) {
+                selection.albums.push(album.clone());
+                if let Some(picked) = self.picked_tracks.get(album_id) {
+                    selection.tracks.insert(album_id.clone(), picked.clone());
+                }
+            }
+        }
+        selection.playlists = self
+            .selected_playlists
+            .iter()
+            .filter_map(|id| self.find_playlist(id).cloned())
+            .collect();
+        selection
+    }
+
+    /// Save the selection as the one last made for the selected device (best-effort)
+    fn remember_selection(&self) {
+        if let Some(device) = &self.selected_device
+            && let Err(e) = self.full_selection().save_for(&device.uuid)
+        {
+            debug!("Failed to save selection for {}: {}", device.display_name(), e);
+        }
+    }
+
+    /// Leave the browser with the selection for `nutune sync`
+    ///
+    /// The full selection is handed back, not just what's missing from the
+    /// device: the sync deletes everything the selection leaves out.
+    fn quit_without_syncing(&self) -> BrowseResult {
+        self.remember_selection();
+        BrowseResult::SelectionOnly(self.full_selection())
+    }
+
+    /// A loaded playlist, or one from a saved selection
+    fn find_playlist(&self, id: &str) -> Option<&Playlist> {
+        self.playlists.iter().find(|p| p.id == id).or_else(|| self.playlist_cache.get(id))
+    }
+
+    /// Whether a song of an album is selected, on its own or with the whole album
+    fn is_track_picked(&self, album_id: &str, song_id: &str) -> bool {
+        self.selected_albums.contains(album_id)
+            && self.picked_tracks.get(album_id).is_none_or(|ids| ids.contains(song_id))
+    }
+
+    /// Select or deselect a whole album, dropping any songs picked from it
+    fn toggle_album(&mut self, album_id: &str) {
+        self.picked_tracks.remove(album_id);
+        if !self.selected_albums.remove(album_id) {
+            self.selected_albums.insert(album_id.to_string());
+        }
+        self.update_artist_selection_status();
+    }
+
+    /// Pick or unpick one song of the album in the album tracks view
+    ///
+    /// Picking every song selects the album whole again, and unpicking the
+    /// last one deselects it.
+    fn toggle_track(&mut self, album: &Album, song_id: &str) {
+        let all: BTreeSet<String> = self.album_songs.iter().map(|s| s.id.clone()).collect();
+        let mut picked = if self.selected_albums.contains(&album.id) {
+            self.picked_tracks.remove(&album.id).unwrap_or_else(|| all.clone())
+        } else {
+            BTreeSet::new()
+        };
+        if !picked.remove(song_id) {
+            picked.insert(song_id.to_string());
+        }
+
+        if picked.is_empty() {
+            self.selected_albums.remove(&album.id);
+        } else {
+            self.selected_albums.insert(album.id.clone());
+            self.album_cache.entry(album.id.clone()).or_insert_with(|| album.clone());
+            if picked != all {
+                self.picked_tracks.insert(album.id.clone(), picked);
+            }
+        }
+        self.update_artist_selection_status();
+    }
+
     /// Toggle selection of all albums for an artist
     fn toggle_artist_selection(&mut self, artist_id: &str) {
         if let Some(album_ids) = self.artist_album_ids.get(artist_id) {
@@ -239,71 +889,655 @@ impl BrowserState {
         }
     }
 
+    /// Record an artist's albums in the lookup caches used for selection
+    fn cache_artist_albums(&mut self, artist_id: &str, albums: &[Album]) {
+        let album_ids: Vec<String> = albums.iter().map(|a| a.id.clone()).collect();
+        self.artist_album_ids.insert(artist_id.to_string(), album_ids);
+        for album in albums {
+            self.album_cache.insert(album.id.clone(), album.clone());
+        }
+    }
+
+    /// Start prefetching album lists for artists that haven't been fetched yet
+    ///
+    /// Runs in the background with bounded concurrency so that toggling many
+    /// artists doesn't block on a serial `getArtist` call for each one.
+    fn start_album_prefetch(&mut self, client: &SubsonicClient) {
+        if self.prefetch_rx.is_some() {
+            return;
+        }
+
+        let artist_ids: Vec<String> = self
+            .artists
+            .iter()
+            .filter(|a| !self.artist_album_ids.contains_key(&a.id))
+            .map(|a| a.id.clone())
+            .collect();
+
+        if artist_ids.is_empty() {
+            return;
+        }
+
+        debug!("Prefetching albums for {} artists", artist_ids.len());
+
+        let (tx, rx) = mpsc::channel::<(String, Vec<Album>)>(PREFETCH_CONCURRENCY * 4);
+        let client = client.clone();
+        tokio::spawn(async move {
+            let mut results = stream::iter(artist_ids)
+                .map(|artist_id| {
+                    let client = client.clone();
+                    async move {
+                        let result = client.get_artist(&artist_id).await;
+                        (artist_id, result)
+                    }
+                })
+                .buffer_unordered(PREFETCH_CONCURRENCY);
+
+            while let Some((artist_id, result)) = results.next().await {
+                match result {
+                    Ok(details) => {
+                        if tx.send((artist_id, details.album)).await.is_err() {
+                            // Browser closed, stop prefetching
+                            break;
+                        }
+                    }
+                    Err(e) => debug!("Prefetch failed for artist {}: {}", artist_id, e),
+                }
+            }
+        });
+
+        self.prefetch_rx = Some(rx);
+    }
+
+    /// Apply any album prefetch results that have arrived
+    fn drain_prefetch(&mut self) {
+        let Some(rx) = &mut self.prefetch_rx else {
+            return;
+        };
+
+        let mut results = Vec::new();
+        let finished = loop {
+            match rx.try_recv() {
+                Ok(result) => results.push(result),
+                Err(mpsc::error::TryRecvError::Empty) => break false,
+                Err(mpsc::error::TryRecvError::Disconnected) => break true,
+            }
+        };
+
+        if finished {
+            self.prefetch_rx = None;
+        }
+
+        if results.is_empty() {
+            return;
+        }
+
+        for (artist_id, albums) in results {
+            self.cache_artist_albums(&artist_id, &albums);
+        }
+        self.update_artist_selection_status();
+    }
+
+    /// Fetch the albums of every artist the prefetch hasn't delivered yet
+    ///
+    /// Select all in the artists view needs the full album list, not just the
+    /// part the background prefetch happens to have reached.
+    async fn fetch_missing_artist_albums(&mut self, client: &SubsonicClient) -> Result<()> {
+        self.drain_prefetch();
+
+        let missing: Vec<String> = self
+            .artists
+            .iter()
+            .filter(|a| !self.artist_album_ids.contains_key(&a.id))
+            .map(|a| a.id.clone())
+            .collect();
+
+        let fetched: Vec<(String, Vec<Album>)> = stream::iter(missing)
+            .map(|artist_id| async move {
+                let details = client.get_artist(&artist_id).await?;
+                Ok::<_, anyhow::Error>((artist_id, details.album))
+            })
+            .buffer_unordered(PREFETCH_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        for (artist_id, albums) in fetched {
+            self.cache_artist_albums(&artist_id, &albums);
+        }
+        Ok(())
+    }
+
+    /// Re-fetch the current view's data from the server in the background
+    fn start_refresh(&mut self, client: &SubsonicClient) {
+        if self.refresh_rx.is_some() {
+            self.set_status("Refresh already in progress");
+            return;
+        }
+
+        let view = self.view.clone();
+        if !matches!(view, BrowseView::Artists | BrowseView::Albums { .. } | BrowseView::Playlists) {
+            self.set_status("Nothing to refresh in this view");
+            return;
+        }
+
+        let (tx, rx) = mpsc::channel::<LibraryRefresh>(1);
+        let client = client.clone();
+        tokio::spawn(async move {
+            let refresh = match view {
+                BrowseView::Artists => client.get_artists().await.map(LibraryRefresh::Artists),
+                BrowseView::Albums { artist_id, .. } => client
+                    .get_artist(&artist_id)
+                    .await
+                    .map(|details| LibraryRefresh::Albums { artist_id, albums: details.album }),
+                _ => client.get_playlists().await.map(LibraryRefresh::Playlists),
+            };
+            let _ = tx.send(refresh.unwrap_or_else(|e| LibraryRefresh::Failed(e.to_string()))).await;
+        });
+
+        self.refresh_rx = Some(rx);
+        self.status_message = "Refreshing library...".to_string();
+    }
+
+    /// Apply a finished background refresh, if any
+    fn drain_refresh(&mut self, client: &SubsonicClient) {
+        let Some(rx) = &mut self.refresh_rx else {
+            return;
+        };
+
+        match rx.try_recv() {
+            Ok(refresh) => {
+                self.refresh_rx = None;
+                let refreshed_artists = matches!(refresh, LibraryRefresh::Artists(_));
+                self.apply_refresh(refresh);
+                if refreshed_artists {
+                    // Pick up albums for any artists that are new to the library
+                    self.start_album_prefetch(client);
+                }
+            }
+            Err(mpsc::error::TryRecvError::Empty) => {}
+            Err(mpsc::error::TryRecvError::Disconnected) => self.refresh_rx = None,
+        }
+    }
+
+    /// Merge refreshed data into state, keeping selections and the cursor item
+    ///
+    /// Selections are stored by ID, so items removed from the server drop out
+    /// of the list but stay selected (via the album cache) until synced.
+    fn apply_refresh(&mut self, refresh: LibraryRefresh) {
+        let cursor_id = self.cursor_item_id();
+
+        let (view_matches, new_items) = match refresh {
+            LibraryRefresh::Artists(artists) => {
+                let new_items = count_new(&self.artists, &artists, |a| &a.id);
+                self.artists = artists;
+                (self.view == BrowseView::Artists, new_items)
+            }
+            LibraryRefresh::Albums { artist_id, albums } => {
+                self.cache_artist_albums(&artist_id, &albums);
+                self.update_artist_selection_status();
+                match &self.view {
+                    BrowseView::Albums { artist_id: current, .. } if *current == artist_id => {
+                        let new_items = count_new(&self.albums, &albums, |a| &a.id);
+                        self.albums = albums;
+                        (true, new_items)
+                    }
+                    _ => (false, 0),
+                }
+            }
+            LibraryRefresh::Playlists(playlists) => {
+                let new_items = count_new(&self.playlists, &playlists, |p| &p.id);
+                self.playlists = playlists;
+                (self.view == BrowseView::Playlists, new_items)
+            }
+            LibraryRefresh::Failed(message) => {
+                self.set_error(format!("Refresh failed: {}", message));
+                return;
+            }
+        };
+
+        if view_matches {
+            self.sort_current_view();
+            self.apply_filter();
+            self.restore_cursor(cursor_id.as_deref());
+        }
+
+        self.set_status(format!("Library refreshed ({} new)", new_items));
+    }
+
+    /// Switch to the next sort mode and reorder the current list
+    fn cycle_sort(&mut self) {
+        if !matches!(
+            self.view,
+            BrowseView::Artists | BrowseView::Albums { .. } | BrowseView::GenreAlbums { .. } | BrowseView::Playlists
+        ) {
+            self.set_status("Nothing to sort in this view");
+            return;
+        }
+
+        let cursor_id = self.cursor_item_id();
+        self.sort_mode = self.sort_mode.next();
+        self.sort_current_view();
+        self.apply_filter();
+        self.restore_cursor(cursor_id.as_deref());
+        self.set_status(format!("Sorted by {}", self.sort_mode.label()));
+    }
+
+    /// Reorder the list of the current view by the sort mode
+    ///
+    /// Server order can't be recovered without refetching, so it leaves the
+    /// list as it is. Callers re-apply the filter afterwards.
+    fn sort_current_view(&mut self) {
+        let mode = self.sort_mode;
+        match &self.view {
+            BrowseView::Artists => {
+                sort_by_mode(&mut self.artists, mode, |a| &a.name, |_| None, |a| a.album_count, |_| None)
+            }
+            BrowseView::Albums { .. } | BrowseView::GenreAlbums { .. } => sort_by_mode(
+                &mut self.albums,
+                mode,
+                |a| &a.name,
+                |a| a.year,
+                |a| a.song_count,
+                Album::created_at,
+            ),
+            BrowseView::Playlists => sort_by_mode(
+                &mut self.playlists,
+                mode,
+                |p| &p.name,
+                |_| None,
+                |p| p.song_count,
+                Playlist::created_at,
+            ),
+            _ => {}
+        }
+    }
+
+    /// ID of the artist, album or playlist under the cursor
+    fn cursor_item_id(&self) -> Option<String> {
+        let idx = self.get_actual_index(self.list_state.selected().unwrap_or(0));
+        match &self.view {
+            BrowseView::Artists => self.artists.get(idx).map(|a| a.id.clone()),
+            BrowseView::Albums { .. } | BrowseView::GenreAlbums { .. } => self.albums.get(idx).map(|a| a.id.clone()),
+            BrowseView::Playlists => self.playlists.get(idx).map(|p| p.id.clone()),
+            BrowseView::Genres => self.genres.get(idx).map(|g| g.value.clone()),
+            BrowseView::Folders { .. } => self.folder_entries.get(idx).map(|e| e.id.clone()),
+            BrowseView::SearchResults { .. } => self.search_hits.get(idx).map(|hit| match hit {
+                SearchHit::Artist(artist) => artist.id.clone(),
+                SearchHit::Album(album) => album.id.clone(),
+                SearchHit::Song(song) => song.id.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Move the cursor back to an item by ID, or clamp it if the item is gone
+    fn restore_cursor(&mut self, id: Option<&str>) {
+        let position = id.and_then(|id| {
+            let actual = match &self.view {
+                BrowseView::Artists => self.artists.iter().position(|a| a.id == id),
+                BrowseView::Albums { .. } | BrowseView::GenreAlbums { .. } => {
+                    self.albums.iter().position(|a| a.id == id)
+                }
+                BrowseView::Playlists => self.playlists.iter().position(|p| p.id == id),
+                BrowseView::Genres => self.genres.iter().position(|g| g.value == id),
+                BrowseView::Folders { .. } => self.folder_entries.iter().position(|e| e.id == id),
+                BrowseView::SearchResults { .. } => self.search_hits.iter().position(|hit| match hit {
+                    SearchHit::Artist(artist) => artist.id == id,
+                    SearchHit::Album(album) => album.id == id,
+                    SearchHit::Song(song) => song.id == id,
+                }),
+                _ => None,
+            }?;
+            if self.filtered_indices.is_empty() {
+                Some(actual)
+            } else {
+                self.filtered_indices.iter().position(|&i| i == actual)
+            }
+        });
+
+        let len = self.current_list_len();
+        let selected = position.unwrap_or_else(|| {
+            self.list_state.selected().unwrap_or(0).min(len.saturating_sub(1))
+        });
+        self.list_state.select(Some(selected));
+    }
+
     /// Set status message with auto-clear timeout
     fn set_status(&mut self, message: impl Into<String>) {
         self.status_message = message.into();
         self.status_message_time = Some(std::time::Instant::now());
+        self.status_is_error = false;
+    }
+
+    /// Set an error message that stays until a key is pressed
+    fn set_error(&mut self, message: impl Into<String>) {
+        self.status_message = message.into();
+        self.status_message_time = None;
+        self.status_is_error = true;
     }
 
     /// Clear status message
     fn clear_status(&mut self) {
         self.status_message.clear();
         self.status_message_time = None;
+        self.status_is_error = false;
     }
 
-    /// Check and clear status message if timeout expired (3 seconds)
+    /// Check and clear status message if its timeout expired
     fn check_status_timeout(&mut self) {
-        if let Some(time) = self.status_message_time
-            && time.elapsed() > std::time::Duration::from_secs(3)
+        if let (Some(time), Some(timeout)) = (self.status_message_time, self.status_timeout)
+            && time.elapsed() > timeout
         {
             self.clear_status();
         }
     }
 
-    /// Apply search filter to current view
+    /// Whether synced items are being hidden from the current view
+    fn hiding_synced(&self) -> bool {
+        self.hide_synced
+            && matches!(
+                self.view,
+                BrowseView::Albums { .. } | BrowseView::GenreAlbums { .. } | BrowseView::Playlists
+            )
+    }
+
+    /// Apply search filter (and hiding of synced items) to current view
     fn apply_filter(&mut self) {
         let query = self.search_query.to_lowercase();
-        if query.is_empty() {
+        let hide_synced = self.hiding_synced();
+        if query.is_empty() && !hide_synced {
             self.filtered_indices.clear();
             return;
         }
 
-        self.filtered_indices = match &self.view {
+        // Score each visible item against the query, best matches first
+        let exact = self.exact_search;
+        let score = |name: &str| {
+            if exact {
+                name.to_lowercase().contains(&query).then_some(0)
+            } else {
+                fuzzy_score(&query, name)
+            }
+        };
+        let mut scored: Vec<(usize, i64)> = match &self.view {
             BrowseView::Artists => self
                 .artists
                 .iter()
                 .enumerate()
-                .filter(|(_, a)| a.name.to_lowercase().contains(&query))
-                .map(|(i, _)| i)
+                .filter_map(|(i, a)| score(&a.name).map(|s| (i, s)))
                 .collect(),
-            BrowseView::Albums { .. } => self
+            BrowseView::Albums { .. } | BrowseView::GenreAlbums { .. } => self
                 .albums
                 .iter()
                 .enumerate()
-                .filter(|(_, a)| a.name.to_lowercase().contains(&query))
-                .map(|(i, _)| i)
+                .filter(|(_, a)| !(hide_synced && self.album_status(&a.id, a.song_count).is_current()))
+                .filter_map(|(i, a)| score(&a.name).map(|s| (i, s)))
                 .collect(),
             BrowseView::Playlists => self
                 .playlists
                 .iter()
                 .enumerate()
-                .filter(|(_, p)| p.name.to_lowercase().contains(&query))
-                .map(|(i, _)| i)
+                .filter(|(_, p)| !(hide_synced && self.playlist_status(p).is_current()))
+                .filter_map(|(i, p)| score(&p.name).map(|s| (i, s)))
+                .collect(),
+            BrowseView::Genres => self
+                .genres
+                .iter()
+                .enumerate()
+                .filter_map(|(i, g)| score(&g.value).map(|s| (i, s)))
+                .collect(),
+            BrowseView::Folders { .. } => self
+                .folder_entries
+                .iter()
+                .enumerate()
+                .filter_map(|(i, e)| score(&e.name).map(|s| (i, s)))
+                .collect(),
+            BrowseView::AlbumTracks { .. } => self
+                .album_songs
+                .iter()
+                .enumerate()
+                .filter_map(|(i, s)| score(&s.title).map(|score| (i, score)))
                 .collect(),
             _ => Vec::new(),
         };
+        // Stable, so equal scores keep the list's own order
+        scored.sort_by_key(|&(_, s)| Reverse(s));
+        self.filtered_indices = scored.into_iter().map(|(i, _)| i).collect();
+
+        // Reset selection to first filtered item
+        if !self.filtered_indices.is_empty() {
+            self.list_state.select(Some(0));
+        }
+    }
+
+    /// Switch between fuzzy and exact-substring search
+    fn toggle_exact_search(&mut self) {
+        self.exact_search = !self.exact_search;
+        self.apply_filter();
+        self.set_status(if self.exact_search { "Exact search" } else { "Fuzzy search" });
+    }
+
+    /// Clear search filter, keeping synced items hidden if that is on
+    fn clear_filter(&mut self) {
+        self.search_mode = false;
+        self.server_search = false;
+        self.search_query.clear();
+        self.apply_filter();
+    }
+
+    /// Show the results of a server search, remembering the view to come back to
+    ///
+    /// Found albums are cached so they can be selected like browsed ones.
+    fn show_search_results(&mut self, query: String, result: SearchResult) {
+        for album in &result.album {
+            self.album_cache.insert(album.id.clone(), album.clone());
+        }
+        self.search_hits = result
+            .artist
+            .into_iter()
+            .map(SearchHit::Artist)
+            .chain(result.album.into_iter().map(SearchHit::Album))
+            .chain(result.song.into_iter().map(SearchHit::Song))
+            .collect();
+
+        self.clear_filter();
+        let previous = std::mem::replace(&mut self.view, BrowseView::SearchResults { query });
+        if !matches!(previous, BrowseView::SearchResults { .. }) {
+            self.search_return = Some(previous);
+        }
+        self.list_state.select(Some(0));
+    }
+
+    /// Leave the search results for the view the search was started from
+    fn close_search_results(&mut self) {
+        self.view = self.search_return.take().unwrap_or(BrowseView::Artists);
+        self.search_hits.clear();
+        self.apply_filter();
+        self.list_state.select(Some(0));
+    }
+
+    /// Toggle hiding of already-synced albums and playlists
+    fn toggle_hide_synced(&mut self) {
+        self.hide_synced = !self.hide_synced;
+        self.apply_filter();
+        self.list_state.select(Some(0));
+
+        let message = if !self.hide_synced {
+            "Showing synced items"
+        } else if self.hiding_synced() && self.filtered_indices.is_empty() && self.current_list_len() > 0 {
+            // An empty filter shows everything, so say why nothing disappeared
+            "Everything here is already synced"
+        } else {
+            "Hiding synced items"
+        };
+        self.set_status(message);
+    }
+
+    /// Rows of the selection view: albums by artist and name, then playlists by name
+    fn selection_rows(&self) -> Vec<SelectionRow> {
+        let mut albums: Vec<SelectionRow> = self
+            .selected_albums
+            .iter()
+            .map(|id| {
+                let album = self.album_cache.get(id);
+                let mut label = match album {
+                    Some(a) => format!("{} - {}", a.artist.as_deref().unwrap_or("Unknown Artist"), a.name),
+                    None => format!("Album {}", id),
+                };
+                if let Some(picked) = self.picked_tracks.get(id) {
+                    label.push_str(&format!(" ({} track(s))", picked.len()));
+                }
+                SelectionRow {
+                    item: SelectedItem::Album(id.clone()),
+                    label,
+                    duration: album.and_then(|a| a.duration),
+                }
+            })
+            .collect();
+        albums.sort_by_key(|row| row.label.to_lowercase());
+
+        let mut playlists: Vec<SelectionRow> = self
+            .selected_playlists
+            .iter()
+            .map(|id| {
+                let playlist = self.find_playlist(id);
+                SelectionRow {
+                    item: SelectedItem::Playlist(id.clone()),
+                    label: format!("Playlist: {}", playlist.map_or(id.as_str(), |p| p.name.as_str())),
+                    duration: playlist.and_then(|p| p.duration),
+                }
+            })
+            .collect();
+        playlists.sort_by_key(|row| row.label.to_lowercase());
+
+        albums.extend(playlists);
+        albums
+    }
+
+    /// Show everything selected, remembering the current view to come back to
+    fn open_selection_view(&mut self) {
+        self.clear_filter();
+        self.selection_return = Some(std::mem::replace(&mut self.view, BrowseView::Selection));
+        self.list_state.select(Some(0));
+    }
+
+    /// Leave the selection view for the view it was opened from
+    fn close_selection_view(&mut self) {
+        self.view = self.selection_return.take().unwrap_or(BrowseView::Artists);
+        self.apply_filter();
+        self.list_state.select(Some(0));
+    }
+
+    /// Deselect the album or playlist under the cursor in the selection view
+    fn deselect_selection_row(&mut self) {
+        let rows = self.selection_rows();
+        let idx = self.list_state.selected().unwrap_or(0);
+        let Some(row) = rows.get(idx) else {
+            return;
+        };
+
+        match &row.item {
+            SelectedItem::Album(id) => {
+                self.selected_albums.remove(id);
+                self.picked_tracks.remove(id);
+                self.update_artist_selection_status();
+            }
+            SelectedItem::Playlist(id) => {
+                self.selected_playlists.remove(id);
+            }
+        }
+        self.list_state.select(Some(idx.min(rows.len().saturating_sub(2))));
+    }
+
+    /// Entries of the pending sync plan as (is_deletion, label), deletions first
+    fn plan_entries(&self) -> Vec<(bool, String)> {
+        let mut entries = Vec::new();
+
+        if let Some(ref deletions) = self.pending_deletions {
+            for (_, artist, album) in &deletions.albums {
+                entries.push((true, format!("{} - {}", artist, album)));
+            }
+            for (_, name) in &deletions.playlists {
+                entries.push((true, format!("Playlist: {}", name)));
+            }
+        }
+
+        if let Some(ref selection) = self.sync_selection {
+            for album in &selection.albums {
+                let artist = album.artist.as_deref().unwrap_or("Unknown");
+                entries.push((false, format!("{} - {}", artist, album.name)));
+            }
+            for playlist in &selection.playlists {
+                entries.push((false, format!("Playlist: {}", playlist.name)));
+            }
+        }
+
+        entries
+    }
 
-        // Reset selection to first filtered item
-        if !self.filtered_indices.is_empty() {
-            self.list_state.select(Some(0));
+    /// Toggle a sync plan entry on or off
+    fn toggle_plan_entry(&mut self, idx: usize) {
+        if !self.plan_excluded.remove(&idx) {
+            self.plan_excluded.insert(idx);
         }
     }
 
-    /// Clear search filter
-    fn clear_filter(&mut self) {
-        self.search_mode = false;
-        self.search_query.clear();
-        self.filtered_indices.clear();
+    /// Take the pending sync plan, dropping entries that were toggled off
+    fn take_confirmed_plan(&mut self) -> Option<(SyncSelection, DeletionSelection)> {
+        let mut selection = self.sync_selection.take()?;
+        let mut deletions = self.pending_deletions.take()?;
+        let excluded = std::mem::take(&mut self.plan_excluded);
+
+        // Indices follow the order of plan_entries
+        let mut idx = 0;
+        let mut keep = || {
+            let kept = !excluded.contains(&idx);
+            idx += 1;
+            kept
+        };
+        deletions.albums.retain(|_| keep());
+        deletions.playlists.retain(|_| keep());
+        selection.albums.retain(|_| keep());
+        selection.playlists.retain(|_| keep());
+
+        Some((selection, deletions))
+    }
+
+    /// Show the plan for confirmation, optionally as a preview only
+    fn show_plan(&mut self, selection: SyncSelection, deletions: DeletionSelection, preview: bool) {
+        self.clear_filter();
+        self.sync_selection = Some(selection);
+        self.pending_deletions = Some(deletions);
+        self.plan_excluded.clear();
+        self.plan_preview = preview;
+        self.view = BrowseView::SyncConfirmation;
+        self.list_state.select(Some(0));
+    }
+
+    /// Drop the pending plan and go back to browsing
+    fn cancel_plan(&mut self) {
+        self.sync_selection = None;
+        self.pending_deletions = None;
+        self.plan_excluded.clear();
+        self.plan_preview = false;
+        self.view = BrowseView::Artists;
+        self.list_state.select(Some(0));
+    }
+
+    /// Finish a preview: report what the confirmed plan would do and go back to browsing
+    fn finish_preview(&mut self) {
+        let message = match self.take_confirmed_plan() {
+            Some((selection, deletions)) if !(selection.is_empty() && deletions.is_empty()) => format!(
+                "Preview: would delete {} album(s), {} playlist(s) and add {} album(s), {} playlist(s). Device not changed.",
+                deletions.albums.len(),
+                deletions.playlists.len(),
+                selection.album_count(),
+                selection.playlist_count()
+            ),
+            _ => "Preview: nothing to sync. Device not changed.".to_string(),
+        };
+        self.cancel_plan();
+        self.set_status(&message);
     }
 
     /// Get the actual index in the original list from display index
@@ -323,13 +1557,17 @@ impl BrowserState {
 
         match &self.view {
             BrowseView::Artists => self.artists.len(),
-            BrowseView::Albums { .. } => self.albums.len(),
-            BrowseView::AlbumTracks { album } => album.song_count.unwrap_or(0) as usize,
+            BrowseView::Albums { .. } | BrowseView::GenreAlbums { .. } => self.albums.len(),
+            BrowseView::AlbumTracks { .. } => self.album_songs.len(),
             BrowseView::Playlists => self.playlists.len(),
             BrowseView::PlaylistTracks { playlist } => playlist.song_count.unwrap_or(0) as usize,
+            BrowseView::Genres => self.genres.len(),
+            BrowseView::Folders { .. } => self.folder_entries.len(),
+            BrowseView::Selection => self.selection_rows().len(),
+            BrowseView::SearchResults { .. } => self.search_hits.len(),
             BrowseView::DeviceSelection => self.mounted_devices.len() + self.unmounted_devices.len(),
             BrowseView::SyncProgress => self.sync_progress.log_messages.len(),
-            BrowseView::SyncConfirmation => 2, // Yes/No options
+            BrowseView::SyncConfirmation => self.plan_entries().len(),
         }
     }
 
@@ -377,35 +1615,74 @@ impl BrowserState {
 }
 
 /// Run the interactive browser
-pub async fn run_browser(client: &SubsonicClient, initial_view: BrowseView) -> Result<BrowseResult> {
-    // Enable TUI mode to suppress stderr logging
-    crate::utils::set_tui_mode(true);
-
-    // Setup terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
+///
+/// If `resume_device` is given and a device with that UUID is connected, it is
+/// selected on startup as if it had been picked from the device list.
+pub async fn run_browser(
+    client: &SubsonicClient,
+    initial_view: BrowseView,
+    resume_device: Option<&str>,
+    album_parallel: usize,
+) -> Result<BrowseResult> {
+    // Read the key bindings while logs still reach stderr, so a broken
+    // keys.toml is reported even if the TUI exits before it can show it
+    let keys = KeyMap::load();
+    if let Err(e) = &keys {
+        warn!("{:#}; using the default keys", e);
+    }
+    let devices = DeviceDetector::scan().await.unwrap_or_default();
+
+    // Setup terminal (restored when the guard drops, including on errors)
+    let _guard = TerminalGuard::enter()?;
+    let backend = CrosstermBackend::new(io::stdout());
     let mut terminal = Terminal::new(backend)?;
 
-    // Create state
-    let mut state = BrowserState::new(initial_view.clone());
+    let mut state = start_browser(client, initial_view, resume_device, album_parallel, keys, &devices).await?;
 
-    // Try to detect connected device and load its sync manifest
-    if let Ok(devices) = DeviceDetector::scan().await
-        && let Some(device) = devices.first() {
-            state.load_synced_content(device);
-        }
+    // Main loop
+    let result = run_browser_loop(&mut terminal, &mut state, client).await;
+
+    // Remember the view for the next session
+    let last_view = match state.view {
+        BrowseView::Playlists | BrowseView::PlaylistTracks { .. } => SessionView::Playlists,
+        _ => SessionView::Artists,
+    };
+    update_session(|session| session.view = last_view);
+
+    result
+}
+
+/// Create the browser state and load the data for the first view
+async fn start_browser(
+    client: &SubsonicClient,
+    initial_view: BrowseView,
+    resume_device: Option<&str>,
+    album_parallel: usize,
+    keys: Result<KeyMap>,
+    devices: &[Device],
+) -> Result<BrowserState> {
+    let mut state = BrowserState::new(initial_view.clone());
+    state.status_timeout = Settings::load().unwrap_or_default().status_timeout();
+    state.album_parallel = album_parallel;
 
     // Load initial data
     state.status_message = "Loading...".to_string();
     match &initial_view {
-        BrowseView::Artists | BrowseView::Albums { .. } | BrowseView::AlbumTracks { .. } => {
+        BrowseView::Artists
+        | BrowseView::Albums { .. }
+        | BrowseView::AlbumTracks { .. }
+        | BrowseView::Genres
+        | BrowseView::GenreAlbums { .. }
+        | BrowseView::Selection
+        | BrowseView::SearchResults { .. } => {
             state.artists = client.get_artists().await?;
         }
         BrowseView::Playlists | BrowseView::PlaylistTracks { .. } => {
             state.playlists = client.get_playlists().await?;
         }
+        BrowseView::Folders { .. } => {
+            open_folder(&mut state, client, Vec::new()).await?;
+        }
         BrowseView::DeviceSelection | BrowseView::SyncProgress | BrowseView::SyncConfirmation => {
             // Load devices if starting in device selection (shouldn't happen normally)
             state.mounted_devices = DeviceDetector::scan().await.unwrap_or_default();
@@ -414,17 +1691,28 @@ pub async fn run_browser(client: &SubsonicClient, initial_view: BrowseView) -> R
     }
     state.status_message.clear();
 
-    // Main loop
-    let result = run_browser_loop(&mut terminal, &mut state, client).await;
+    // Resume the last used device if it's connected, otherwise just show the
+    // sync status of the first detected device
+    let resumed = resume_device.and_then(|uuid| devices.iter().find(|d| d.uuid == uuid));
+    if let Some(device) = resumed {
+        state.load_and_select_synced_content(device);
+        state.selected_device = Some(device.clone());
+        state.set_status(format!("Resumed device: {}", device.display_name()));
+    } else if let Some(device) = devices.first() {
+        state.load_synced_content(device);
+    }
 
-    // Restore terminal
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    if matches!(state.view, BrowseView::Artists) {
+        state.start_album_prefetch(client);
+    }
 
-    // Disable TUI mode to restore normal logging
-    crate::utils::set_tui_mode(false);
+    // Last, so the loading messages above don't replace the error
+    match keys {
+        Ok(keys) => state.keys = keys,
+        Err(e) => state.set_error(format!("{:#}; using the default keys", e)),
+    }
 
-    result
+    Ok(state)
 }
 
 async fn run_browser_loop(
@@ -452,6 +1740,10 @@ async fn run_browser_loop(
             }
         }
 
+        // Apply background album prefetch and library refresh results
+        state.drain_prefetch();
+        state.drain_refresh(client);
+
         // Check for status message timeout
         state.check_status_timeout();
 
@@ -465,6 +1757,17 @@ async fn run_browser_loop(
                     continue;
                 }
 
+                // Ctrl-C quits immediately from anywhere
+                if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    interrupt();
+                }
+
+                // Any key dismisses an error message
+                if state.status_is_error {
+                    state.clear_status();
+                    continue;
+                }
+
                 // Handle help overlay first
                 if state.show_help {
                     // Any key closes help
@@ -478,24 +1781,49 @@ async fn run_browser_loop(
                         KeyCode::Esc => {
                             state.clear_filter();
                         }
+                        KeyCode::Enter if state.server_search => {
+                            let query = state.search_query.trim().to_string();
+                            if query.is_empty() {
+                                state.clear_filter();
+                                continue;
+                            }
+                            state.search_mode = false;
+                            state.status_message = format!("Searching the server for '{}'...", query);
+                            terminal.draw(|f| draw_ui(f, state))?;
+
+                            match client.search(&query, SERVER_SEARCH_LIMIT).await {
+                                Ok(result) => {
+                                    state.status_message.clear();
+                                    state.show_search_results(query, result);
+                                }
+                                Err(e) => {
+                                    state.clear_filter();
+                                    state.set_error(format!("Search failed: {}", e));
+                                }
+                            }
+                        }
                         KeyCode::Enter => {
                             state.search_mode = false;
                         }
                         KeyCode::Backspace => {
                             state.search_query.pop();
-                            state.apply_filter();
+                            if !state.server_search {
+                                state.apply_filter();
+                            }
                         }
                         KeyCode::Char(c) => {
                             state.search_query.push(c);
-                            state.apply_filter();
+                            if !state.server_search {
+                                state.apply_filter();
+                            }
                         }
                         _ => {}
                     }
                     continue;
                 }
 
-                match key.code {
-                    KeyCode::Char('q') => {
+                match state.keys.action(key.code) {
+                    Some(Action::Quit) => {
                         if state.view == BrowseView::DeviceSelection {
                             // Go back to previous view
                             state.view = BrowseView::Artists;
@@ -506,17 +1834,17 @@ async fn run_browser_loop(
                                 if let (Some(selection), Some(device)) =
                                     (state.sync_selection.take(), state.selected_device.take())
                                 {
-                                    return Ok(BrowseResult::SyncToDevice { selection, device });
+                                    return Ok(BrowseResult::SyncToDevice { selection, device: Box::new(device) });
                                 }
-                                return Ok(BrowseResult::SelectionOnly(build_selection(state, client).await?));
+                                return Ok(state.quit_without_syncing());
                             }
                             // Don't allow quitting during sync
                         } else {
                             // Return selection without device
-                            return Ok(BrowseResult::SelectionOnly(build_selection(state, client).await?));
+                            return Ok(state.quit_without_syncing());
                         }
                     }
-                    KeyCode::Esc => {
+                    Some(Action::Cancel) => {
                         // Esc clears filter if active, otherwise acts like backspace
                         if !state.search_query.is_empty() {
                             state.clear_filter();
@@ -525,16 +1853,17 @@ async fn run_browser_loop(
                             state.list_state.select(Some(0));
                         } else if state.view == BrowseView::SyncConfirmation {
                             // Cancel sync confirmation
-                            state.sync_selection = None;
-                            state.pending_deletions = None;
-                            state.view = BrowseView::Artists;
-                            state.list_state.select(Some(0));
+                            state.cancel_plan();
                         } else if state.view != BrowseView::SyncProgress {
                             handle_back(state, client).await?;
                         }
                     }
-                    KeyCode::Char('s') => {
-                        // Start sync
+                    Some(Action::PreviewSync) if state.view == BrowseView::SyncConfirmation => {
+                        state.plan_preview = !state.plan_preview;
+                    }
+                    Some(action @ (Action::StartSync | Action::PreviewSync)) => {
+                        // Start sync, or preview it
+                        let preview = action == Action::PreviewSync;
                         if state.view != BrowseView::DeviceSelection && state.view != BrowseView::SyncProgress && state.view != BrowseView::SyncConfirmation {
                             let selection = build_selection(state, client).await?;
                             let deletions = calculate_deletions(state);
@@ -547,22 +1876,37 @@ async fn run_browser_loop(
                                 }
                             } else if state.selected_device.is_some() {
                                 // Device already selected
-                                if !deletions.is_empty() {
-                                    // Show confirmation for deletions
-                                    state.sync_selection = Some(selection);
-                                    state.pending_deletions = Some(deletions);
-                                    state.view = BrowseView::SyncConfirmation;
+                                if preview || !deletions.is_empty() {
+                                    // Show confirmation for deletions, or the preview
+                                    state.show_plan(selection, deletions, preview);
                                 } else {
                                     // No deletions, start sync directly
                                     start_sync(state, client, selection, deletions).await?;
                                 }
                             } else {
                                 // No device selected yet
-                                state.set_status("Select a device first with 'd'");
+                                state.set_status(format!("Select a device first with '{}'", state.keys.primary(Action::SelectDevice)));
                             }
                         }
                     }
-                    KeyCode::Char('d') => {
+                    Some(Action::RetryFailed) => {
+                        // Retry failed items after a sync
+                        if state.view == BrowseView::SyncProgress
+                            && state.sync_progress.is_complete
+                            && state.sync_progress.failure_count() > 0
+                        {
+                            retry_failed(state, client).await?;
+                        }
+                    }
+                    Some(Action::Refresh) => {
+                        // Refresh the current view from the server
+                        state.start_refresh(client);
+                    }
+                    Some(Action::CycleSort) => {
+                        // Cycle the sort order of the current list
+                        state.cycle_sort();
+                    }
+                    Some(Action::SelectDevice) => {
                         // Select device
                         if state.view != BrowseView::DeviceSelection && state.view != BrowseView::SyncProgress {
                             state.status_message = "Loading devices...".to_string();
@@ -573,37 +1917,46 @@ async fn run_browser_loop(
                             state.status_message.clear();
 
                             if state.total_devices() == 0 {
-                                state.status_message = "No devices found! Connect a device and try again.".to_string();
+                                state.set_error("No devices found! Connect a device and try again.");
                             } else {
+                                state.update_space_needed();
                                 state.view = BrowseView::DeviceSelection;
                                 state.list_state.select(Some(0));
                             }
                         }
                     }
-                    KeyCode::Up | KeyCode::Char('k') => {
+                    Some(Action::MoveUp) => {
                         if state.view != BrowseView::SyncProgress {
                             state.move_up();
                         }
                     }
-                    KeyCode::Down | KeyCode::Char('j') => {
+                    Some(Action::MoveDown) => {
                         if state.view != BrowseView::SyncProgress {
                             state.move_down();
                         }
                     }
-                    KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => {
+                    Some(Action::Open) => {
                         if state.view == BrowseView::DeviceSelection {
                             // Select device and load synced content
                             handle_device_select(state, client).await?;
+                        } else if state.view == BrowseView::SyncConfirmation && state.plan_preview {
+                            state.finish_preview();
                         } else if state.view == BrowseView::SyncConfirmation {
-                            // Confirm sync with deletions
-                            if let (Some(selection), Some(deletions)) = (state.sync_selection.take(), state.pending_deletions.take()) {
-                                start_sync(state, client, selection, deletions).await?;
+                            // Confirm sync with deletions, minus anything toggled off
+                            if let Some((selection, deletions)) = state.take_confirmed_plan() {
+                                if selection.is_empty() && deletions.is_empty() {
+                                    state.view = BrowseView::Artists;
+                                    state.list_state.select(Some(0));
+                                    state.set_status("Nothing left to sync");
+                                } else {
+                                    start_sync(state, client, selection, deletions).await?;
+                                }
                             }
                         } else if state.view != BrowseView::SyncProgress {
                             handle_enter(state, client).await?;
                         }
                     }
-                    KeyCode::Backspace | KeyCode::Left | KeyCode::Char('h') => {
+                    Some(Action::Back) => {
                         if state.view == BrowseView::DeviceSelection {
                             state.view = BrowseView::Artists;
                             state.list_state.select(Some(0));
@@ -611,34 +1964,86 @@ async fn run_browser_loop(
                             handle_back(state, client).await?;
                         }
                     }
-                    KeyCode::Char(' ') => {
+                    Some(Action::Toggle) => {
                         if state.view != BrowseView::SyncProgress {
                             handle_toggle(state, client, terminal).await?;
                         }
                     }
-                    KeyCode::Char('a') => {
+                    Some(Action::SelectAll) if state.view == BrowseView::Genres => {
+                        select_genre(state, client, terminal, false).await?;
+                    }
+                    Some(Action::SelectAll) if state.view == BrowseView::Artists => {
+                        select_all_artists(state, client, terminal).await?;
+                    }
+                    Some(Action::SelectAll) => {
                         if state.view != BrowseView::SyncProgress {
                             handle_select_all(state);
                         }
                     }
-                    KeyCode::Char('A') => {
+                    Some(Action::DeselectAll) => {
                         // Deselect all in current view
                         if state.view != BrowseView::SyncProgress {
                             handle_deselect_all(state);
                         }
                     }
-                    KeyCode::Char('/') => {
+                    Some(Action::ShowSelection) => {
+                        // Show or leave the selection view
+                        if state.view == BrowseView::Selection {
+                            state.close_selection_view();
+                        } else if state.view != BrowseView::DeviceSelection
+                            && state.view != BrowseView::SyncProgress
+                            && state.view != BrowseView::SyncConfirmation
+                        {
+                            state.open_selection_view();
+                        }
+                    }
+                    Some(Action::Filter) => {
                         // Enter search mode
-                        if state.view != BrowseView::DeviceSelection && state.view != BrowseView::SyncProgress {
+                        if state.view != BrowseView::DeviceSelection
+                            && state.view != BrowseView::SyncProgress
+                            && state.view != BrowseView::SyncConfirmation
+                            && state.view != BrowseView::Selection
+                            && !matches!(state.view, BrowseView::SearchResults { .. })
+                        {
                             state.search_mode = true;
                             state.search_query.clear();
                         }
                     }
-                    KeyCode::Char('?') => {
+                    Some(Action::Search) => {
+                        // Search the whole library on the server
+                        if state.view != BrowseView::DeviceSelection
+                            && state.view != BrowseView::SyncProgress
+                            && state.view != BrowseView::SyncConfirmation
+                        {
+                            state.clear_filter();
+                            state.search_mode = true;
+                            state.server_search = true;
+                        }
+                    }
+                    Some(Action::Genres) => {
+                        // Browse by genre
+                        if state.view != BrowseView::DeviceSelection
+                            && state.view != BrowseView::SyncProgress
+                            && state.view != BrowseView::SyncConfirmation
+                        {
+                            open_genres(state, client).await?;
+                        }
+                    }
+                    Some(Action::HideSynced) => {
+                        if state.view != BrowseView::SyncProgress {
+                            state.toggle_hide_synced();
+                        }
+                    }
+                    Some(Action::ExactSearch) => {
+                        if state.view != BrowseView::SyncProgress {
+                            state.toggle_exact_search();
+                        }
+                    }
+                    Some(Action::Help) => {
                         // Toggle help overlay
                         state.show_help = !state.show_help;
                     }
-                    KeyCode::Tab => {
+                    Some(Action::SwitchView) => {
                         if state.view != BrowseView::DeviceSelection && state.view != BrowseView::SyncProgress {
                             handle_tab(state, client).await?;
                         }
@@ -649,14 +2054,57 @@ async fn run_browser_loop(
     }
 }
 
+/// Count items in `new` whose ID isn't present in `old`
+fn count_new<T>(old: &[T], new: &[T], id: impl Fn(&T) -> &String) -> usize {
+    let old_ids: HashSet<&String> = old.iter().map(&id).collect();
+    new.iter().filter(|item| !old_ids.contains(id(item))).count()
+}
+
+/// Sort artists, albums or playlists by a sort mode, breaking ties by name
+///
+/// Items missing the sort key (no year, count or date) go last.
+fn sort_by_mode<T>(
+    items: &mut [T],
+    mode: SortMode,
+    name: impl Fn(&T) -> &str,
+    year: impl Fn(&T) -> Option<u32>,
+    tracks: impl Fn(&T) -> Option<u32>,
+    added: impl Fn(&T) -> Option<DateTime<Utc>>,
+) {
+    let name = |item: &T| name(item).to_lowercase();
+    match mode {
+        SortMode::Server => {}
+        SortMode::Name => items.sort_by_cached_key(name),
+        SortMode::Year => items.sort_by_cached_key(|i| (year(i).is_none(), year(i), name(i))),
+        SortMode::TrackCount => items.sort_by_cached_key(|i| (Reverse(tracks(i)), name(i))),
+        SortMode::RecentlyAdded => items.sort_by_cached_key(|i| (Reverse(added(i)), name(i))),
+    }
+}
+
+/// Update the remembered browser session in settings (best-effort)
+fn update_session(update: impl FnOnce(&mut LastSession)) {
+    let mut settings = Settings::load().unwrap_or_default();
+    update(&mut settings.last_session);
+    if let Err(e) = settings.save() {
+        debug!("Failed to save session: {}", e);
+    }
+}
+
 /// Handle a sync progress event
 fn handle_sync_progress_event(state: &mut BrowserState, event: SyncProgressEvent) {
     match event {
-        SyncProgressEvent::Started { total_albums, total_playlists } => {
+        SyncProgressEvent::Started { total_albums, total_playlists, already_synced, estimated_bytes } => {
             state.sync_progress.albums_total = total_albums;
+            state.sync_progress.estimated_bytes = estimated_bytes;
+            state.sync_progress.start_clock(Instant::now());
+            let skipped = if already_synced > 0 {
+                format!(" ({} already synced)", already_synced)
+            } else {
+                String::new()
+            };
             state.sync_progress.log_messages.push(format!(
-                "Starting sync: {} albums, {} playlists",
-                total_albums, total_playlists
+                "Starting sync: {} albums, {} playlists{}",
+                total_albums, total_playlists, skipped
             ));
         }
         SyncProgressEvent::AlbumStarted { artist, album, track_count } => {
@@ -673,6 +2121,9 @@ fn handle_sync_progress_event(state: &mut BrowserState, event: SyncProgressEvent
             state.sync_progress.tracks_completed = track_num;
             state.sync_progress.tracks_total = total_tracks;
         }
+        SyncProgressEvent::BytesProgress { bytes } => {
+            state.sync_progress.record_bytes(bytes, Instant::now());
+        }
         SyncProgressEvent::AlbumCompleted { artist, album } => {
             state.sync_progress.albums_completed += 1;
             state.sync_progress.log_messages.push(format!(
@@ -680,8 +2131,13 @@ fn handle_sync_progress_event(state: &mut BrowserState, event: SyncProgressEvent
                 artist, album
             ));
         }
+        SyncProgressEvent::FirstAlbumPlayable { artist, album } => {
+            state.sync_progress.log_messages.push(format!(
+                "  First album ready: {} - {} (safe to unplug for partial use)",
+                artist, album
+            ));
+        }
         SyncProgressEvent::AlbumSkipped { artist, album } => {
-            state.sync_progress.albums_completed += 1;
             state.sync_progress.log_messages.push(format!(
                 "  Skipped (already synced): {} - {}",
                 artist, album
@@ -713,18 +2169,43 @@ fn handle_sync_progress_event(state: &mut BrowserState, event: SyncProgressEvent
             state.sync_progress.error = Some(message.clone());
             state.sync_progress.log_messages.push(format!("ERROR: {}", message));
         }
+        SyncProgressEvent::InsufficientSpace { required, available } => {
+            let message = InsufficientSpace { required, available }.to_string();
+            state.sync_progress.error = Some(message.clone());
+            state.sync_progress.log_messages.push(format!("ERROR: {}", message));
+        }
+        SyncProgressEvent::AlbumFailed { album_id, artist, album, message } => {
+            let label = format!("{} - {}", artist, album);
+            state.sync_progress.albums_completed += 1;
+            state.sync_progress.log_messages.push(format!("ERROR: Album {}: {}", label, message));
+            state.sync_progress.failed_albums.push((album_id, label));
+        }
+        SyncProgressEvent::PlaylistFailed { playlist_id, name, message } => {
+            state.sync_progress.log_messages.push(format!("ERROR: Playlist {}: {}", name, message));
+            state.sync_progress.failed_playlists.push((playlist_id, name));
+        }
+        SyncProgressEvent::CoverEmbedFallbacks { tracks } => {
+            state.sync_progress.log_messages.push(format!(
+                "Note: cover art could not be embedded in {} track(s); original audio kept (albums still get a cover file)",
+                tracks
+            ));
+        }
         SyncProgressEvent::Complete { albums_synced, playlists_synced, tracks_downloaded, bytes_downloaded, albums_deleted, playlists_deleted } => {
             state.sync_progress.is_complete = true;
             state.sync_progress.bytes_downloaded = bytes_downloaded;
             let mb = bytes_downloaded as f64 / 1_048_576.0;
+            let rate_info = match state.sync_progress.average_rate(Instant::now()) {
+                Some(rate) if bytes_downloaded > 0 => format!(" at {:.1} MB/s", rate / 1_048_576.0),
+                _ => String::new(),
+            };
             let delete_info = if albums_deleted > 0 || playlists_deleted > 0 {
                 format!(", deleted {} albums, {} playlists", albums_deleted, playlists_deleted)
             } else {
                 String::new()
             };
             state.sync_progress.log_messages.push(format!(
-                "Sync complete! {} albums, {} playlists, {} tracks ({:.1} MB){}",
-                albums_synced, playlists_synced, tracks_downloaded, mb, delete_info
+                "Sync complete! {} albums, {} playlists, {} tracks ({:.1} MB{}){}",
+                albums_synced, playlists_synced, tracks_downloaded, mb, rate_info, delete_info
             ));
         }
         SyncProgressEvent::DeletionStarted { albums_to_delete, playlists_to_delete } => {
@@ -759,10 +2240,12 @@ fn handle_sync_progress_event(state: &mut BrowserState, event: SyncProgressEvent
 /// Start sync with the selected device
 async fn start_sync(state: &mut BrowserState, client: &SubsonicClient, selection: SyncSelection, deletions: DeletionSelection) -> Result<()> {
     let Some(ref device) = state.selected_device else {
-        state.status_message = "No device selected!".to_string();
+        state.set_error("No device selected!");
         return Ok(());
     };
 
+    state.remember_selection();
+
     // Create progress channel
     let (tx, rx) = mpsc::channel::<SyncProgressEvent>(100);
 
@@ -777,10 +2260,32 @@ async fn start_sync(state: &mut BrowserState, client: &SubsonicClient, selection
 
     // Spawn sync task
     let device_path = device.mount_point.clone();
+    let free_space = device.free_space;
+    let fs_type = device.fs_type.clone();
+    let (cover_size, cover_quality) = (device.cover_size, device.cover_quality);
+    let transcode = device.transcode.clone().map(|format| Transcode {
+        format: Some(format),
+        max_bitrate: None,
+    });
+    let album_parallel = state.album_parallel;
+    let layout = device.layout.as_deref().and_then(|saved| {
+        LayoutTemplate::parse(saved)
+            .inspect_err(|e| debug!("Ignoring invalid saved layout for {}: {}", device.display_name(), e))
+            .ok()
+    });
     let client_clone = client.clone();
     tokio::spawn(async move {
+        let options = SyncOptions {
+            temp_dir: Settings::load().unwrap_or_default().temp_dir,
+            layout,
+            cover_size,
+            cover_quality,
+            album_parallel,
+            transcode,
+            ..Default::default()
+        };
         let mut engine = match SyncEngine::new(client_clone, device_path, 4) {
-            Ok(e) => e,
+            Ok(e) => e.with_options(options).with_free_space(free_space).with_fs_type(&fs_type),
             Err(e) => {
                 let _ = tx.send(SyncProgressEvent::Error {
                     message: format!("Failed to create sync engine: {}", e),
@@ -789,7 +2294,10 @@ async fn start_sync(state: &mut BrowserState, client: &SubsonicClient, selection
             }
         };
 
-        if let Err(e) = engine.sync_with_progress(&selection, &deletions, tx.clone()).await {
+        // Running out of space is reported by its own event
+        if let Err(e) = engine.sync_with_progress(&selection, &deletions, tx.clone()).await
+            && e.downcast_ref::<InsufficientSpace>().is_none()
+        {
             let _ = tx.send(SyncProgressEvent::Error {
                 message: format!("Sync failed: {}", e),
             }).await;
@@ -803,6 +2311,49 @@ async fn start_sync(state: &mut BrowserState, client: &SubsonicClient, selection
     Ok(())
 }
 
+/// Re-run the sync engine for only the albums and playlists that failed
+async fn retry_failed(state: &mut BrowserState, client: &SubsonicClient) -> Result<()> {
+    let Some(original) = state.sync_selection.clone() else {
+        return Ok(());
+    };
+
+    let failed_albums: HashSet<&str> =
+        state.sync_progress.failed_albums.iter().map(|(id, _)| id.as_str()).collect();
+    let failed_playlists: HashSet<&str> =
+        state.sync_progress.failed_playlists.iter().map(|(id, _)| id.as_str()).collect();
+
+    let retry = SyncSelection {
+        albums: original
+            .albums
+            .iter()
+            .filter(|a| failed_albums.contains(a.id.as_str()))
+            .cloned()
+            .collect(),
+        playlists: original
+            .playlists
+            .iter()
+            .filter(|p| failed_playlists.contains(p.id.as_str()))
+            .cloned()
+            .collect(),
+        tracks: original
+            .tracks
+            .iter()
+            .filter(|(id, _)| failed_albums.contains(id.as_str()))
+            .map(|(id, picked)| (id.clone(), picked.clone()))
+            .collect(),
+    };
+
+    if retry.is_empty() {
+        return Ok(());
+    }
+
+    start_sync(state, client, retry, DeletionSelection::default()).await?;
+
+    // Keep reporting the full selection when the browser exits
+    state.sync_selection = Some(original);
+    Ok(())
+}
+
 /// Handle device selection - loads synced content and returns to browse
 async fn handle_device_select(state: &mut BrowserState, _client: &SubsonicClient) -> Result<bool> {
     let selected = state.list_state.selected().unwrap_or(0);
@@ -826,7 +2377,7 @@ async fn handle_device_select(state: &mut BrowserState, _client: &SubsonicClient
                     state.mounted_devices.iter().find(|d| d.name == unmounted.name).cloned()
                 }
                 Err(e) => {
-                    state.status_message = format!("Mount failed: {}", e);
+                    state.set_error(format!("Mount failed: {}", e));
                     return Ok(false);
                 }
             }
@@ -842,6 +2393,7 @@ async fn handle_device_select(state: &mut BrowserState, _client: &SubsonicClient
     // Load synced content and auto-select
     state.load_and_select_synced_content(&device);
     state.selected_device = Some(device.clone());
+    update_session(|session| session.device_uuid = Some(device.uuid.clone()));
 
     // Count synced items
     let album_count = state.selected_albums.len();
@@ -867,67 +2419,223 @@ async fn handle_enter(state: &mut BrowserState, client: &SubsonicClient) -> Resu
     match &state.view {
         BrowseView::Artists => {
             if let Some(artist) = state.artists.get(actual_idx) {
-                state.status_message = format!("Loading albums for {}...", artist.name);
-                let artist_details = client.get_artist(&artist.id).await?;
-
-                // Cache album IDs for this artist (for artist-level selection)
-                let album_ids: Vec<String> = artist_details.album.iter().map(|a| a.id.clone()).collect();
-                state.artist_album_ids.insert(artist.id.clone(), album_ids);
-
-                state.albums = artist_details.album;
-                // Populate album cache for selection building
-                for album in &state.albums {
-                    state.album_cache.insert(album.id.clone(), album.clone());
+                let (artist_id, artist_name) = (artist.id.clone(), artist.name.clone());
+                state.push_nav();
+                open_artist(state, client, artist_id, artist_name).await?;
+            }
+        }
+        BrowseView::SearchResults { .. } => {
+            if let Some(SearchHit::Artist(artist)) = state.search_hits.get(actual_idx) {
+                let (artist_id, artist_name) = (artist.id.clone(), artist.name.clone());
+                state.push_nav();
+                open_artist(state, client, artist_id, artist_name).await?;
+            }
+        }
+        BrowseView::Genres => {
+            if let Some(genre) = state.cursor_genre() {
+                if !state.genre_album_ids.contains_key(&genre) {
+                    state.status_message = format!("Loading albums tagged {}...", genre);
+                    let albums = client.get_albums_by_genre(&genre).await?;
+                    state.cache_genre_albums(&genre, &albums);
+                    state.status_message.clear();
                 }
-                state.view = BrowseView::Albums {
-                    artist_id: artist.id.clone(),
-                    artist_name: artist.name.clone(),
-                };
-                state.clear_filter(); // Clear filter when navigating
-                state.list_state.select(Some(0));
-                state.status_message.clear();
+                state.push_nav();
+                state.show_genre_albums(genre);
             }
         }
-        BrowseView::Albums { .. } => {
-            if let Some(album) = state.albums.get(actual_idx) {
-                state.view = BrowseView::AlbumTracks {
-                    album: album.clone(),
-                };
+        BrowseView::Albums { .. } | BrowseView::GenreAlbums { .. } => {
+            if let Some(album) = state.albums.get(actual_idx).cloned() {
+                state.status_message = format!("Loading tracks of {}...", album.name);
+                state.album_songs = client.get_album(&album.id).await?.song;
+                state.status_message.clear();
+                state.push_nav();
+                state.view = BrowseView::AlbumTracks { album };
                 state.clear_filter();
                 state.list_state.select(Some(0));
             }
         }
         BrowseView::Playlists => {
-            if let Some(playlist) = state.playlists.get(actual_idx) {
-                state.view = BrowseView::PlaylistTracks {
-                    playlist: playlist.clone(),
-                };
+            if let Some(playlist) = state.playlists.get(actual_idx).cloned() {
+                state.push_nav();
+                state.view = BrowseView::PlaylistTracks { playlist };
                 state.clear_filter();
                 state.list_state.select(Some(0));
             }
         }
+        BrowseView::Folders { path } => {
+            if let Some(entry) = state.folder_entries.get(actual_idx).filter(|e| e.is_dir) {
+                let mut path = path.clone();
+                path.push((entry.id.clone(), entry.name.clone()));
+                state.push_nav();
+                open_folder(state, client, path).await?;
+            }
+        }
         _ => {}
     }
 
     Ok(())
 }
 
-async fn handle_back(state: &mut BrowserState, _client: &SubsonicClient) -> Result<()> {
-    match &state.view {
+/// Show an artist's albums in the albums view
+async fn open_artist(
+    state: &mut BrowserState,
+    client: &SubsonicClient,
+    artist_id: String,
+    artist_name: String,
+) -> Result<()> {
+    state.status_message = format!("Loading albums for {}...", artist_name);
+    let artist_details = client.get_artist(&artist_id).await?;
+
+    // Cache album IDs and objects for artist-level selection
+    state.cache_artist_albums(&artist_id, &artist_details.album);
+
+    state.albums = artist_details.album;
+    state.view = BrowseView::Albums {
+        artist_id,
+        artist_name,
+    };
+    state.sort_current_view();
+    state.clear_filter(); // Clear filter when navigating
+    state.list_state.select(Some(0));
+    state.status_message.clear();
+    Ok(())
+}
+
+/// Show the genres view, fetching the genres the first time
+async fn open_genres(state: &mut BrowserState, client: &SubsonicClient) -> Result<()> {
+    if state.genres.is_empty() {
+        state.status_message = "Loading genres...".to_string();
+        state.genres = client.get_genres().await?;
+        state.genres.sort_by_key(|g| g.value.to_lowercase());
+        state.status_message.clear();
+    }
+    state.show_genres();
+    Ok(())
+}
+
+/// Select the albums of the genre under the cursor, fetching them first if needed
+///
+/// With `toggle`, a genre whose albums are all selected is deselected instead.
+async fn select_genre(
+    state: &mut BrowserState,
+    client: &SubsonicClient,
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    toggle: bool,
+) -> Result<()> {
+    let Some(genre) = state.cursor_genre() else {
+        return Ok(());
+    };
+
+    if !state.genre_album_ids.contains_key(&genre) {
+        state.status_message = format!("Loading albums tagged {}...", genre);
+        terminal.draw(|f| draw_ui(f, state))?;
+
+        let albums = client.get_albums_by_genre(&genre).await?;
+        state.cache_genre_albums(&genre, &albums);
+        state.status_message.clear();
+    }
+
+    let selected = !(toggle && state.is_genre_selected(&genre));
+    state.set_genre_selected(&genre, selected);
+    Ok(())
+}
+
+/// Show a folder in the folder view (the top-level folders for an empty path)
+async fn open_folder(
+    state: &mut BrowserState,
+    client: &SubsonicClient,
+    path: Vec<(String, String)>,
+) -> Result<()> {
+    state.folder_entries = match path.last() {
+        None => {
+            if state.folder_roots.is_empty() {
+                state.status_message = "Loading folders...".to_string();
+                state.folder_roots = client
+                    .get_indexes()
+                    .await?
+                    .into_iter()
+                    .map(|f| FolderEntry {
+                        id: f.id,
+                        name: f.name,
+                        is_dir: true,
+                        duration: None,
+                    })
+                    .collect();
+                state.status_message.clear();
+            }
+            state.folder_roots.clone()
+        }
+        Some((id, _)) => client
+            .get_music_directory(id)
+            .await?
+            .child
+            .into_iter()
+            .map(|c| FolderEntry {
+                id: c.song.id,
+                name: c.song.title,
+                is_dir: c.is_dir,
+                duration: c.song.duration,
+            })
+            .collect(),
+    };
+
+    state.view = BrowseView::Folders { path };
+    state.clear_filter();
+    state.list_state.select(Some(0));
+    Ok(())
+}
+
+async fn handle_back(state: &mut BrowserState, client: &SubsonicClient) -> Result<()> {
+    // Views opened from somewhere go back there, to the item they were opened
+    // from; without a fitting nav entry, to the top of the parent list
+    let parent = match &state.view {
         BrowseView::Albums { .. } => {
-            state.view = BrowseView::Artists;
-            state.list_state.select(Some(0));
+            state.pop_nav(|v| matches!(v, BrowseView::Artists | BrowseView::SearchResults { .. }))
         }
         BrowseView::AlbumTracks { .. } => {
-            // Go back to albums view - need to know which artist
-            // For now, go to artists view
+            state.pop_nav(|v| matches!(v, BrowseView::Albums { .. } | BrowseView::GenreAlbums { .. }))
+        }
+        BrowseView::PlaylistTracks { .. } => state.pop_nav(|v| *v == BrowseView::Playlists),
+        BrowseView::GenreAlbums { .. } => state.pop_nav(|v| *v == BrowseView::Genres),
+        _ => None,
+    };
+    if let Some(entry) = parent {
+        state.restore_nav(entry);
+        return Ok(());
+    }
+
+    match &state.view {
+        BrowseView::Albums { .. } | BrowseView::AlbumTracks { .. } => {
             state.view = BrowseView::Artists;
+            state.clear_filter();
             state.list_state.select(Some(0));
         }
         BrowseView::PlaylistTracks { .. } => {
             state.view = BrowseView::Playlists;
+            state.clear_filter();
             state.list_state.select(Some(0));
         }
+        BrowseView::GenreAlbums { .. } => {
+            state.show_genres();
+        }
+        BrowseView::Genres => {
+            state.close_genres();
+        }
+        BrowseView::Folders { path } if !path.is_empty() => {
+            let mut path = path.clone();
+            path.pop();
+            let entry = state.pop_nav(|v| *v == BrowseView::Folders { path: path.clone() });
+            open_folder(state, client, path).await?;
+            if let Some(entry) = entry {
+                state.restore_cursor(entry.cursor.as_deref());
+            }
+        }
+        BrowseView::Selection => {
+            state.close_selection_view();
+        }
+        BrowseView::SearchResults { .. } => {
+            state.close_search_results();
+        }
         _ => {}
     }
     Ok(())
@@ -945,36 +2653,41 @@ async fn handle_toggle(
         BrowseView::Artists => {
             // Toggle all albums for this artist
             if let Some(artist) = state.artists.get(actual_idx) {
-                let artist_id = artist.id.clone();
-                let artist_name = artist.name.clone();
-
-                // If we haven't fetched this artist's albums yet, fetch them now
-                if !state.artist_album_ids.contains_key(&artist_id) {
-                    state.status_message = format!("Loading {}...", artist_name);
-                    terminal.draw(|f| draw_ui(f, state))?;
-
-                    let artist_details = client.get_artist(&artist_id).await?;
-                    let album_ids: Vec<String> = artist_details.album.iter().map(|a| a.id.clone()).collect();
-                    state.artist_album_ids.insert(artist_id.clone(), album_ids);
-                    // Cache album objects for selection building
-                    for album in artist_details.album {
-                        state.album_cache.insert(album.id.clone(), album);
-                    }
-                    state.status_message.clear();
-                }
-
-                state.toggle_artist_selection(&artist_id);
+                let (artist_id, artist_name) = (artist.id.clone(), artist.name.clone());
+                toggle_artist(state, client, terminal, &artist_id, &artist_name).await?;
             }
         }
-        BrowseView::Albums { .. } => {
+        BrowseView::SearchResults { .. } => match state.search_hits.get(actual_idx).cloned() {
+            Some(SearchHit::Artist(artist)) => {
+                toggle_artist(state, client, terminal, &artist.id, &artist.name).await?;
+            }
+            Some(SearchHit::Album(album)) => {
+                state.toggle_album(&album.id);
+            }
+            Some(SearchHit::Song(_)) => {
+                state.set_status("Select the song's album to sync it");
+            }
+            None => {}
+        },
+        BrowseView::SyncConfirmation => {
+            state.toggle_plan_entry(display_idx);
+        }
+        BrowseView::Selection => {
+            state.deselect_selection_row();
+        }
+        BrowseView::Albums { .. } | BrowseView::GenreAlbums { .. } => {
             if let Some(album) = state.albums.get(actual_idx) {
-                if state.selected_albums.contains(&album.id) {
-                    state.selected_albums.remove(&album.id);
-                } else {
-                    state.selected_albums.insert(album.id.clone());
-                }
-                // Update artist selection status
-                state.update_artist_selection_status();
+                let album_id = album.id.clone();
+                state.toggle_album(&album_id);
+            }
+        }
+        BrowseView::Genres => {
+            select_genre(state, client, terminal, true).await?;
+        }
+        BrowseView::AlbumTracks { album } => {
+            if let Some(song) = state.album_songs.get(actual_idx) {
+                let (album, song_id) = (album.clone(), song.id.clone());
+                state.toggle_track(&album, &song_id);
             }
         }
         BrowseView::Playlists => {
@@ -986,14 +2699,91 @@ async fn handle_toggle(
                 }
             }
         }
+        BrowseView::Folders { path } => {
+            let Some(entry) = state.folder_entries.get(actual_idx).cloned() else {
+                return Ok(());
+            };
+            if !entry.is_dir {
+                state.set_status("Select the folder to sync its tracks");
+                return Ok(());
+            }
+
+            // Find the album folders under this one the first time it's toggled
+            if !state.folder_album_ids.contains_key(&entry.id) {
+                state.status_message = format!("Scanning {}...", entry.name);
+                terminal.draw(|f| draw_ui(f, state))?;
+
+                let parent_name = path.last().map(|(_, name)| name.as_str());
+                let albums = client.get_folder_albums(&entry.id, parent_name).await?;
+                state.folder_album_ids.insert(
+                    entry.id.clone(),
+                    albums.iter().map(|a| a.id.clone()).collect(),
+                );
+                for album in albums {
+                    state.album_cache.insert(album.id.clone(), album);
+                }
+                state.status_message.clear();
+            }
+
+            state.toggle_folder_selection(&entry.id);
+        }
         _ => {}
     }
     Ok(())
 }
 
+/// Toggle all albums of an artist, fetching them first if needed
+async fn toggle_artist(
+    state: &mut BrowserState,
+    client: &SubsonicClient,
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    artist_id: &str,
+    artist_name: &str,
+) -> Result<()> {
+    // Pick up anything the background prefetch has fetched since the last tick
+    state.drain_prefetch();
+
+    // If the prefetch hasn't reached this artist yet, fetch it now
+    if !state.artist_album_ids.contains_key(artist_id) {
+        state.status_message = format!("Loading {}...", artist_name);
+        terminal.draw(|f| draw_ui(f, state))?;
+
+        let artist_details = client.get_artist(artist_id).await?;
+        state.cache_artist_albums(artist_id, &artist_details.album);
+        state.status_message.clear();
+    }
+
+    state.toggle_artist_selection(artist_id);
+    Ok(())
+}
+
+/// Select every artist, fetching the albums of those not loaded yet
+async fn select_all_artists(
+    state: &mut BrowserState,
+    client: &SubsonicClient,
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+) -> Result<()> {
+    if state.artists.iter().any(|a| !state.artist_album_ids.contains_key(&a.id)) {
+        state.status_message = "Loading albums for all artists...".to_string();
+        terminal.draw(|f| draw_ui(f, state))?;
+
+        state.fetch_missing_artist_albums(client).await?;
+        state.status_message.clear();
+    }
+
+    handle_select_all(state);
+    Ok(())
+}
+
 fn handle_select_all(state: &mut BrowserState) {
     match &state.view {
-        BrowseView::Albums { .. } => {
+        BrowseView::AlbumTracks { album } => {
+            let album_id = album.id.clone();
+            state.picked_tracks.remove(&album_id);
+            state.selected_albums.insert(album_id);
+            state.update_artist_selection_status();
+        }
+        BrowseView::Albums { .. } | BrowseView::GenreAlbums { .. } => {
             for album in &state.albums {
                 state.selected_albums.insert(album.id.clone());
             }
@@ -1005,13 +2795,11 @@ fn handle_select_all(state: &mut BrowserState) {
             }
         }
         BrowseView::Artists => {
-            // Select all visited artists
-            for artist_id in state.artist_album_ids.keys() {
-                if let Some(album_ids) = state.artist_album_ids.get(artist_id).cloned() {
-                    for album_id in album_ids {
-                        state.selected_albums.insert(album_id);
-                    }
-                    state.selected_artists.insert(artist_id.clone());
+            // Albums of every artist are fetched first (see `select_all_artists`)
+            for artist in &state.artists {
+                if let Some(album_ids) = state.artist_album_ids.get(&artist.id) {
+                    state.selected_albums.extend(album_ids.iter().cloned());
+                    state.selected_artists.insert(artist.id.clone());
                 }
             }
         }
@@ -1021,12 +2809,23 @@ fn handle_select_all(state: &mut BrowserState) {
 
 fn handle_deselect_all(state: &mut BrowserState) {
     match &state.view {
-        BrowseView::Albums { .. } => {
+        BrowseView::AlbumTracks { album } => {
+            let album_id = album.id.clone();
+            state.picked_tracks.remove(&album_id);
+            state.selected_albums.remove(&album_id);
+            state.update_artist_selection_status();
+        }
+        BrowseView::Albums { .. } | BrowseView::GenreAlbums { .. } => {
             for album in &state.albums {
                 state.selected_albums.remove(&album.id);
             }
             state.update_artist_selection_status();
         }
+        BrowseView::Genres => {
+            if let Some(genre) = state.cursor_genre() {
+                state.set_genre_selected(&genre, false);
+            }
+        }
         BrowseView::Playlists => {
             for playlist in &state.playlists {
                 state.selected_playlists.remove(&playlist.id);
@@ -1037,34 +2836,69 @@ fn handle_deselect_all(state: &mut BrowserState) {
             state.selected_albums.clear();
             state.selected_artists.clear();
         }
+        BrowseView::SearchResults { .. } => {
+            for hit in &state.search_hits {
+                if let SearchHit::Album(album) = hit {
+                    state.selected_albums.remove(&album.id);
+                }
+            }
+            state.update_artist_selection_status();
+        }
+        BrowseView::Selection => {
+            // Clear the whole selection
+            state.selected_albums.clear();
+            state.selected_artists.clear();
+            state.selected_playlists.clear();
+            state.list_state.select(Some(0));
+        }
         _ => {}
     }
 }
 
 async fn handle_tab(state: &mut BrowserState, client: &SubsonicClient) -> Result<()> {
     match &state.view {
-        BrowseView::Artists | BrowseView::Albums { .. } | BrowseView::AlbumTracks { .. } => {
-            // Switch to playlists
+        BrowseView::Artists
+        | BrowseView::Albums { .. }
+        | BrowseView::AlbumTracks { .. }
+        | BrowseView::Genres
+        | BrowseView::GenreAlbums { .. } => {
+            // Switch to playlists, leaving nothing to go back to
+            state.nav_stack.clear();
             if state.playlists.is_empty() {
                 state.status_message = "Loading playlists...".to_string();
                 state.playlists = client.get_playlists().await?;
                 state.status_message.clear();
             }
             state.view = BrowseView::Playlists;
+            state.sort_current_view();
+            state.clear_filter();
             state.list_state.select(Some(0));
         }
         BrowseView::Playlists | BrowseView::PlaylistTracks { .. } => {
+            // Switch to folders
+            state.nav_stack.clear();
+            open_folder(state, client, Vec::new()).await?;
+        }
+        BrowseView::Folders { .. } => {
             // Switch to artists
+            state.nav_stack.clear();
             if state.artists.is_empty() {
                 state.status_message = "Loading artists...".to_string();
                 state.artists = client.get_artists().await?;
                 state.status_message.clear();
             }
+            state.start_album_prefetch(client);
             state.view = BrowseView::Artists;
+            state.sort_current_view();
+            state.clear_filter();
             state.list_state.select(Some(0));
         }
-        BrowseView::DeviceSelection | BrowseView::SyncProgress | BrowseView::SyncConfirmation => {
-            // Don't switch views from device selection, sync progress, or confirmation
+        BrowseView::Selection
+        | BrowseView::SearchResults { .. }
+        | BrowseView::DeviceSelection
+        | BrowseView::SyncProgress
+        | BrowseView::SyncConfirmation => {
+            // Don't switch views from the selection view, search results, device selection, sync progress, or confirmation
         }
     }
     Ok(())
@@ -1073,19 +2907,26 @@ async fn handle_tab(state: &mut BrowserState, client: &SubsonicClient) -> Result
 async fn build_selection(state: &BrowserState, _client: &SubsonicClient) -> Result<SyncSelection> {
     let mut selection = SyncSelection::new();
 
-    // Add selected albums that are NOT already synced
+    // Add selected albums that are NOT already synced with the same tracks
+    // (outdated ones are, to pick up what changed)
     for album_id in &state.selected_albums {
-        if !state.synced_album_ids.contains(album_id)
-            && let Some(album) = state.album_cache.get(album_id)
-        {
+        let picked = state.picked_tracks.get(album_id);
+        let album = state.album_cache.get(album_id);
+        let synced = state.synced_album_ids.contains(album_id)
+            && picked == state.synced_picks.get(album_id)
+            && state.album_status(album_id, album.and_then(|a| a.song_count)) != SyncStatus::Outdated;
+        if !synced && let Some(album) = album {
             selection.albums.push(album.clone());
+            if let Some(picked) = picked {
+                selection.tracks.insert(album_id.clone(), picked.clone());
+            }
         }
     }
 
-    // Add selected playlists that are NOT already synced
+    // Add selected playlists that are NOT already synced and up to date
     for playlist_id in &state.selected_playlists {
-        if !state.synced_playlist_ids.contains(playlist_id)
-            && let Some(playlist) = state.playlists.iter().find(|p| &p.id == playlist_id)
+        if let Some(playlist) = state.find_playlist(playlist_id)
+            && !state.playlist_status(playlist).is_current()
         {
             selection.playlists.push(playlist.clone());
         }
@@ -1152,7 +2993,10 @@ fn draw_sync_progress(f: &mut Frame, state: &BrowserState) {
     let header_text = if state.sync_progress.is_complete {
         format!("Sync Complete - {}", device_name)
     } else {
-        format!("Syncing to {} ...", device_name)
+        match state.sync_progress.throughput_label(Instant::now()) {
+            Some(throughput) => format!("Syncing to {} ... {}", device_name, throughput),
+            None => format!("Syncing to {} ...", device_name),
+        }
     };
 
     let header_style = if state.sync_progress.is_complete {
@@ -1234,16 +3078,50 @@ fn draw_sync_progress(f: &mut Frame, state: &BrowserState) {
         })
         .collect();
 
+    let failure_count = state.sync_progress.failure_count();
+    let show_failures = state.sync_progress.is_complete && failure_count > 0;
+    let log_area = if show_failures {
+        Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(3),
+                Constraint::Length((failure_count as u16).min(8) + 2),
+            ])
+            .split(chunks[3])
+    } else {
+        Layout::default().constraints([Constraint::Min(0)]).split(chunks[3])
+    };
+
     let log = Paragraph::new(messages)
         .block(Block::default().title("Activity Log").borders(Borders::ALL))
         .wrap(Wrap { trim: false });
-    f.render_widget(log, chunks[3]);
+    f.render_widget(log, log_area[0]);
+
+    // Failed items, offered for retry once the sync is done
+    if show_failures {
+        let failed: Vec<ListItem> = state.sync_progress.failed_albums
+            .iter()
+            .map(|(_, label)| label.clone())
+            .chain(state.sync_progress.failed_playlists.iter().map(|(_, name)| format!("Playlist: {}", name)))
+            .map(|label| ListItem::new(format!("  ✗ {}", label)).style(Style::default().fg(Color::Red)))
+            .collect();
+        let failed_list = List::new(failed)
+            .block(Block::default().title(format!("Failed ({})", failure_count)).borders(Borders::ALL));
+        f.render_widget(failed_list, log_area[1]);
+    }
 
     // Footer
-    let help_text = if state.sync_progress.is_complete {
-        "Press q to finish"
+    let help_text = if show_failures {
+        format!(
+            "Press {} to retry {} failed item(s), {} to finish",
+            state.keys.primary(Action::RetryFailed),
+            failure_count,
+            state.keys.primary(Action::Quit)
+        )
+    } else if state.sync_progress.is_complete {
+        format!("Press {} to finish", state.keys.primary(Action::Quit))
     } else {
-        "Syncing in progress..."
+        "Syncing in progress...".to_string()
     };
 
     let footer = Paragraph::new(help_text)
@@ -1263,45 +3141,59 @@ fn draw_sync_confirmation(f: &mut Frame, state: &BrowserState) {
         ])
         .split(f.area());
 
-    let header = Paragraph::new("Sync Confirmation")
+    let heading = if state.plan_preview {
+        "Sync Preview (device will not be changed)"
+    } else {
+        "Sync Confirmation"
+    };
+    let header = Paragraph::new(heading)
         .style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
         .block(Block::default().borders(Borders::BOTTOM));
     f.render_widget(header, chunks[0]);
 
-    let mut lines = vec![];
-
-    if let Some(ref deletions) = state.pending_deletions
-        && (!deletions.albums.is_empty() || !deletions.playlists.is_empty())
-    {
-        lines.push(Line::styled("Will DELETE:", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)));
-        for (_, artist, album) in &deletions.albums {
-            lines.push(Line::styled(format!("  - {} - {}", artist, album), Style::default().fg(Color::Red)));
-        }
-        for (_, name) in &deletions.playlists {
-            lines.push(Line::styled(format!("  - Playlist: {}", name), Style::default().fg(Color::Red)));
-        }
-        lines.push(Line::from(""));
-    }
-
-    if let Some(ref selection) = state.sync_selection
-        && (!selection.albums.is_empty() || !selection.playlists.is_empty())
-    {
-        lines.push(Line::styled("Will ADD:", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)));
-        for album in &selection.albums {
-            let artist = album.artist.as_deref().unwrap_or("Unknown");
-            lines.push(Line::styled(format!("  + {} - {}", artist, album.name), Style::default().fg(Color::Green)));
-        }
-        for playlist in &selection.playlists {
-            lines.push(Line::styled(format!("  + Playlist: {}", playlist.name), Style::default().fg(Color::Green)));
-        }
-    }
+    let entries = state.plan_entries();
+    let delete_count = entries.iter().filter(|(is_delete, _)| *is_delete).count();
+    let items: Vec<ListItem> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, (is_delete, label))| {
+            let included = !state.plan_excluded.contains(&i);
+            let checkbox = if included { "[x]" } else { "[ ]" };
+            let (marker, color) = if *is_delete { ("DELETE", Color::Red) } else { ("ADD", Color::Green) };
+            let style = if included {
+                Style::default().fg(color)
+            } else {
+                Style::default().fg(Color::DarkGray).add_modifier(Modifier::CROSSED_OUT)
+            };
+            ListItem::new(format!("{} {:<6} {}", checkbox, marker, label)).style(style)
+        })
+        .collect();
 
-    let content = Paragraph::new(lines)
-        .block(Block::default().borders(Borders::ALL))
-        .wrap(Wrap { trim: false });
-    f.render_widget(content, chunks[1]);
+    let title = format!(
+        " {} DELETE {}, ADD {} ",
+        if state.plan_preview { "Would" } else { "Will" },
+        delete_count,
+        entries.len() - delete_count
+    );
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(list, chunks[1], &mut state.list_state.clone());
 
-    let footer = Paragraph::new("Press Enter to confirm, Esc to cancel")
+    let key = |action| state.keys.primary(action);
+    let footer_text = format!(
+        "{}{} navigate | {}: toggle item | {}: preview only [{}] | {}: {} | {}: cancel",
+        key(Action::MoveUp),
+        key(Action::MoveDown),
+        key(Action::Toggle),
+        key(Action::PreviewSync),
+        if state.plan_preview { "on" } else { "off" },
+        key(Action::Open),
+        if state.plan_preview { "finish preview" } else { "confirm" },
+        key(Action::Cancel)
+    );
+    let footer = Paragraph::new(footer_text)
         .style(Style::default().fg(Color::DarkGray))
         .block(Block::default().borders(Borders::TOP));
     f.render_widget(footer, chunks[2]);
@@ -1330,19 +3222,44 @@ fn draw_ui(f: &mut Frame, state: &BrowserState) {
         .split(f.area());
 
     // Header
+    let selection_rows = if state.view == BrowseView::Selection {
+        state.selection_rows()
+    } else {
+        Vec::new()
+    };
+    let selection_title = {
+        let size: u64 = selection_rows.iter().map(|r| estimated_size(r.duration.unwrap_or(0))).sum();
+        format!(
+            "Selection: {} album(s), {} playlist(s), ~{:.1} GB",
+            state.selected_albums.len(),
+            state.selected_playlists.len(),
+            size as f64 / 1_073_741_824.0
+        )
+    };
+    let search_title = match &state.view {
+        BrowseView::SearchResults { query } => {
+            format!("Server search: '{}' ({} results)", query, state.search_hits.len())
+        }
+        _ => String::new(),
+    };
     let title = match &state.view {
         BrowseView::Artists => "Artists",
         BrowseView::Albums { artist_name, .. } => artist_name,
         BrowseView::AlbumTracks { album } => &album.name,
         BrowseView::Playlists => "Playlists",
         BrowseView::PlaylistTracks { playlist } => &playlist.name,
+        BrowseView::Genres => "Genres",
+        BrowseView::GenreAlbums { genre } => genre,
+        BrowseView::Folders { path } => path.last().map(|(_, name)| name.as_str()).unwrap_or("Folders"),
+        BrowseView::Selection => &selection_title,
+        BrowseView::SearchResults { .. } => &search_title,
         BrowseView::DeviceSelection => "Select Device",
         BrowseView::SyncConfirmation => "Confirm Sync",
         BrowseView::SyncProgress => "Syncing...",
     };
 
     let selection_count = state.selected_albums.len() + state.selected_playlists.len();
-    let header_text = if selection_count > 0 {
+    let header_text = if selection_count > 0 && state.view != BrowseView::Selection {
         format!("{} ({} selected)", title, selection_count)
     } else {
         title.to_string()
@@ -1372,13 +3289,16 @@ fn draw_ui(f: &mut Frame, state: &BrowserState) {
         (0..state.playlists.len()).collect()
     };
 
+    // Usable row width inside the list borders and highlight symbol
+    let row_width = chunks[1].width.saturating_sub(4) as usize;
+
     // List
     let items: Vec<ListItem> = match &state.view {
         BrowseView::Artists => artist_indices
             .iter()
             .filter_map(|&i| state.artists.get(i))
             .map(|a| {
-                let album_count = a.album_count.map(|c| format!(" ({} albums)", c)).unwrap_or_default();
+                let album_count = a.album_count.map(|c| format!("{} albums", c)).unwrap_or_default();
 
                 // Check if artist is fully or partially selected
                 let (prefix, style) = if let Some(album_ids) = state.artist_album_ids.get(&a.id) {
@@ -1398,59 +3318,244 @@ fn draw_ui(f: &mut Frame, state: &BrowserState) {
                     ("    ", Style::default())
                 };
 
-                ListItem::new(format!("{}{}{}", prefix, a.name, album_count)).style(style)
+                let name = format!("{}{}", prefix, a.name);
+                ListItem::new(format_row(&name, &[(&album_count, COUNT_COLUMN_WIDTH)], row_width)).style(style)
             })
             .collect(),
-        BrowseView::Albums { .. } => album_indices
+        BrowseView::Albums { .. } | BrowseView::GenreAlbums { .. } => album_indices
             .iter()
             .filter_map(|&i| state.albums.get(i))
             .map(|a| {
                 let selected = state.selected_albums.contains(&a.id);
-                let synced = state.synced_album_ids.contains(&a.id);
-                let prefix = if selected { "[x] " } else { "[ ] " };
-                let suffix = if synced { " [SYNCED]" } else { "" };
+                let partial = selected && state.picked_tracks.contains_key(&a.id);
+                let status = state.album_status(&a.id, a.song_count);
+                let prefix = if partial {
+                    "[-] "
+                } else if selected {
+                    "[x] "
+                } else {
+                    "[ ] "
+                };
                 let year = a.year.map(|y| format!(" ({})", y)).unwrap_or_default();
-                let style = if selected {
+                let count = a.song_count.map(|c| format!("{} tracks", c)).unwrap_or_default();
+                let duration = a.duration.map(format_duration).unwrap_or_default();
+                let size = a.duration.map(format_estimated_size).unwrap_or_default();
+                // Outdated albums stand out even when selected, as synced ones usually are
+                let style = if partial || status == SyncStatus::Outdated {
+                    Style::default().fg(Color::Yellow)
+                } else if selected {
                     Style::default().fg(Color::Green)
-                } else if synced {
-                    Style::default().fg(Color::Cyan)
+                } else if let Some(color) = status.color() {
+                    Style::default().fg(color)
                 } else {
                     Style::default()
                 };
-                ListItem::new(format!("{}{}{}{}", prefix, a.name, year, suffix)).style(style)
+                let name = format!("{}{}{}", prefix, a.name, year);
+                let columns = [
+                    (status.label(), STATUS_COLUMN_WIDTH),
+                    (count.as_str(), COUNT_COLUMN_WIDTH),
+                    (duration.as_str(), DURATION_COLUMN_WIDTH),
+                    (size.as_str(), SIZE_COLUMN_WIDTH),
+                ];
+                ListItem::new(format_row(&name, &columns, row_width)).style(style)
             })
             .collect(),
         BrowseView::AlbumTracks { album } => {
-            vec![ListItem::new(format!(
-                "Album has {} tracks - press Backspace to go back",
-                album.song_count.unwrap_or(0)
-            ))]
+            if state.album_songs.is_empty() {
+                vec![ListItem::new("Album has no tracks - press Backspace to go back")]
+            } else {
+                let indices: Vec<usize> = if !state.filtered_indices.is_empty() {
+                    state.filtered_indices.clone()
+                } else {
+                    (0..state.album_songs.len()).collect()
+                };
+                indices
+                    .iter()
+                    .filter_map(|&i| state.album_songs.get(i))
+                    .map(|s| {
+                        let picked = state.is_track_picked(&album.id, &s.id);
+                        let prefix = if picked { "[x] " } else { "[ ] " };
+                        let number = s.track.map(|n| format!("{:02}. ", n)).unwrap_or_default();
+                        let duration = s.duration.map(format_duration).unwrap_or_default();
+                        let style = if picked { Style::default().fg(Color::Green) } else { Style::default() };
+                        let name = format!("{}{}{}", prefix, number, s.title);
+                        ListItem::new(format_row(&name, &[(&duration, DURATION_COLUMN_WIDTH)], row_width)).style(style)
+                    })
+                    .collect()
+            }
         }
         BrowseView::Playlists => playlist_indices
             .iter()
             .filter_map(|&i| state.playlists.get(i))
             .map(|p| {
                 let selected = state.selected_playlists.contains(&p.id);
-                let synced = state.synced_playlist_ids.contains(&p.id);
+                let status = state.playlist_status(p);
                 let prefix = if selected { "[x] " } else { "[ ] " };
-                let suffix = if synced { " [SYNCED]" } else { "" };
-                let count = p.song_count.map(|c| format!(" ({} tracks)", c)).unwrap_or_default();
-                let style = if selected {
+                let count = p.song_count.map(|c| format!("{} tracks", c)).unwrap_or_default();
+                let duration = p.duration.map(format_duration).unwrap_or_default();
+                let size = p.duration.map(format_estimated_size).unwrap_or_default();
+                let style = if status == SyncStatus::Outdated {
+                    Style::default().fg(Color::Yellow)
+                } else if selected {
                     Style::default().fg(Color::Green)
-                } else if synced {
-                    Style::default().fg(Color::Cyan)
+                } else if let Some(color) = status.color() {
+                    Style::default().fg(color)
                 } else {
                     Style::default()
                 };
-                ListItem::new(format!("{}{}{}{}", prefix, p.name, count, suffix)).style(style)
+                let name = format!("{}{}", prefix, p.name);
+                let columns = [
+                    (status.label(), STATUS_COLUMN_WIDTH),
+                    (count.as_str(), COUNT_COLUMN_WIDTH),
+                    (duration.as_str(), DURATION_COLUMN_WIDTH),
+                    (size.as_str(), SIZE_COLUMN_WIDTH),
+                ];
+                ListItem::new(format_row(&name, &columns, row_width)).style(style)
             })
             .collect(),
+        BrowseView::Genres => {
+            let indices: Vec<usize> = if !state.filtered_indices.is_empty() {
+                state.filtered_indices.clone()
+            } else {
+                (0..state.genres.len()).collect()
+            };
+            indices
+                .iter()
+                .filter_map(|&i| state.genres.get(i))
+                .map(|g| {
+                    let album_count = g.album_count.map(|c| format!("{} albums", c)).unwrap_or_default();
+
+                    // Genres show a checkbox once their albums are known
+                    let (prefix, style) = match state.genre_album_ids.get(&g.value) {
+                        Some(ids) if !ids.is_empty() => {
+                            let selected = ids.iter().filter(|id| state.selected_albums.contains(*id)).count();
+                            if selected == ids.len() {
+                                ("[x] ", Style::default().fg(Color::Green))
+                            } else if selected > 0 {
+                                ("[-] ", Style::default().fg(Color::Yellow))
+                            } else {
+                                ("[ ] ", Style::default())
+                            }
+                        }
+                        _ => ("    ", Style::default()),
+                    };
+                    let name = format!("{}{}", prefix, g.value);
+                    ListItem::new(format_row(&name, &[(&album_count, COUNT_COLUMN_WIDTH)], row_width)).style(style)
+                })
+                .collect()
+        }
         BrowseView::PlaylistTracks { playlist } => {
             vec![ListItem::new(format!(
                 "Playlist has {} tracks - press Backspace to go back",
                 playlist.song_count.unwrap_or(0)
             ))]
         }
+        BrowseView::Folders { .. } => {
+            let indices: Vec<usize> = if !state.filtered_indices.is_empty() {
+                state.filtered_indices.clone()
+            } else {
+                (0..state.folder_entries.len()).collect()
+            };
+            indices
+                .iter()
+                .filter_map(|&i| state.folder_entries.get(i))
+                .map(|e| {
+                    if !e.is_dir {
+                        let duration = e.duration.map(format_duration).unwrap_or_default();
+                        let name = format!("    {}", e.name);
+                        return ListItem::new(format_row(&name, &[(&duration, DURATION_COLUMN_WIDTH)], row_width))
+                            .style(Style::default().fg(Color::DarkGray));
+                    }
+
+                    // Folders show a checkbox once their album folders are known
+                    let (prefix, style) = match state.folder_album_ids.get(&e.id) {
+                        Some(ids) if !ids.is_empty() => {
+                            let selected = ids.iter().filter(|id| state.selected_albums.contains(*id)).count();
+                            if selected == ids.len() {
+                                ("[x] ", Style::default().fg(Color::Green))
+                            } else if selected > 0 {
+                                ("[-] ", Style::default().fg(Color::Yellow))
+                            } else {
+                                ("[ ] ", Style::default())
+                            }
+                        }
+                        _ => ("    ", Style::default()),
+                    };
+                    // Folder listings don't report song counts
+                    let status = state.album_status(&e.id, None);
+                    let name = format!("{}{}/", prefix, e.name);
+                    ListItem::new(format_row(&name, &[(status.label(), STATUS_COLUMN_WIDTH)], row_width)).style(style)
+                })
+                .collect()
+        }
+        BrowseView::Selection => {
+            if selection_rows.is_empty() {
+                vec![ListItem::new("Nothing selected - press Space on albums, folders or playlists to select them")]
+            } else {
+                selection_rows
+                    .iter()
+                    .map(|row| {
+                        let duration = row.duration.map(format_duration).unwrap_or_default();
+                        let name = format!("[x] {}", row.label);
+                        ListItem::new(format_row(&name, &[(&duration, DURATION_COLUMN_WIDTH)], row_width))
+                            .style(Style::default().fg(Color::Green))
+                    })
+                    .collect()
+            }
+        }
+        BrowseView::SearchResults { .. } => {
+            if state.search_hits.is_empty() {
+                vec![ListItem::new("Nothing on the server matches - press S to search again")]
+            } else {
+                state
+                    .search_hits
+                    .iter()
+                    .map(|hit| match hit {
+                        SearchHit::Artist(a) => {
+                            let selected = state.is_artist_selected(&a.id);
+                            let prefix = if selected { "[x] " } else { "[ ] " };
+                            let album_count = a.album_count.map(|c| format!("{} albums", c)).unwrap_or_default();
+                            let name = format!("{}Artist: {}", prefix, a.name);
+                            let style = if selected { Style::default().fg(Color::Green) } else { Style::default() };
+                            ListItem::new(format_row(&name, &[(&album_count, COUNT_COLUMN_WIDTH)], row_width)).style(style)
+                        }
+                        SearchHit::Album(a) => {
+                            let selected = state.selected_albums.contains(&a.id);
+                            let status = state.album_status(&a.id, a.song_count);
+                            let prefix = if selected { "[x] " } else { "[ ] " };
+                            let duration = a.duration.map(format_duration).unwrap_or_default();
+                            let style = if status == SyncStatus::Outdated {
+                                Style::default().fg(Color::Yellow)
+                            } else if selected {
+                                Style::default().fg(Color::Green)
+                            } else if let Some(color) = status.color() {
+                                Style::default().fg(color)
+                            } else {
+                                Style::default()
+                            };
+                            let name = format!(
+                                "{}Album: {} - {}",
+                                prefix,
+                                a.artist.as_deref().unwrap_or("Unknown Artist"),
+                                a.name
+                            );
+                            let columns = [(status.label(), STATUS_COLUMN_WIDTH), (duration.as_str(), DURATION_COLUMN_WIDTH)];
+                            ListItem::new(format_row(&name, &columns, row_width)).style(style)
+                        }
+                        SearchHit::Song(s) => {
+                            let duration = s.duration.map(format_duration).unwrap_or_default();
+                            let name = format!(
+                                "    Song: {} - {}",
+                                s.artist.as_deref().unwrap_or("Unknown Artist"),
+                                s.title
+                            );
+                            ListItem::new(format_row(&name, &[(&duration, DURATION_COLUMN_WIDTH)], row_width))
+                                .style(Style::default().fg(Color::DarkGray))
+                        }
+                    })
+                    .collect()
+            }
+        }
         BrowseView::DeviceSelection => {
             let mut items: Vec<ListItem> = Vec::new();
 
@@ -1458,10 +3563,18 @@ fn draw_ui(f: &mut Frame, state: &BrowserState) {
             for device in &state.mounted_devices {
                 let label = device.label.as_deref().unwrap_or("(no label)");
                 let free_gb = device.free_space as f64 / 1_073_741_824.0;
-                items.push(ListItem::new(format!(
-                    "  {} - {} ({:.1} GB free)",
-                    device.name, label, free_gb
-                )).style(Style::default().fg(Color::Green)));
+                if state.too_small(device) {
+                    let needed_gb = state.space_needed[&device.uuid] as f64 / 1_073_741_824.0;
+                    items.push(ListItem::new(format!(
+                        "  {} - {} ({:.1} GB free, ~{:.1} GB needed)",
+                        device.name, label, free_gb, needed_gb
+                    )).style(Style::default().fg(Color::DarkGray)));
+                } else {
+                    items.push(ListItem::new(format!(
+                        "  {} - {} ({:.1} GB free)",
+                        device.name, label, free_gb
+                    )).style(Style::default().fg(Color::Green)));
+                }
             }
 
             // Add unmounted devices
@@ -1510,12 +3623,25 @@ fn draw_ui(f: &mut Frame, state: &BrowserState) {
         String::new()
     };
 
+    let sort = state.sort_mode.label();
+    let key = |action| state.keys.primary(action);
+    let nav = format!("{}/{}: Navigate", key(Action::MoveUp), key(Action::MoveDown));
+    let (open, back, toggle) = (key(Action::Open), key(Action::Back), key(Action::Toggle));
+    let all_none = format!("{}/{}", key(Action::SelectAll), key(Action::DeselectAll));
+    let (sort_key, hide, search) = (key(Action::CycleSort), key(Action::HideSynced), key(Action::Filter));
+    let (device, sync, done) = (key(Action::SelectDevice), key(Action::StartSync), key(Action::Quit));
     let help_text = match &state.view {
-        BrowseView::Artists => format!("↑/↓: Navigate | Space: Select | /: Search | ?: Help | d: Device | s: Sync | q: Done{}", device_info),
-        BrowseView::Albums { .. } => format!("↑/↓: Navigate | Space: Select | a/A: All/None | /: Search | d: Device | s: Sync | q: Done{}", device_info),
-        BrowseView::Playlists => format!("↑/↓: Navigate | Space: Select | a/A: All/None | /: Search | d: Device | s: Sync | q: Done{}", device_info),
-        BrowseView::DeviceSelection => "↑/↓: Navigate | Enter: Select device | Backspace/q: Cancel".to_string(),
-        _ => "Backspace: Back | q: Done".to_string(),
+        BrowseView::Artists => format!("{nav} | {toggle}: Select | {sort_key}: Sort ({sort}) | {search}: Search | {}: Help | {device}: Device | {sync}: Sync | {done}: Done{device_info}", key(Action::Help)),
+        BrowseView::Albums { .. } => format!("{nav} | {open}: Tracks | {toggle}: Select | {all_none}: All/None | {sort_key}: Sort ({sort}) | {hide}: Hide synced | {search}: Search | {device}: Device | {sync}: Sync | {done}: Done{device_info}"),
+        BrowseView::GenreAlbums { .. } => format!("{nav} | {open}: Tracks | {toggle}: Select | {all_none}: All/None | {sort_key}: Sort ({sort}) | {hide}: Hide synced | {back}: Genres | {sync}: Sync | {done}: Done{device_info}"),
+        BrowseView::Genres => format!("{nav} | {open}: Albums | {toggle}/{}: Select genre | {}: Deselect | {back}: Back | {search}: Search | {sync}: Sync | {done}: Done{device_info}", key(Action::SelectAll), key(Action::DeselectAll)),
+        BrowseView::AlbumTracks { .. } => format!("{nav} | {toggle}: Select track | {all_none}: Whole album/None | {back}: Back | {search}: Search | {sync}: Sync | {done}: Done{device_info}"),
+        BrowseView::Playlists => format!("{nav} | {toggle}: Select | {all_none}: All/None | {sort_key}: Sort ({sort}) | {hide}: Hide synced | {search}: Search | {device}: Device | {sync}: Sync | {done}: Done{device_info}"),
+        BrowseView::Folders { .. } => format!("{nav} | {open}: Open | {toggle}: Select folder | {back}: Up | {search}: Search | {device}: Device | {sync}: Sync | {done}: Done{device_info}"),
+        BrowseView::SearchResults { .. } => format!("{nav} | {toggle}: Select | {open}: Open artist | {}: New search | {back}: Back | {sync}: Sync | {done}: Done{device_info}", key(Action::Search)),
+        BrowseView::Selection => format!("{nav} | {toggle}: Deselect | {}: Clear all | {}/{back}: Back | {device}: Device | {sync}: Sync | {done}: Done{device_info}", key(Action::DeselectAll), key(Action::ShowSelection)),
+        BrowseView::DeviceSelection => format!("{nav} | {open}: Select device | {back}/{done}: Cancel"),
+        _ => format!("{back}: Back | {done}: Done"),
     };
 
     let footer = Paragraph::new(help_text)
@@ -1525,7 +3651,9 @@ fn draw_ui(f: &mut Frame, state: &BrowserState) {
 
     // Search input overlay
     if state.search_mode || !state.search_query.is_empty() {
-        let search_text = if state.search_mode {
+        let search_text = if state.search_mode && state.server_search {
+            format!("Search server: {}█", state.search_query)
+        } else if state.search_mode {
             format!("Search: {}█", state.search_query)
         } else {
             format!("Filter: {} (Esc to clear)", state.search_query)
@@ -1537,55 +3665,99 @@ fn draw_ui(f: &mut Frame, state: &BrowserState) {
         };
         let search = Paragraph::new(search_text)
             .style(search_style)
-            .block(Block::default().borders(Borders::ALL).title("Search"));
+            .block(Block::default().borders(Borders::ALL).title(if state.server_search {
+                "Search the whole library (Enter to search)".to_string()
+            } else if state.exact_search {
+                format!("Search (exact, {}: fuzzy)", state.keys.primary(Action::ExactSearch))
+            } else {
+                format!("Search (fuzzy, {}: exact)", state.keys.primary(Action::ExactSearch))
+            }));
         let area = centered_rect(60, 3, f.area());
         f.render_widget(search, area);
     }
 
     // Help overlay
     if state.show_help {
-        let help_lines = vec![
-            Line::from("Keyboard Shortcuts"),
-            Line::from(""),
-            Line::styled("Navigation", Style::default().add_modifier(Modifier::BOLD)),
-            Line::from("  ↑/k, ↓/j    Move up/down"),
-            Line::from("  Enter/l     Enter/expand"),
-            Line::from("  Backspace/h Go back"),
-            Line::from("  Tab         Switch Artists/Playlists"),
-            Line::from(""),
-            Line::styled("Selection", Style::default().add_modifier(Modifier::BOLD)),
-            Line::from("  Space       Toggle selection"),
-            Line::from("  a           Select all in view"),
-            Line::from("  A           Deselect all in view"),
-            Line::from(""),
-            Line::styled("Search & Actions", Style::default().add_modifier(Modifier::BOLD)),
-            Line::from("  /           Search/filter"),
-            Line::from("  d           Select device"),
-            Line::from("  s           Start sync"),
-            Line::from("  q, Esc      Quit/Cancel"),
-            Line::from(""),
-            Line::styled("Press any key to close", Style::default().fg(Color::DarkGray)),
+        let sections = [
+            ("Navigation", &[Action::MoveUp, Action::MoveDown, Action::Open, Action::Back, Action::SwitchView, Action::Genres, Action::CycleSort][..]),
+            ("Selection", &[Action::Toggle, Action::SelectAll, Action::DeselectAll, Action::HideSynced, Action::ShowSelection][..]),
+            ("Search & Actions", &[
+                Action::Filter,
+                Action::ExactSearch,
+                Action::Search,
+                Action::Refresh,
+                Action::SelectDevice,
+                Action::StartSync,
+                Action::PreviewSync,
+                Action::RetryFailed,
+                Action::Cancel,
+                Action::Quit,
+            ][..]),
         ];
+        let mut help_lines = vec![Line::from("Keyboard Shortcuts")];
+        for (title, actions) in sections {
+            help_lines.push(Line::from(""));
+            help_lines.push(Line::styled(title, Style::default().add_modifier(Modifier::BOLD)));
+            for &action in actions {
+                help_lines.push(Line::from(format!("  {:<15} {}", state.keys.label(action), action.description())));
+            }
+        }
+        help_lines.push(Line::from(""));
+        help_lines.push(Line::styled("Press any key to close", Style::default().fg(Color::DarkGray)));
         let help_popup = Paragraph::new(help_lines)
             .block(Block::default()
                 .borders(Borders::ALL)
                 .title("Help")
                 .style(Style::default().bg(Color::Black)));
-        let area = centered_rect(50, 22, f.area());
+        let area = centered_rect(60, 34, f.area());
         f.render_widget(ratatui::widgets::Clear, area);
         f.render_widget(help_popup, area);
     }
 
     // Status message overlay
     if !state.status_message.is_empty() && !state.show_help {
+        let (color, block) = if state.status_is_error {
+            (Color::Red, Block::default().borders(Borders::ALL).title("Error (press any key)"))
+        } else {
+            (Color::Yellow, Block::default().borders(Borders::ALL))
+        };
         let status = Paragraph::new(state.status_message.clone())
-            .style(Style::default().fg(Color::Yellow))
-            .block(Block::default().borders(Borders::ALL));
+            .style(Style::default().fg(color))
+            .block(block);
         let area = centered_rect(50, 3, f.area());
         f.render_widget(status, area);
     }
 }
 
+/// Width of the sync status column in list rows (fits "[OUTDATED]")
+const STATUS_COLUMN_WIDTH: usize = 10;
+/// Width of the track/album count column in list rows
+const COUNT_COLUMN_WIDTH: usize = 11;
+/// Width of the duration column in list rows (fits "999:59:59")
+const DURATION_COLUMN_WIDTH: usize = 9;
+/// Width of the estimated size column in list rows (fits "~1023 MB")
+const SIZE_COLUMN_WIDTH: usize = 8;
+
+/// Format a duration in seconds as `m:ss` or `h:mm:ss`
+fn format_duration(seconds: u32) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, (seconds % 3600) / 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// Format the estimated on-device size of audio lasting `seconds`, e.g. `~85 MB` or `~1.2 GB`
+fn format_estimated_size(seconds: u32) -> String {
+    let megabytes = estimated_size(seconds) as f64 / 1_048_576.0;
+    if megabytes < 1024.0 {
+        format!("~{:.0} MB", megabytes)
+    } else {
+        format!("~{:.1} GB", megabytes / 1024.0)
+    }
+}
+
 fn centered_rect(percent_x: u16, height: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -1611,28 +3783,873 @@ impl SyncSelection {
     const SELECTION_FILE: &'static str = ".nutune-selection.json";
 
     pub fn save(&self) -> Result<()> {
-        let path = dirs::cache_dir()
-            .unwrap_or_else(|| std::path::PathBuf::from("."))
-            .join(Self::SELECTION_FILE);
+        self.save_to(&crate::config::cache_dir()?.join(Self::SELECTION_FILE))
+    }
+
+    pub fn load() -> Result<Self> {
+        Self::load_from(&crate::config::cache_dir()?.join(Self::SELECTION_FILE))
+    }
+
+    /// Save the selection last made for a device, by device UUID
+    pub fn save_for(&self, device_uuid: &str) -> Result<()> {
+        self.save_to(&Self::device_path(device_uuid)?)
+    }
+
+    /// Load the selection last made for a device, or an empty one
+    pub fn load_for(device_uuid: &str) -> Result<Self> {
+        Self::load_from(&Self::device_path(device_uuid)?)
+    }
+
+    /// Drop unsynced albums and playlists from the selection last made for a device
+    ///
+    /// Otherwise the browser would select them again for the next sync.
+    pub fn forget_for(device_uuid: &str, album_ids: &[String], playlist_ids: &[String]) -> Result<()> {
+        Self::forget_in(&Self::device_path(device_uuid)?, album_ids, playlist_ids)
+    }
+
+    fn forget_in(path: &std::path::Path, album_ids: &[String], playlist_ids: &[String]) -> Result<()> {
+        if !path.exists() {
+            return Ok(());
+        }
+
+        let mut selection = Self::load_from(path)?;
+        for id in album_ids {
+            selection.remove_album(id);
+        }
+        selection.playlists.retain(|p| !playlist_ids.contains(&p.id));
+        selection.save_to(path)
+    }
+
+    fn device_path(device_uuid: &str) -> Result<std::path::PathBuf> {
+        Ok(crate::config::cache_dir()?.join(format!("selection-{}.json", device_uuid)))
+    }
 
+    fn save_to(&self, path: &std::path::Path) -> Result<()> {
         let content = serde_json::to_string_pretty(self)?;
-        std::fs::write(&path, content)?;
+        crate::config::write_app_file(path, &content)?;
         debug!("Saved selection to {}", path.display());
         Ok(())
     }
 
-    pub fn load() -> Result<Self> {
-        let path = dirs::cache_dir()
-            .unwrap_or_else(|| std::path::PathBuf::from("."))
-            .join(Self::SELECTION_FILE);
-
+    fn load_from(path: &std::path::Path) -> Result<Self> {
         if !path.exists() {
             return Ok(Self::new());
         }
 
-        let content = std::fs::read_to_string(&path)?;
+        let content = std::fs::read_to_string(path)?;
         let selection: Self = serde_json::from_str(&content)?;
         debug!("Loaded selection from {}", path.display());
         Ok(selection)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confirmed_plan_drops_toggled_entries() {
+        let mut state = BrowserState::new(BrowseView::SyncConfirmation);
+        state.sync_selection = Some(SyncSelection::new());
+        state.pending_deletions = Some(DeletionSelection {
+            albums: vec![
+                ("a1".to_string(), "Artist".to_string(), "One".to_string()),
+                ("a2".to_string(), "Artist".to_string(), "Two".to_string()),
+            ],
+            playlists: vec![("p1".to_string(), "Mix".to_string())],
+        });
+
+        assert_eq!(state.plan_entries().len(), 3);
+        state.toggle_plan_entry(1);
+        state.toggle_plan_entry(2);
+        state.toggle_plan_entry(2);
+
+        let (_, deletions) = state.take_confirmed_plan().unwrap();
+        assert_eq!(deletions.albums.len(), 1);
+        assert_eq!(deletions.albums[0].0, "a1");
+        assert_eq!(deletions.playlists.len(), 1);
+        assert!(state.plan_excluded.is_empty());
+    }
+
+    #[test]
+    fn test_selection_view_lists_and_deselects() {
+        let mut state = BrowserState::new(BrowseView::Playlists);
+        for (id, artist, name) in [("a1", "Zed", "Last"), ("a2", "Abba", "First")] {
+            let album = Album {
+                id: id.to_string(),
+                name: name.to_string(),
+                artist: Some(artist.to_string()),
+                artist_id: None,
+                cover_art: None,
+                cover_art_ids: Vec::new(),
+                song_count: None,
+                duration: Some(600),
+                year: None,
+                genre: None,
+                created: None,
+                source: AlbumSource::Id3,
+            };
+            state.album_cache.insert(id.to_string(), album);
+            state.selected_albums.insert(id.to_string());
+        }
+        state.playlists = vec![playlist("p1")];
+        state.selected_playlists.insert("p1".to_string());
+
+        state.open_selection_view();
+        assert_eq!(state.view, BrowseView::Selection);
+        let labels: Vec<String> = state.selection_rows().into_iter().map(|r| r.label).collect();
+        assert_eq!(labels, ["Abba - First", "Zed - Last", "Playlist: p1"]);
+
+        // Deselecting the last row moves the cursor up
+        state.list_state.select(Some(2));
+        state.deselect_selection_row();
+        assert!(state.selected_playlists.is_empty());
+        assert_eq!(state.list_state.selected(), Some(1));
+
+        state.list_state.select(Some(0));
+        state.deselect_selection_row();
+        assert_eq!(state.selected_albums.iter().collect::<Vec<_>>(), ["a1"]);
+
+        state.close_selection_view();
+        assert_eq!(state.view, BrowseView::Playlists);
+    }
+
+    #[test]
+    fn test_genre_selects_its_albums() {
+        let mut state = BrowserState::new(BrowseView::Playlists);
+        state.genres = vec![Genre {
+            value: "Jazz".to_string(),
+            song_count: None,
+            album_count: Some(2),
+        }];
+        let albums: Vec<Album> = ["Kind of Blue", "Blue Train"]
+            .iter()
+            .map(|name| serde_json::from_value(serde_json::json!({"id": name, "name": name})).unwrap())
+            .collect();
+        state.cache_genre_albums("Jazz", &albums);
+
+        state.show_genres();
+        assert_eq!(state.cursor_genre().as_deref(), Some("Jazz"));
+        state.set_genre_selected("Jazz", true);
+        assert!(state.is_genre_selected("Jazz"));
+        assert_eq!(state.selected_albums.len(), 2);
+
+        state.show_genre_albums("Jazz".to_string());
+        assert_eq!(state.current_list_len(), 2);
+        state.show_genres();
+        state.set_genre_selected("Jazz", false);
+        assert!(state.selected_albums.is_empty());
+
+        // Leaving the genres goes back to where they were opened from
+        state.close_genres();
+        assert_eq!(state.view, BrowseView::Playlists);
+    }
+
+    #[test]
+    fn test_search_results_cache_albums_and_return() {
+        let mut state = BrowserState::new(BrowseView::Playlists);
+        let album = Album {
+            id: "a1".to_string(),
+            name: "Found".to_string(),
+            artist: Some("Artist".to_string()),
+            artist_id: None,
+            cover_art: None,
+            cover_art_ids: Vec::new(),
+            song_count: None,
+            duration: None,
+            year: None,
+            genre: None,
+            created: None,
+            source: AlbumSource::Id3,
+        };
+        let result = SearchResult {
+            artist: vec![artist("1", "Artist")],
+            album: vec![album],
+            song: Vec::new(),
+        };
+
+        state.show_search_results("found".to_string(), result.clone());
+        assert_eq!(state.current_list_len(), 2);
+        assert!(state.album_cache.contains_key("a1"));
+
+        // A second search keeps the original view to return to
+        state.show_search_results("again".to_string(), result);
+        assert_eq!(state.view, BrowseView::SearchResults { query: "again".to_string() });
+
+        state.close_search_results();
+        assert_eq!(state.view, BrowseView::Playlists);
+        assert!(state.search_hits.is_empty());
+    }
+
+    #[test]
+    fn test_preview_reports_plan_without_syncing() {
+        let mut state = BrowserState::new(BrowseView::Artists);
+        let deletions = DeletionSelection {
+            albums: vec![
+                ("a1".to_string(), "Artist".to_string(), "One".to_string()),
+                ("a2".to_string(), "Artist".to_string(), "Two".to_string()),
+            ],
+            playlists: Vec::new(),
+        };
+        state.show_plan(SyncSelection::new(), deletions, true);
+        assert_eq!(state.view, BrowseView::SyncConfirmation);
+
+        state.toggle_plan_entry(0);
+        state.finish_preview();
+
+        assert_eq!(state.view, BrowseView::Artists);
+        assert!(state.sync_selection.is_none());
+        assert!(state.pending_deletions.is_none());
+        assert!(!state.plan_preview);
+        assert!(state.status_message.contains("would delete 1 album(s)"));
+    }
+
+    #[tokio::test]
+    async fn test_picking_album_tracks() {
+        let album = Album {
+            id: "a1".to_string(),
+            name: "Compilation".to_string(),
+            artist: Some("Various Artists".to_string()),
+            artist_id: None,
+            cover_art: None,
+            cover_art_ids: Vec::new(),
+            song_count: Some(2),
+            duration: None,
+            year: None,
+            genre: None,
+            created: None,
+            source: AlbumSource::Id3,
+        };
+        let song = |id: &str| serde_json::from_value::<Song>(serde_json::json!({ "id": id, "title": id })).unwrap();
+        let mut state = BrowserState::new(BrowseView::AlbumTracks { album: album.clone() });
+        state.album_songs = vec![song("s1"), song("s2")];
+        let picks = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<BTreeSet<_>>();
+
+        state.toggle_track(&album, "s1");
+        assert!(state.is_track_picked("a1", "s1") && !state.is_track_picked("a1", "s2"));
+        assert_eq!(state.picked_tracks["a1"], picks(&["s1"]));
+
+        // Picking every track selects the album whole
+        state.toggle_track(&album, "s2");
+        assert!(state.selected_albums.contains("a1"));
+        assert!(state.picked_tracks.is_empty());
+
+        state.toggle_track(&album, "s1");
+        let client = SubsonicClient::new("http://server", "user", "pass").unwrap();
+        let selection = build_selection(&state, &client).await.unwrap();
+        assert_eq!(selection.albums.len(), 1);
+        assert_eq!(selection.tracks["a1"], picks(&["s2"]));
+
+        // Already on the device with the same pick, so nothing to sync
+        state.synced_album_ids.insert("a1".to_string());
+        state.synced_picks.insert("a1".to_string(), picks(&["s2"]));
+        assert!(build_selection(&state, &client).await.unwrap().is_empty());
+
+        state.toggle_track(&album, "s2");
+        assert!(!state.selected_albums.contains("a1"));
+        assert!(state.picked_tracks.is_empty());
+    }
+
+    fn artist(id: &str, name: &str) -> Artist {
+        Artist {
+            id: id.to_string(),
+            name: name.to_string(),
+            album_count: None,
+            cover_art: None,
+        }
+    }
+
+    #[test]
+    fn test_refresh_keeps_cursor_and_selection() {
+        let mut state = BrowserState::new(BrowseView::Artists);
+        state.artists = vec![artist("1", "Alpha"), artist("2", "Beta"), artist("3", "Gamma")];
+        state.selected_albums.insert("album-of-gone-artist".to_string());
+        state.list_state.select(Some(1));
+
+        state.apply_refresh(LibraryRefresh::Artists(vec![
+            artist("0", "Aardvark"),
+            artist("1", "Alpha"),
+            artist("2", "Beta"),
+        ]));
+
+        assert_eq!(state.artists.len(), 3);
+        assert_eq!(state.cursor_item_id().as_deref(), Some("2"));
+        assert!(state.selected_albums.contains("album-of-gone-artist"));
+        assert_eq!(state.status_message, "Library refreshed (1 new)");
+    }
+
+    #[tokio::test]
+    async fn test_back_returns_to_the_item_opened() {
+        let album = |id: &str| serde_json::from_value::<Album>(serde_json::json!({ "id": id, "name": id })).unwrap();
+        let client = SubsonicClient::new("http://server", "user", "pass").unwrap();
+        let mut state = BrowserState::new(BrowseView::Artists);
+        state.artists = vec![artist("1", "Alpha"), artist("2", "Beta"), artist("3", "Gamma")];
+        state.list_state.select(Some(2));
+
+        // Open Gamma, then its second album (as handle_enter does, without the server)
+        state.push_nav();
+        let albums = vec![album("g1"), album("g2")];
+        state.cache_artist_albums("3", &albums);
+        state.albums = albums;
+        let gamma = BrowseView::Albums {
+            artist_id: "3".to_string(),
+            artist_name: "Gamma".to_string(),
+        };
+        state.view = gamma.clone();
+        state.list_state.select(Some(1));
+        state.push_nav();
+        state.view = BrowseView::AlbumTracks { album: album("g2") };
+        state.list_state.select(Some(0));
+
+        // A genre's albums replaced the list in the meantime
+        state.albums = vec![album("x1")];
+
+        handle_back(&mut state, &client).await.unwrap();
+        assert_eq!(state.view, gamma);
+        assert_eq!(state.albums.len(), 2);
+        assert_eq!(state.cursor_item_id().as_deref(), Some("g2"));
+
+        handle_back(&mut state, &client).await.unwrap();
+        assert_eq!(state.view, BrowseView::Artists);
+        assert_eq!(state.cursor_item_id().as_deref(), Some("3"));
+
+        // With nothing to go back to, tracks fall back to the top of the artists
+        state.view = BrowseView::AlbumTracks { album: album("g1") };
+        handle_back(&mut state, &client).await.unwrap();
+        assert_eq!(state.view, BrowseView::Artists);
+        assert_eq!(state.list_state.selected(), Some(0));
+    }
+
+    #[test]
+    fn test_device_too_small_for_selection() {
+        let mut state = BrowserState::new(BrowseView::DeviceSelection);
+        let device = Device {
+            name: "sdz1".to_string(),
+            label: None,
+            mount_point: std::path::PathBuf::from("/nonexistent/nutune-test"),
+            size: 4 << 30,
+            free_space: 1 << 30,
+            fs_type: "vfat".to_string(),
+            uuid: "test-device".to_string(),
+            friendly_name: None,
+            layout: None,
+            cover_size: None,
+            cover_quality: None,
+            transcode: None,
+        };
+        state.mounted_devices = vec![device.clone()];
+        state.album_cache.insert(
+            "a1".to_string(),
+            Album {
+                id: "a1".to_string(),
+                name: "Long Album".to_string(),
+                artist: None,
+                artist_id: None,
+                cover_art: None,
+                cover_art_ids: Vec::new(),
+                song_count: None,
+                duration: Some(3 * 3600),
+                year: None,
+                genre: None,
+                created: None,
+                source: AlbumSource::Id3,
+            },
+        );
+
+        state.update_space_needed();
+        assert!(!state.too_small(&device));
+
+        state.selected_albums.insert("a1".to_string());
+        state.update_space_needed();
+        assert!(state.too_small(&device));
+    }
+
+    #[test]
+    fn test_toggle_folder_selects_album_folders() {
+        let mut state = BrowserState::new(BrowseView::Folders { path: Vec::new() });
+        state
+            .folder_album_ids
+            .insert("artist".to_string(), vec!["a1".to_string(), "a2".to_string()]);
+        state.selected_albums.insert("a1".to_string());
+
+        state.toggle_folder_selection("artist");
+        assert!(state.selected_albums.contains("a1") && state.selected_albums.contains("a2"));
+
+        state.toggle_folder_selection("artist");
+        assert!(state.selected_albums.is_empty());
+    }
+
+    #[test]
+    fn test_album_gauge_ignores_skipped_and_counts_failures() {
+        let mut state = BrowserState::new(BrowseView::SyncProgress);
+        let events = [
+            SyncProgressEvent::Started { total_albums: 2, total_playlists: 0, already_synced: 1, estimated_bytes: None },
+            SyncProgressEvent::AlbumSkipped { artist: "A".into(), album: "Old".into() },
+            SyncProgressEvent::AlbumCompleted { artist: "A".into(), album: "New".into() },
+            SyncProgressEvent::AlbumFailed {
+                album_id: "3".into(),
+                artist: "A".into(),
+                album: "Broken".into(),
+                message: "timeout".into(),
+            },
+        ];
+        for event in events {
+            handle_sync_progress_event(&mut state, event);
+        }
+
+        assert_eq!(state.sync_progress.albums_completed, 2);
+        assert_eq!(state.sync_progress.albums_total, 2);
+    }
+
+    #[test]
+    fn test_throughput_and_eta() {
+        let start = Instant::now();
+        let mut progress = SyncProgressInfo {
+            albums_total: 4,
+            ..Default::default()
+        };
+        progress.start_clock(start);
+        progress.record_bytes(10_000_000, start + Duration::from_secs(5));
+        progress.record_bytes(10_000_000, start + Duration::from_secs(10));
+
+        let now = start + Duration::from_secs(10);
+        assert_eq!(progress.current_rate(now), Some(2_000_000.0));
+        // No size estimate and no album done yet
+        assert_eq!(progress.eta(now), None);
+
+        progress.albums_completed = 1;
+        assert_eq!(progress.eta(now), Some(Duration::from_secs(30)));
+
+        progress.estimated_bytes = Some(50_000_000);
+        assert_eq!(progress.eta(now), Some(Duration::from_secs(15)));
+
+        // Samples older than the window stop counting towards the current rate
+        progress.record_bytes(1_000_000, start + Duration::from_secs(30));
+        let now = start + Duration::from_secs(30);
+        assert_eq!(progress.current_rate(now), Some(1_000_000.0 / 20.0));
+        assert_eq!(progress.average_rate(now), Some(21_000_000.0 / 30.0));
+    }
+
+    fn playlist(id: &str) -> Playlist {
+        Playlist {
+            id: id.to_string(),
+            name: id.to_string(),
+            song_count: None,
+            duration: None,
+            owner: None,
+            public: None,
+            cover_art: None,
+            created: None,
+            changed: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_select_all_in_artists_takes_every_artist() {
+        use crate::subsonic::mock::{MockReply, mock_api};
+
+        let url = mock_api(vec![(
+            "getArtist",
+            MockReply::ok(serde_json::json!({
+                "artist": { "id": "3", "name": "Gamma", "album": [{ "id": "g1", "name": "g1" }] }
+            })),
+        )])
+        .await;
+        let client = SubsonicClient::new(&url, "user", "pass").unwrap();
+
+        let album = |id: &str| serde_json::from_value::<Album>(serde_json::json!({ "id": id, "name": id })).unwrap();
+        let mut state = BrowserState::new(BrowseView::Artists);
+        state.artists = vec![artist("1", "Alpha"), artist("2", "Beta"), artist("3", "Gamma")];
+        // The prefetch has reached Alpha and Beta but not Gamma
+        state.cache_artist_albums("1", &[album("a1"), album("a2")]);
+        state.cache_artist_albums("2", &[album("b1")]);
+
+        state.fetch_missing_artist_albums(&client).await.unwrap();
+        handle_select_all(&mut state);
+        assert_eq!(state.selected_artists, HashSet::from(["1".to_string(), "2".to_string(), "3".to_string()]));
+        assert_eq!(state.selected_albums.len(), 4);
+
+        handle_deselect_all(&mut state);
+        assert!(state.selected_artists.is_empty() && state.selected_albums.is_empty());
+    }
+
+    #[test]
+    fn test_select_all_in_album_lists() {
+        let album = |id: &str| serde_json::from_value::<Album>(serde_json::json!({ "id": id, "name": id })).unwrap();
+        let views = [
+            BrowseView::Albums {
+                artist_id: "1".to_string(),
+                artist_name: "Alpha".to_string(),
+            },
+            BrowseView::GenreAlbums { genre: "Jazz".to_string() },
+        ];
+        for view in views {
+            let mut state = BrowserState::new(view.clone());
+            state.cache_artist_albums("1", &[album("a1"), album("a2")]);
+            state.albums = vec![album("a1"), album("a2")];
+            state.selected_albums.insert("elsewhere".to_string());
+
+            // Selecting every album of an artist selects the artist too
+            handle_select_all(&mut state);
+            assert_eq!(state.selected_albums.len(), 3, "{:?}", view);
+            assert!(state.selected_artists.contains("1"), "{:?}", view);
+
+            // Deselecting only touches the albums listed
+            handle_deselect_all(&mut state);
+            assert_eq!(state.selected_albums.iter().collect::<Vec<_>>(), ["elsewhere"], "{:?}", view);
+            assert!(state.selected_artists.is_empty(), "{:?}", view);
+        }
+    }
+
+    #[test]
+    fn test_select_all_in_album_tracks_takes_the_whole_album() {
+        let album = serde_json::from_value::<Album>(serde_json::json!({ "id": "a1", "name": "a1" })).unwrap();
+        let mut state = BrowserState::new(BrowseView::AlbumTracks { album: album.clone() });
+        state.toggle_track(&album, "s1");
+        assert!(state.picked_tracks.contains_key("a1"));
+
+        handle_select_all(&mut state);
+        assert!(state.selected_albums.contains("a1"));
+        assert!(state.picked_tracks.is_empty());
+
+        state.toggle_track(&album, "s1");
+        handle_deselect_all(&mut state);
+        assert!(state.selected_albums.is_empty() && state.picked_tracks.is_empty());
+    }
+
+    #[test]
+    fn test_select_all_in_playlists() {
+        let mut state = BrowserState::new(BrowseView::Playlists);
+        state.playlists = vec![playlist("p1"), playlist("p2")];
+
+        handle_select_all(&mut state);
+        assert_eq!(state.selected_playlists, HashSet::from(["p1".to_string(), "p2".to_string()]));
+        handle_deselect_all(&mut state);
+        assert!(state.selected_playlists.is_empty());
+
+        // Views without a list of their own leave the selection alone
+        state.view = BrowseView::Selection;
+        handle_select_all(&mut state);
+        assert!(state.selected_playlists.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sync_after_quitting_keeps_synced_items() {
+        use crate::cli::SelectionArgs;
+        use crate::cli::auth::SubsonicCredentials;
+        use crate::cli::commands::run_sync;
+        use crate::subsonic::auth::AuthMode;
+        use crate::subsonic::mock::{MockReply, mock_api};
+
+        let url = mock_api(vec![(
+            "getAlbum",
+            MockReply::ok(serde_json::json!({ "album": { "id": "a2", "name": "New", "song": [] } })),
+        )])
+        .await;
+        let dir = std::env::temp_dir().join(format!("nutune_quit_then_sync_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let root = dir.join("device");
+        std::fs::create_dir_all(root.join("Artists/Artist/Old")).unwrap();
+        std::fs::write(root.join("Artists/Artist/Old/01 - A.mp3"), b"a").unwrap();
+        let mut manifest = SyncManifest::new(&url);
+        manifest.add_album(SyncedAlbum {
+            id: "a1".to_string(),
+            artist: "Artist".to_string(),
+            album: "Old".to_string(),
+            genre: None,
+            folder: None,
+            track_count: 1,
+            expected_tracks: Some(1),
+            server_tracks: None,
+            synced_tracks: Vec::new(),
+            partial: false,
+            synced_at: Utc::now(),
+            tracks: Vec::new(),
+        });
+        std::fs::write(root.join(crate::device::manifest::MANIFEST_FILE), manifest.to_json().unwrap()).unwrap();
+
+        // The old album is on the device and stays selected; a new one is added
+        let album = |id: &str, name: &str| {
+            serde_json::from_value::<Album>(serde_json::json!({ "id": id, "name": name, "artist": "Artist" })).unwrap()
+        };
+        let mut state = BrowserState::new(BrowseView::Artists);
+        for album in [album("a1", "Old"), album("a2", "New")] {
+            state.selected_albums.insert(album.id.clone());
+            state.album_cache.insert(album.id.clone(), album);
+        }
+        state.synced_album_ids.insert("a1".to_string());
+        let BrowseResult::SelectionOnly(selection) = state.quit_without_syncing() else {
+            panic!("quitting doesn't sync");
+        };
+        let path = dir.join("selection.json");
+        selection.save_to(&path).unwrap();
+
+        let credentials = SubsonicCredentials {
+            url: url.clone(),
+            username: "user".to_string(),
+            password: "pass".to_string(),
+            api_path: "/rest".to_string(),
+            insecure: false,
+            auth_mode: AuthMode::Plaintext,
+        };
+        let args = SelectionArgs {
+            selection: Some(path),
+            ..Default::default()
+        };
+        // Without --yes, a deletion would have to be confirmed, which fails without a terminal
+        let result = run_sync(credentials, root.clone(), None, false, 1, None, args, SyncOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(result.albums_deleted, 0);
+        let manifest = SyncManifest::load(&root).unwrap().unwrap();
+        assert!(manifest.is_album_synced("a1") && manifest.is_album_synced("a2"));
+        assert!(root.join("Artists/Artist/Old/01 - A.mp3").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_forget_drops_unsynced_items_from_saved_selection() {
+        let album = |id: &str| serde_json::from_value::<Album>(serde_json::json!({ "id": id, "name": id })).unwrap();
+        let dir = std::env::temp_dir().join(format!("nutune_forget_selection_{}", std::process::id()));
+        let path = dir.join("selection.json");
+        let mut selection = SyncSelection {
+            albums: vec![album("a1"), album("a2")],
+            playlists: vec![playlist("p1"), playlist("p2")],
+            ..Default::default()
+        };
+        selection.tracks.insert("a1".to_string(), ["s1".to_string()].into());
+        selection.save_to(&path).unwrap();
+
+        SyncSelection::forget_in(&path, &["a1".to_string()], &["p2".to_string()]).unwrap();
+        let saved = SyncSelection::load_from(&path).unwrap();
+        assert_eq!(saved.albums.iter().map(|a| a.id.as_str()).collect::<Vec<_>>(), ["a2"]);
+        assert_eq!(saved.playlists.iter().map(|p| p.id.as_str()).collect::<Vec<_>>(), ["p1"]);
+        assert!(saved.tracks.is_empty());
+
+        // Devices without a saved selection don't get one
+        let missing = dir.join("missing.json");
+        SyncSelection::forget_in(&missing, &["a2".to_string()], &[]).unwrap();
+        assert!(!missing.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_hide_synced_playlists() {
+        let mut state = BrowserState::new(BrowseView::Playlists);
+        state.playlists = vec![playlist("p1"), playlist("p2"), playlist("p3")];
+        state.synced_playlist_ids.insert("p2".to_string());
+
+        state.toggle_hide_synced();
+        assert_eq!(state.filtered_indices, [0, 2]);
+        assert_eq!(state.current_list_len(), 2);
+
+        // Clearing a search keeps synced items hidden
+        state.search_query = "p3".to_string();
+        state.apply_filter();
+        assert_eq!(state.filtered_indices, [2]);
+        state.clear_filter();
+        assert_eq!(state.filtered_indices, [0, 2]);
+
+        // Views without synced markers are unaffected
+        state.view = BrowseView::Artists;
+        state.clear_filter();
+        assert!(state.filtered_indices.is_empty());
+
+        state.view = BrowseView::Playlists;
+        state.toggle_hide_synced();
+        assert!(state.filtered_indices.is_empty());
+        assert_eq!(state.status_message, "Showing synced items");
+    }
+
+    #[tokio::test]
+    async fn test_outdated_items_are_synced_again() {
+        let synced = |id: &str, tracks: u32| {
+            serde_json::json!({
+                "id": id, "artist": "Artist", "album": id, "name": id, "track_count": tracks,
+                "expected_tracks": tracks, "server_tracks": tracks, "synced_at": "2024-01-01T00:00:00Z"
+            })
+        };
+        let album = |id: &str, songs: u32| {
+            serde_json::from_value::<Album>(serde_json::json!({ "id": id, "name": id, "songCount": songs })).unwrap()
+        };
+        let mut manifest = SyncManifest::new("http://server");
+        manifest.add_album(serde_json::from_value(synced("a1", 2)).unwrap());
+        manifest.add_album(serde_json::from_value(synced("a2", 2)).unwrap());
+        manifest.add_playlist(serde_json::from_value(synced("p1", 5)).unwrap());
+
+        let mut state = BrowserState::new(BrowseView::Playlists);
+        state.load_synced_ids(&manifest);
+        state.album_cache.insert("a1".to_string(), album("a1", 2));
+        state.album_cache.insert("a2".to_string(), album("a2", 3));
+        state.selected_albums = state.synced_album_ids.clone();
+        state.selected_playlists = state.synced_playlist_ids.clone();
+        let mut grown = playlist("p1");
+        grown.song_count = Some(6);
+        state.playlists = vec![grown];
+
+        assert_eq!(state.album_status("a1", Some(2)), SyncStatus::Synced);
+        assert_eq!(state.album_status("a2", Some(3)), SyncStatus::Outdated);
+        assert_eq!(state.album_status("a3", Some(3)), SyncStatus::NotSynced);
+        assert_eq!(state.playlist_status(&state.playlists[0]), SyncStatus::Outdated);
+
+        // Outdated items aren't hidden with the synced ones
+        state.toggle_hide_synced();
+        assert_eq!(state.current_list_len(), 1);
+
+        let client = SubsonicClient::new("http://server", "user", "pass").unwrap();
+        let selection = build_selection(&state, &client).await.unwrap();
+        assert_eq!(selection.albums.iter().map(|a| a.id.as_str()).collect::<Vec<_>>(), ["a2"]);
+        assert_eq!(selection.playlists.iter().map(|p| p.id.as_str()).collect::<Vec<_>>(), ["p1"]);
+    }
+
+    #[test]
+    fn test_merge_saved_selection() {
+        let album = serde_json::from_value::<Album>(serde_json::json!({
+            "id": "a1", "name": "Album", "artistId": "artist"
+        }))
+        .unwrap();
+        let mut saved = SyncSelection::new();
+        saved.albums.push(album);
+        saved.tracks.insert("a1".to_string(), BTreeSet::from(["s1".to_string()]));
+        saved.playlists.push(playlist("p1"));
+
+        let mut state = BrowserState::new(BrowseView::Artists);
+        state.selected_albums.insert("a2".to_string());
+        state.picked_tracks.insert("a1".to_string(), BTreeSet::from(["s2".to_string()]));
+        state.merge_saved_selection(saved);
+
+        assert!(state.selected_albums.contains("a1") && state.selected_albums.contains("a2"));
+        assert!(state.is_track_picked("a1", "s1") && !state.is_track_picked("a1", "s2"));
+        assert_eq!(state.artist_album_ids["artist"], ["a1"]);
+
+        // Playlists that haven't been loaded still make it into the selection
+        let selection = state.full_selection();
+        assert_eq!(selection.albums.len(), 1);
+        assert_eq!(selection.playlists[0].id, "p1");
+        assert_eq!(selection.tracks["a1"].len(), 1);
+    }
+
+    #[test]
+    fn test_sort_modes_keep_cursor() {
+        let album = |id: &str, name: &str, year: Option<u32>, songs: u32, created: &str| {
+            serde_json::from_value::<Album>(serde_json::json!({
+                "id": id, "name": name, "year": year, "songCount": songs, "created": created
+            }))
+            .unwrap()
+        };
+        let mut state = BrowserState::new(BrowseView::Albums {
+            artist_id: "artist".to_string(),
+            artist_name: "Artist".to_string(),
+        });
+        state.albums = vec![
+            album("1", "beta", Some(2001), 12, "2020-01-01T00:00:00Z"),
+            album("2", "Alpha", None, 8, "2024-01-01T00:00:00Z"),
+            album("3", "Gamma", Some(1999), 10, "2022-01-01T00:00:00Z"),
+            album("4", "Delta", None, 10, "2021-01-01T00:00:00Z"),
+        ];
+        state.list_state.select(Some(2));
+        let order = |state: &BrowserState| state.albums.iter().map(|a| a.id.clone()).collect::<Vec<_>>();
+
+        state.cycle_sort();
+        assert_eq!(order(&state), ["2", "1", "4", "3"]);
+        assert_eq!(state.cursor_item_id().as_deref(), Some("3"));
+
+        // Undated albums go last, by name
+        state.cycle_sort();
+        assert_eq!(order(&state), ["3", "1", "2", "4"]);
+
+        state.cycle_sort();
+        assert_eq!(order(&state), ["1", "4", "3", "2"]);
+
+        state.cycle_sort();
+        assert_eq!(order(&state), ["2", "3", "4", "1"]);
+        assert_eq!(state.status_message, "Sorted by recent");
+
+        state.view = BrowseView::Selection;
+        state.cycle_sort();
+        assert_eq!(state.sort_mode, SortMode::RecentlyAdded);
+    }
+
+    #[test]
+    fn test_fuzzy_filter_ranks_matches() {
+        let mut state = BrowserState::new(BrowseView::Artists);
+        state.artists = vec![
+            artist("1", "Radio Dept."),
+            artist("2", "Portishead"),
+            artist("3", "Radiohead"),
+        ];
+
+        state.search_query = "radiohd".to_string();
+        state.apply_filter();
+        assert_eq!(state.filtered_indices, [2]);
+
+        state.search_query = "head".to_string();
+        state.apply_filter();
+        assert_eq!(state.filtered_indices, [1, 2]);
+
+        // Prefix matches rank above scattered ones
+        state.search_query = "rad".to_string();
+        state.apply_filter();
+        assert_eq!(state.filtered_indices, [0, 2, 1]);
+
+        state.search_query = "radiohd".to_string();
+        state.toggle_exact_search();
+        assert!(state.filtered_indices.is_empty());
+    }
+
+    #[test]
+    fn test_error_status_outlives_timeout() {
+        let mut state = BrowserState::new(BrowseView::Artists);
+        state.status_timeout = Some(std::time::Duration::ZERO);
+
+        state.set_status("Library refreshed");
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        state.check_status_timeout();
+        assert!(state.status_message.is_empty());
+
+        state.set_error("Mount failed");
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        state.check_status_timeout();
+        assert_eq!(state.status_message, "Mount failed");
+
+        state.status_timeout = None;
+        state.set_status("Kept");
+        state.check_status_timeout();
+        assert_eq!(state.status_message, "Kept");
+    }
+
+    #[tokio::test]
+    async fn test_key_binding_error_shown_after_loading() {
+        use crate::subsonic::mock::{MockReply, mock_api};
+
+        let url = mock_api(vec![(
+            "getPlaylists",
+            MockReply::ok(serde_json::json!({ "playlists": { "playlist": [] } })),
+        )])
+        .await;
+        let client = SubsonicClient::new(&url, "user", "pass").unwrap();
+        let keys = KeyMap::parse("no_such_action = \"x\"");
+        assert!(keys.is_err());
+
+        let state = start_browser(&client, BrowseView::Playlists, None, 1, keys, &[]).await.unwrap();
+        assert!(state.status_is_error);
+        assert!(state.status_message.contains("unknown action 'no_such_action'"));
+        assert!(state.status_message.ends_with("using the default keys"));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(59), "0:59");
+        assert_eq!(format_duration(3599), "59:59");
+        assert_eq!(format_duration(3661), "1:01:01");
+    }
+
+    #[test]
+    fn test_format_estimated_size() {
+        assert_eq!(format_estimated_size(0), "~0 MB");
+        assert_eq!(format_estimated_size(2400), "~286 MB");
+        assert_eq!(format_estimated_size(36_000), "~4.2 GB");
+        assert!(format_estimated_size(8580).len() <= SIZE_COLUMN_WIDTH);
+    }
+}
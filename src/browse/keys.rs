@@ -0,0 +1,361 @@
+//! Key bindings for the browser
+//!
+//! Defaults can be changed in ~/.config/nutune/keys.toml, which maps action
+//! names to keys:
+//!
+//! ```toml
+//! move_up = ["Up"]
+//! move_down = ["Down"]
+//! quit = []
+//! ```
+//!
+//! An action that's listed gets exactly the keys given (none unbinds it);
+//! the rest keep their defaults. Ctrl-C always quits.
+
+use anyhow::{Context, Result};
+use crossterm::event::KeyCode;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use toml_edit::{DocumentMut, Item};
+
+use crate::config::config_dir;
+
+/// Something a key does in the browser
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Cancel,
+    MoveUp,
+    MoveDown,
+    Open,
+    Back,
+    SwitchView,
+    Genres,
+    CycleSort,
+    Toggle,
+    SelectAll,
+    DeselectAll,
+    HideSynced,
+    ShowSelection,
+    Filter,
+    ExactSearch,
+    Search,
+    Refresh,
+    SelectDevice,
+    StartSync,
+    PreviewSync,
+    RetryFailed,
+    Help,
+}
+
+impl Action {
+    /// Every action, in the order the help lists them
+    const ALL: [Self; 23] = [
+        Self::MoveUp,
+        Self::MoveDown,
+        Self::Open,
+        Self::Back,
+        Self::SwitchView,
+        Self::Genres,
+        Self::CycleSort,
+        Self::Toggle,
+        Self::SelectAll,
+        Self::DeselectAll,
+        Self::HideSynced,
+        Self::ShowSelection,
+        Self::Filter,
+        Self::ExactSearch,
+        Self::Search,
+        Self::Refresh,
+        Self::SelectDevice,
+        Self::StartSync,
+        Self::PreviewSync,
+        Self::RetryFailed,
+        Self::Help,
+        Self::Quit,
+        Self::Cancel,
+    ];
+
+    /// Name of the action in keys.toml
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Quit => "quit",
+            Self::Cancel => "cancel",
+            Self::MoveUp => "move_up",
+            Self::MoveDown => "move_down",
+            Self::Open => "open",
+            Self::Back => "back",
+            Self::SwitchView => "switch_view",
+            Self::Genres => "genres",
+            Self::CycleSort => "cycle_sort",
+            Self::Toggle => "toggle",
+            Self::SelectAll => "select_all",
+            Self::DeselectAll => "deselect_all",
+            Self::HideSynced => "hide_synced",
+            Self::ShowSelection => "show_selection",
+            Self::Filter => "filter",
+            Self::ExactSearch => "exact_search",
+            Self::Search => "search",
+            Self::Refresh => "refresh",
+            Self::SelectDevice => "select_device",
+            Self::StartSync => "start_sync",
+            Self::PreviewSync => "preview_sync",
+            Self::RetryFailed => "retry_failed",
+            Self::Help => "help",
+        }
+    }
+
+    /// What the action does, for the help overlay
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Quit => "Quit",
+            Self::Cancel => "Cancel/clear filter",
+            Self::MoveUp => "Move up",
+            Self::MoveDown => "Move down",
+            Self::Open => "Enter/expand",
+            Self::Back => "Go back",
+            Self::SwitchView => "Switch Artists/Playlists/Folders",
+            Self::Genres => "Browse by genre",
+            Self::CycleSort => "Sort by name/year/tracks/recently added",
+            Self::Toggle => "Toggle selection",
+            Self::SelectAll => "Select all in view",
+            Self::DeselectAll => "Deselect all in view",
+            Self::HideSynced => "Hide/show synced albums and playlists",
+            Self::ShowSelection => "Show everything selected",
+            Self::Filter => "Search/filter",
+            Self::ExactSearch => "Toggle fuzzy/exact search",
+            Self::Search => "Search the whole library on the server",
+            Self::Refresh => "Refresh library",
+            Self::SelectDevice => "Select device",
+            Self::StartSync => "Start sync",
+            Self::PreviewSync => "Preview sync without changing the device",
+            Self::RetryFailed => "Retry failed items after a sync",
+            Self::Help => "Show this help",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.name() == name)
+    }
+
+    /// The keys bound to the action unless keys.toml says otherwise
+    fn default_keys(self) -> Vec<KeyCode> {
+        use KeyCode::*;
+        match self {
+            Self::Quit => vec![Char('q')],
+            Self::Cancel => vec![Esc],
+            Self::MoveUp => vec![Up, Char('k')],
+            Self::MoveDown => vec![Down, Char('j')],
+            Self::Open => vec![Enter, Right, Char('l')],
+            Self::Back => vec![Backspace, Left, Char('h')],
+            Self::SwitchView => vec![Tab],
+            Self::Genres => vec![Char('g')],
+            Self::CycleSort => vec![Char('o')],
+            Self::Toggle => vec![Char(' ')],
+            Self::SelectAll => vec![Char('a')],
+            Self::DeselectAll => vec![Char('A')],
+            Self::HideSynced => vec![Char('H')],
+            Self::ShowSelection => vec![Char('v')],
+            Self::Filter => vec![Char('/')],
+            Self::ExactSearch => vec![Char('f')],
+            Self::Search => vec![Char('S')],
+            Self::Refresh => vec![Char('R')],
+            Self::SelectDevice => vec![Char('d')],
+            Self::StartSync => vec![Char('s')],
+            Self::PreviewSync => vec![Char('p')],
+            Self::RetryFailed => vec![Char('r')],
+            Self::Help => vec![Char('?')],
+        }
+    }
+}
+
+/// Which keys trigger which actions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMap {
+    bindings: HashMap<Action, Vec<KeyCode>>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self {
+            bindings: Action::ALL.into_iter().map(|a| (a, a.default_keys())).collect(),
+        }
+    }
+}
+
+impl KeyMap {
+    /// Load the key bindings from keys.toml, or the defaults if there is none
+    pub fn load() -> Result<Self> {
+        let path = config_dir()?.join("keys.toml");
+        Self::load_from(&path)
+    }
+
+    fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        Self::parse(&contents).with_context(|| format!("Invalid key bindings in {:?}", path))
+    }
+
+    /// Apply the bindings of a keys.toml on top of the defaults
+    pub fn parse(contents: &str) -> Result<Self> {
+        let document: DocumentMut = contents.parse()?;
+        let mut keymap = Self::default();
+
+        for (name, item) in document.iter() {
+            let action = Action::from_name(name).ok_or_else(|| anyhow::anyhow!("unknown action '{}'", name))?;
+            let names: Vec<&str> = match item {
+                Item::Value(value) if value.is_str() => value.as_str().into_iter().collect(),
+                Item::Value(value) if value.is_array() => value
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|v| v.as_str().ok_or_else(|| anyhow::anyhow!("keys for '{}' must be strings", name)))
+                    .collect::<Result<_>>()?,
+                _ => anyhow::bail!("'{}' must be a key or a list of keys", name),
+            };
+            let keys = names
+                .into_iter()
+                .map(|key| parse_key(key).ok_or_else(|| anyhow::anyhow!("unknown key '{}' for '{}'", key, name)))
+                .collect::<Result<_>>()?;
+            keymap.bindings.insert(action, keys);
+        }
+
+        // A key can only do one thing
+        let mut seen: HashMap<KeyCode, Action> = HashMap::new();
+        for action in Action::ALL {
+            for &key in &keymap.bindings[&action] {
+                if let Some(other) = seen.insert(key, action) {
+                    anyhow::bail!(
+                        "'{}' is bound to both '{}' and '{}'",
+                        key_name(key),
+                        other.name(),
+                        action.name()
+                    );
+                }
+            }
+        }
+
+        Ok(keymap)
+    }
+
+    /// The action a key triggers, if any
+    pub fn action(&self, key: KeyCode) -> Option<Action> {
+        Action::ALL
+            .into_iter()
+            .find(|a| self.bindings[a].contains(&key))
+    }
+
+    /// All keys of an action for display, such as `↑/k` (`-` if unbound)
+    pub fn label(&self, action: Action) -> String {
+        let keys = &self.bindings[&action];
+        if keys.is_empty() {
+            return "-".to_string();
+        }
+        keys.iter().map(|&k| key_name(k)).collect::<Vec<_>>().join("/")
+    }
+
+    /// The first key of an action for display, such as `↑` (`-` if unbound)
+    pub fn primary(&self, action: Action) -> String {
+        self.bindings[&action]
+            .first()
+            .map_or_else(|| "-".to_string(), |&k| key_name(k))
+    }
+}
+
+/// Parse a key from keys.toml: a single character, or a name such as `Up` or `Space`
+fn parse_key(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+
+    let key = match name.to_lowercase().as_str() {
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "enter" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "backspace" => KeyCode::Backspace,
+        "tab" => KeyCode::Tab,
+        "space" => KeyCode::Char(' '),
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "delete" => KeyCode::Delete,
+        function => KeyCode::F(function.strip_prefix('f')?.parse().ok().filter(|n| (1..=12).contains(n))?),
+    };
+    Some(key)
+}
+
+/// How a key is shown in the help and footers
+fn key_name(key: KeyCode) -> String {
+    match key {
+        KeyCode::Up => "↑".to_string(),
+        KeyCode::Down => "↓".to_string(),
+        KeyCode::Left => "←".to_string(),
+        KeyCode::Right => "→".to_string(),
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        other => format!("{:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_match_the_built_in_keys() {
+        let keys = KeyMap::default();
+        assert_eq!(keys.action(KeyCode::Char('q')), Some(Action::Quit));
+        assert_eq!(keys.action(KeyCode::Char('k')), Some(Action::MoveUp));
+        assert_eq!(keys.action(KeyCode::Left), Some(Action::Back));
+        assert_eq!(keys.action(KeyCode::Char(' ')), Some(Action::Toggle));
+        assert_eq!(keys.action(KeyCode::Char('x')), None);
+        assert_eq!(keys.label(Action::Open), "Enter/→/l");
+
+        // The defaults pass the same checks as a config file
+        assert_eq!(KeyMap::parse("").unwrap(), keys);
+    }
+
+    #[test]
+    fn test_config_replaces_the_keys_of_listed_actions() {
+        let keys = KeyMap::parse(
+            r#"
+            move_up = ["Up"]
+            move_down = "Down"
+            quit = []
+            help = ["F1", "?"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(keys.action(KeyCode::Char('k')), None);
+        assert_eq!(keys.action(KeyCode::Down), Some(Action::MoveDown));
+        assert_eq!(keys.action(KeyCode::Char('q')), None);
+        assert_eq!(keys.label(Action::Quit), "-");
+        assert_eq!(keys.action(KeyCode::F(1)), Some(Action::Help));
+        assert_eq!(keys.action(KeyCode::Char('s')), Some(Action::StartSync));
+    }
+
+    #[test]
+    fn test_invalid_configs_are_rejected() {
+        for config in [
+            "move_up = [",
+            "jump = ['k']",
+            "move_up = ['Hyper']",
+            "move_up = [1]",
+            "[move_up]",
+            // 'q' still quits
+            "move_up = ['q']",
+        ] {
+            assert!(KeyMap::parse(config).is_err(), "{} should be rejected", config);
+        }
+    }
+}
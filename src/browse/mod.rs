@@ -1,5 +1,7 @@
 //! Interactive browse module
 
 mod interactive;
+mod keys;
+mod terminal;
 
 pub use interactive::{run_browser, BrowseResult, BrowseView};
@@ -0,0 +1,97 @@
+//! Terminal setup and teardown for the TUI
+//!
+//! Raw mode and the alternate screen must be undone however the browser
+//! exits, or the user's shell is left unusable. `TerminalGuard` restores the
+//! terminal when dropped (normal return, `?` errors, unwinding) and on Ctrl-C,
+//! and a panic hook restores it before the panic message is printed.
+
+use anyhow::Result;
+use crossterm::{
+    cursor::Show,
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::io;
+use std::sync::{Once, OnceLock};
+use std::thread::{self, ThreadId};
+use tokio::task::JoinHandle;
+
+/// Exit status used when the user interrupts the TUI (128 + SIGINT)
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// Thread the TUI event loop runs on
+static TUI_THREAD: OnceLock<ThreadId> = OnceLock::new();
+
+/// Guards installing the panic hook once per process
+static PANIC_HOOK: Once = Once::new();
+
+/// Puts the terminal into TUI mode and restores it when dropped
+pub struct TerminalGuard {
+    /// Listens for SIGINT sent to the process while the TUI is up
+    signal_task: JoinHandle<()>,
+}
+
+impl TerminalGuard {
+    /// Enable raw mode and switch to the alternate screen
+    pub fn enter() -> Result<Self> {
+        // Suppress stderr logging so it doesn't corrupt the display
+        crate::utils::set_tui_mode(true);
+        let _ = TUI_THREAD.set(thread::current().id());
+        install_panic_hook();
+
+        // Install the guard before touching the terminal so a failure
+        // halfway through still gets cleaned up
+        let guard = Self {
+            signal_task: tokio::spawn(async {
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    interrupt();
+                }
+            }),
+        };
+
+        enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        self.signal_task.abort();
+        restore_terminal();
+    }
+}
+
+/// Restore the terminal before a TUI panic is reported
+///
+/// The default hook prints the message before unwinding reaches the guard,
+/// so it would land on the alternate screen and vanish. Panics in background
+/// tasks are caught by tokio and leave the TUI running, so only panics on
+/// the TUI thread restore the terminal. The previous hook then runs as usual.
+fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if crate::utils::is_tui_mode() && TUI_THREAD.get() == Some(&thread::current().id()) {
+                restore_terminal();
+            }
+            previous(info);
+        }));
+    });
+}
+
+/// Leave raw mode and the alternate screen (best-effort, safe to repeat)
+pub fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(io::stdout(), LeaveAlternateScreen, Show);
+    crate::utils::set_tui_mode(false);
+}
+
+/// Restore the terminal and exit as if interrupted by Ctrl-C
+///
+/// In raw mode Ctrl-C arrives as a key press rather than SIGINT, so the
+/// event loop calls this directly.
+pub fn interrupt() -> ! {
+    restore_terminal();
+    std::process::exit(EXIT_INTERRUPTED);
+}
@@ -1,38 +1,117 @@
 //! Keyring-based credential storage for Subsonic
+//!
+//! Credentials can also be read from a dotenv or TOML style file passed with
+//! `--credentials-file`, for systems where the keyring is inconvenient.
 
 use anyhow::{Context, Result};
 use dialoguer::{Input, Password};
 use keyring::Entry;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
 use tracing::{debug, info};
 
+use crate::subsonic::SubsonicClient;
+use crate::subsonic::auth::AuthMode;
+use crate::subsonic::client::{DEFAULT_API_PATH, normalize_api_path};
+
 const KEYRING_SERVICE: &str = "nutune";
 
+/// Credentials file to use instead of the keyring for this run
+static CREDENTIALS_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Accept invalid TLS certificates for this run (`--insecure`)
+static INSECURE: AtomicBool = AtomicBool::new(false);
+
+/// Request timeout for this run (`--timeout`)
+static TIMEOUT: OnceLock<Duration> = OnceLock::new();
+
 /// Subsonic server credentials
 #[derive(Debug, Clone)]
 pub struct SubsonicCredentials {
     pub url: String,
     pub username: String,
     pub password: String,
+    /// API path prefix under the server URL (e.g. "/rest" or "/music/rest")
+    pub api_path: String,
+    /// Accept invalid TLS certificates, saved by `auth --insecure`
+    pub insecure: bool,
+    /// How the password is sent; `auth` saves the mode the server accepted
+    pub auth_mode: AuthMode,
+}
+
+impl SubsonicCredentials {
+    /// Create a Subsonic client for these credentials
+    pub fn client(&self) -> Result<SubsonicClient> {
+        let client = SubsonicClient::new(&self.url, &self.username, &self.password)?
+            .with_api_path(&self.api_path)
+            .with_auth_mode(self.auth_mode)
+            .with_insecure(self.insecure || AuthManager::insecure())?;
+        match TIMEOUT.get() {
+            Some(&timeout) => client.with_timeout(timeout),
+            None => Ok(client),
+        }
+    }
 }
 
 /// Manages authentication credentials storage
 pub struct AuthManager;
 
 impl AuthManager {
+    /// Read credentials from a file instead of the keyring for this run
+    pub fn use_credentials_file(path: PathBuf) {
+        let _ = CREDENTIALS_FILE.set(path);
+    }
+
+    /// Credentials file in use, if one was given
+    pub fn credentials_file() -> Option<&'static Path> {
+        CREDENTIALS_FILE.get().map(PathBuf::as_path)
+    }
+
+    /// Accept invalid TLS certificates for this run, whatever the credentials say
+    pub fn allow_insecure() {
+        INSECURE.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether `--insecure` was given for this run
+    pub fn insecure() -> bool {
+        INSECURE.load(Ordering::Relaxed)
+    }
+
+    /// Time out server requests after `timeout` for this run
+    pub fn use_timeout(timeout: Duration) {
+        let _ = TIMEOUT.set(timeout);
+    }
+
     /// Authenticate with Subsonic server
     ///
     /// Tries to load credentials from keyring first, or prompts for new ones.
-    /// Verifies credentials work before storing.
+    /// Verifies credentials work before storing, along with the auth mode the
+    /// server accepted. With `--insecure`, the stored credentials are marked to
+    /// accept invalid certificates from now on; an `auth_mode` replaces the
+    /// stored one.
     pub async fn authenticate(
         url: Option<String>,
         username: Option<String>,
         password: Option<String>,
+        api_path: Option<String>,
+        auth_mode: Option<AuthMode>,
         force: bool,
     ) -> Result<SubsonicCredentials> {
         // Try to load existing credentials if not forcing re-auth
         if !force {
-            if let Ok(creds) = Self::load() {
+            if let Ok(mut creds) = Self::load() {
                 info!("Found existing credentials in keyring");
+                let insecure_changed = Self::insecure() && !creds.insecure;
+                let mode_changed = auth_mode.is_some_and(|mode| mode != creds.auth_mode);
+                if insecure_changed || mode_changed {
+                    creds.insecure |= Self::insecure();
+                    creds.auth_mode = auth_mode.unwrap_or(creds.auth_mode);
+                    Self::verify(&mut creds).await?;
+                    Self::store(&creds)?;
+                    info!("Saved the new settings with the credentials");
+                }
                 return Ok(creds);
             }
         } else {
@@ -61,14 +140,17 @@ impl AuthManager {
                 .expect("Failed to read password")
         });
 
-        let creds = SubsonicCredentials {
+        let mut creds = SubsonicCredentials {
             url: url.trim_end_matches('/').to_string(),
             username,
             password,
+            api_path: normalize_api_path(api_path.as_deref().unwrap_or(DEFAULT_API_PATH)),
+            insecure: Self::insecure(),
+            auth_mode: auth_mode.unwrap_or_default(),
         };
 
         // Verify credentials work
-        Self::verify(&creds).await?;
+        Self::verify(&mut creds).await?;
 
         // Store credentials
         Self::store(&creds)?;
@@ -77,8 +159,12 @@ impl AuthManager {
         Ok(creds)
     }
 
-    /// Load credentials from keyring
+    /// Load credentials from the credentials file if one was given, otherwise the keyring
     pub fn load() -> Result<SubsonicCredentials> {
+        if let Some(path) = Self::credentials_file() {
+            return Self::load_file(path);
+        }
+
         let url = Self::get_entry("url")?
             .get_password()
             .context("No Subsonic URL in keyring")?;
@@ -91,13 +177,41 @@ impl AuthManager {
             .get_password()
             .context("No Subsonic password in keyring")?;
 
+        // Older installs stored no API path; they always used the default
+        let api_path = Self::get_entry("api_path")?
+            .get_password()
+            .unwrap_or_else(|_| DEFAULT_API_PATH.to_string());
+
+        let insecure = Self::get_entry("insecure")?
+            .get_password()
+            .is_ok_and(|value| value == "true");
+
+        // Older installs stored no auth mode; they always sent a token
+        let auth_mode = Self::get_entry("auth_mode")?
+            .get_password()
+            .ok()
+            .and_then(|value| AuthMode::from_name(&value))
+            .unwrap_or_default();
+
         Ok(SubsonicCredentials {
             url,
             username,
             password,
+            api_path,
+            insecure,
+            auth_mode,
         })
     }
 
+    /// Load credentials from a dotenv or TOML style file
+    fn load_file(path: &Path) -> Result<SubsonicCredentials> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read credentials file {}", path.display()))?;
+
+        parse_credentials_file(&contents)
+            .with_context(|| format!("Invalid credentials file {}", path.display()))
+    }
+
     /// Store credentials in keyring
     pub fn store(creds: &SubsonicCredentials) -> Result<()> {
         Self::get_entry("url")?
@@ -112,18 +226,42 @@ impl AuthManager {
             .set_password(&creds.password)
             .context("Failed to store password in keyring")?;
 
+        Self::get_entry("api_path")?
+            .set_password(&creds.api_path)
+            .context("Failed to store API path in keyring")?;
+
+        let insecure = Self::get_entry("insecure")?;
+        if creds.insecure {
+            insecure
+                .set_password("true")
+                .context("Failed to store the insecure setting in keyring")?;
+        } else if let Err(e) = insecure.delete_credential()
+            && !matches!(e, keyring::Error::NoEntry)
+        {
+            return Err(e).context("Failed to clear the insecure setting in keyring");
+        }
+
+        Self::get_entry("auth_mode")?
+            .set_password(creds.auth_mode.name())
+            .context("Failed to store auth mode in keyring")?;
+
         debug!("Credentials stored in keyring");
         Ok(())
     }
 
     /// Verify credentials by pinging the Subsonic server
-    async fn verify(creds: &SubsonicCredentials) -> Result<()> {
-        use crate::subsonic::SubsonicClient;
-
-        debug!("Verifying credentials against {}", creds.url);
+    ///
+    /// An `AuthMode::Auto` is replaced by the mode the server accepted.
+    pub async fn verify(creds: &mut SubsonicCredentials) -> Result<()> {
+        debug!("Verifying credentials against {}{}", creds.url, creds.api_path);
 
-        let client = SubsonicClient::new(&creds.url, &creds.username, &creds.password)?;
-        client.ping().await.context("Failed to verify credentials")?;
+        let client = creds.client()?;
+        creds.auth_mode = client.negotiate_auth_mode().await.with_context(|| {
+            format!(
+                "Failed to verify credentials at {}{} (use --api-path if the API is served elsewhere)",
+                creds.url, creds.api_path
+            )
+        })?;
 
         info!("Credentials verified successfully");
         Ok(())
@@ -135,3 +273,97 @@ impl AuthManager {
         Entry::new(KEYRING_SERVICE, &entry_key).context("Failed to access keyring")
     }
 }
+
+/// Parse credentials from `KEY=value` (dotenv) or `key = "value"` (TOML) lines
+///
+/// Accepts the same names as the environment variables (`SUBSONIC_URL`,
+/// `SUBSONIC_USER`, `SUBSONIC_PASS`, `SUBSONIC_API_PATH`, `SUBSONIC_INSECURE`,
+/// `SUBSONIC_AUTH_MODE`) or the short forms `url`, `username`, `password`,
+/// `api_path`, `insecure` and `auth_mode`. Comments, blank lines,
+/// `export` prefixes and TOML section headers are ignored.
+fn parse_credentials_file(contents: &str) -> Result<SubsonicCredentials> {
+    let mut url = None;
+    let mut username = None;
+    let mut password = None;
+    let mut api_path = None;
+    let mut insecure = false;
+    let mut auth_mode = AuthMode::default();
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+            .unwrap_or(value)
+            .to_string();
+
+        match key.trim().to_lowercase().as_str() {
+            "subsonic_url" | "url" => url = Some(value),
+            "subsonic_user" | "username" | "user" => username = Some(value),
+            "subsonic_pass" | "password" | "pass" => password = Some(value),
+            "subsonic_api_path" | "api_path" => api_path = Some(value),
+            "subsonic_insecure" | "insecure" => insecure = matches!(value.to_lowercase().as_str(), "true" | "1" | "yes"),
+            "subsonic_auth_mode" | "auth_mode" => {
+                auth_mode = AuthMode::from_name(&value)
+                    .with_context(|| format!("Unknown auth mode '{}'; expected auto, token or plaintext", value))?
+            }
+            other => debug!("Ignoring unknown credentials key: {}", other),
+        }
+    }
+
+    Ok(SubsonicCredentials {
+        url: url
+            .context("Missing server URL (SUBSONIC_URL or url)")?
+            .trim_end_matches('/')
+            .to_string(),
+        username: username.context("Missing username (SUBSONIC_USER or username)")?,
+        password: password.context("Missing password (SUBSONIC_PASS or password)")?,
+        api_path: normalize_api_path(api_path.as_deref().unwrap_or(DEFAULT_API_PATH)),
+        insecure,
+        auth_mode,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dotenv_credentials() {
+        let contents = "# server\nexport SUBSONIC_URL=https://music.example.com/\nSUBSONIC_USER=alice\nSUBSONIC_PASS='s3cr=t'\n";
+        let creds = parse_credentials_file(contents).unwrap();
+        assert_eq!(creds.url, "https://music.example.com");
+        assert_eq!(creds.username, "alice");
+        assert_eq!(creds.password, "s3cr=t");
+        assert_eq!(creds.api_path, "/rest");
+        assert!(!creds.insecure);
+        assert_eq!(creds.auth_mode, AuthMode::Auto);
+    }
+
+    #[test]
+    fn test_parse_toml_credentials() {
+        let contents = "[subsonic]\nurl = \"https://example.com\"\nusername = \"bob\"\npassword = \"pw\"\napi_path = \"/music/rest/\"\ninsecure = true\nauth_mode = \"plaintext\"\n";
+        let creds = parse_credentials_file(contents).unwrap();
+        assert_eq!(creds.username, "bob");
+        assert_eq!(creds.api_path, "/music/rest");
+        assert!(creds.insecure);
+        assert_eq!(creds.auth_mode, AuthMode::Plaintext);
+    }
+
+    #[test]
+    fn test_parse_missing_password() {
+        let err = parse_credentials_file("url=https://example.com\nuser=bob").unwrap_err();
+        assert!(err.to_string().contains("Missing password"));
+    }
+}
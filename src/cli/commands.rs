@@ -1,44 +1,137 @@
 //! CLI command handlers
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use chrono::{NaiveDate, NaiveTime};
 use clap_complete::generate;
 use colored::Colorize;
-use std::io;
+use dialoguer::Confirm;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tracing::warn;
 
-use super::AuthManager;
+use super::{AuthManager, SelectionArgs};
+use super::auth::SubsonicCredentials;
 use crate::browse;
-use crate::device::{DeviceDetector, SyncManifest};
-use crate::subsonic::SubsonicClient;
-use crate::sync::SyncEngine;
+use crate::config::{SessionView, Settings};
+use crate::device::config::DeviceConfigStore;
+use crate::device::layout::LayoutTemplate;
+use crate::device::manifest::MANIFEST_FILE;
+use crate::device::{Device, DeviceDetector, DeviceStorage, SyncManifest, SyncedAlbum};
+use crate::subsonic::auth::AuthMode;
+use crate::subsonic::{Album, AlbumSource, Artist, Playlist, SubsonicClient, SubsonicError, SyncSelection};
+use crate::sync;
+use crate::sync::bundle::apply_bundle;
+use crate::sync::downloader::Transcode;
+use crate::sync::verify::{forget_broken, verify_device, VerifyReport};
+use crate::sync::{AUTO_PARALLEL_MAX, CoverRepairResult, DeletionSelection, OnError, SyncEngine, SyncOptions, SyncProgress, SyncResult};
+use crate::utils::size::estimated_size;
+use crate::utils::text::format_row;
 
 /// Handle the `auth` command
 pub async fn auth(
     url: Option<String>,
     username: Option<String>,
     password: Option<String>,
+    api_path: Option<String>,
+    auth_mode: Option<AuthMode>,
     force: bool,
 ) -> Result<()> {
+    if let Some(path) = AuthManager::credentials_file() {
+        println!("{}", format!("Checking credentials from {}...", path.display()).cyan());
+        let creds = load_credentials().await?;
+        println!();
+        println!("{}", "Authentication successful!".green().bold());
+        println!("  Server: {}", creds.url);
+        println!("  User: {}", creds.username);
+        print_auth_mode(creds.auth_mode);
+        report_server_capabilities(&creds).await;
+        println!();
+        println!("Credentials are read from the file; nothing was stored in the keyring.");
+        return Ok(());
+    }
+
     println!("{}", "Configuring Subsonic credentials...".cyan());
 
-    let creds = AuthManager::authenticate(url, username, password, force).await?;
+    let creds = AuthManager::authenticate(url, username, password, api_path, auth_mode, force).await?;
 
     println!();
     println!("{}", "Authentication successful!".green().bold());
     println!("  Server: {}", creds.url);
+    println!("  API path: {}", if creds.api_path.is_empty() { "/" } else { &creds.api_path });
     println!("  User: {}", creds.username);
+    print_auth_mode(creds.auth_mode);
+    if creds.insecure {
+        println!("  {}", "TLS certificate checks: off (--insecure)".yellow());
+    }
+    report_server_capabilities(&creds).await;
     println!();
     println!("Credentials stored securely in system keyring.");
 
     Ok(())
 }
 
+/// Print how the password is sent, warning when it isn't hashed
+fn print_auth_mode(mode: AuthMode) {
+    if mode == AuthMode::Plaintext {
+        println!("  {}", "Auth: plaintext (the password is only hex-encoded; use HTTPS)".yellow());
+    } else {
+        println!("  Auth: {}", mode.name());
+    }
+}
+
+/// Print what the server is and supports, and remember it in the settings
+///
+/// Failures only log: authentication has already succeeded at this point.
+async fn report_server_capabilities(creds: &SubsonicCredentials) {
+    let caps = match creds.client() {
+        Ok(client) => client.server_capabilities().await,
+        Err(e) => Err(e),
+    };
+    let caps = match caps {
+        Ok(caps) => caps,
+        Err(e) => {
+            warn!("Could not detect server capabilities: {:#}", e);
+            return;
+        }
+    };
+
+    let server = match (&caps.server_type, &caps.server_version) {
+        (Some(kind), Some(version)) => format!("{} {}", kind, version),
+        (Some(kind), None) => kind.clone(),
+        _ => "unknown".to_string(),
+    };
+    println!("  Server type: {}", server);
+    println!(
+        "  API version: {}{}",
+        caps.api_version.as_deref().unwrap_or("unknown"),
+        if caps.open_subsonic { " (OpenSubsonic)" } else { "" }
+    );
+    if caps.open_subsonic {
+        if caps.extensions.is_empty() {
+            println!("  Extensions: none");
+        } else {
+            let names: Vec<&str> = caps.extensions.iter().map(|e| e.name.as_str()).collect();
+            println!("  Extensions: {}", names.join(", "));
+        }
+    }
+
+    let mut settings = Settings::load().unwrap_or_default();
+    settings.server = Some(caps);
+    if let Err(e) = settings.save() {
+        warn!("Failed to save server capabilities: {:#}", e);
+    }
+}
+
 /// Handle the `devices` command
-pub async fn devices(detailed: bool) -> Result<()> {
+pub async fn devices(detailed: bool, min_free: Option<u64>) -> Result<()> {
     println!("{}", "Scanning for devices...".cyan());
     println!();
 
-    let mounted_devices = DeviceDetector::scan().await?;
-    let unmounted_devices = DeviceDetector::scan_unmounted().await.unwrap_or_default();
+    let mut mounted_devices = DeviceDetector::scan().await?;
+    let mut unmounted_devices = DeviceDetector::scan_unmounted().await.unwrap_or_default();
 
     if mounted_devices.is_empty() && unmounted_devices.is_empty() {
         println!("{}", "No removable devices found.".yellow());
@@ -46,6 +139,25 @@ pub async fn devices(detailed: bool) -> Result<()> {
         return Ok(());
     }
 
+    if let Some(min_free) = min_free {
+        // Free space of unmounted devices isn't known, so only rule out
+        // those that are too small in total
+        mounted_devices.retain(|d| d.free_space >= min_free);
+        unmounted_devices.retain(|d| d.size >= min_free);
+
+        if mounted_devices.is_empty() && unmounted_devices.is_empty() {
+            println!(
+                "{}",
+                format!(
+                    "No devices with at least {:.1} GB free.",
+                    min_free as f64 / 1_073_741_824.0
+                )
+                .yellow()
+            );
+            return Ok(());
+        }
+    }
+
     // Show mounted devices
     if !mounted_devices.is_empty() {
         println!("{}", "Mounted devices:".green().bold());
@@ -123,13 +235,63 @@ pub async fn devices(detailed: bool) -> Result<()> {
     Ok(())
 }
 
+/// The library as printed by `browse --json`
+#[derive(Serialize)]
+struct LibraryExport {
+    artists: Vec<ArtistExport>,
+    playlists: Vec<Playlist>,
+}
+
+#[derive(Serialize)]
+struct ArtistExport {
+    #[serde(flatten)]
+    artist: Artist,
+    /// Only filled in with `--albums`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    albums: Option<Vec<Album>>,
+}
+
+/// Requests in flight while listing or checking many items at once
+const LOOKUP_PARALLELISM: usize = 4;
+
+/// Handle `browse --json`: print the library to stdout without touching the terminal
+pub async fn browse_json(with_albums: bool) -> Result<()> {
+    let client = load_credentials().await?.client()?;
+
+    let artists = client.get_artists().await?;
+    let artists: Vec<ArtistExport> = if with_albums {
+        stream::iter(artists)
+            .map(|artist| {
+                let client = &client;
+                async move {
+                    let albums = client
+                        .get_artist(&artist.id)
+                        .await
+                        .with_context(|| format!("Failed to list albums of {}", artist.name))?
+                        .album;
+                    Ok::<_, anyhow::Error>(ArtistExport { artist, albums: Some(albums) })
+                }
+            })
+            .buffered(LOOKUP_PARALLELISM)
+            .try_collect()
+            .await?
+    } else {
+        artists.into_iter().map(|artist| ArtistExport { artist, albums: None }).collect()
+    };
+    let playlists = client.get_playlists().await?;
+
+    let export = LibraryExport { artists, playlists };
+    let mut stdout = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, &export).context("Failed to write JSON")?;
+    writeln!(stdout)?;
+    Ok(())
+}
+
 /// Handle the `browse` command
-pub async fn browse(_start_artists: bool, start_playlists: bool) -> Result<()> {
-    let creds = AuthManager::load().map_err(|_| {
-        anyhow::anyhow!("No credentials found. Run 'nutune auth' first to configure.")
-    })?;
+pub async fn browse(start_artists: bool, start_playlists: bool, resume: bool, album_parallel: usize) -> Result<()> {
+    let creds = load_credentials().await?;
 
-    let client = SubsonicClient::new(&creds.url, &creds.username, &creds.password)?;
+    let client = creds.client()?;
 
     // Verify connection
     println!("{}", "Connecting to Subsonic server...".cyan());
@@ -137,14 +299,27 @@ pub async fn browse(_start_artists: bool, start_playlists: bool) -> Result<()> {
     println!("{}", "Connected!".green());
     println!();
 
-    // Run interactive browser
+    let settings = Settings::load().unwrap_or_default();
+
+    // Run interactive browser, restoring the last view unless one was requested
     let initial_view = if start_playlists {
         browse::BrowseView::Playlists
-    } else {
+    } else if start_artists || !(resume || settings.resume_last_view) {
         browse::BrowseView::Artists
+    } else {
+        match settings.last_session.view {
+            SessionView::Artists => browse::BrowseView::Artists,
+            SessionView::Playlists => browse::BrowseView::Playlists,
+        }
     };
 
-    let result = browse::run_browser(&client, initial_view).await?;
+    let resume_device = if resume || settings.resume_last_device {
+        settings.last_session.device_uuid.as_deref()
+    } else {
+        None
+    };
+
+    let result = browse::run_browser(&client, initial_view, resume_device, album_parallel).await?;
 
     match result {
         browse::BrowseResult::SelectionOnly(selection) => {
@@ -183,21 +358,361 @@ pub async fn browse(_start_artists: bool, start_playlists: bool) -> Result<()> {
     Ok(())
 }
 
+/// Load credentials for a command
+///
+/// Credentials from a `--credentials-file` haven't been checked by `auth`, so
+/// they are verified against the server before use.
+async fn load_credentials() -> Result<SubsonicCredentials> {
+    if let Some(path) = AuthManager::credentials_file() {
+        let mut creds = AuthManager::load()?;
+        AuthManager::verify(&mut creds)
+            .await
+            .with_context(|| format!("Credentials from {} failed to verify", path.display()))?;
+        return Ok(creds);
+    }
+
+    AuthManager::load()
+        .map_err(|_| anyhow::anyhow!("No credentials found. Run 'nutune auth' first to configure."))
+}
+
+/// Resolve a device argument, falling back to the default device
+fn device_or_default(device_id: Option<String>) -> Result<String> {
+    Settings::load()
+        .unwrap_or_default()
+        .device_or_default(device_id)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "No device given and no default device set. Pass a device, or set a default with 'nutune default-device <DEVICE>'."
+            )
+        })
+}
+
+/// Handle the `default-device` command
+pub fn default_device(device_id: Option<String>, clear: bool) -> Result<()> {
+    let mut settings = Settings::load().unwrap_or_default();
+
+    if clear {
+        settings.default_device = None;
+        settings.save()?;
+        println!("Cleared the default device.");
+    } else if let Some(id) = device_id {
+        settings.default_device = Some(id.clone());
+        settings.save()?;
+        println!("Default device set to {}.", id.green());
+    } else {
+        match &settings.default_device {
+            Some(id) => println!("Default device: {}", id.green()),
+            None => println!("{}", "No default device set.".yellow()),
+        }
+    }
+
+    Ok(())
+}
+
+/// Fill in options left to the settings file, and check them
+fn with_settings(mut options: SyncOptions) -> Result<SyncOptions> {
+    if options.temp_dir.is_none() {
+        options.temp_dir = Settings::load().unwrap_or_default().temp_dir;
+    }
+    if let Some(dir) = &options.temp_dir
+        && !dir.is_dir()
+    {
+        anyhow::bail!("Temp directory {} does not exist", dir.display());
+    }
+    if !cfg!(unix) && (options.file_mode.is_some() || options.dir_mode.is_some()) {
+        anyhow::bail!("--file-mode and --dir-mode are only supported on Unix");
+    }
+    // --min-bitrate judges the originals, so a lower cap would undercut every track it keeps
+    if let (Some(min), Some(Transcode { max_bitrate: Some(max), .. })) = (options.min_bitrate, &options.transcode)
+        && *max < min
+    {
+        anyhow::bail!(
+            "--max-bitrate {} is below --min-bitrate {}: every track would be transcoded under the limit",
+            max,
+            min
+        );
+    }
+    Ok(options)
+}
+
+/// Use the layout and cover settings saved for a device, saving any given on the command line instead
+fn with_device_settings(mut options: SyncOptions, device: &Device) -> Result<SyncOptions> {
+    let cover_changed = options.cover_size.is_some_and(|size| device.cover_size != Some(size))
+        || options.cover_quality.is_some_and(|quality| device.cover_quality != Some(quality));
+    if cover_changed {
+        DeviceConfigStore::load()?.set_cover(&device.uuid, options.cover_size, options.cover_quality)?;
+        println!("Saved cover art settings for {}", device.display_name());
+    }
+    options.cover_size = options.cover_size.or(device.cover_size);
+    options.cover_quality = options.cover_quality.or(device.cover_quality);
+
+    if let Some(layout) = &options.layout {
+        let layout = layout.to_string();
+        if device.layout.as_deref() != Some(layout.as_str()) {
+            DeviceConfigStore::load()?.set_layout(&device.uuid, &layout)?;
+            println!("Saved layout {} for {}", layout.cyan(), device.display_name());
+        }
+    } else if let Some(saved) = device.layout.as_deref().filter(|_| !options.genre_folders) {
+        let layout = LayoutTemplate::parse(saved)
+            .map_err(|e| anyhow::anyhow!("Saved layout for {} is invalid: {}", device.display_name(), e))?;
+        options.layout = Some(layout);
+    }
+
+    match &options.transcode {
+        Some(Transcode { format: Some(format), .. }) => {
+            if device.transcode.as_deref() != Some(format.as_str()) {
+                DeviceConfigStore::load()?.set_transcode(&device.uuid, format)?;
+                println!("Saved transcode format {} for {}", format.cyan(), device.display_name());
+            }
+        }
+        _ => {
+            if let Some(saved) = &device.transcode {
+                options.transcode.get_or_insert_with(Transcode::default).format = Some(saved.clone());
+            }
+        }
+    }
+    if let Some(manifest) = SyncManifest::load(&device.mount_point).ok().flatten()
+        && let Some(warning) = transcode_mismatch(&manifest, options.transcode_format())
+    {
+        println!("{}", warning.yellow());
+    }
+    Ok(options)
+}
+
+/// Warn about a sync that would add tracks in another format than the device already has
+fn transcode_mismatch(manifest: &SyncManifest, format: Option<&str>) -> Option<String> {
+    let has_tracks = !manifest.synced_albums.is_empty() || !manifest.synced_playlists.is_empty();
+    if !has_tracks || manifest.transcode.as_deref() == format {
+        return None;
+    }
+    let describe = |format: Option<&str>| match format {
+        Some(format) => format!("transcoded to {}", format),
+        None => "in their original format".to_string(),
+    };
+    Some(format!(
+        "Warning: the device's tracks were synced {}, but this sync writes them {}; pass --transcode to match",
+        describe(manifest.transcode.as_deref()),
+        describe(format)
+    ))
+}
+
+/// Exit code for a sync that finished but had failed items
+pub const EXIT_SYNC_FAILURES: i32 = 2;
+
 /// Handle the `sync` command
 pub async fn sync_to_device(
-    device_id: String,
+    device_id: Option<String>,
     dry_run: bool,
     parallel: usize,
-    no_playlists: bool,
-    playlists_only: bool,
-) -> Result<()> {
+    max_connections: Option<usize>,
+    selection: SelectionArgs,
+    options: SyncOptions,
+) -> Result<SyncResult> {
+    let device_id = device_or_default(device_id)?;
+
     // Load credentials
-    let creds = AuthManager::load().map_err(|_| {
-        anyhow::anyhow!("No credentials found. Run 'nutune auth' first to configure.")
-    })?;
+    let creds = load_credentials().await?;
+
+    let device = find_or_mount_device(&device_id).await?;
+    println!("Syncing to: {} ({})", device.name.green(), device.mount_point.display());
+    let options = with_device_settings(options, &device)?;
+
+    let recorded = !options.no_manifest;
+    let result = run_sync(
+        creds,
+        device.mount_point.clone(),
+        Some(&device),
+        dry_run,
+        parallel,
+        max_connections,
+        selection,
+        options,
+    )
+    .await?;
 
-    // Find device - check mounted first, then unmounted
-    let device = match DeviceDetector::find(&device_id).await? {
+    if recorded && (result.albums_failed > 0 || result.playlists_failed > 0) {
+        println!("  Retry the failed items with: {}", format!("nutune retry {}", device_id).cyan());
+    }
+    Ok(result)
+}
+
+/// Handle the `retry` command: re-sync the albums and playlists that failed before
+///
+/// Failed items are recorded in the device's manifest and drop out of it
+/// once they sync, so running this until it succeeds clears the list.
+pub async fn retry(
+    device_id: Option<String>,
+    parallel: usize,
+    max_connections: Option<usize>,
+    options: SyncOptions,
+) -> Result<SyncResult> {
+    let device_id = device_or_default(device_id)?;
+    if options.no_manifest {
+        anyhow::bail!("retry reads the failed items from the manifest, so it can't be used with --no-manifest");
+    }
+    let options = with_settings(options)?;
+
+    let device = find_or_mount_device(&device_id).await?;
+    let options = with_device_settings(options, &device)?;
+    let selection = SyncManifest::load(&device.mount_point)?
+        .map(|manifest| manifest.failed)
+        .unwrap_or_default();
+    if selection.is_empty() {
+        println!("{}", format!("Nothing to retry: no failed albums or playlists recorded on {}.", device.name).green());
+        return Ok(SyncResult::default());
+    }
+
+    println!(
+        "Retrying {} album(s) and {} playlist(s) on: {} ({})",
+        selection.album_count(),
+        selection.playlist_count(),
+        device.name.green(),
+        device.mount_point.display()
+    );
+
+    let default_connections = if options.auto_parallel { AUTO_PARALLEL_MAX } else { parallel };
+    let client = load_credentials()
+        .await?
+        .client()?
+        .with_max_connections(max_connections.unwrap_or(default_connections))?;
+    sync_and_report(
+        client,
+        device.mount_point.clone(),
+        Some(&device),
+        parallel,
+        &selection,
+        &DeletionSelection::default(),
+        options,
+    )
+    .await
+}
+
+/// Handle `sync --covers-only`: re-embed cover art into albums already on a device
+pub async fn sync_covers(
+    device_id: Option<String>,
+    parallel: usize,
+    max_connections: Option<usize>,
+    options: SyncOptions,
+) -> Result<CoverRepairResult> {
+    let device_id = device_or_default(device_id)?;
+    let options = with_settings(options)?;
+    let creds = load_credentials().await?;
+
+    let device = find_or_mount_device(&device_id).await?;
+    println!("Updating cover art on: {} ({})", device.name.green(), device.mount_point.display());
+    let options = with_device_settings(options, &device)?;
+
+    let client = creds
+        .client()?
+        .with_max_connections(max_connections.unwrap_or(parallel))?;
+    let mut engine = SyncEngine::new(client, device.mount_point, parallel)?.with_options(options);
+    let result = engine.sync_covers().await?;
+
+    println!();
+    if result.albums_failed > 0 {
+        println!("{}", "Cover art update finished with failures".red().bold());
+    } else {
+        println!("{}", "Cover art updated!".green().bold());
+    }
+    println!("  Albums updated: {}", result.albums_updated);
+    println!("  Tracks updated: {}", result.tracks_updated);
+    if result.tracks_kept > 0 {
+        println!(
+            "  Already had cover art: {} track(s) left unchanged (--force-cover replaces it)",
+            result.tracks_kept
+        );
+    }
+    if result.albums_without_cover > 0 {
+        println!(
+            "  {}",
+            format!("No cover art available: {} album(s) left unchanged", result.albums_without_cover).yellow()
+        );
+    }
+    if result.cover_fallbacks > 0 {
+        println!(
+            "  {}",
+            format!("Cover art not embedded: {} track(s) (original audio kept)", result.cover_fallbacks).yellow()
+        );
+    }
+    if result.albums_failed > 0 {
+        println!("  {}", format!("Failed: {} album(s)", result.albums_failed).red());
+    }
+
+    Ok(result)
+}
+
+/// Handle the `prepare` command: sync into a directory instead of a device
+pub async fn prepare(
+    out: PathBuf,
+    parallel: usize,
+    max_connections: Option<usize>,
+    selection: SelectionArgs,
+    options: SyncOptions,
+) -> Result<SyncResult> {
+    let creds = load_credentials().await?;
+
+    std::fs::create_dir_all(&out)
+        .with_context(|| format!("Failed to create bundle directory {}", out.display()))?;
+    println!("Preparing bundle in: {}", out.display().to_string().green());
+
+    let result = run_sync(
+        creds,
+        out.clone(),
+        None,
+        false,
+        parallel,
+        max_connections,
+        selection,
+        options,
+    )
+    .await?;
+
+    println!();
+    println!(
+        "Apply it with: {}",
+        format!("nutune apply <DEVICE> --from {}", out.display()).cyan()
+    );
+    Ok(result)
+}
+
+/// Handle the `apply` command: copy a prepared bundle to a device
+pub async fn apply(device_id: String, from: PathBuf) -> Result<()> {
+    let device = find_or_mount_device(&device_id).await?;
+    println!(
+        "Applying {} to: {} ({})",
+        from.display(),
+        device.name.green(),
+        device.mount_point.display()
+    );
+
+    // The bundle's server URL is used if the device has never been synced
+    let mut manifest = match SyncManifest::load(&device.mount_point)? {
+        Some(manifest) => manifest,
+        None => SyncManifest::load(&from)?
+            .map(|bundle| SyncManifest::new(&bundle.subsonic_url))
+            .unwrap_or_else(|| SyncManifest::new("")),
+    };
+
+    let storage = DeviceStorage::new(device.mount_point.clone());
+    let result = apply_bundle(&from, &storage, &mut manifest).await?;
+
+    println!();
+    println!("{}", "Apply complete!".green().bold());
+    println!("  Albums: {}", result.albums);
+    println!("  Playlists: {}", result.playlists);
+    println!("  Files copied: {}", result.files_copied);
+    println!(
+        "  Total size: {:.1} MB",
+        result.bytes_copied as f64 / 1_048_576.0
+    );
+
+    Ok(())
+}
+
+/// Find a device by identifier, mounting it first if it is present but unmounted
+async fn find_or_mount_device(device_id: &str) -> Result<Device> {
+    // Check mounted first, then unmounted
+    let device = match DeviceDetector::find(device_id).await? {
         Some(d) => d,
         None => {
             // Check if it's an unmounted device we can mount
@@ -206,7 +721,7 @@ pub async fn sync_to_device(
                 d.name == device_id
                     || d.label
                         .as_ref()
-                        .is_some_and(|l| l.eq_ignore_ascii_case(&device_id))
+                        .is_some_and(|l| l.eq_ignore_ascii_case(device_id))
             });
 
             if let Some(um) = unmounted_match {
@@ -231,38 +746,129 @@ pub async fn sync_to_device(
         }
     };
 
-    println!("Syncing to: {} ({})", device.name.green(), device.mount_point.display());
+    Ok(device)
+}
+
+/// Sync the selection to a device mount point or bundle directory
+///
+/// With a `device`, the sync doesn't start unless the selection fits, and
+/// names are kept within the limits of its filesystem.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn run_sync(
+    creds: SubsonicCredentials,
+    root: PathBuf,
+    device: Option<&Device>,
+    dry_run: bool,
+    parallel: usize,
+    max_connections: Option<usize>,
+    selection_args: SelectionArgs,
+    options: SyncOptions,
+) -> Result<SyncResult> {
+    let options = with_settings(options)?;
+
+    // --auto-parallel picks its own concurrency, so let it use more connections
+    let default_connections = if options.auto_parallel { AUTO_PARALLEL_MAX } else { parallel };
+    let client = creds
+        .client()?
+        .with_max_connections(max_connections.unwrap_or(default_connections))?;
 
-    // Load selection
-    let selection = crate::subsonic::SyncSelection::load()?;
+    // Load selection, or the whole library if asked for
+    let selection = if selection_args.whole_library() {
+        match library_selection(&client, &selection_args, dry_run).await? {
+            Some(selection) => selection,
+            None => return Ok(SyncResult::default()),
+        }
+    } else if let Some(path) = &selection_args.selection {
+        let selection = read_selection(path)?;
+        check_selection_on_server(&client, &selection).await?;
+        selection
+    } else if selection_args.starred {
+        let selection = starred_selection(&client).await?;
+        if selection.is_empty() {
+            println!("{}", "Nothing is starred on the server.".yellow());
+            return Ok(SyncResult::default());
+        }
+        selection
+    } else {
+        SyncSelection::load()?
+    };
     if selection.is_empty() {
         println!("{}", "No items selected. Run 'nutune browse' first to select music.".yellow());
-        return Ok(());
+        return Ok(SyncResult::default());
     }
 
     // Filter selection based on flags
-    let selection = if no_playlists {
-        crate::subsonic::SyncSelection {
-            albums: selection.albums,
+    let mut selection = if selection_args.no_playlists {
+        SyncSelection {
             playlists: vec![],
+            ..selection
         }
-    } else if playlists_only {
-        crate::subsonic::SyncSelection {
+    } else if selection_args.playlists_only {
+        SyncSelection {
             albums: vec![],
             playlists: selection.playlists,
+            ..Default::default()
         }
     } else {
         selection
     };
 
+    if let Some(since) = selection_args.new_since {
+        selection = new_albums_selection(&client, &root, &selection, since).await?;
+        if selection.is_empty() {
+            println!("{}", "No new albums by the selected artists.".green());
+            return Ok(SyncResult::default());
+        }
+    }
+
+    // Anything on the device that's no longer selected gets deleted, as in the
+    // browser. Worked out before --include/--exclude so filtered-out items stay.
+    // --new-since only adds, so it deletes nothing, and a sync of only albums
+    // or only playlists leaves the other kind alone.
+    let deletions = if selection_args.no_delete || options.no_manifest || selection_args.new_since.is_some() {
+        DeletionSelection::default()
+    } else {
+        let manifest = SyncManifest::load(&root)?.unwrap_or_else(|| SyncManifest::new("unknown"));
+        DeletionSelection::deselected(
+            &manifest,
+            &selection,
+            selection_args.replaces_albums(),
+            selection_args.replaces_playlists(),
+        )
+    };
+
+    let before = (selection.album_count(), selection.playlist_count());
+    selection.filter_by_name(&selection_args.include, &selection_args.exclude);
+    let name_filtered = !selection_args.include.is_empty() || !selection_args.exclude.is_empty();
+    if name_filtered && selection.is_empty() {
+        println!("{}", "Nothing matches --include/--exclude.".yellow());
+        return Ok(SyncResult::default());
+    }
+
     println!(
         "Syncing {} album(s) and {} playlist(s)...",
         selection.album_count(),
         selection.playlist_count()
     );
+    if !deletions.is_empty() {
+        println!(
+            "Deleting {} deselected album(s) and {} playlist(s) (--no-delete keeps them)",
+            deletions.albums.len(),
+            deletions.playlists.len()
+        );
+    }
 
     if dry_run {
         println!();
+        if name_filtered {
+            println!(
+                "Matched --include/--exclude: {} of {} album(s), {} of {} playlist(s)",
+                selection.album_count(),
+                before.0,
+                selection.playlist_count(),
+                before.1
+            );
+        }
         println!("{}", "[DRY RUN] Would sync:".yellow());
         for album in &selection.albums {
             let artist = album.artist.as_deref().unwrap_or("Unknown Artist");
@@ -271,18 +877,191 @@ pub async fn sync_to_device(
         for playlist in &selection.playlists {
             println!("  Playlist: {}", playlist.name);
         }
-        return Ok(());
+        if !deletions.is_empty() {
+            println!("{}", "[DRY RUN] Would delete:".yellow());
+            for (_, artist, album) in &deletions.albums {
+                println!("  Album: {} - {}", artist, album);
+            }
+            for (_, name) in &deletions.playlists {
+                println!("  Playlist: {}", name);
+            }
+        }
+        return Ok(SyncResult::default());
     }
 
-    // Create client and sync engine
-    let client = SubsonicClient::new(&creds.url, &creds.username, &creds.password)?;
-    let mut engine = SyncEngine::new(client, device.mount_point.clone(), parallel)?;
+    if !deletions.is_empty() && !selection_args.yes {
+        let confirmed = Confirm::new()
+            .with_prompt("Delete them from the device?")
+            .default(false)
+            .interact()
+            .context("Confirmation needs a terminal; pass --yes to skip it or --no-delete to keep them")?;
+        if !confirmed {
+            println!("{}", "Cancelled.".yellow());
+            return Ok(SyncResult::default());
+        }
+    }
+
+    // Keep the new albums selected, so the next full sync doesn't delete them
+    if selection_args.new_since.is_some() && selection_args.uses_saved_selection() {
+        let mut saved = SyncSelection::load()?;
+        let before = saved.album_count();
+        for album in &selection.albums {
+            if !saved.albums.iter().any(|a| a.id == album.id) {
+                saved.albums.push(album.clone());
+            }
+        }
+        if saved.album_count() > before {
+            saved.save()?;
+            println!("Added {} new album(s) to the saved selection", saved.album_count() - before);
+        }
+    }
+
+    sync_and_report(client, root, device, parallel, &selection, &deletions, options).await
+}
+
+/// Read a selection from a JSON file, or from stdin for `-`
+fn read_selection(path: &Path) -> Result<SyncSelection> {
+    let (contents, source) = if path == Path::new("-") {
+        let contents = io::read_to_string(io::stdin()).context("Failed to read the selection from stdin")?;
+        (contents, "stdin".to_string())
+    } else {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read selection file {}", path.display()))?;
+        (contents, path.display().to_string())
+    };
+
+    serde_json::from_str(&contents).with_context(|| {
+        format!(
+            "The selection from {} is not valid: expected {{\"albums\": [...], \"playlists\": [...]}} with an id and name for each item",
+            source
+        )
+    })
+}
+
+/// Check that every album and playlist in a selection still exists on the server
+async fn check_selection_on_server(client: &SubsonicClient, selection: &SyncSelection) -> Result<()> {
+    let is_missing = |result: Result<()>| match result {
+        Ok(()) => Ok(false),
+        Err(e) if e.downcast_ref::<SubsonicError>().is_some_and(SubsonicError::is_not_found) => Ok(true),
+        Err(e) => Err(e),
+    };
+
+    let albums: Vec<bool> = stream::iter(&selection.albums)
+        .map(|album| async move {
+            // Albums from a file-based library are folders, which getAlbum doesn't know
+            let found = match album.source {
+                AlbumSource::Id3 => client.get_album(&album.id).await.map(drop),
+                AlbumSource::Folder => client.get_music_directory(&album.id).await.map(drop),
+            };
+            is_missing(found)
+        })
+        .buffered(LOOKUP_PARALLELISM)
+        .try_collect()
+        .await?;
+    let playlists: Vec<bool> = stream::iter(&selection.playlists)
+        .map(|playlist| async move { is_missing(client.get_playlist(&playlist.id).await.map(drop)) })
+        .buffered(LOOKUP_PARALLELISM)
+        .try_collect()
+        .await?;
+
+    let missing_albums = selection
+        .albums
+        .iter()
+        .zip(albums)
+        .filter(|(_, missing)| *missing)
+        .map(|(album, _)| {
+            let artist = album.artist.as_deref().unwrap_or("Unknown Artist");
+            format!("album {} - {} ({})", artist, album.name, album.id)
+        });
+    let missing_playlists = selection
+        .playlists
+        .iter()
+        .zip(playlists)
+        .filter(|(_, missing)| *missing)
+        .map(|(playlist, _)| format!("playlist {} ({})", playlist.name, playlist.id));
+    let missing: Vec<String> = missing_albums.chain(missing_playlists).collect();
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "The selection lists {} item(s) the server no longer has:\n  {}",
+            missing.len(),
+            missing.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+/// The albums added to the server since `since`, or since the last sync, by the selection's artists
+///
+/// A date counts from midnight UTC.
+async fn new_albums_selection(
+    client: &SubsonicClient,
+    root: &Path,
+    selection: &SyncSelection,
+    since: Option<NaiveDate>,
+) -> Result<SyncSelection> {
+    let cutoff = match since {
+        Some(date) => date.and_time(NaiveTime::MIN).and_utc(),
+        None => SyncManifest::load(root)?
+            .map(|manifest| manifest.last_sync)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{} has never been synced; pass a date to --new-since (e.g. --new-since 2024-01-31)",
+                    root.display()
+                )
+            })?,
+    };
+
+    println!(
+        "{}",
+        format!("Checking for albums added since {}...", cutoff.format("%Y-%m-%d %H:%M")).cyan()
+    );
+    let added = client.get_albums_added_since(cutoff).await?;
+    Ok(SyncSelection {
+        albums: selection.by_selected_artists(added),
+        ..Default::default()
+    })
+}
+
+/// The user's starred albums, and their starred songs grouped into albums
+async fn starred_selection(client: &SubsonicClient) -> Result<SyncSelection> {
+    println!("{}", "Fetching starred items...".cyan());
+    let starred = client.get_starred2().await?;
+
+    let song_albums: Vec<Album> = stream::iter(starred.song_album_ids())
+        .map(|id| async move { client.get_album(id).await.map(|album| album.info) })
+        .buffered(LOOKUP_PARALLELISM)
+        .try_collect()
+        .await?;
+
+    Ok(SyncSelection::from_starred(starred, song_albums))
+}
+
+/// Run the engine over a selection and print the summary
+async fn sync_and_report(
+    client: SubsonicClient,
+    root: PathBuf,
+    device: Option<&Device>,
+    parallel: usize,
+    selection: &SyncSelection,
+    deletions: &DeletionSelection,
+    options: SyncOptions,
+) -> Result<SyncResult> {
+    // Create sync engine
+    let on_error = options.on_error;
+    let mut engine = SyncEngine::new(client, root, parallel)?.with_options(options);
+    if let Some(device) = device {
+        engine = engine.with_free_space(device.free_space).with_fs_type(&device.fs_type);
+    }
 
     // Run sync
-    let result = engine.sync(&selection).await?;
+    let result = engine.sync(selection, deletions).await?;
 
     println!();
-    println!("{}", "Sync complete!".green().bold());
+    if result.has_failures() {
+        println!("{}", "Sync finished with failures".red().bold());
+    } else {
+        println!("{}", "Sync complete!".green().bold());
+    }
     println!(
         "  Albums synced: {}",
         result.albums_synced
@@ -299,12 +1078,127 @@ pub async fn sync_to_device(
         "  Total size: {:.1} MB",
         result.bytes_downloaded as f64 / 1_048_576.0
     );
+    if result.cover_fallbacks > 0 {
+        println!(
+            "  {}",
+            format!(
+                "Cover art not embedded: {} track(s) (original audio kept)",
+                result.cover_fallbacks
+            )
+            .yellow()
+        );
+    }
+    if result.albums_failed > 0 || result.playlists_failed > 0 {
+        println!(
+            "  {}",
+            format!(
+                "Failed: {} album(s), {} playlist(s)",
+                result.albums_failed, result.playlists_failed
+            )
+            .red()
+        );
+    }
+    if result.tracks_failed > 0 {
+        println!("  {}", format!("Tracks failed to download: {}", result.tracks_failed).red());
+    }
+    if !result.low_bitrate_skipped.is_empty() {
+        println!(
+            "  {}",
+            format!("Skipped below minimum bitrate: {} track(s)", result.low_bitrate_skipped.len()).yellow()
+        );
+        for track in &result.low_bitrate_skipped {
+            println!("    {}", track);
+        }
+    }
+    if !result.videos_skipped.is_empty() {
+        println!(
+            "  {}",
+            format!("Skipped videos: {} (sync them with --include-video)", result.videos_skipped.len()).yellow()
+        );
+        for video in &result.videos_skipped {
+            println!("    {}", video);
+        }
+    }
+    if result.has_failures() {
+        let outcome = match on_error {
+            OnError::Skip => "failed items were skipped",
+            OnError::Retry => "failed downloads were retried, then skipped",
+            // A stopped sync returns an error before getting here
+            OnError::Stop => "sync stopped",
+        };
+        println!("  On error: {} ({})", on_error.name(), outcome);
+    }
+    if result.albums_deleted > 0 || result.playlists_deleted > 0 {
+        println!(
+            "  Deleted: {} album(s), {} playlist(s)",
+            result.albums_deleted, result.playlists_deleted
+        );
+    }
+    if result.deletions_failed > 0 {
+        println!("  {}", format!("Deletions failed: {}", result.deletions_failed).red());
+    }
 
-    Ok(())
+    Ok(result)
 }
 
+/// Select every album and/or playlist on the server, after confirmation
+///
+/// Returns `None` if the user declined.
+async fn library_selection(
+    client: &SubsonicClient,
+    args: &SelectionArgs,
+    dry_run: bool,
+) -> Result<Option<SyncSelection>> {
+    println!("{}", "Fetching library...".cyan());
+    let albums = if args.all_albums { client.get_all_albums().await? } else { Vec::new() };
+    let playlists = if args.all_playlists { client.get_playlists().await? } else { Vec::new() };
+
+    let seconds: u64 = albums
+        .iter()
+        .map(|a| a.duration.unwrap_or(0))
+        .chain(playlists.iter().map(|p| p.duration.unwrap_or(0)))
+        .map(u64::from)
+        .sum();
+    let estimate = estimated_size(seconds.min(u32::MAX as u64) as u32);
+    println!(
+        "Whole library: {} album(s), {} playlist(s), about {:.1} GB",
+        albums.len(),
+        playlists.len(),
+        estimate as f64 / 1_073_741_824.0
+    );
+
+    if !args.yes && !dry_run {
+        let confirmed = Confirm::new()
+            .with_prompt("Sync all of it?")
+            .default(false)
+            .interact()
+            .context("Confirmation needs a terminal; pass --yes to skip it")?;
+        if !confirmed {
+            println!("{}", "Cancelled.".yellow());
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(SyncSelection {
+        albums,
+        playlists,
+        ..Default::default()
+    }))
+}
+
+/// Widest row used when listing synced content in `status`
+const STATUS_MAX_WIDTH: usize = 100;
+/// Width of the track count column in `status` listings
+const STATUS_COUNT_WIDTH: usize = 10;
+
 /// Handle the `status` command
-pub async fn status(device_id: Option<String>) -> Result<()> {
+pub async fn status(device_id: Option<String>, all: bool, summary: bool, limit: Option<usize>) -> Result<()> {
+    let device_id = if all {
+        None
+    } else {
+        Settings::load().unwrap_or_default().device_or_default(device_id)
+    };
+
     let devices = if let Some(id) = device_id {
         let device = DeviceDetector::find(&id)
             .await?
@@ -326,15 +1220,7 @@ pub async fn status(device_id: Option<String>) -> Result<()> {
 
         match SyncManifest::load(&device.mount_point)? {
             Some(manifest) => {
-                println!("  Last sync: {}", manifest.last_sync.format("%Y-%m-%d %H:%M:%S"));
-                println!("  Synced albums: {}", manifest.synced_albums.len());
-                for album in &manifest.synced_albums {
-                    println!("    - {} - {}", album.artist, album.album);
-                }
-                println!("  Synced playlists: {}", manifest.synced_playlists.len());
-                for playlist in &manifest.synced_playlists {
-                    println!("    - {} ({} tracks)", playlist.name, playlist.track_count);
-                }
+                print_manifest_status(&manifest, summary, limit);
             }
             None => {
                 println!("  {}", "No nutune sync history found.".yellow());
@@ -346,6 +1232,614 @@ pub async fn status(device_id: Option<String>) -> Result<()> {
     Ok(())
 }
 
+/// Handle the `manifest` command
+pub async fn manifest(device_id: String, json: bool) -> Result<()> {
+    let device = DeviceDetector::find(&device_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Device '{}' not found", device_id))?;
+    let path = device.mount_point.join(MANIFEST_FILE);
+    let manifest = SyncManifest::load(&device.mount_point)?
+        .ok_or_else(|| anyhow::anyhow!("No nutune sync history found on '{}'", device_id))?;
+
+    if json {
+        let raw = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        print!("{}", raw);
+        if !raw.ends_with('\n') {
+            println!();
+        }
+        return Ok(());
+    }
+
+    let timestamp = |t: &chrono::DateTime<chrono::Utc>| t.format("%Y-%m-%d %H:%M:%S UTC").to_string();
+
+    println!("{}", path.display().to_string().bold());
+    println!("  Version:   {}", manifest.version);
+    println!("  Server:    {}", manifest.subsonic_url);
+    println!("  Last sync: {}", timestamp(&manifest.last_sync));
+
+    println!();
+    println!("  {} ({})", "Albums".cyan().bold(), manifest.synced_albums.len());
+    for album in &manifest.synced_albums {
+        println!("    {} - {}", album.artist, album.album);
+        println!("      ID:     {}", album.id.dimmed());
+        if let Some(genre) = &album.genre {
+            println!("      Genre:  {}", genre);
+        }
+        let tracks = match album.expected_tracks {
+            Some(expected) => format!("{} of {}", album.track_count, expected),
+            None => album.track_count.to_string(),
+        };
+        println!(
+            "      Tracks: {}{}{}",
+            tracks,
+            if album.tracks.is_empty() { "" } else { " (signed)" },
+            if album.is_complete() { "" } else { " (incomplete, will be resumed)" }
+        );
+        println!("      Synced: {}", timestamp(&album.synced_at));
+    }
+
+    println!();
+    println!("  {} ({})", "Playlists".magenta().bold(), manifest.synced_playlists.len());
+    for playlist in &manifest.synced_playlists {
+        println!("    {}", playlist.name);
+        println!("      ID:     {}", playlist.id.dimmed());
+        let tracks = match playlist.expected_tracks {
+            Some(expected) => format!("{} of {}", playlist.track_count, expected),
+            None => playlist.track_count.to_string(),
+        };
+        println!(
+            "      Tracks: {}{}",
+            tracks,
+            if playlist.is_complete() { "" } else { " (incomplete, will be retried)" }
+        );
+        println!("      Synced: {}", timestamp(&playlist.synced_at));
+    }
+
+    Ok(())
+}
+
+/// Handle the `unsync` command, returning how many items couldn't be removed
+pub async fn unsync(
+    device_id: Option<String>,
+    album_ids: Vec<String>,
+    playlist_ids: Vec<String>,
+    all: bool,
+    dry_run: bool,
+) -> Result<usize> {
+    if !all && album_ids.is_empty() && playlist_ids.is_empty() {
+        anyhow::bail!("Nothing to remove: pass --album-id, --playlist-id or --all");
+    }
+    let device_id = device_or_default(device_id)?;
+    let device = find_or_mount_device(&device_id).await?;
+    let mut manifest = SyncManifest::load(&device.mount_point)?
+        .ok_or_else(|| anyhow::anyhow!("No nutune sync history found on '{}'", device_id))?;
+
+    let mut deletions = DeletionSelection::default();
+    for album in manifest.synced_albums.iter().filter(|a| all || album_ids.contains(&a.id)) {
+        deletions.albums.push((album.id.clone(), album.artist.clone(), album.album.clone()));
+    }
+    for playlist in manifest.synced_playlists.iter().filter(|p| all || playlist_ids.contains(&p.id)) {
+        deletions.playlists.push((playlist.id.clone(), playlist.name.clone()));
+    }
+
+    let unknown_albums = album_ids.iter().filter(|id| manifest.album(id).is_none());
+    let unknown_playlists = playlist_ids
+        .iter()
+        .filter(|id| !manifest.synced_playlists.iter().any(|p| p.id == **id));
+    for id in unknown_albums.chain(unknown_playlists) {
+        println!("{}", format!("  {} is not synced to {}, skipping", id, device.name).yellow());
+    }
+
+    if deletions.is_empty() {
+        println!("Nothing to remove from {}.", device.name);
+        return Ok(0);
+    }
+
+    if dry_run {
+        println!("Would remove from {} ({}):", device.name.green(), device.mount_point.display());
+        for (_, artist, album) in &deletions.albums {
+            println!("  {} - {}", artist, album);
+        }
+        for (_, name) in &deletions.playlists {
+            println!("  Playlist: {}", name);
+        }
+        return Ok(0);
+    }
+
+    println!("Removing from: {} ({})", device.name.green(), device.mount_point.display());
+    let mut storage = DeviceStorage::new(device.mount_point.clone());
+    storage.set_fs_type(&device.fs_type);
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let printer = tokio::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            match event {
+                SyncProgress::AlbumDeleted { artist, album } => eprintln!("  Removed {} - {}", artist, album),
+                SyncProgress::PlaylistDeleted { name } => eprintln!("  Removed playlist {}", name),
+                SyncProgress::AlbumDeleteFailed { artist, album, error } => {
+                    eprintln!("  {}", format!("Failed to remove {} - {}: {}", artist, album, error).red());
+                }
+                SyncProgress::PlaylistDeleteFailed { name, error } => {
+                    eprintln!("  {}", format!("Failed to remove playlist {}: {}", name, error).red());
+                }
+                _ => {}
+            }
+        }
+    });
+    let result = sync::unsync(&storage, &mut manifest, &deletions, &tx).await;
+    drop(tx);
+    let _ = printer.await;
+    let (albums, playlists) = result?;
+
+    // Items that failed to delete are still in the manifest and stay selected
+    let album_ids: Vec<String> = deletions
+        .albums
+        .iter()
+        .filter(|(id, _, _)| manifest.album(id).is_none())
+        .map(|(id, _, _)| id.clone())
+        .collect();
+    let playlist_ids: Vec<String> = deletions
+        .playlists
+        .iter()
+        .filter(|(id, _)| !manifest.synced_playlists.iter().any(|p| p.id == *id))
+        .map(|(id, _)| id.clone())
+        .collect();
+    if let Err(e) = SyncSelection::forget_for(&device.uuid, &album_ids, &playlist_ids) {
+        warn!("Failed to update the saved selection for {}: {}", device.name, e);
+    }
+
+    let failed = deletions.albums.len() + deletions.playlists.len() - albums - playlists;
+    println!();
+    println!("Removed {} album(s) and {} playlist(s)", albums, playlists);
+    if failed > 0 {
+        println!("{}", format!("{} item(s) couldn't be removed", failed).red().bold());
+    }
+    Ok(failed)
+}
+
+/// Handle the `clean` command, returning how many orphans couldn't be removed
+pub async fn clean(device_id: Option<String>, yes: bool, dry_run: bool) -> Result<usize> {
+    let device_id = device_or_default(device_id)?;
+    let device = find_or_mount_device(&device_id).await?;
+    let manifest = SyncManifest::load(&device.mount_point)?
+        .ok_or_else(|| anyhow::anyhow!("No nutune sync history found on '{}'", device_id))?;
+
+    let mut storage = DeviceStorage::new(device.mount_point.clone());
+    storage.set_fs_type(&device.fs_type);
+    let tree = storage.walk_synced_tree(&manifest).await?;
+
+    if tree.orphans.is_empty() {
+        println!(
+            "Nothing to clean on {}: all {} folder(s) belong to synced albums and playlists.",
+            device.name,
+            tree.synced.len()
+        );
+        return Ok(0);
+    }
+
+    println!(
+        "Not part of any synced album or playlist on {} ({}):",
+        device.name.green(),
+        device.mount_point.display()
+    );
+    for orphan in &tree.orphans {
+        let path = orphan.path.strip_prefix(&device.mount_point).unwrap_or(&orphan.path);
+        let suffix = if orphan.is_dir { "/" } else { "" };
+        println!("  {}{}", path.display(), suffix);
+    }
+    println!();
+
+    if dry_run {
+        println!("Would remove {} item(s).", tree.orphans.len());
+        return Ok(0);
+    }
+    if !yes {
+        let confirmed = Confirm::new()
+            .with_prompt(format!("Delete these {} item(s)?", tree.orphans.len()))
+            .default(false)
+            .interact()
+            .context("Confirmation needs a terminal; pass --yes to skip it")?;
+        if !confirmed {
+            println!("{}", "Cancelled.".yellow());
+            return Ok(0);
+        }
+    }
+
+    let mut failed = 0;
+    for orphan in &tree.orphans {
+        if let Err(e) = storage.delete_orphan(orphan).await {
+            println!("  {}", format!("Failed to remove {}: {:#}", orphan.path.display(), e).red());
+            failed += 1;
+        }
+    }
+
+    println!("Removed {} item(s)", tree.orphans.len() - failed);
+    if failed > 0 {
+        println!("{}", format!("{} item(s) couldn't be removed", failed).red().bold());
+    }
+    Ok(failed)
+}
+
+/// Handle the `verify` command
+///
+/// With `repair`, albums and playlists with problems are dropped from the
+/// manifest so the next sync downloads them again.
+pub async fn verify(device_id: String, full: bool, probe: bool, repair: bool) -> Result<VerifyReport> {
+    let device = DeviceDetector::find(&device_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Device '{}' not found", device_id))?;
+    let mut manifest = SyncManifest::load(&device.mount_point)?
+        .ok_or_else(|| anyhow::anyhow!("No nutune sync history found on '{}'", device_id))?;
+
+    println!("Verifying: {} ({})", device.name.green(), device.mount_point.display());
+    let report = verify_device(&device.mount_point, &manifest, full, probe);
+
+    for issue in &report.item_issues {
+        println!("  {} {}: {}", issue.item.red(), issue.path, issue.problem);
+    }
+    for issue in &report.issues {
+        println!("  {} {}: {}", issue.item.red(), issue.path, issue.problem);
+    }
+
+    println!();
+    println!(
+        "Checked {} track(s), {} hashed in full",
+        report.tracks_checked, report.full_hashes
+    );
+    if report.unsigned_items > 0 {
+        println!(
+            "  {}",
+            format!(
+                "{} album(s)/playlist(s) were synced before signatures were recorded and were only checked for empty files",
+                report.unsigned_items
+            )
+            .yellow()
+        );
+    }
+    if !report.has_issues() {
+        println!("{}", "All tracks OK".green().bold());
+        return Ok(report);
+    }
+    if !report.item_issues.is_empty() {
+        let count = report.item_issues.len();
+        println!("{}", format!("{} album/playlist folder(s) or M3U file(s) missing", count).red().bold());
+    }
+    if !report.issues.is_empty() {
+        println!("{}", format!("{} track(s) missing or damaged", report.issues.len()).red().bold());
+    }
+
+    if repair {
+        let forgotten = forget_broken(&mut manifest, &report);
+        DeviceStorage::new(device.mount_point.clone()).save_manifest(&manifest).await?;
+        println!(
+            "Removed {} album(s)/playlist(s) from the manifest; the next sync downloads them again.",
+            forgotten
+        );
+    } else {
+        println!(
+            "  Have the next sync re-download them with: {}",
+            format!("nutune verify {} --repair", device_id).cyan()
+        );
+    }
+
+    Ok(report)
+}
+
+/// List albums added to the server since the device was last synced
+pub async fn whatsnew(device_id: String) -> Result<()> {
+    let device = DeviceDetector::find(&device_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Device '{}' not found", device_id))?;
+
+    let manifest = SyncManifest::load(&device.mount_point)?.ok_or_else(|| {
+        anyhow::anyhow!(
+            "Device '{}' has never been synced; run `nutune sync` first",
+            device_id
+        )
+    })?;
+
+    let creds = load_credentials().await?;
+    let client = creds.client()?;
+
+    println!(
+        "{}",
+        format!(
+            "Checking for albums added since {}...",
+            manifest.last_sync.format("%Y-%m-%d %H:%M")
+        )
+        .cyan()
+    );
+
+    let new_albums: Vec<Album> = client
+        .get_albums_added_since(manifest.last_sync)
+        .await?
+        .into_iter()
+        .filter(|a| !manifest.is_album_synced(&a.id))
+        .collect();
+
+    if new_albums.is_empty() {
+        println!("{}", "Nothing new on the server.".green());
+        return Ok(());
+    }
+
+    println!();
+    println!(
+        "{}",
+        format!("{} new albums:", new_albums.len()).green().bold()
+    );
+    for album in &new_albums {
+        let added = album
+            .created_at()
+            .map(|d| d.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        println!(
+            "  {} - {} {}",
+            album.artist.as_deref().unwrap_or("Unknown Artist"),
+            album.name,
+            added.dimmed()
+        );
+    }
+
+    Ok(())
+}
+
+/// Handle the `search` command: search the server's whole library
+pub async fn search(query: String, limit: usize) -> Result<()> {
+    let client = load_credentials().await?.client()?;
+    let result = client.search(&query, limit).await?;
+
+    if result.artist.is_empty() && result.album.is_empty() && result.song.is_empty() {
+        println!("{}", format!("Nothing on the server matches '{}'.", query).yellow());
+        return Ok(());
+    }
+
+    if !result.artist.is_empty() {
+        println!("{}", format!("Artists ({}):", result.artist.len()).green().bold());
+        for artist in &result.artist {
+            let albums = artist.album_count.map(|c| format!("{} albums", c)).unwrap_or_default();
+            println!("  {} {}", artist.name, albums.dimmed());
+        }
+        println!();
+    }
+
+    if !result.album.is_empty() {
+        println!("{}", format!("Albums ({}):", result.album.len()).green().bold());
+        for album in &result.album {
+            let year = album.year.map(|y| format!(" ({})", y)).unwrap_or_default();
+            println!(
+                "  {} - {}{} {}",
+                album.artist.as_deref().unwrap_or("Unknown Artist"),
+                album.name,
+                year,
+                album.id.dimmed()
+            );
+        }
+        println!();
+    }
+
+    if !result.song.is_empty() {
+        println!("{}", format!("Songs ({}):", result.song.len()).green().bold());
+        for song in &result.song {
+            let album = song.album.as_deref().map(|a| format!("[{}]", a)).unwrap_or_default();
+            println!(
+                "  {} - {} {}",
+                song.artist.as_deref().unwrap_or("Unknown Artist"),
+                song.title,
+                album.dimmed()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Print a device manifest grouped by artist, optionally limited or summarized
+fn print_manifest_status(manifest: &SyncManifest, summary: bool, limit: Option<usize>) {
+    let mut by_artist: HashMap<&str, Vec<&SyncedAlbum>> = HashMap::new();
+    for album in &manifest.synced_albums {
+        by_artist.entry(album.artist.as_str()).or_default().push(album);
+    }
+    let album_tracks: u32 = manifest.synced_albums.iter().map(|a| a.track_count).sum();
+    let playlist_tracks: u32 = manifest.synced_playlists.iter().map(|p| p.track_count).sum();
+
+    println!("  Last sync: {}", manifest.last_sync.format("%Y-%m-%d %H:%M:%S"));
+    println!(
+        "  Synced albums: {} by {} artists ({} tracks)",
+        manifest.synced_albums.len().to_string().bold(),
+        by_artist.len(),
+        album_tracks
+    );
+    println!(
+        "  Synced playlists: {} ({} tracks)",
+        manifest.synced_playlists.len().to_string().bold(),
+        playlist_tracks
+    );
+
+    if summary {
+        return;
+    }
+
+    let width = console::Term::stdout()
+        .size_checked()
+        .map(|(_, cols)| cols as usize)
+        .unwrap_or(80)
+        .min(STATUS_MAX_WIDTH);
+    let limit = limit.unwrap_or(usize::MAX);
+
+    // Albums grouped by artist, sorted case-insensitively
+    let mut artists: Vec<_> = by_artist.into_iter().collect();
+    artists.sort_by_key(|(artist, _)| artist.to_lowercase());
+
+    let mut shown = 0;
+    'artists: for (artist, mut albums) in artists {
+        if shown >= limit {
+            break;
+        }
+        albums.sort_by_key(|a| a.album.to_lowercase());
+
+        println!();
+        println!("  {} {}", artist.cyan().bold(), format!("({})", albums.len()).dimmed());
+        for album in albums {
+            if shown >= limit {
+                break 'artists;
+            }
+            let tracks = match album.expected_tracks {
+                Some(expected) if !album.is_complete() => format!("{}/{} tracks", album.track_count, expected),
+                _ => format!("{} tracks", album.track_count),
+            };
+            let row = format_row(&album.album, &[], width.saturating_sub(STATUS_COUNT_WIDTH + 5));
+            println!("    {} {}", row, format!("{:>width$}", tracks, width = STATUS_COUNT_WIDTH).dimmed());
+            shown += 1;
+        }
+    }
+    print_hidden_count(manifest.synced_albums.len(), shown, "albums");
+
+    if !manifest.synced_playlists.is_empty() && limit > 0 {
+        println!();
+        println!("  {}", "Playlists".magenta().bold());
+        let mut playlists: Vec<_> = manifest.synced_playlists.iter().collect();
+        playlists.sort_by_key(|p| p.name.to_lowercase());
+        for playlist in playlists.iter().take(limit) {
+            let tracks = match playlist.expected_tracks {
+                Some(expected) if !playlist.is_complete() => {
+                    format!("{}/{} tracks", playlist.track_count, expected)
+                }
+                _ => format!("{} tracks", playlist.track_count),
+            };
+            let row = format_row(&playlist.name, &[], width.saturating_sub(STATUS_COUNT_WIDTH + 5));
+            println!("    {} {}", row, format!("{:>width$}", tracks, width = STATUS_COUNT_WIDTH).dimmed());
+        }
+    }
+    print_hidden_count(manifest.synced_playlists.len(), limit.min(manifest.synced_playlists.len()), "playlists");
+}
+
+/// Note how many items were left out of a limited listing
+fn print_hidden_count(total: usize, shown: usize, kind: &str) {
+    if shown < total {
+        println!(
+            "    {}",
+            format!("... and {} more {} (raise --limit to see more)", total - shown, kind).dimmed()
+        );
+    }
+}
+
+/// Handle the `import-selection` command
+pub async fn import_selection(csv_path: PathBuf, merge: bool) -> Result<()> {
+    let creds = load_credentials().await?;
+
+    let content = std::fs::read_to_string(&csv_path)
+        .with_context(|| format!("Failed to read {}", csv_path.display()))?;
+
+    let mut rows = crate::utils::csv::parse_csv(&content);
+
+    // Skip an optional header row
+    if rows
+        .first()
+        .and_then(|row| row.first())
+        .is_some_and(|field| field.eq_ignore_ascii_case("artist"))
+    {
+        rows.remove(0);
+    }
+
+    if rows.is_empty() {
+        println!("{}", format!("No entries found in {}", csv_path.display()).yellow());
+        return Ok(());
+    }
+
+    let client = creds.client()?;
+
+    println!("{}", format!("Resolving {} entries...", rows.len()).cyan());
+
+    let mut selection = if merge {
+        SyncSelection::load()?
+    } else {
+        SyncSelection::new()
+    };
+
+    let mut resolved = 0;
+    let mut unresolved = Vec::new();
+
+    for row in &rows {
+        let artist = row.first().map(String::as_str).unwrap_or("");
+        let album = row.get(1).map(String::as_str).unwrap_or("");
+        let description = if album.is_empty() {
+            format!("{} (all albums)", artist)
+        } else {
+            format!("{} - {}", artist, album)
+        };
+
+        match resolve_import_entry(&client, artist, album).await {
+            Ok(albums) if !albums.is_empty() => {
+                resolved += 1;
+                for album in albums {
+                    if !selection.albums.iter().any(|a| a.id == album.id) {
+                        selection.albums.push(album);
+                    }
+                }
+            }
+            Ok(_) => unresolved.push(description),
+            Err(e) => unresolved.push(format!("{} ({})", description, e)),
+        }
+    }
+
+    println!();
+    println!("Resolved {} of {} entries.", resolved, rows.len());
+
+    if !unresolved.is_empty() {
+        println!("{}", "Unresolved:".yellow().bold());
+        for entry in &unresolved {
+            println!("  - {}", entry);
+        }
+    }
+
+    selection.save()?;
+
+    println!();
+    println!(
+        "Selection saved: {} album(s) and {} playlist(s).",
+        selection.album_count(),
+        selection.playlist_count()
+    );
+    println!("Run {} to sync to a device.", "nutune sync <device>".cyan());
+
+    Ok(())
+}
+
+/// Resolve an imported artist/album entry to albums on the server
+///
+/// An empty album name resolves to every album by the artist.
+async fn resolve_import_entry(
+    client: &SubsonicClient,
+    artist: &str,
+    album: &str,
+) -> Result<Vec<Album>> {
+    let names_match = |a: &str, b: &str| a.trim().to_lowercase() == b.trim().to_lowercase();
+
+    if album.is_empty() {
+        if artist.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let result = client.search3(artist, 20, 0).await?;
+        let Some(found) = result.artist.iter().find(|a| names_match(&a.name, artist)) else {
+            return Ok(Vec::new());
+        };
+        return Ok(client.get_artist(&found.id).await?.album);
+    }
+
+    let result = client.search3(album, 50, 0).await?;
+    Ok(result
+        .album
+        .into_iter()
+        .filter(|a| names_match(&a.name, album))
+        .find(|a| {
+            artist.is_empty() || a.artist.as_deref().is_some_and(|name| names_match(name, artist))
+        })
+        .into_iter()
+        .collect())
+}
+
 /// Handle the `completion` command
 pub fn completion(shell: clap_complete::Shell) {
     let mut cmd = super::Cli::command();
@@ -358,3 +1852,260 @@ impl super::Cli {
         <Self as clap::CommandFactory>::command()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::m3u::PlaylistFormat;
+    use crate::device::manifest::SyncedPlaylist;
+    use crate::subsonic::mock::{MockReply, mock_api};
+    use chrono::Utc;
+    use serde_json::json;
+
+    fn credentials(url: &str) -> SubsonicCredentials {
+        SubsonicCredentials {
+            url: url.to_string(),
+            username: "user".to_string(),
+            password: "pass".to_string(),
+            api_path: "/rest".to_string(),
+            insecure: false,
+            auth_mode: AuthMode::Plaintext,
+        }
+    }
+
+    /// A device root with album "a1" and playlist "p1" synced to it
+    fn device_with_album_and_playlist(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("nutune_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("Artists/Artist/Old")).unwrap();
+        std::fs::write(root.join("Artists/Artist/Old/01 - A.mp3"), b"a").unwrap();
+
+        let mut manifest = SyncManifest::new("http://server");
+        manifest.add_album(SyncedAlbum {
+            id: "a1".to_string(),
+            artist: "Artist".to_string(),
+            album: "Old".to_string(),
+            genre: None,
+            folder: None,
+            track_count: 1,
+            expected_tracks: Some(1),
+            server_tracks: None,
+            synced_tracks: Vec::new(),
+            partial: false,
+            synced_at: Utc::now(),
+            tracks: Vec::new(),
+        });
+        manifest.add_playlist(SyncedPlaylist {
+            id: "p1".to_string(),
+            name: "Mix".to_string(),
+            track_count: 0,
+            expected_tracks: Some(0),
+            server_tracks: None,
+            changed: None,
+            song_ids: Vec::new(),
+            synced_at: Utc::now(),
+            tracks: Vec::new(),
+            format: PlaylistFormat::M3u,
+        });
+        std::fs::write(root.join(MANIFEST_FILE), manifest.to_json().unwrap()).unwrap();
+        root
+    }
+
+    #[test]
+    fn test_device_transcode_format_is_the_default() {
+        let root = device_with_album_and_playlist("device_transcode");
+        let device = Device {
+            name: "sdz1".to_string(),
+            label: None,
+            mount_point: root.clone(),
+            size: 4 << 30,
+            free_space: 1 << 30,
+            fs_type: "vfat".to_string(),
+            uuid: "test-device".to_string(),
+            friendly_name: None,
+            layout: None,
+            cover_size: None,
+            cover_quality: None,
+            transcode: Some("mp3".to_string()),
+        };
+
+        let options = with_device_settings(SyncOptions::default(), &device).unwrap();
+        assert_eq!(options.transcode_format(), Some("mp3"));
+
+        let capped = SyncOptions {
+            transcode: Some(Transcode { format: None, max_bitrate: Some(192) }),
+            ..Default::default()
+        };
+        let options = with_device_settings(capped, &device).unwrap();
+        assert_eq!(options.transcode, Some(Transcode { format: Some("mp3".to_string()), max_bitrate: Some(192) }));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_max_bitrate_below_min_bitrate_is_rejected() {
+        let options = |max_bitrate| SyncOptions {
+            temp_dir: Some(std::env::temp_dir()),
+            min_bitrate: Some(256),
+            transcode: Some(Transcode { format: Some("mp3".to_string()), max_bitrate }),
+            ..Default::default()
+        };
+        assert!(with_settings(options(Some(320))).is_ok());
+        assert!(with_settings(options(Some(256))).is_ok());
+        assert!(with_settings(options(None)).is_ok());
+        let err = with_settings(options(Some(192))).unwrap_err();
+        assert!(err.to_string().contains("--max-bitrate 192 is below --min-bitrate 256"), "{}", err);
+    }
+
+    #[test]
+    fn test_transcode_mismatch_warns_about_mixed_formats() {
+        let mut manifest = SyncManifest::new("http://server");
+        assert!(transcode_mismatch(&manifest, Some("mp3")).is_none());
+
+        manifest.add_playlist(SyncedPlaylist {
+            id: "p1".to_string(),
+            name: "Mix".to_string(),
+            track_count: 0,
+            expected_tracks: Some(0),
+            server_tracks: None,
+            changed: None,
+            song_ids: Vec::new(),
+            synced_at: Utc::now(),
+            tracks: Vec::new(),
+            format: PlaylistFormat::M3u,
+        });
+        assert!(transcode_mismatch(&manifest, None).is_none());
+        let warning = transcode_mismatch(&manifest, Some("mp3")).unwrap();
+        assert!(warning.contains("synced in their original format, but this sync writes them transcoded to mp3"));
+
+        manifest.transcode = Some("mp3".to_string());
+        assert!(transcode_mismatch(&manifest, Some("mp3")).is_none());
+        assert!(transcode_mismatch(&manifest, Some("opus")).is_some());
+    }
+
+    #[test]
+    fn test_read_selection_from_file() {
+        let path = std::env::temp_dir().join(format!("nutune_read_selection_{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{"albums": [{"id": "a1", "name": "Album", "source": "folder"}], "playlists": [{"id": "p1", "name": "Mix"}]}"#,
+        )
+        .unwrap();
+        let selection = read_selection(&path).unwrap();
+        assert_eq!(selection.albums[0].id, "a1");
+        assert_eq!(selection.albums[0].source, AlbumSource::Folder);
+        assert_eq!(selection.playlists[0].name, "Mix");
+
+        std::fs::write(&path, r#"{"albums": [{"name": "No id"}]}"#).unwrap();
+        let err = format!("{:#}", read_selection(&path).unwrap_err());
+        assert!(err.contains("is not valid"), "{}", err);
+
+        let _ = std::fs::remove_file(&path);
+        assert!(read_selection(&path).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_check_selection_looks_up_folders_as_directories() {
+        let not_found = MockReply::new(
+            "200 OK",
+            r#"{"subsonic-response":{"status":"failed","version":"1.16.1","error":{"code":70,"message":"Not found"}}}"#,
+        );
+        let url = mock_api(vec![
+            ("getAlbum", not_found.clone()),
+            ("getMusicDirectory", MockReply::ok(json!({ "directory": { "id": "f1", "name": "Folder" } }))),
+            ("getPlaylist", not_found),
+        ])
+        .await;
+        let client = credentials(&url).client().unwrap();
+
+        let folder: Album = serde_json::from_value(json!({ "id": "f1", "name": "Folder", "source": "folder" })).unwrap();
+        let mut selection = SyncSelection {
+            albums: vec![folder],
+            ..Default::default()
+        };
+        check_selection_on_server(&client, &selection).await.unwrap();
+
+        // Gone albums and playlists are all listed
+        selection.albums.push(serde_json::from_value(json!({ "id": "a9", "name": "Gone", "artist": "Artist" })).unwrap());
+        selection.playlists.push(serde_json::from_value(json!({ "id": "p9", "name": "Old mix" })).unwrap());
+        let err = check_selection_on_server(&client, &selection).await.unwrap_err().to_string();
+        assert!(err.contains("2 item(s)"), "{}", err);
+        assert!(err.contains("album Artist - Gone (a9)"), "{}", err);
+        assert!(err.contains("playlist Old mix (p9)"), "{}", err);
+        assert!(!err.contains("f1"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_library_selection_selects_everything_asked_for() {
+        let url = mock_api(vec![
+            ("getAlbumList2", MockReply::ok(json!({ "albumList2": { "album": [
+                { "id": "a1", "name": "One", "duration": 1800 },
+                { "id": "a2", "name": "Two", "duration": 1800 }
+            ] } }))),
+            ("getPlaylists", MockReply::ok(json!({ "playlists": { "playlist": [{ "id": "p1", "name": "Mix" }] } }))),
+        ])
+        .await;
+        let client = SubsonicClient::new(&url, "user", "pass").unwrap();
+        let ids = |selection: SyncSelection| {
+            let albums: Vec<String> = selection.albums.into_iter().map(|a| a.id).collect();
+            let playlists: Vec<String> = selection.playlists.into_iter().map(|p| p.id).collect();
+            (albums, playlists)
+        };
+
+        let everything = SelectionArgs {
+            all_albums: true,
+            all_playlists: true,
+            yes: true,
+            ..Default::default()
+        };
+        let (albums, playlists) = ids(library_selection(&client, &everything, false).await.unwrap().unwrap());
+        assert_eq!(albums, ["a1", "a2"]);
+        assert_eq!(playlists, ["p1"]);
+
+        // A dry run doesn't ask either
+        let albums_only = SelectionArgs {
+            all_albums: true,
+            ..Default::default()
+        };
+        let (albums, playlists) = ids(library_selection(&client, &albums_only, true).await.unwrap().unwrap());
+        assert_eq!(albums, ["a1", "a2"]);
+        assert!(playlists.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_whole_library_sync_only_deletes_the_kind_it_selects() {
+        let url = mock_api(vec![
+            ("getAlbumList2", MockReply::ok(json!({ "albumList2": { "album": [{ "id": "a2", "name": "New", "songCount": 0 }] } }))),
+            ("getAlbum", MockReply::ok(json!({ "album": { "id": "a2", "name": "New", "song": [] } }))),
+            ("getPlaylists", MockReply::ok(json!({ "playlists": { "playlist": [{ "id": "p2", "name": "Other", "songCount": 0 }] } }))),
+            ("getPlaylist", MockReply::ok(json!({ "playlist": { "id": "p2", "name": "Other", "entry": [] } }))),
+        ])
+        .await;
+
+        for (all_albums, all_playlists) in [(false, true), (true, false)] {
+            let root = device_with_album_and_playlist(&format!("whole_library_{}", all_albums));
+            let args = SelectionArgs {
+                all_albums,
+                all_playlists,
+                yes: true,
+                ..Default::default()
+            };
+            let result = run_sync(credentials(&url), root.clone(), None, false, 1, None, args, SyncOptions::default())
+                .await
+                .unwrap();
+
+            let manifest = SyncManifest::load(&root).unwrap().unwrap();
+            if all_playlists {
+                // --all-playlists replaces the playlists, and keeps the albums
+                assert_eq!((result.albums_deleted, result.playlists_deleted), (0, 1));
+                assert!(manifest.is_album_synced("a1"));
+                assert!(root.join("Artists/Artist/Old/01 - A.mp3").exists());
+            } else {
+                // --all-albums replaces the albums, and keeps the playlists
+                assert_eq!((result.albums_deleted, result.playlists_deleted), (1, 0));
+                assert!(manifest.is_playlist_synced("p1"));
+                assert!(!root.join("Artists/Artist/Old").exists());
+            }
+            let _ = std::fs::remove_dir_all(&root);
+        }
+    }
+}
@@ -1,6 +1,17 @@
 //! CLI module for nutune
 
-use clap::{Parser, Subcommand};
+use chrono::NaiveDate;
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::device::layout::LayoutTemplate;
+use crate::device::storage::validate_playlist_track_format;
+use crate::sync::downloader::Transcode;
+use crate::sync::{ArtistSource, OnError, SyncOptions};
+use crate::subsonic::auth::AuthMode;
+use crate::utils::cover_art::CoverFormat;
+use crate::utils::m3u::{M3uEncoding, PlaylistFormat};
+use crate::utils::size::parse_size;
 
 pub mod auth;
 pub mod commands;
@@ -17,6 +28,24 @@ pub struct Cli {
     /// Enable verbose logging
     #[arg(short, long, global = true)]
     pub verbose: bool,
+
+    /// Read server credentials from a dotenv or TOML file instead of the keyring
+    #[arg(long, global = true, value_name = "PATH")]
+    pub credentials_file: Option<PathBuf>,
+
+    /// Accept self-signed or otherwise invalid TLS certificates from the server
+    ///
+    /// Turns off certificate checks, so only use it for a server you trust on a
+    /// network you trust. With `auth`, it's saved with the credentials.
+    #[arg(long, global = true)]
+    pub insecure: bool,
+
+    /// Give up on a server request after this many seconds [default: 300]
+    ///
+    /// Track downloads can take longer, but fail once no data has arrived for
+    /// this long; they are retried as usual. Connecting has its own 10 second limit.
+    #[arg(long, global = true, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub timeout: Option<u64>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -35,6 +64,15 @@ pub enum Commands {
         #[arg(short, long, env = "SUBSONIC_PASS")]
         password: Option<String>,
 
+        /// API path prefix under the server URL, for reverse-proxied servers [default: /rest]
+        #[arg(long, env = "SUBSONIC_API_PATH", value_name = "PATH")]
+        api_path: Option<String>,
+
+        /// How to send the password: auto tries a token, then the hex-encoded
+        /// password if the server rejects tokens (e.g. LDAP accounts) [default: auto]
+        #[arg(long, env = "SUBSONIC_AUTH_MODE", value_enum, value_name = "MODE")]
+        auth_mode: Option<AuthMode>,
+
         /// Force re-authentication (ignore stored credentials)
         #[arg(long)]
         force: bool,
@@ -45,6 +83,10 @@ pub enum Commands {
         /// Show detailed information (free space, filesystem type)
         #[arg(short, long)]
         detailed: bool,
+
+        /// Only show devices with at least this much free space (e.g. 500M, 4G)
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        min_free: Option<u64>,
     },
 
     /// Interactive browse and select music to sync
@@ -56,13 +98,44 @@ pub enum Commands {
         /// Start with playlists view
         #[arg(long, conflicts_with = "artists")]
         playlists: bool,
+
+        /// Resume the last session (reselect the last device and view)
+        #[arg(long)]
+        resume: bool,
+
+        /// Print the artists and playlists as JSON instead of opening the browser
+        #[arg(long, conflicts_with_all = ["artists", "playlists", "resume"])]
+        json: bool,
+
+        /// With --json, also list each artist's albums (one request per artist)
+        #[arg(long, requires = "json")]
+        albums: bool,
+
+        /// Number of albums a sync from the browser downloads at once
+        ///
+        /// They share the sync's 4 parallel downloads, and are still written to
+        /// the device one album at a time.
+        #[arg(long, value_name = "N", default_value = "1")]
+        album_parallel: usize,
+    },
+
+    /// Search the server's library for artists, albums and songs
+    Search {
+        /// Text to search for
+        query: String,
+
+        /// Maximum results of each type
+        #[arg(long, value_name = "N", default_value = "100")]
+        limit: usize,
     },
 
     /// Sync selected content to device
+    ///
+    /// Exits with status 2 if any album, playlist, track or deletion failed.
     Sync {
-        /// Device identifier (name, label, or mount point from `devices` command)
+        /// Device identifier (name, label, or mount point from `devices` command) [default: the default device]
         #[arg(value_name = "DEVICE")]
-        device: String,
+        device: Option<String>,
 
         /// Dry run - show what would be synced without downloading
         #[arg(long)]
@@ -72,19 +145,199 @@ pub enum Commands {
         #[arg(short, long, default_value = "4")]
         parallel: usize,
 
-        /// Skip playlists, only sync artist/album folders
-        #[arg(long)]
-        no_playlists: bool,
+        /// Maximum simultaneous connections to the server (defaults to --parallel)
+        #[arg(long, value_name = "N")]
+        max_connections: Option<usize>,
 
-        /// Skip artist folders, only sync playlists
-        #[arg(long)]
-        playlists_only: bool,
+        /// Only re-embed cover art into albums already on the device, without downloading audio
+        #[arg(long, conflicts_with_all = ["dry_run", "no_manifest"])]
+        covers_only: bool,
+
+        #[command(flatten)]
+        selection: SelectionArgs,
+
+        #[command(flatten)]
+        options: SyncOptionsArgs,
+    },
+
+    /// Download and process the selection into a directory, to apply to a device later
+    ///
+    /// The directory gets the same layout and manifest a device would, so
+    /// re-running `prepare` only fetches what is missing.
+    Prepare {
+        /// Directory to write the bundle to (created if missing)
+        #[arg(long, value_name = "DIR")]
+        out: PathBuf,
+
+        /// Number of parallel downloads
+        #[arg(short, long, default_value = "4")]
+        parallel: usize,
+
+        /// Maximum simultaneous connections to the server (defaults to --parallel)
+        #[arg(long, value_name = "N")]
+        max_connections: Option<usize>,
+
+        #[command(flatten)]
+        selection: SelectionArgs,
+
+        #[command(flatten)]
+        options: SyncOptionsArgs,
+    },
+
+    /// Re-sync the albums and playlists that failed in earlier syncs to a device
+    ///
+    /// Failures are recorded in the device's manifest and cleared as they
+    /// succeed. Pass the same layout options as the original sync.
+    /// Exits with status 2 if anything fails again.
+    Retry {
+        /// Device identifier (name, label, or mount point from `devices` command) [default: the default device]
+        #[arg(value_name = "DEVICE")]
+        device: Option<String>,
+
+        /// Number of parallel downloads
+        #[arg(short, long, default_value = "4")]
+        parallel: usize,
+
+        /// Maximum simultaneous connections to the server (defaults to --parallel)
+        #[arg(long, value_name = "N")]
+        max_connections: Option<usize>,
+
+        #[command(flatten)]
+        options: SyncOptionsArgs,
+    },
+
+    /// Copy a bundle made by `prepare` to a device without re-downloading
+    Apply {
+        /// Device identifier (name, label, or mount point from `devices` command)
+        #[arg(value_name = "DEVICE")]
+        device: String,
+
+        /// Bundle directory written by `prepare --out`
+        #[arg(long, value_name = "DIR")]
+        from: PathBuf,
     },
 
     /// Show sync status for a device
     Status {
-        /// Device identifier (optional, shows all if omitted)
+        /// Device identifier (defaults to the default device, or all devices if none is set)
+        device: Option<String>,
+
+        /// Show every detected device, even if a default device is set
+        #[arg(long, conflicts_with = "device")]
+        all: bool,
+
+        /// Only show counts, not the list of synced albums and playlists
+        #[arg(long)]
+        summary: bool,
+
+        /// Maximum number of albums and playlists to list
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+    },
+
+    /// Check synced tracks on a device against the signatures in its manifest
+    ///
+    /// Exits with status 2 if anything is missing or damaged (unless --repair is given).
+    Verify {
+        /// Device identifier (name, label, or mount point from `devices` command)
+        #[arg(value_name = "DEVICE")]
+        device: String,
+
+        /// Hash every track in full instead of only those the quick check can't vouch for
+        #[arg(long)]
+        full: bool,
+
+        /// Also parse each track to check it is readable audio
+        #[arg(long)]
+        probe: bool,
+
+        /// Remove albums and playlists with problems from the manifest, so the next sync downloads them again
+        #[arg(long)]
+        repair: bool,
+    },
+
+    /// Remove synced albums and playlists from a device
+    ///
+    /// Takes album and playlist IDs as shown by `nutune manifest`. Exits
+    /// with status 2 if anything couldn't be removed.
+    Unsync {
+        /// Device identifier (name, label, or mount point from `devices` command) [default: the default device]
+        #[arg(value_name = "DEVICE")]
         device: Option<String>,
+
+        /// Album to remove, by ID (repeatable)
+        #[arg(long = "album-id", value_name = "ID")]
+        album_ids: Vec<String>,
+
+        /// Playlist to remove, by ID (repeatable)
+        #[arg(long = "playlist-id", value_name = "ID")]
+        playlist_ids: Vec<String>,
+
+        /// Remove everything nutune has synced to the device
+        #[arg(long, conflicts_with_all = ["album_ids", "playlist_ids"])]
+        all: bool,
+
+        /// List what would be removed without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Remove files and folders that no synced album or playlist accounts for
+    ///
+    /// Looks in Artists/, Genres/ and Playlists/ on the device, and asks
+    /// before deleting anything. Exits with status 2 if anything couldn't be removed.
+    Clean {
+        /// Device identifier (name, label, or mount point from `devices` command) [default: the default device]
+        #[arg(value_name = "DEVICE")]
+        device: Option<String>,
+
+        /// Delete without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+
+        /// List what would be removed without deleting anything
+        #[arg(long, conflicts_with = "yes")]
+        dry_run: bool,
+    },
+
+    /// Show a device's sync manifest: every synced album and playlist with IDs and timestamps
+    Manifest {
+        /// Device identifier (name, label, or mount point from `devices` command)
+        #[arg(value_name = "DEVICE")]
+        device: String,
+
+        /// Print the manifest file as stored on the device
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List albums added to the server since the last sync to a device
+    Whatsnew {
+        /// Device identifier (name, label, or mount point from `devices` command)
+        #[arg(value_name = "DEVICE")]
+        device: String,
+    },
+
+    /// Show or set the device used when `sync` and `status` aren't given one
+    DefaultDevice {
+        /// Device identifier to make the default (name, label, or mount point)
+        #[arg(value_name = "DEVICE")]
+        device: Option<String>,
+
+        /// Forget the default device
+        #[arg(long, conflicts_with = "device")]
+        clear: bool,
+    },
+
+    /// Import a selection from a CSV file of artist/album rows
+    ImportSelection {
+        /// CSV file with `artist,album` rows (header row optional, empty album selects all albums by the artist)
+        #[arg(long, value_name = "FILE")]
+        csv: PathBuf,
+
+        /// Add to the saved selection instead of replacing it
+        #[arg(long)]
+        merge: bool,
     },
 
     /// Generate shell completions
@@ -93,3 +346,387 @@ pub enum Commands {
         shell: clap_complete::Shell,
     },
 }
+
+/// Which albums and playlists a sync covers
+#[derive(Args, Debug, Clone, Default)]
+pub struct SelectionArgs {
+    /// Read the selection from a JSON file (or - for stdin) instead of the one saved by the browser
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["all_albums", "all_playlists"])]
+    pub selection: Option<PathBuf>,
+
+    /// Sync your starred albums, and the starred songs of other albums, instead of the saved selection
+    ///
+    /// Albums that are no longer starred are deleted (unless --no-delete);
+    /// playlists on the device are left alone.
+    #[arg(
+        long,
+        conflicts_with_all = ["selection", "all_albums", "all_playlists", "playlists_only", "new_since"]
+    )]
+    pub starred: bool,
+
+    /// Skip playlists, only sync artist/album folders
+    #[arg(long)]
+    pub no_playlists: bool,
+
+    /// Skip artist folders, only sync playlists
+    #[arg(long)]
+    pub playlists_only: bool,
+
+    /// Sync every album on the server instead of the saved selection
+    #[arg(long, conflicts_with = "playlists_only")]
+    pub all_albums: bool,
+
+    /// Sync every playlist on the server instead of the saved selection
+    #[arg(long, conflicts_with = "no_playlists")]
+    pub all_playlists: bool,
+
+    /// Keep albums/playlists on the device that are no longer selected
+    #[arg(long)]
+    pub no_delete: bool,
+
+    /// Don't ask for confirmation before syncing the whole library or deleting deselected items
+    #[arg(short, long)]
+    pub yes: bool,
+
+    /// Only sync albums/playlists whose name matches this glob (repeatable)
+    ///
+    /// Albums match on either "Album" or "Artist - Album"; matching ignores case.
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Skip albums/playlists whose name matches this glob (repeatable, applied after --include)
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Only add albums put on the server since the last sync (or since DATE, as YYYY-MM-DD)
+    ///
+    /// Limited to artists with an album in the selection. Nothing is deleted,
+    /// and the new albums are added to the saved selection.
+    #[arg(long, value_name = "DATE", num_args = 0..=1, conflicts_with = "playlists_only")]
+    pub new_since: Option<Option<NaiveDate>>,
+}
+
+impl SelectionArgs {
+    /// Whether the selection comes from the whole library rather than the saved selection
+    pub fn whole_library(&self) -> bool {
+        self.all_albums || self.all_playlists
+    }
+
+    /// Whether albums on the device that aren't in this sync's selection should go
+    ///
+    /// Only when the selection covers albums: not with --playlists-only, nor
+    /// with --all-playlists alone, which selects no albums.
+    pub fn replaces_albums(&self) -> bool {
+        !self.playlists_only && (!self.whole_library() || self.all_albums)
+    }
+
+    /// Whether playlists on the device that aren't in this sync's selection should go
+    ///
+    /// --no-playlists, --starred and --all-albums alone select no playlists.
+    pub fn replaces_playlists(&self) -> bool {
+        !self.no_playlists && !self.starred && (!self.whole_library() || self.all_playlists)
+    }
+
+    /// Whether the selection is the one saved by the browser
+    pub fn uses_saved_selection(&self) -> bool {
+        !self.whole_library() && self.selection.is_none() && !self.starred
+    }
+}
+
+/// Options controlling how downloaded music is processed and laid out
+#[derive(Args, Debug)]
+pub struct SyncOptionsArgs {
+    /// Group albums by genre (Genres/<genre>/<artist>/<album>)
+    #[arg(long)]
+    genre_folders: bool,
+
+    /// Where album tracks go on the device, using {artist}, {album}, {year},
+    /// {genre}, {disc}, {track} and {title}; saved as the device's default
+    /// [default: "Artists/{artist}/{album}/{track} - {title}"]
+    #[arg(long, value_name = "TEMPLATE", value_parser = LayoutTemplate::parse, conflicts_with = "genre_folders")]
+    layout: Option<LayoutTemplate>,
+
+    /// Size in pixels to request cover art at (defaults to the processed cover size)
+    #[arg(long, value_name = "PIXELS")]
+    cover_fetch_size: Option<u32>,
+
+    /// Fail an album/playlist if cover art can't be embedded in a track
+    #[arg(long)]
+    strict_cover: bool,
+
+    /// Write the server's album notes to notes.txt in each album folder
+    #[arg(long)]
+    write_album_notes: bool,
+
+    /// Image format for cover art (webp is smaller but needs player support; png for players that only show PNG)
+    #[arg(long, value_enum, default_value_t = CoverFormat::Jpeg)]
+    cover_format: CoverFormat,
+
+    /// Largest width/height in pixels to resize cover art to; saved as the device's default [default: 300]
+    #[arg(long, value_name = "PIXELS", value_parser = clap::value_parser!(u32).range(1..))]
+    cover_size: Option<u32>,
+
+    /// JPEG or WebP quality (1-100) for cover art; saved as the device's default [default: 75]
+    #[arg(long, value_name = "QUALITY", value_parser = clap::value_parser!(u8).range(1..=100))]
+    cover_quality: Option<u8>,
+
+    /// Name for the cover file in each album folder, e.g. folder.jpg (repeatable to write several)
+    /// [default: cover.jpg]
+    ///
+    /// The extension always follows --cover-format.
+    #[arg(long = "cover-filename", value_name = "NAME", value_parser = parse_cover_filename)]
+    cover_names: Vec<String>,
+
+    /// Use a local image as an album's cover art (repeatable)
+    #[arg(long = "cover-override", value_name = "ALBUM_ID=PATH", value_parser = parse_cover_override)]
+    cover_overrides: Vec<(String, PathBuf)>,
+
+    /// Set each track's modification time to when it was added to the server
+    #[arg(long)]
+    preserve_dates: bool,
+
+    /// Write a .cue chapter sidecar for tracks with bookmarks on the server
+    #[arg(long)]
+    with_chapters: bool,
+
+    /// Playlist track filename format; {n} is the playlist position [default: "{artist} - {title}"]
+    #[arg(long, value_name = "FORMAT", value_parser = parse_playlist_track_format)]
+    playlist_track_format: Option<String>,
+
+    /// Line endings and BOM for playlist.m3u files, for players that need them
+    #[arg(long, value_enum, default_value_t = M3uEncoding::Utf8)]
+    m3u_encoding: M3uEncoding,
+
+    /// Write playlist.m3u files with bare filenames, without #EXTINF duration and title lines
+    #[arg(long)]
+    simple_m3u: bool,
+
+    /// Playlist file written to each playlist folder
+    #[arg(long, value_enum, default_value_t = PlaylistFormat::M3u)]
+    playlist_format: PlaylistFormat,
+
+    /// Sync the smallest albums first and say when the first one is complete on the device
+    #[arg(long)]
+    first_complete: bool,
+
+    /// What to do when an album, playlist or track fails
+    #[arg(long, value_enum, default_value_t = OnError::Skip)]
+    on_error: OnError,
+
+    /// Artist name for album folders and playlist track names [default: album for albums, track for playlists]
+    ///
+    /// "album" uses the album artist, falling back to the track's album artist, then the
+    /// track artist; "track" tries the same names in the opposite order. Album folders
+    /// always use one artist per album, so compilations stay together; albums with several
+    /// track artists use the album artist (or "Various Artists") whichever source is picked.
+    #[arg(long, value_enum, value_name = "SOURCE")]
+    artist_source: Option<ArtistSource>,
+
+    /// Tune download concurrency from measured throughput instead of using --parallel
+    ///
+    /// Starts at 2 downloads and ramps up while throughput improves (to at most 16, or
+    /// --max-connections), backing off on failures. Cover processing uses every CPU core.
+    #[arg(long)]
+    auto_parallel: bool,
+
+    /// Also embed the album's back cover and disc/booklet scans, when the server lists them
+    #[arg(long)]
+    all_art: bool,
+
+    /// Ignore the sync manifest and don't write one: sync everything and leave no state on the device
+    ///
+    /// For throwaway cards and players that dislike extra files. Nothing is skipped as
+    /// already synced and nothing is deleted, since nutune has no record of the device.
+    #[arg(long)]
+    no_manifest: bool,
+
+    /// With --covers-only, re-embed cover art even into tracks that already have acceptable art
+    #[arg(long)]
+    force_cover: bool,
+
+    /// Overwrite title, artist, album, track/disc, year and genre tags with the server's
+    ///
+    /// By default these are only written into files where they're empty.
+    #[arg(long)]
+    force_tags: bool,
+
+    /// Directory for temporary files while embedding cover art [default: temp_dir setting, else the system temp directory]
+    #[arg(long, value_name = "DIR")]
+    temp_dir: Option<PathBuf>,
+
+    /// Write a plain-text log of this sync (items synced, failures, totals) to .nutune-sync.log on the device
+    #[arg(long)]
+    device_log: bool,
+
+    /// Skip tracks below this bitrate in kbps, e.g. 256 to leave out 128 kbps rips
+    ///
+    /// Uses the bitrate the server reports for the original, also when transcoding (transcoding
+    /// can't restore what a low-bitrate rip lost); tracks it reports none for are kept. Albums
+    /// and playlists with every track below the limit are skipped entirely. A --max-bitrate
+    /// below this limit is rejected.
+    #[arg(long, value_name = "KBPS")]
+    min_bitrate: Option<u32>,
+
+    /// Sync music videos found in albums and playlists instead of skipping them
+    #[arg(long)]
+    include_video: bool,
+
+    /// Octal permissions for written files, e.g. 664 for group-writable (Unix) [default: umask]
+    #[arg(long, value_name = "MODE", value_parser = parse_mode)]
+    file_mode: Option<u32>,
+
+    /// Octal permissions for created directories, e.g. 775 (Unix) [default: umask]
+    #[arg(long, value_name = "MODE", value_parser = parse_mode)]
+    dir_mode: Option<u32>,
+
+    /// Have the server transcode tracks to this format (mp3, opus, aac, ...) instead of downloading the originals
+    ///
+    /// Uses the server's stream endpoint, so its transcoding must be set up for the format.
+    /// Tracks are written with the format's extension. The format is saved for the device
+    /// and used by later syncs to it; `raw` goes back to the original files.
+    #[arg(long, value_name = "FORMAT", value_parser = parse_transcode_format)]
+    transcode: Option<String>,
+
+    /// Times to retry a track download after a network error, waiting twice as long each time [default: 3]
+    #[arg(long, value_name = "N")]
+    retries: Option<usize>,
+
+    /// Have the server transcode tracks above this bitrate in kbps down to it
+    ///
+    /// Without --transcode the server picks the format; tracks already under the limit
+    /// usually come through untouched.
+    #[arg(long, value_name = "KBPS")]
+    max_bitrate: Option<u32>,
+
+    /// Download a song that's in several selected albums/playlists once
+    ///
+    /// Its other copies are hard links to the first file, or plain copies on
+    /// filesystems without hard links (FAT). A linked file keeps the tags of its first place.
+    #[arg(long)]
+    dedup: bool,
+
+    /// Sync even if the selection looks too big for the device's free space
+    ///
+    /// The check uses sizes estimated from the server, which can be well off for
+    /// transcoded tracks; this only logs a warning instead of refusing to start.
+    #[arg(long)]
+    ignore_free_space: bool,
+}
+
+impl SyncOptionsArgs {
+    /// Convert the parsed flags into engine options
+    pub fn into_options(self) -> SyncOptions {
+        SyncOptions {
+            genre_folders: self.genre_folders,
+            layout: self.layout,
+            cover_fetch_size: self.cover_fetch_size,
+            strict_cover: self.strict_cover,
+            write_album_notes: self.write_album_notes,
+            cover_format: self.cover_format,
+            cover_size: self.cover_size,
+            cover_quality: self.cover_quality,
+            cover_names: self.cover_names,
+            cover_overrides: self.cover_overrides.into_iter().collect(),
+            preserve_dates: self.preserve_dates,
+            with_chapters: self.with_chapters,
+            playlist_track_format: self.playlist_track_format,
+            m3u_encoding: self.m3u_encoding,
+            simple_m3u: self.simple_m3u,
+            playlist_format: self.playlist_format,
+            first_complete: self.first_complete,
+            on_error: self.on_error,
+            artist_source: self.artist_source,
+            auto_parallel: self.auto_parallel,
+            all_art: self.all_art,
+            no_manifest: self.no_manifest,
+            force_cover: self.force_cover,
+            force_tags: self.force_tags,
+            temp_dir: self.temp_dir,
+            device_log: self.device_log,
+            min_bitrate: self.min_bitrate,
+            include_video: self.include_video,
+            file_mode: self.file_mode,
+            dir_mode: self.dir_mode,
+            retries: self.retries,
+            album_parallel: 1,
+            dedup: self.dedup,
+            ignore_free_space: self.ignore_free_space,
+            transcode: (self.transcode.is_some() || self.max_bitrate.is_some()).then_some(Transcode {
+                format: self.transcode,
+                max_bitrate: self.max_bitrate,
+            }),
+        }
+    }
+}
+
+/// Parse a playlist track filename format such as `{n} - {artist} - {title}`
+fn parse_playlist_track_format(value: &str) -> Result<String, String> {
+    validate_playlist_track_format(value)?;
+    Ok(value.to_string())
+}
+
+/// Parse a transcode format such as `mp3` or `opus`
+fn parse_transcode_format(value: &str) -> Result<String, String> {
+    let format = value.to_ascii_lowercase();
+    if format.is_empty() || !format.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("invalid format '{}' (expected a name like mp3 or opus)", value));
+    }
+    Ok(format)
+}
+
+/// Parse octal permission bits such as `664`, `0664` or `0o664`
+fn parse_mode(value: &str) -> Result<u32, String> {
+    let digits = value.strip_prefix("0o").unwrap_or(value);
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err(format!("invalid mode '{}' (expected octal like 664)", value)),
+    }
+}
+
+/// Parse a cover file name such as `folder.jpg` or `folder`, keeping just the stem
+fn parse_cover_filename(value: &str) -> Result<String, String> {
+    let stem = match value.rsplit_once('.') {
+        Some((stem, extension)) if ["jpg", "jpeg", "png", "webp"].contains(&extension.to_lowercase().as_str()) => stem,
+        _ => value,
+    };
+    if stem.trim().is_empty() || stem.starts_with('.') || stem.contains(['/', '\\']) {
+        return Err(format!("invalid cover file name '{}' (expected e.g. folder.jpg)", value));
+    }
+    Ok(stem.to_string())
+}
+
+/// Parse an `ALBUM_ID=PATH` cover override
+fn parse_cover_override(value: &str) -> Result<(String, PathBuf), String> {
+    let (album_id, path) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected ALBUM_ID=PATH, got '{}'", value))?;
+
+    if album_id.is_empty() {
+        return Err("album ID must not be empty".to_string());
+    }
+
+    let path = PathBuf::from(path);
+    if !path.is_file() {
+        return Err(format!("cover image not found: {}", path.display()));
+    }
+
+    Ok((album_id.to_string(), path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cover_filename() {
+        assert_eq!(parse_cover_filename("folder.jpg").unwrap(), "folder");
+        assert_eq!(parse_cover_filename("Folder.PNG").unwrap(), "Folder");
+        assert_eq!(parse_cover_filename("folder").unwrap(), "folder");
+        // Only image extensions are dropped
+        assert_eq!(parse_cover_filename("album.art").unwrap(), "album.art");
+
+        for invalid in ["", " ", ".jpg", ".hidden", "art/cover.jpg", "art\\cover"] {
+            assert!(parse_cover_filename(invalid).is_err(), "{:?} should be rejected", invalid);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Application settings persistence
+//!
+//! Stores user preferences, the last browser session and the detected
+//! server capabilities in ~/.config/nutune/settings.json. Missing fields fall back to defaults so
+//! older settings files keep loading as new options are added.
+//!
+//! Also resolves where nutune keeps its files: the config directory
+//! (`NUTUNE_CONFIG_DIR`, else ~/.config/nutune) and the cache directory
+//! (`NUTUNE_CACHE_DIR`, else ~/.cache).
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::debug;
+
+use crate::subsonic::ServerCapabilities;
+
+/// Environment variable overriding the config directory
+const CONFIG_DIR_ENV: &str = "NUTUNE_CONFIG_DIR";
+
+/// Environment variable overriding the cache directory
+const CACHE_DIR_ENV: &str = "NUTUNE_CACHE_DIR";
+
+/// Directory holding nutune's config files (settings, known devices)
+pub fn config_dir() -> Result<PathBuf> {
+    resolve_dir(CONFIG_DIR_ENV, dirs::config_dir().map(|d| d.join("nutune")), "config")
+}
+
+/// Directory holding cached state such as the saved selection
+pub fn cache_dir() -> Result<PathBuf> {
+    resolve_dir(CACHE_DIR_ENV, dirs::cache_dir(), "cache")
+}
+
+/// Pick a directory from its override variable or the platform default
+///
+/// There's deliberately no fallback to the current directory: files would
+/// silently end up wherever nutune happened to be run from.
+fn resolve_dir(env_var: &str, default: Option<PathBuf>, kind: &str) -> Result<PathBuf> {
+    std::env::var_os(env_var)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or(default)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Could not determine a {} directory; set {} to choose one",
+                kind,
+                env_var
+            )
+        })
+}
+
+/// Write a file under the config or cache directory, creating it if needed
+///
+/// Errors name the directory and how to move it, so an unwritable home
+/// doesn't leave the user guessing where their data went.
+pub fn write_app_file(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| {
+            format!(
+                "Failed to create {:?} (set {} or {} to use another location)",
+                parent, CONFIG_DIR_ENV, CACHE_DIR_ENV
+            )
+        })?;
+    }
+
+    fs::write(path, contents).with_context(|| {
+        format!(
+            "Failed to write {:?} (set {} or {} to use another location)",
+            path, CONFIG_DIR_ENV, CACHE_DIR_ENV
+        )
+    })
+}
+
+/// Persistent application settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Reselect the last used device when the browser starts
+    pub resume_last_device: bool,
+    /// Reopen the browser in the last used view (Artists or Playlists)
+    pub resume_last_view: bool,
+    /// State of the last browser session
+    pub last_session: LastSession,
+    /// What the configured server supports, as detected by `auth`
+    pub server: Option<ServerCapabilities>,
+    /// Seconds before informational messages in the browser clear (0 keeps them)
+    ///
+    /// Error messages always stay until a key is pressed.
+    pub status_timeout_secs: Option<u64>,
+    /// Device used by `sync` and `status` when none is given
+    pub default_device: Option<String>,
+    /// Directory for cover embedding temp files, for systems with a small /tmp
+    pub temp_dir: Option<PathBuf>,
+}
+
+/// State remembered from the last browser session
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LastSession {
+    /// UUID of the last device selected in the browser
+    pub device_uuid: Option<String>,
+    /// Last top-level view shown in the browser
+    pub view: SessionView,
+}
+
+/// Top-level browser view that can be restored on startup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionView {
+    #[default]
+    Artists,
+    Playlists,
+}
+
+/// How long browser status messages stay up when not configured
+const DEFAULT_STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl Settings {
+    /// How long informational status messages stay up, or `None` to keep them
+    pub fn status_timeout(&self) -> Option<Duration> {
+        match self.status_timeout_secs {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => Some(DEFAULT_STATUS_TIMEOUT),
+        }
+    }
+
+    /// The given device, or the default device if none was given
+    pub fn device_or_default(&self, device: Option<String>) -> Option<String> {
+        device.or_else(|| self.default_device.clone())
+    }
+
+    /// Load settings from disk, using defaults if none have been saved
+    pub fn load() -> Result<Self> {
+        let path = Self::settings_path()?;
+
+        if !path.exists() {
+            debug!("No settings found, using defaults");
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read settings from {:?}", path))?;
+
+        serde_json::from_str(&contents).context("Failed to parse settings")
+    }
+
+    /// Save settings to disk
+    pub fn save(&self) -> Result<()> {
+        let path = Self::settings_path()?;
+
+        let contents =
+            serde_json::to_string_pretty(self).context("Failed to serialize settings")?;
+
+        write_app_file(&path, &contents)?;
+
+        debug!("Saved settings to {:?}", path);
+        Ok(())
+    }
+
+    /// Get the settings file path
+    fn settings_path() -> Result<PathBuf> {
+        Ok(config_dir()?.join("settings.json"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_missing_fields_use_defaults() {
+        let settings: Settings = serde_json::from_str("{}").unwrap();
+        assert!(!settings.resume_last_device);
+        assert_eq!(settings.last_session.view, SessionView::Artists);
+        assert!(settings.last_session.device_uuid.is_none());
+    }
+
+    #[test]
+    fn test_resolve_dir_without_default_errors() {
+        let default = PathBuf::from("/tmp/cache");
+        let dir = resolve_dir("NUTUNE_TEST_UNSET_DIR", Some(default.clone()), "cache").unwrap();
+        assert_eq!(dir, default);
+
+        let err = resolve_dir("NUTUNE_TEST_UNSET_DIR", None, "cache").unwrap_err();
+        assert!(err.to_string().contains("NUTUNE_TEST_UNSET_DIR"));
+    }
+
+    #[test]
+    fn test_status_timeout() {
+        let mut settings = Settings::default();
+        assert_eq!(settings.status_timeout(), Some(DEFAULT_STATUS_TIMEOUT));
+        settings.status_timeout_secs = Some(10);
+        assert_eq!(settings.status_timeout(), Some(Duration::from_secs(10)));
+        settings.status_timeout_secs = Some(0);
+        assert_eq!(settings.status_timeout(), None);
+    }
+
+    #[test]
+    fn test_device_or_default() {
+        let mut settings = Settings::default();
+        assert_eq!(settings.device_or_default(None), None);
+
+        settings.default_device = Some("SDCARD".to_string());
+        assert_eq!(settings.device_or_default(None).as_deref(), Some("SDCARD"));
+        assert_eq!(settings.device_or_default(Some("IPOD".to_string())).as_deref(), Some("IPOD"));
+    }
+
+    #[test]
+    fn test_session_view_serialization() {
+        let json = serde_json::to_string(&SessionView::Playlists).unwrap();
+        assert_eq!(json, "\"playlists\"");
+    }
+}
@@ -0,0 +1,341 @@
+//! Storage backends for device file operations
+//!
+//! `DeviceStorage` decides where files go on a device; a `StorageBackend`
+//! does the actual I/O. The filesystem backend is used for real devices,
+//! the in-memory one keeps sync/delete/manifest tests hermetic.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use filetime::FileTime;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Low-level file operations performed on a device
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// Create a directory and any missing parents
+    async fn create_dir_all(&self, path: &Path) -> Result<()>;
+
+    /// Write a file, replacing it if it exists
+    async fn write(&self, path: &Path, data: &[u8]) -> Result<()>;
+
+    /// Read a whole file
+    async fn read(&self, path: &Path) -> Result<Vec<u8>>;
+
+    /// Create a hard link at `link` to the file at `original`
+    async fn hard_link(&self, original: &Path, link: &Path) -> Result<()>;
+
+    /// Size of a file in bytes
+    async fn file_size(&self, path: &Path) -> Result<u64>;
+
+    /// List the files (not subdirectories) directly inside a directory
+    async fn list_files(&self, dir: &Path) -> Result<Vec<PathBuf>>;
+
+    /// List the subdirectories directly inside a directory
+    async fn list_dirs(&self, dir: &Path) -> Result<Vec<PathBuf>>;
+
+    /// Whether a file or directory exists
+    async fn exists(&self, path: &Path) -> bool;
+
+    /// Remove a file
+    async fn remove_file(&self, path: &Path) -> Result<()>;
+
+    /// Remove a directory and all its contents
+    async fn remove_dir_all(&self, path: &Path) -> Result<()>;
+
+    /// Remove a directory if it has no entries, returning whether it was removed
+    async fn remove_dir_if_empty(&self, path: &Path) -> Result<bool>;
+
+    /// Set a file's modification time
+    fn set_mtime(&self, path: &Path, time: DateTime<Utc>) -> Result<()>;
+
+    /// Set the Unix permission bits of a file or directory
+    fn set_mode(&self, path: &Path, mode: u32) -> Result<()>;
+}
+
+/// Backend that writes to the local filesystem
+pub struct FsBackend;
+
+#[async_trait]
+impl StorageBackend for FsBackend {
+    async fn create_dir_all(&self, path: &Path) -> Result<()> {
+        fs::create_dir_all(path)
+            .await
+            .with_context(|| format!("Failed to create directory {}", path.display()))
+    }
+
+    async fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        fs::write(path, data)
+            .await
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        fs::read(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))
+    }
+
+    async fn hard_link(&self, original: &Path, link: &Path) -> Result<()> {
+        fs::hard_link(original, link)
+            .await
+            .with_context(|| format!("Failed to link {} to {}", link.display(), original.display()))
+    }
+
+    async fn file_size(&self, path: &Path) -> Result<u64> {
+        let metadata = fs::metadata(path)
+            .await
+            .with_context(|| format!("Failed to read the size of {}", path.display()))?;
+        Ok(metadata.len())
+    }
+
+    async fn list_files(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut entries = fs::read_dir(dir)
+            .await
+            .with_context(|| format!("Failed to list {}", dir.display()))?;
+
+        let mut files = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_file() {
+                files.push(entry.path());
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    async fn list_dirs(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut entries = fs::read_dir(dir)
+            .await
+            .with_context(|| format!("Failed to list {}", dir.display()))?;
+
+        let mut dirs = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_dir() {
+                dirs.push(entry.path());
+            }
+        }
+        dirs.sort();
+        Ok(dirs)
+    }
+
+    async fn exists(&self, path: &Path) -> bool {
+        fs::try_exists(path).await.unwrap_or(false)
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        fs::remove_file(path)
+            .await
+            .with_context(|| format!("Failed to delete {}", path.display()))
+    }
+
+    async fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        fs::remove_dir_all(path)
+            .await
+            .with_context(|| format!("Failed to delete directory {}", path.display()))
+    }
+
+    async fn remove_dir_if_empty(&self, path: &Path) -> Result<bool> {
+        if let Ok(mut entries) = fs::read_dir(path).await
+            && entries.next_entry().await?.is_none()
+        {
+            return Ok(fs::remove_dir(path).await.is_ok());
+        }
+        Ok(false)
+    }
+
+    fn set_mtime(&self, path: &Path, time: DateTime<Utc>) -> Result<()> {
+        let mtime = FileTime::from_unix_time(time.timestamp(), time.timestamp_subsec_nanos());
+        filetime::set_file_mtime(path, mtime)
+            .with_context(|| format!("Failed to set modification time of {}", path.display()))
+    }
+
+    #[cfg(unix)]
+    fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+            .with_context(|| format!("Failed to set permissions of {}", path.display()))
+    }
+
+    #[cfg(not(unix))]
+    fn set_mode(&self, path: &Path, _mode: u32) -> Result<()> {
+        anyhow::bail!("Cannot set permissions of {}: only supported on Unix", path.display())
+    }
+}
+
+/// Backend that keeps files in memory, for tests
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryBackend {
+    state: std::sync::Mutex<MemoryState>,
+}
+
+#[cfg(test)]
+#[derive(Default)]
+struct MemoryState {
+    dirs: std::collections::BTreeSet<PathBuf>,
+    files: std::collections::BTreeMap<PathBuf, Vec<u8>>,
+    mtimes: std::collections::HashMap<PathBuf, DateTime<Utc>>,
+    modes: std::collections::HashMap<PathBuf, u32>,
+    /// Writes left to lose the last byte of, like a failing card
+    short_writes: usize,
+}
+
+#[cfg(test)]
+impl MemoryBackend {
+    /// Contents of a file, if it exists
+    pub fn file(&self, path: &Path) -> Option<Vec<u8>> {
+        self.state.lock().unwrap().files.get(path).cloned()
+    }
+
+    /// Whether a directory exists
+    pub fn has_dir(&self, path: &Path) -> bool {
+        self.state.lock().unwrap().dirs.contains(path)
+    }
+
+    /// Modification time set on a file, if any
+    pub fn mtime(&self, path: &Path) -> Option<DateTime<Utc>> {
+        self.state.lock().unwrap().mtimes.get(path).copied()
+    }
+
+    /// Lose the last byte of the next `count` writes
+    pub fn cut_short_writes(&self, count: usize) {
+        self.state.lock().unwrap().short_writes = count;
+    }
+
+    /// Permission bits set on a file or directory, if any
+    pub fn mode(&self, path: &Path) -> Option<u32> {
+        self.state.lock().unwrap().modes.get(path).copied()
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl StorageBackend for MemoryBackend {
+    async fn create_dir_all(&self, path: &Path) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        for dir in path.ancestors() {
+            if state.files.contains_key(dir) {
+                anyhow::bail!("Not a directory: {}", dir.display());
+            }
+            state.dirs.insert(dir.to_path_buf());
+        }
+        Ok(())
+    }
+
+    async fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let parent = path.parent().unwrap_or(Path::new(""));
+        if !state.dirs.contains(parent) {
+            anyhow::bail!("No such directory: {}", parent.display());
+        }
+        let mut data = data.to_vec();
+        if state.short_writes > 0 {
+            state.short_writes -= 1;
+            data.pop();
+        }
+        state.files.insert(path.to_path_buf(), data);
+        Ok(())
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let state = self.state.lock().unwrap();
+        state
+            .files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("No such file: {}", path.display()))
+    }
+
+    /// Memory files can't share their data, so a link is a copy
+    async fn hard_link(&self, original: &Path, link: &Path) -> Result<()> {
+        let data = self.read(original).await?;
+        self.write(link, &data).await
+    }
+
+    async fn file_size(&self, path: &Path) -> Result<u64> {
+        Ok(self.read(path).await?.len() as u64)
+    }
+
+    async fn list_files(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        let state = self.state.lock().unwrap();
+        if !state.dirs.contains(dir) {
+            anyhow::bail!("No such directory: {}", dir.display());
+        }
+        Ok(state
+            .files
+            .keys()
+            .filter(|f| f.parent() == Some(dir))
+            .cloned()
+            .collect())
+    }
+
+    async fn list_dirs(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        let state = self.state.lock().unwrap();
+        if !state.dirs.contains(dir) {
+            anyhow::bail!("No such directory: {}", dir.display());
+        }
+        Ok(state
+            .dirs
+            .iter()
+            .filter(|d| d.parent() == Some(dir))
+            .cloned()
+            .collect())
+    }
+
+    async fn exists(&self, path: &Path) -> bool {
+        let state = self.state.lock().unwrap();
+        state.dirs.contains(path) || state.files.contains_key(path)
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.files.remove(path).is_none() {
+            anyhow::bail!("No such file: {}", path.display());
+        }
+        state.mtimes.remove(path);
+        state.modes.remove(path);
+        Ok(())
+    }
+
+    async fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if !state.dirs.contains(path) {
+            anyhow::bail!("No such directory: {}", path.display());
+        }
+        state.dirs.retain(|d| !d.starts_with(path));
+        state.files.retain(|f, _| !f.starts_with(path));
+        state.mtimes.retain(|f, _| !f.starts_with(path));
+        state.modes.retain(|f, _| !f.starts_with(path));
+        Ok(())
+    }
+
+    async fn remove_dir_if_empty(&self, path: &Path) -> Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        let has_entries = state.dirs.iter().any(|d| d.parent() == Some(path))
+            || state.files.keys().any(|f| f.parent() == Some(path));
+        if has_entries || !state.dirs.contains(path) {
+            return Ok(false);
+        }
+        Ok(state.dirs.remove(path))
+    }
+
+    fn set_mtime(&self, path: &Path, time: DateTime<Utc>) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if !state.files.contains_key(path) {
+            anyhow::bail!("No such file: {}", path.display());
+        }
+        state.mtimes.insert(path.to_path_buf(), time);
+        Ok(())
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if !state.files.contains_key(path) && !state.dirs.contains(path) {
+            anyhow::bail!("No such file or directory: {}", path.display());
+        }
+        state.modes.insert(path.to_path_buf(), mode);
+        Ok(())
+    }
+}
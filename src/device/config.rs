@@ -23,6 +23,18 @@ pub struct DeviceConfig {
     pub last_seen: DateTime<Utc>,
     /// Device identifiers used for matching
     pub identifiers: DeviceIdentifiers,
+    /// Directory layout for album tracks, saved by `sync --layout`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+    /// Cover art size in pixels, saved by `sync --cover-size`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover_size: Option<u32>,
+    /// Cover art JPEG quality, saved by `sync --cover-quality`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover_quality: Option<u8>,
+    /// Format the server transcodes tracks to, saved by `sync --transcode`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcode: Option<String>,
 }
 
 /// Identifying properties of a device
@@ -69,17 +81,10 @@ impl DeviceConfigStore {
     pub fn save(&self) -> Result<()> {
         let path = Self::config_path()?;
 
-        // Ensure parent directory exists
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)
-                .with_context(|| format!("Failed to create config directory {:?}", parent))?;
-        }
-
         let contents = serde_json::to_string_pretty(self)
             .context("Failed to serialize device config")?;
 
-        fs::write(&path, contents)
-            .with_context(|| format!("Failed to write device config to {:?}", path))?;
+        crate::config::write_app_file(&path, &contents)?;
 
         debug!("Saved {} devices to config", self.devices.len());
         Ok(())
@@ -87,9 +92,38 @@ impl DeviceConfigStore {
 
     /// Get the config file path
     fn config_path() -> Result<PathBuf> {
-        let config_dir = dirs::config_dir()
-            .ok_or_else(|| anyhow::anyhow!("Could not determine config directory"))?;
-        Ok(config_dir.join("nutune").join("devices.json"))
+        Ok(crate::config::config_dir()?.join("devices.json"))
+    }
+
+    /// Save the layout to use for a device's albums
+    pub fn set_layout(&mut self, uuid: &str, layout: &str) -> Result<()> {
+        let config = self
+            .devices
+            .get_mut(uuid)
+            .ok_or_else(|| anyhow::anyhow!("Device {} is not in the device config", uuid))?;
+        config.layout = Some(layout.to_string());
+        self.save()
+    }
+
+    /// Save the cover art size and quality to use for a device; `None` keeps the saved value
+    pub fn set_cover(&mut self, uuid: &str, size: Option<u32>, quality: Option<u8>) -> Result<()> {
+        let config = self
+            .devices
+            .get_mut(uuid)
+            .ok_or_else(|| anyhow::anyhow!("Device {} is not in the device config", uuid))?;
+        config.cover_size = size.or(config.cover_size);
+        config.cover_quality = quality.or(config.cover_quality);
+        self.save()
+    }
+
+    /// Save the transcode format to use for a device
+    pub fn set_transcode(&mut self, uuid: &str, format: &str) -> Result<()> {
+        let config = self
+            .devices
+            .get_mut(uuid)
+            .ok_or_else(|| anyhow::anyhow!("Device {} is not in the device config", uuid))?;
+        config.transcode = Some(format.to_string());
+        self.save()
     }
 
     /// Get or create config for a device
@@ -102,6 +136,10 @@ impl DeviceConfigStore {
                 first_seen: now,
                 last_seen: now,
                 identifiers,
+                layout: None,
+                cover_size: None,
+                cover_quality: None,
+                transcode: None,
             }
         })
     }
@@ -119,6 +157,10 @@ impl Default for DeviceConfig {
                 size_bytes: 0,
                 fs_type: String::new(),
             },
+            layout: None,
+            cover_size: None,
+            cover_quality: None,
+            transcode: None,
         }
     }
 }
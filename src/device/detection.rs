@@ -1,17 +1,48 @@
-//! Device detection using lsblk and udisksctl
+//! Device detection
+//!
+//! Volumes are listed by a platform backend: lsblk and udisksctl on Linux,
+//! the drive-letter APIs on Windows. This module matches them against the
+//! device config store.
 
-use anyhow::{Context, Result};
-use serde::Deserialize;
+use anyhow::Result;
 use std::path::PathBuf;
-use std::process::Command;
-use tracing::{debug, info};
+use tracing::{debug, warn};
 
 use super::config::{generate_device_uuid, DeviceConfigStore, DeviceIdentifiers};
 
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "linux")]
+use linux as platform;
+
+#[cfg(windows)]
+mod windows;
+#[cfg(windows)]
+use windows as platform;
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod platform {
+    use super::{UnmountedDevice, Volume};
+    use anyhow::Result;
+    use std::path::PathBuf;
+
+    pub(super) fn mounted_volumes() -> Result<Vec<Volume>> {
+        anyhow::bail!("Device detection is only supported on Linux and Windows; use --path instead")
+    }
+
+    pub(super) fn unmounted_devices() -> Result<Vec<UnmountedDevice>> {
+        Ok(Vec::new())
+    }
+
+    pub(super) fn mount(device_name: &str) -> Result<PathBuf> {
+        anyhow::bail!("Mounting {} is not supported on this platform", device_name)
+    }
+}
+
 /// Detected removable device
 #[derive(Debug, Clone)]
 pub struct Device {
-    /// Device name (e.g., "sdb1")
+    /// Device name (e.g., "sdb1", or "E:" on Windows)
     pub name: String,
     /// Volume label (e.g., "FIIO")
     pub label: Option<String>,
@@ -27,6 +58,14 @@ pub struct Device {
     pub uuid: String,
     /// User-defined friendly name (from config)
     pub friendly_name: Option<String>,
+    /// Saved directory layout for album tracks (from config)
+    pub layout: Option<String>,
+    /// Saved cover art size in pixels (from config)
+    pub cover_size: Option<u32>,
+    /// Saved cover art JPEG quality (from config)
+    pub cover_quality: Option<u8>,
+    /// Saved transcode format (from config)
+    pub transcode: Option<String>,
 }
 
 impl Device {
@@ -48,100 +87,47 @@ impl DeviceDetector {
         // Load device config for friendly names
         let mut config_store = DeviceConfigStore::load().unwrap_or_default();
 
-        // Run lsblk with JSON output
-        let output = Command::new("lsblk")
-            .args([
-                "-J",
-                "-o",
-                "NAME,LABEL,MOUNTPOINT,SIZE,FSTYPE,HOTPLUG,FSAVAIL,FSSIZE",
-                "-b", // bytes
-            ])
-            .output()
-            .context("Failed to run lsblk")?;
-
-        if !output.status.success() {
-            anyhow::bail!(
-                "lsblk failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            );
-        }
-
-        let json_output = String::from_utf8_lossy(&output.stdout);
-        debug!("lsblk output: {}", json_output);
-
-        let lsblk: LsblkOutput = serde_json::from_str(&json_output)
-            .context("Failed to parse lsblk output")?;
-
-        let mut devices = Vec::new();
-
-        for block_device in lsblk.blockdevices {
-            Self::collect_devices(&block_device, &mut devices, &mut config_store);
-        }
+        let devices: Vec<Device> = platform::mounted_volumes()?
+            .into_iter()
+            .map(|volume| Self::to_device(volume, &mut config_store))
+            .collect();
 
         // Save config to update last_seen timestamps
-        let _ = config_store.save();
+        if let Err(e) = config_store.save() {
+            warn!("Failed to save device config: {:#}", e);
+        }
 
         debug!("Found {} removable devices", devices.len());
         Ok(devices)
     }
 
-    /// Recursively collect removable devices from lsblk output
-    fn collect_devices(
-        block: &BlockDevice,
-        devices: &mut Vec<Device>,
-        config_store: &mut DeviceConfigStore,
-    ) {
-        // Check if this is a mounted removable device
-        if (block.hotplug == Some(true) || block.hotplug == Some(false))
-            && let Some(mountpoint) = &block.mountpoint
-                && !mountpoint.is_empty()
-                    && mountpoint != "[SWAP]"
-                    && !mountpoint.starts_with("/boot")
-                {
-                    // Try to determine if it's removable
-                    // Check if it's a partition of a hotplug device or has typical removable paths
-                    let is_removable = block.hotplug == Some(true)
-                        || mountpoint.starts_with("/run/media")
-                        || mountpoint.starts_with("/media")
-                        || mountpoint.starts_with("/mnt");
-
-                    if is_removable {
-                        let size = block.size.or(block.fssize).unwrap_or(0);
-                        let free_space = block.fsavail.unwrap_or(0);
-                        let fs_type = block.fstype.clone().unwrap_or_default();
-
-                        // Generate UUID and get config
-                        let uuid =
-                            generate_device_uuid(block.label.as_deref(), size, &fs_type);
-
-                        // Get or create device config, update last_seen
-                        let identifiers = DeviceIdentifiers {
-                            label: block.label.clone(),
-                            size_bytes: size,
-                            fs_type: fs_type.clone(),
-                        };
-                        let device_config = config_store.get_or_create(&uuid, identifiers);
-                        device_config.last_seen = chrono::Utc::now();
-                        let friendly_name = device_config.friendly_name.clone();
-
-                        devices.push(Device {
-                            name: block.name.clone(),
-                            label: block.label.clone(),
-                            mount_point: PathBuf::from(mountpoint),
-                            size,
-                            free_space,
-                            fs_type,
-                            uuid,
-                            friendly_name,
-                        });
-                    }
-                }
-
-        // Check children
-        if let Some(children) = &block.children {
-            for child in children {
-                Self::collect_devices(child, devices, config_store);
-            }
+    /// Attach the saved config (friendly name, layout, cover and transcode settings) to a detected volume
+    fn to_device(volume: Volume, config_store: &mut DeviceConfigStore) -> Device {
+        // Generate UUID and get config
+        let uuid = generate_device_uuid(volume.label.as_deref(), volume.size, &volume.fs_type);
+
+        // Get or create device config, update last_seen
+        let identifiers = DeviceIdentifiers {
+            label: volume.label.clone(),
+            size_bytes: volume.size,
+            fs_type: volume.fs_type.clone(),
+        };
+        let device_config = config_store.get_or_create(&uuid, identifiers);
+        device_config.last_seen = chrono::Utc::now();
+
+        Device {
+            name: volume.name,
+            label: volume.label,
+            mount_point: volume.mount_point,
+            size: volume.size,
+            free_space: volume.free_space,
+            fs_type: volume.fs_type,
+            uuid,
+            friendly_name: device_config.friendly_name.clone(),
+            layout: device_config.layout.clone(),
+            cover_size: device_config.cover_size,
+            cover_quality: device_config.cover_quality,
+            transcode: device_config.transcode.clone(),
         }
     }
 
@@ -177,70 +163,15 @@ impl DeviceDetector {
     }
 
     /// Get unmounted but available devices (for offering to mount)
+    ///
+    /// Always empty on Windows, which mounts drives as they're plugged in.
     pub async fn scan_unmounted() -> Result<Vec<UnmountedDevice>> {
-        let output = Command::new("lsblk")
-            .args(["-J", "-o", "NAME,LABEL,SIZE,FSTYPE,HOTPLUG", "-b"])
-            .output()
-            .context("Failed to run lsblk")?;
-
-        if !output.status.success() {
-            anyhow::bail!("lsblk failed: {}", String::from_utf8_lossy(&output.stderr));
-        }
-
-        let json_output = String::from_utf8_lossy(&output.stdout);
-        let lsblk: LsblkOutput = serde_json::from_str(&json_output)
-            .context("Failed to parse lsblk output")?;
-
-        let mut devices = Vec::new();
-        for block_device in lsblk.blockdevices {
-            Self::collect_unmounted(&block_device, &mut devices);
-        }
-
-        Ok(devices)
+        platform::unmounted_devices()
     }
 
-    fn collect_unmounted(block: &BlockDevice, devices: &mut Vec<UnmountedDevice>) {
-        // Check if this is an unmounted removable device with a filesystem
-        if block.hotplug == Some(true) && block.fstype.is_some() && block.mountpoint.is_none() {
-            devices.push(UnmountedDevice {
-                name: block.name.clone(),
-                label: block.label.clone(),
-                size: block.size.unwrap_or(0),
-                fs_type: block.fstype.clone().unwrap_or_default(),
-            });
-        }
-
-        if let Some(children) = &block.children {
-            for child in children {
-                Self::collect_unmounted(child, devices);
-            }
-        }
-    }
-
-    /// Mount a device using udisksctl (triggers polkit GUI prompt on KDE/GNOME)
+    /// Mount a device (via udisksctl on Linux, which may show an auth dialog)
     pub async fn mount(device_name: &str) -> Result<PathBuf> {
-        info!("Mounting {} via udisksctl (may show auth dialog)...", device_name);
-
-        let output = Command::new("udisksctl")
-            .args(["mount", "-b", &format!("/dev/{}", device_name)])
-            .output()
-            .context("Failed to run udisksctl")?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            anyhow::bail!("Failed to mount device: {}", stderr);
-        }
-
-        // Parse mount point from output like "Mounted /dev/sda1 at /run/media/user/LABEL"
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let mount_point = stdout
-            .split(" at ")
-            .nth(1)
-            .map(|s| s.trim().trim_end_matches('.'))
-            .ok_or_else(|| anyhow::anyhow!("Could not parse mount point from: {}", stdout))?;
-
-        info!("Mounted at: {}", mount_point);
-        Ok(PathBuf::from(mount_point))
+        platform::mount(device_name)
     }
 }
 
@@ -253,22 +184,13 @@ pub struct UnmountedDevice {
     pub fs_type: String,
 }
 
-// JSON structures for lsblk output
-
-#[derive(Debug, Deserialize)]
-struct LsblkOutput {
-    blockdevices: Vec<BlockDevice>,
-}
-
-#[derive(Debug, Deserialize)]
-struct BlockDevice {
+/// A mounted removable volume, as reported by the platform backend
+#[derive(Debug)]
+struct Volume {
     name: String,
     label: Option<String>,
-    mountpoint: Option<String>,
-    size: Option<u64>,
-    fstype: Option<String>,
-    hotplug: Option<bool>,
-    fsavail: Option<u64>,
-    fssize: Option<u64>,
-    children: Option<Vec<BlockDevice>>,
+    mount_point: PathBuf,
+    size: u64,
+    free_space: u64,
+    fs_type: String,
 }
@@ -0,0 +1,151 @@
+//! Volume detection using lsblk, and mounting with udisksctl
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::process::Command;
+use tracing::{debug, info};
+
+use super::{UnmountedDevice, Volume};
+
+/// List mounted removable volumes
+pub(super) fn mounted_volumes() -> Result<Vec<Volume>> {
+    let lsblk = run_lsblk("NAME,LABEL,MOUNTPOINT,SIZE,FSTYPE,HOTPLUG,FSAVAIL,FSSIZE")?;
+
+    let mut volumes = Vec::new();
+    for block_device in &lsblk.blockdevices {
+        collect_volumes(block_device, &mut volumes);
+    }
+    Ok(volumes)
+}
+
+/// List removable devices with a filesystem that aren't mounted
+pub(super) fn unmounted_devices() -> Result<Vec<UnmountedDevice>> {
+    let lsblk = run_lsblk("NAME,LABEL,SIZE,FSTYPE,HOTPLUG")?;
+
+    let mut devices = Vec::new();
+    for block_device in &lsblk.blockdevices {
+        collect_unmounted(block_device, &mut devices);
+    }
+    Ok(devices)
+}
+
+/// Mount a device using udisksctl (triggers polkit GUI prompt on KDE/GNOME)
+pub(super) fn mount(device_name: &str) -> Result<PathBuf> {
+    info!("Mounting {} via udisksctl (may show auth dialog)...", device_name);
+
+    let output = Command::new("udisksctl")
+        .args(["mount", "-b", &format!("/dev/{}", device_name)])
+        .output()
+        .context("Failed to run udisksctl")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("Failed to mount device: {}", stderr);
+    }
+
+    // Parse mount point from output like "Mounted /dev/sda1 at /run/media/user/LABEL"
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mount_point = stdout
+        .split(" at ")
+        .nth(1)
+        .map(|s| s.trim().trim_end_matches('.'))
+        .ok_or_else(|| anyhow::anyhow!("Could not parse mount point from: {}", stdout))?;
+
+    info!("Mounted at: {}", mount_point);
+    Ok(PathBuf::from(mount_point))
+}
+
+/// Run lsblk with JSON output and the given columns
+fn run_lsblk(columns: &str) -> Result<LsblkOutput> {
+    let output = Command::new("lsblk")
+        .args(["-J", "-o", columns, "-b"]) // -b: sizes in bytes
+        .output()
+        .context("Failed to run lsblk")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "lsblk failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let json_output = String::from_utf8_lossy(&output.stdout);
+    debug!("lsblk output: {}", json_output);
+
+    serde_json::from_str(&json_output).context("Failed to parse lsblk output")
+}
+
+/// Recursively collect mounted removable volumes from lsblk output
+fn collect_volumes(block: &BlockDevice, volumes: &mut Vec<Volume>) {
+    // Check if this is a mounted removable device
+    if (block.hotplug == Some(true) || block.hotplug == Some(false))
+        && let Some(mountpoint) = &block.mountpoint
+        && !mountpoint.is_empty()
+        && mountpoint != "[SWAP]"
+        && !mountpoint.starts_with("/boot")
+    {
+        // Try to determine if it's removable
+        // Check if it's a partition of a hotplug device or has typical removable paths
+        let is_removable = block.hotplug == Some(true)
+            || mountpoint.starts_with("/run/media")
+            || mountpoint.starts_with("/media")
+            || mountpoint.starts_with("/mnt");
+
+        if is_removable {
+            volumes.push(Volume {
+                name: block.name.clone(),
+                label: block.label.clone(),
+                mount_point: PathBuf::from(mountpoint),
+                size: block.size.or(block.fssize).unwrap_or(0),
+                free_space: block.fsavail.unwrap_or(0),
+                fs_type: block.fstype.clone().unwrap_or_default(),
+            });
+        }
+    }
+
+    // Check children
+    if let Some(children) = &block.children {
+        for child in children {
+            collect_volumes(child, volumes);
+        }
+    }
+}
+
+fn collect_unmounted(block: &BlockDevice, devices: &mut Vec<UnmountedDevice>) {
+    // Check if this is an unmounted removable device with a filesystem
+    if block.hotplug == Some(true) && block.fstype.is_some() && block.mountpoint.is_none() {
+        devices.push(UnmountedDevice {
+            name: block.name.clone(),
+            label: block.label.clone(),
+            size: block.size.unwrap_or(0),
+            fs_type: block.fstype.clone().unwrap_or_default(),
+        });
+    }
+
+    if let Some(children) = &block.children {
+        for child in children {
+            collect_unmounted(child, devices);
+        }
+    }
+}
+
+// JSON structures for lsblk output
+
+#[derive(Debug, Deserialize)]
+struct LsblkOutput {
+    blockdevices: Vec<BlockDevice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockDevice {
+    name: String,
+    label: Option<String>,
+    mountpoint: Option<String>,
+    size: Option<u64>,
+    fstype: Option<String>,
+    hotplug: Option<bool>,
+    fsavail: Option<u64>,
+    fssize: Option<u64>,
+    children: Option<Vec<BlockDevice>>,
+}
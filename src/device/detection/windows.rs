@@ -0,0 +1,101 @@
+//! Removable drive detection on Windows
+//!
+//! Windows mounts removable drives under a drive letter as soon as they're
+//! plugged in, so there is nothing to mount and no unmounted list.
+
+use anyhow::Result;
+use std::path::PathBuf;
+use std::ptr::null_mut;
+use tracing::debug;
+use windows_sys::Win32::Storage::FileSystem::{
+    GetDiskFreeSpaceExW, GetDriveTypeW, GetLogicalDrives, GetVolumeInformationW,
+};
+use windows_sys::Win32::System::WindowsProgramming::DRIVE_REMOVABLE;
+
+use super::{UnmountedDevice, Volume};
+
+/// Buffer length for volume labels and filesystem names (`MAX_PATH + 1`)
+const NAME_BUFFER_LEN: usize = 261;
+
+/// List removable drives that have a volume
+pub(super) fn mounted_volumes() -> Result<Vec<Volume>> {
+    // SAFETY: takes no arguments; returns a bitmask of drive letters (bit 0 = A:)
+    let drives = unsafe { GetLogicalDrives() };
+
+    let mut volumes = Vec::new();
+    for (bit, letter) in ('A'..='Z').enumerate() {
+        if drives & (1 << bit) == 0 {
+            continue;
+        }
+
+        let root = format!("{}:\\", letter);
+        let root_wide = to_wide(&root);
+
+        // SAFETY: root_wide is a NUL-terminated UTF-16 string that outlives the calls below
+        if unsafe { GetDriveTypeW(root_wide.as_ptr()) } != DRIVE_REMOVABLE {
+            continue;
+        }
+
+        let mut label = [0u16; NAME_BUFFER_LEN];
+        let mut fs_type = [0u16; NAME_BUFFER_LEN];
+        // SAFETY: the buffers are writable for the lengths given; unused outputs are null
+        let has_volume = unsafe {
+            GetVolumeInformationW(
+                root_wide.as_ptr(),
+                label.as_mut_ptr(),
+                label.len() as u32,
+                null_mut(),
+                null_mut(),
+                null_mut(),
+                fs_type.as_mut_ptr(),
+                fs_type.len() as u32,
+            )
+        } != 0;
+        if !has_volume {
+            // e.g. a card reader slot with no card in it
+            debug!("No volume in removable drive {}", root);
+            continue;
+        }
+
+        let mut free_space = 0u64;
+        let mut size = 0u64;
+        // SAFETY: the outputs point to live u64s; the total free bytes aren't needed
+        let has_space = unsafe { GetDiskFreeSpaceExW(root_wide.as_ptr(), &mut free_space, &mut size, null_mut()) } != 0;
+        if !has_space {
+            debug!("Could not read the size of {}", root);
+        }
+
+        let label = from_wide(&label);
+        volumes.push(Volume {
+            name: format!("{}:", letter),
+            label: (!label.is_empty()).then_some(label),
+            mount_point: PathBuf::from(root),
+            size,
+            free_space,
+            fs_type: from_wide(&fs_type),
+        });
+    }
+
+    Ok(volumes)
+}
+
+/// Windows mounts removable drives automatically, so none are left unmounted
+pub(super) fn unmounted_devices() -> Result<Vec<UnmountedDevice>> {
+    Ok(Vec::new())
+}
+
+pub(super) fn mount(device_name: &str) -> Result<PathBuf> {
+    anyhow::bail!(
+        "{} has no drive letter; Windows mounts removable drives automatically, check Disk Management",
+        device_name
+    )
+}
+
+fn to_wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+fn from_wide(buffer: &[u16]) -> String {
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    String::from_utf16_lossy(&buffer[..len])
+}
@@ -0,0 +1,190 @@
+//! Name and path length limits of FAT filesystems
+//!
+//! FAT32 and exFAT cap each name at 255 UTF-16 code units, and players
+//! (and Windows, when the card is read there) often choke on paths longer
+//! than `MAX_PATH`. Names are kept within 255 UTF-8 bytes, which also keeps
+//! them within 255 code units, and paths within 255 code units of the
+//! device root. Shortened names keep their start (so the track number
+//! prefix survives) and their extension.
+
+use std::path::{Path, PathBuf};
+
+/// Longest file or folder name, in UTF-8 bytes
+const MAX_NAME_BYTES: usize = 255;
+
+/// Longest file or folder name, in UTF-16 code units
+const MAX_NAME_UNITS: usize = 255;
+
+/// Longest path below the device root, in UTF-16 code units
+///
+/// Windows' `MAX_PATH` of 260, less the drive (`E:\`) and terminator.
+const MAX_PATH_UNITS: usize = 255;
+
+/// Room kept for file names when a folder path is shortened
+const FILENAME_RESERVE: usize = 80;
+
+/// Shortest a name is cut to when shortening a path
+const MIN_NAME_UNITS: usize = 16;
+
+/// Whether a filesystem type (as reported by lsblk or Windows) has FAT name limits
+pub fn has_fat_limits(fs_type: &str) -> bool {
+    matches!(
+        fs_type.to_ascii_lowercase().as_str(),
+        "vfat" | "fat" | "fat12" | "fat16" | "fat32" | "msdos" | "exfat"
+    )
+}
+
+/// Shorten a folder path relative to the device root
+///
+/// Each name is cut to the name limit; if the path is still too long to
+/// leave room for file names, the longest names are cut further. `suffix`
+/// (e.g. ` (2)`) is appended to the last name.
+pub fn fit_dir(path: &Path, suffix: &str) -> PathBuf {
+    let names = path_names(path);
+    let last = names.len().saturating_sub(1);
+    let mut limits = vec![MAX_NAME_UNITS; names.len()];
+    let render = |limits: &[usize]| -> Vec<String> {
+        names
+            .iter()
+            .zip(limits)
+            .enumerate()
+            .map(|(i, (name, &limit))| truncate_name(name, limit, if i == last { suffix } else { "" }, false))
+            .collect()
+    };
+
+    let budget = MAX_PATH_UNITS - FILENAME_RESERVE;
+    let mut fitted = render(&limits);
+    loop {
+        let total = path_units(&fitted);
+        let Some((i, longest)) = fitted
+            .iter()
+            .map(|name| utf16_len(name))
+            .enumerate()
+            .max_by_key(|&(_, units)| units)
+        else {
+            break;
+        };
+        if total <= budget || longest <= MIN_NAME_UNITS {
+            break;
+        }
+        limits[i] = longest.saturating_sub(total - budget).max(MIN_NAME_UNITS);
+        fitted = render(&limits);
+    }
+
+    fitted.iter().collect()
+}
+
+/// Shorten the part of a file's path below an already fitted folder
+///
+/// Folders in `rest` are cut to the name limit and the file name to
+/// whatever is left of the path limit (but no shorter than a minimum).
+/// `suffix` goes before the file's extension.
+pub fn fit_below(dir: &Path, rest: &Path, suffix: &str) -> PathBuf {
+    let names = path_names(rest);
+    let Some((file, folders)) = names.split_last() else {
+        return rest.to_path_buf();
+    };
+
+    let mut fitted: Vec<String> = folders
+        .iter()
+        .map(|name| truncate_name(name, MAX_NAME_UNITS, "", false))
+        .collect();
+    let used = path_units(&path_names(dir)) + fitted.iter().map(|n| utf16_len(n) + 1).sum::<usize>() + 1;
+    let limit = MAX_PATH_UNITS.saturating_sub(used).clamp(MIN_NAME_UNITS, MAX_NAME_UNITS);
+    fitted.push(truncate_name(file, limit, suffix, true));
+
+    fitted.iter().collect()
+}
+
+/// Cut a name to at most `max_units` UTF-16 code units (and the byte limit), adding `suffix`
+///
+/// File names keep their extension. Trailing dots and spaces left by the
+/// cut are dropped, since FAT strips them.
+fn truncate_name(name: &str, max_units: usize, suffix: &str, is_file: bool) -> String {
+    let fits = |s: &str| s.len() <= MAX_NAME_BYTES && utf16_len(s) <= max_units;
+    if suffix.is_empty() && fits(name) {
+        return name.to_string();
+    }
+
+    let (stem, extension) = match name.rfind('.') {
+        Some(dot) if is_file && dot > 0 => name.split_at(dot),
+        _ => (name, ""),
+    };
+    let tail = format!("{}{}", suffix, extension);
+
+    let mut cut = String::new();
+    for c in stem.chars() {
+        cut.push(c);
+        if !fits(&format!("{}{}", cut, tail)) {
+            cut.pop();
+            break;
+        }
+    }
+
+    format!("{}{}", cut.trim_end_matches(['.', ' ']), tail)
+}
+
+fn path_names(path: &Path) -> Vec<String> {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Length of a `/`-joined path, in UTF-16 code units
+fn path_units(names: &[String]) -> usize {
+    names.iter().map(|n| utf16_len(n)).sum::<usize>() + names.len().saturating_sub(1)
+}
+
+fn utf16_len(s: &str) -> usize {
+    s.encode_utf16().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_long_album_name_is_cut() {
+        let album = "A".repeat(300);
+        let dir = fit_dir(&Path::new("Artists/Artist").join(&album), "");
+
+        let name = dir.file_name().unwrap().to_str().unwrap();
+        assert!(name.len() < album.len());
+        assert!(name.chars().all(|c| c == 'A'));
+        assert!(path_units(&path_names(&dir)) <= MAX_PATH_UNITS - FILENAME_RESERVE);
+        assert!(dir.starts_with("Artists/Artist"));
+
+        let track = fit_below(&dir, Path::new(&format!("07 - {}.flac", "T".repeat(300))), "");
+        let filename = track.to_str().unwrap();
+        assert!(filename.starts_with("07 - TTT"));
+        assert!(filename.ends_with("T.flac"));
+        assert!(path_units(&path_names(&dir.join(&track))) <= MAX_PATH_UNITS);
+    }
+
+    #[test]
+    fn test_multibyte_names_fit_in_bytes() {
+        // Each of these is one UTF-16 unit but three UTF-8 bytes
+        let name = format!("01 - {}.mp3", "音".repeat(100));
+        let cut = truncate_name(&name, MAX_NAME_UNITS, "", true);
+
+        assert!(cut.len() <= MAX_NAME_BYTES);
+        assert!(cut.starts_with("01 - 音"));
+        assert!(cut.ends_with("音.mp3"));
+        assert_eq!(cut.chars().filter(|&c| c == '音').count(), (MAX_NAME_BYTES - "01 - .mp3".len()) / 3);
+    }
+
+    #[test]
+    fn test_short_names_are_kept_and_suffixed() {
+        assert_eq!(truncate_name("Album", MAX_NAME_UNITS, "", false), "Album");
+        assert_eq!(truncate_name("Vol. 1", 4, "", false), "Vol");
+        assert_eq!(truncate_name("01 - Song.mp3", MAX_NAME_UNITS, " (2)", true), "01 - Song (2).mp3");
+        assert_eq!(fit_dir(Path::new("Artists/Artist/Album"), ""), PathBuf::from("Artists/Artist/Album"));
+    }
+
+    #[test]
+    fn test_fat_filesystems() {
+        assert!(has_fat_limits("vfat"));
+        assert!(has_fat_limits("exFAT"));
+        assert!(!has_fat_limits("ext4"));
+    }
+}
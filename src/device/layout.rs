@@ -0,0 +1,281 @@
+//! Templates for where album tracks are written on a device
+//!
+//! A layout is a `/`-separated path such as
+//! `Artists/{artist}/{album}/{track} - {title}`, relative to the device
+//! root. Each segment is rendered and sanitized on its own, so a `/` in a
+//! tag can't add a folder. The file extension is appended to the last
+//! segment.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+
+use crate::utils::sanitize_filename;
+
+/// Layout used when none is configured
+pub const DEFAULT_LAYOUT: &str = "Artists/{artist}/{album}/{track} - {title}";
+
+/// Layout used with `--genre-folders`
+pub const GENRE_LAYOUT: &str = "Genres/{genre}/{artist}/{album}/{track} - {title}";
+
+static DEFAULT: LazyLock<LayoutTemplate> =
+    LazyLock::new(|| LayoutTemplate::parse(DEFAULT_LAYOUT).expect("default layout is valid"));
+static GENRE: LazyLock<LayoutTemplate> =
+    LazyLock::new(|| LayoutTemplate::parse(GENRE_LAYOUT).expect("genre layout is valid"));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Artist,
+    Album,
+    Year,
+    Genre,
+    Disc,
+    Track,
+    Title,
+}
+
+impl Token {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "artist" => Self::Artist,
+            "album" => Self::Album,
+            "year" => Self::Year,
+            "genre" => Self::Genre,
+            "disc" => Self::Disc,
+            "track" => Self::Track,
+            "title" => Self::Title,
+            _ => return None,
+        })
+    }
+
+    /// Whether the token differs between tracks of one album
+    fn is_per_track(self) -> bool {
+        matches!(self, Self::Disc | Self::Track | Self::Title)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Token(Token),
+}
+
+/// Album-level values a layout is rendered with
+#[derive(Debug, Clone, Copy)]
+pub struct AlbumFolder<'a> {
+    /// Genre folder name, when albums are grouped by genre
+    pub genre: Option<&'a str>,
+    pub artist: &'a str,
+    pub album: &'a str,
+    pub year: Option<u32>,
+}
+
+/// Track-level values a layout is rendered with
+#[derive(Debug, Clone, Copy)]
+pub struct TrackName<'a> {
+    pub disc: Option<u32>,
+    pub track: u32,
+    pub title: &'a str,
+}
+
+/// A parsed on-device directory layout
+///
+/// Supports `{artist}`, `{album}`, `{year}`, `{genre}`, `{disc}`,
+/// `{track}` (zero-padded to two digits) and `{title}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutTemplate {
+    source: String,
+    segments: Vec<Vec<Part>>,
+    /// Number of leading segments that make up the album folder
+    album_depth: usize,
+}
+
+impl LayoutTemplate {
+    /// Parse and check a layout such as `{artist}/{year} - {album}/{track} {title}`
+    pub fn parse(template: &str) -> Result<Self, String> {
+        if template.trim().is_empty() {
+            return Err("layout must not be empty".to_string());
+        }
+        if template.starts_with('/') || template.contains('\\') {
+            return Err("layout must be a relative path with / separators".to_string());
+        }
+
+        let segments = template
+            .split('/')
+            .map(parse_segment)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let has = |parts: &[Part], tokens: &[Token]| {
+            parts.iter().any(|p| matches!(p, Part::Token(t) if tokens.contains(t)))
+        };
+        let (filename, dirs) = segments.split_last().expect("split yields a segment");
+        if !has(filename, &[Token::Track, Token::Title]) {
+            return Err("the last segment (the filename) must contain {track} or {title}".to_string());
+        }
+        if dirs.iter().any(|d| has(d, &[Token::Track, Token::Title])) {
+            return Err("{track} and {title} can only be used in the filename".to_string());
+        }
+
+        // Deleting an album removes its folder, so each album needs its own
+        let album_depth = segments
+            .iter()
+            .position(|s| s.iter().any(|p| matches!(p, Part::Token(t) if t.is_per_track())))
+            .expect("the filename has a per-track token");
+        if !segments[..album_depth].iter().any(|s| has(s, &[Token::Album])) {
+            return Err("{album} must be in a folder above the tracks, so each album gets its own folder".to_string());
+        }
+        // Albums of different artists often share a name ("Greatest Hits")
+        if !segments[..album_depth].iter().any(|s| has(s, &[Token::Artist])) {
+            return Err(
+                "{artist} must be in a folder above the tracks, so same-named albums by different artists don't share a folder"
+                    .to_string(),
+            );
+        }
+
+        Ok(Self {
+            source: template.to_string(),
+            segments,
+            album_depth,
+        })
+    }
+
+    /// The layout used when none is configured
+    pub fn builtin(genre_folders: bool) -> &'static Self {
+        if genre_folders { &GENRE } else { &DEFAULT }
+    }
+
+    /// Whether albums are placed by genre
+    pub fn uses_genre(&self) -> bool {
+        self.segments.iter().flatten().any(|p| *p == Part::Token(Token::Genre))
+    }
+
+    /// Album folder path relative to the device root
+    pub fn album_dir(&self, album: &AlbumFolder) -> PathBuf {
+        self.segments[..self.album_depth]
+            .iter()
+            .map(|s| render_segment(s, album, None))
+            .collect()
+    }
+
+    /// Track file path relative to the album folder
+    pub fn track_subpath(&self, album: &AlbumFolder, track: &TrackName, extension: &str) -> PathBuf {
+        let mut path: PathBuf = self.segments[self.album_depth..]
+            .iter()
+            .map(|s| render_segment(s, album, Some(track)))
+            .collect();
+        let filename = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        path.set_file_name(format!("{}.{}", filename, extension));
+        path
+    }
+}
+
+impl fmt::Display for LayoutTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn parse_segment(segment: &str) -> Result<Vec<Part>, String> {
+    if segment.trim().is_empty() || segment == "." || segment == ".." {
+        return Err(format!("invalid layout segment '{}'", segment));
+    }
+
+    let mut parts = Vec::new();
+    let mut rest = segment;
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            parts.push(Part::Text(rest[..start].to_string()));
+        }
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unclosed '{{' in layout segment '{}'", segment))?;
+        let name = &rest[start + 1..start + end];
+        let token = Token::from_name(name).ok_or_else(|| {
+            format!(
+                "unknown layout token {{{}}}; expected {{artist}}, {{album}}, {{year}}, {{genre}}, {{disc}}, {{track}} or {{title}}",
+                name
+            )
+        })?;
+        parts.push(Part::Token(token));
+        rest = &rest[start + end + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Text(rest.to_string()));
+    }
+    Ok(parts)
+}
+
+fn render_segment(parts: &[Part], album: &AlbumFolder, track: Option<&TrackName>) -> String {
+    let mut rendered = String::new();
+    for part in parts {
+        match part {
+            Part::Text(text) => rendered.push_str(text),
+            Part::Token(token) => rendered.push_str(&token_value(*token, album, track)),
+        }
+    }
+    sanitize_filename(&rendered)
+}
+
+fn token_value(token: Token, album: &AlbumFolder, track: Option<&TrackName>) -> String {
+    match token {
+        Token::Artist => album.artist.to_string(),
+        Token::Album => album.album.to_string(),
+        Token::Year => album.year.map_or_else(|| "Unknown Year".to_string(), |y| y.to_string()),
+        Token::Genre => album.genre.unwrap_or("Unknown Genre").to_string(),
+        Token::Disc => track.and_then(|t| t.disc).unwrap_or(1).to_string(),
+        Token::Track => format!("{:02}", track.map_or(1, |t| t.track)),
+        Token::Title => track.map_or("", |t| t.title).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALBUM: AlbumFolder = AlbumFolder {
+        genre: Some("Jazz"),
+        artist: "AC/DC",
+        album: "Album",
+        year: Some(1979),
+    };
+
+    #[test]
+    fn test_builtin_layouts() {
+        let track = TrackName { disc: None, track: 3, title: "Song" };
+        let layout = LayoutTemplate::builtin(false);
+        assert_eq!(layout.album_dir(&ALBUM), PathBuf::from("Artists/AC⧸DC/Album"));
+        assert_eq!(layout.track_subpath(&ALBUM, &track, "mp3"), PathBuf::from("03 - Song.mp3"));
+        assert_eq!(
+            LayoutTemplate::builtin(true).album_dir(&ALBUM),
+            PathBuf::from("Genres/Jazz/AC⧸DC/Album")
+        );
+    }
+
+    #[test]
+    fn test_custom_layout_with_disc_folders() {
+        let layout = LayoutTemplate::parse("Music/{artist}/{year} - {album}/CD{disc}/{track}. {title}").unwrap();
+        let track = TrackName { disc: Some(2), track: 7, title: "What?" };
+
+        assert_eq!(layout.album_dir(&ALBUM), PathBuf::from("Music/AC⧸DC/1979 - Album"));
+        assert_eq!(layout.track_subpath(&ALBUM, &track, "flac"), PathBuf::from("CD2/07. What？.flac"));
+        assert!(!layout.uses_genre());
+    }
+
+    #[test]
+    fn test_invalid_layouts() {
+        for layout in [
+            "",
+            "/Music/{album}/{title}",
+            "{artist}/{album}",
+            "{artist}/{title}/{track}",
+            "{artist}/{album} - {track} - {title}",
+            "{artist}/../{album}/{title}",
+            "{artist}/{albm}/{title}",
+            "{artist}/{album/{title}",
+            "{album}/{track} - {title}",
+            "Music/{year} - {album}/{track} - {artist} - {title}",
+        ] {
+            assert!(LayoutTemplate::parse(layout).is_err(), "{} should be rejected", layout);
+        }
+    }
+}
@@ -3,10 +3,18 @@
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
 use tracing::debug;
 
-const MANIFEST_FILE: &str = ".nutune-manifest.json";
+use super::layout::{AlbumFolder, LayoutTemplate};
+use super::storage::PLAYLISTS_DIR;
+use crate::subsonic::{Album, Playlist, SyncSelection};
+use crate::utils::m3u::PlaylistFormat;
+use crate::utils::sanitize_filename;
+use crate::utils::signature::{edge_crc32, sha256_hex};
+
+pub const MANIFEST_FILE: &str = ".nutune-manifest.json";
 
 /// Tracks what has been synced to a device
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +29,13 @@ pub struct SyncManifest {
     pub synced_albums: Vec<SyncedAlbum>,
     /// Playlists that have been synced
     pub synced_playlists: Vec<SyncedPlaylist>,
+    /// Albums and playlists that failed and haven't synced since, for `nutune retry`
+    #[serde(default, skip_serializing_if = "SyncSelection::is_empty")]
+    pub failed: SyncSelection,
+    /// Format the server transcoded tracks to in the last sync (absent when
+    /// the original files were copied)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcode: Option<String>,
 }
 
 /// Record of a synced album
@@ -32,10 +47,33 @@ pub struct SyncedAlbum {
     pub artist: String,
     /// Album name
     pub album: String,
+    /// Genre folder the album was written under (when grouped by genre)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genre: Option<String>,
+    /// Album folder relative to the device root (absent for albums synced
+    /// before layouts were configurable, which used the built-in layout)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
     /// Number of tracks synced
     pub track_count: u32,
+    /// Number of tracks the album had on the server (absent in older manifests)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_tracks: Option<u32>,
+    /// Song count the server reported for the album, before any tracks were
+    /// skipped or left unpicked (absent in older manifests)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_tracks: Option<u32>,
+    /// IDs of the songs written, so an interrupted sync can resume the album
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub synced_tracks: Vec<String>,
+    /// Only some of the album's tracks were selected (see `SyncSelection::tracks`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub partial: bool,
     /// When this album was synced
     pub synced_at: DateTime<Utc>,
+    /// Signatures of the written tracks (empty for albums synced by older versions)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tracks: Vec<TrackSignature>,
 }
 
 /// Record of a synced playlist
@@ -45,10 +83,131 @@ pub struct SyncedPlaylist {
     pub id: String,
     /// Playlist name
     pub name: String,
-    /// Number of tracks in playlist
+    /// Number of tracks written to the device
     pub track_count: u32,
+    /// Number of tracks the playlist had on the server (absent in older manifests)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_tracks: Option<u32>,
+    /// Song count the server reported for the playlist, before any tracks
+    /// were skipped (absent in older manifests)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_tracks: Option<u32>,
+    /// When the server last changed the playlist, as it reported at the
+    /// sync (absent in older manifests, or if the server doesn't say)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changed: Option<String>,
+    /// IDs of the songs written, in playlist order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub song_ids: Vec<String>,
     /// When this playlist was synced
     pub synced_at: DateTime<Utc>,
+    /// Signatures of the written tracks (empty for playlists synced by older
+    /// versions), in the order of `song_ids` for playlists synced since it was kept
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tracks: Vec<TrackSignature>,
+    /// Format the playlist file was written in
+    #[serde(default, skip_serializing_if = "PlaylistFormat::is_m3u")]
+    pub format: PlaylistFormat,
+}
+
+/// Signature of a written track, for checking it later without re-reading it all
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackSignature {
+    /// Path relative to the device root, with `/` separators
+    pub path: String,
+    /// File size in bytes
+    pub size: u64,
+    /// CRC32 of the first and last 64 KiB (see `utils::signature`)
+    pub edge_crc32: u32,
+    /// SHA-256 of the whole file, for when the quick check isn't conclusive
+    pub sha256: String,
+}
+
+impl TrackSignature {
+    /// Sign track data written at `path` (relative to the device root)
+    pub fn new(path: String, data: &[u8]) -> Self {
+        Self {
+            path,
+            size: data.len() as u64,
+            edge_crc32: edge_crc32(data),
+            sha256: sha256_hex(data),
+        }
+    }
+}
+
+impl SyncedAlbum {
+    /// Whether every track of the album made it onto the device
+    pub fn is_complete(&self) -> bool {
+        self.expected_tracks.is_none_or(|expected| self.track_count >= expected)
+    }
+
+    /// Whether the album needs syncing again to match the server, which now
+    /// reports `song_count` songs for it
+    ///
+    /// That's when tracks are missing from an earlier sync, or the server's
+    /// count changed since (compared with the tracks written for older
+    /// manifests). Albums with only some tracks picked are judged by the
+    /// pick instead, so a count change doesn't affect them.
+    pub fn is_outdated(&self, song_count: Option<u32>) -> bool {
+        !self.is_complete()
+            || (!self.partial && count_changed(self.server_tracks.unwrap_or(self.track_count), song_count))
+    }
+
+    /// Album folder relative to the device root
+    ///
+    /// Albums synced before the folder was recorded used the built-in layout.
+    pub fn dir(&self) -> PathBuf {
+        if let Some(folder) = &self.folder {
+            return PathBuf::from(folder);
+        }
+        let album = AlbumFolder {
+            genre: self.genre.as_deref(),
+            artist: &self.artist,
+            album: &self.album,
+            year: None,
+        };
+        LayoutTemplate::builtin(album.genre.is_some()).album_dir(&album)
+    }
+}
+
+impl SyncedPlaylist {
+    /// Whether every track of the playlist made it onto the device
+    pub fn is_complete(&self) -> bool {
+        self.expected_tracks.is_none_or(|expected| self.track_count >= expected)
+    }
+
+    /// Whether the playlist needs syncing again to match `playlist` on the
+    /// server: tracks are missing, or it changed since it was synced
+    ///
+    /// Changes are told by the server's `changed` time, which also catches
+    /// songs swapped or reordered. Without one on both sides, only a change
+    /// in the song count is noticed.
+    pub fn is_outdated(&self, playlist: &Playlist) -> bool {
+        if !self.is_complete() {
+            return true;
+        }
+        match (&self.changed, &playlist.changed) {
+            (Some(synced), Some(changed)) => synced != changed,
+            _ => count_changed(self.server_tracks.unwrap_or(self.track_count), playlist.song_count),
+        }
+    }
+
+    /// Playlist folder relative to the device root
+    ///
+    /// Taken from the tracks' paths when recorded, since long names may
+    /// have been shortened to fit the filesystem.
+    pub fn dir(&self) -> PathBuf {
+        self.tracks
+            .first()
+            .and_then(|t| Path::new(&t.path).parent())
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| Path::new(PLAYLISTS_DIR).join(sanitize_filename(&self.name)))
+    }
+}
+
+/// Whether the server's current song count (if it reports one) differs from the synced one
+fn count_changed(synced: u32, song_count: Option<u32>) -> bool {
+    song_count.is_some_and(|count| count != synced)
 }
 
 impl SyncManifest {
@@ -60,6 +219,8 @@ impl SyncManifest {
             subsonic_url: subsonic_url.to_string(),
             synced_albums: Vec::new(),
             synced_playlists: Vec::new(),
+            failed: SyncSelection::default(),
+            transcode: None,
         }
     }
 
@@ -87,34 +248,61 @@ impl SyncManifest {
         Ok(Some(manifest))
     }
 
-    /// Save manifest to device root
-    pub fn save(&self, device_root: &Path) -> Result<()> {
-        let manifest_path = device_root.join(MANIFEST_FILE);
-
-        let content = serde_json::to_string_pretty(self)
-            .context("Failed to serialize manifest")?;
-
-        std::fs::write(&manifest_path, content)
-            .context("Failed to write manifest file")?;
-
-        debug!("Saved manifest to {}", manifest_path.display());
-        Ok(())
+    /// Serialize the manifest as it is stored on the device
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize manifest")
     }
 
     /// Check if an album has been synced
     pub fn is_album_synced(&self, album_id: &str) -> bool {
-        self.synced_albums.iter().any(|a| a.id == album_id)
+        self.synced_albums
+            .iter()
+            .any(|a| a.id == album_id && a.is_complete())
     }
 
-    /// Check if a playlist has been synced
+    /// Check if an album is on the device with exactly the given tracks
+    ///
+    /// `tracks` is the songs picked from the album, or `None` for the whole
+    /// album. Changing the pick (or going from a pick to the whole album)
+    /// makes the album unsynced, so the next sync adds or removes tracks.
+    pub fn is_album_synced_with(&self, album_id: &str, tracks: Option<&BTreeSet<String>>) -> bool {
+        self.album(album_id).is_some_and(|a| {
+            a.is_complete()
+                && match tracks {
+                    None => !a.partial,
+                    Some(ids) => {
+                        a.partial && a.synced_tracks.len() == ids.len() && a.synced_tracks.iter().all(|id| ids.contains(id))
+                    }
+                }
+        })
+    }
+
+    /// Check if a playlist has been synced completely
+    ///
+    /// Playlists with tracks missing count as unsynced, so the next sync retries them.
     pub fn is_playlist_synced(&self, playlist_id: &str) -> bool {
-        self.synced_playlists.iter().any(|p| p.id == playlist_id)
+        self.synced_playlists
+            .iter()
+            .any(|p| p.id == playlist_id && p.is_complete())
+    }
+
+    /// Check if a playlist has been synced completely with its file in `format`
+    ///
+    /// A playlist written in another format counts as unsynced, so the next
+    /// sync writes the new file.
+    pub fn is_playlist_synced_in(&self, playlist_id: &str, format: PlaylistFormat) -> bool {
+        self.synced_playlists
+            .iter()
+            .any(|p| p.id == playlist_id && p.is_complete() && p.format == format)
     }
 
     /// Add a synced album
     pub fn add_album(&mut self, album: SyncedAlbum) {
         // Remove existing entry if present (for re-sync)
         self.synced_albums.retain(|a| a.id != album.id);
+        if album.is_complete() {
+            self.failed.remove_album(&album.id);
+        }
         self.synced_albums.push(album);
         self.last_sync = Utc::now();
     }
@@ -123,10 +311,43 @@ impl SyncManifest {
     pub fn add_playlist(&mut self, playlist: SyncedPlaylist) {
         // Remove existing entry if present (for re-sync)
         self.synced_playlists.retain(|p| p.id != playlist.id);
+        self.failed.playlists.retain(|p| p.id != playlist.id);
         self.synced_playlists.push(playlist);
         self.last_sync = Utc::now();
     }
 
+    /// Record an album that failed to sync, to be retried later
+    ///
+    /// `tracks` is the songs picked from the album, if not all of them.
+    pub fn add_failed_album(&mut self, album: &Album, tracks: Option<&BTreeSet<String>>) {
+        self.failed.remove_album(&album.id);
+        self.failed.albums.push(album.clone());
+        if let Some(tracks) = tracks {
+            self.failed.tracks.insert(album.id.clone(), tracks.clone());
+        }
+    }
+
+    /// Record a playlist that failed to sync (or synced incompletely), to be retried later
+    pub fn add_failed_playlist(&mut self, playlist: &Playlist) {
+        self.failed.playlists.retain(|p| p.id != playlist.id);
+        self.failed.playlists.push(playlist.clone());
+    }
+
+    /// Record everything synced in `other`, replacing entries with the same ID
+    pub fn merge(&mut self, other: &SyncManifest) {
+        for album in &other.synced_albums {
+            self.add_album(album.clone());
+        }
+        for playlist in &other.synced_playlists {
+            self.add_playlist(playlist.clone());
+        }
+    }
+
+    /// Get the manifest record for an album
+    pub fn album(&self, album_id: &str) -> Option<&SyncedAlbum> {
+        self.synced_albums.iter().find(|a| a.id == album_id)
+    }
+
     /// Remove an album from the manifest
     pub fn remove_album(&mut self, album_id: &str) -> Option<SyncedAlbum> {
         let idx = self.synced_albums.iter().position(|a| a.id == album_id);
@@ -139,3 +360,131 @@ impl SyncManifest {
         idx.map(|i| self.synced_playlists.remove(i))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playlist(track_count: u32, expected_tracks: Option<u32>) -> SyncedPlaylist {
+        SyncedPlaylist {
+            id: "p1".to_string(),
+            name: "Mix".to_string(),
+            track_count,
+            expected_tracks,
+            server_tracks: None,
+            changed: None,
+            song_ids: Vec::new(),
+            synced_at: Utc::now(),
+            tracks: Vec::new(),
+            format: PlaylistFormat::M3u,
+        }
+    }
+
+    #[test]
+    fn test_incomplete_playlist_is_not_synced() {
+        let mut manifest = SyncManifest::new("http://server");
+        manifest.add_playlist(playlist(8, Some(10)));
+        assert!(!manifest.is_playlist_synced("p1"));
+
+        manifest.add_playlist(playlist(10, Some(10)));
+        assert!(manifest.is_playlist_synced("p1"));
+
+        // Older manifests didn't record the expected count
+        manifest.add_playlist(playlist(8, None));
+        assert!(manifest.is_playlist_synced("p1"));
+
+        // ...nor the format, which was always M3U
+        assert!(manifest.is_playlist_synced_in("p1", PlaylistFormat::M3u));
+        assert!(!manifest.is_playlist_synced_in("p1", PlaylistFormat::Pls));
+    }
+
+    /// The playlist as the server lists it now
+    fn on_server(song_count: Option<u32>, changed: Option<&str>) -> Playlist {
+        serde_json::from_value(serde_json::json!({
+            "id": "p1", "name": "Mix", "songCount": song_count, "changed": changed
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_outdated_when_server_count_changes() {
+        let mut mix = playlist(10, Some(10));
+        assert!(!mix.is_outdated(&on_server(Some(10), None)));
+        assert!(!mix.is_outdated(&on_server(None, None)));
+        assert!(mix.is_outdated(&on_server(Some(12), None)));
+
+        // Compared with what the server listed, not what was written
+        mix.expected_tracks = Some(9);
+        mix.server_tracks = Some(12);
+        assert!(!mix.is_outdated(&on_server(Some(12), None)));
+        assert!(mix.is_outdated(&on_server(Some(11), None)));
+        assert!(playlist(8, Some(10)).is_outdated(&on_server(None, None)));
+    }
+
+    #[test]
+    fn test_outdated_when_server_changed_the_playlist() {
+        let mut mix = playlist(10, Some(10));
+        mix.changed = Some("2024-05-01T10:00:00Z".to_string());
+        assert!(!mix.is_outdated(&on_server(Some(10), Some("2024-05-01T10:00:00Z"))));
+
+        // A song swapped for another keeps the count, but not the time
+        assert!(mix.is_outdated(&on_server(Some(10), Some("2024-06-01T10:00:00Z"))));
+
+        // Without a time on both sides, the count decides
+        assert!(!mix.is_outdated(&on_server(Some(10), None)));
+        mix.changed = None;
+        assert!(!mix.is_outdated(&on_server(Some(10), Some("2024-06-01T10:00:00Z"))));
+    }
+
+    #[test]
+    fn test_partial_album_is_not_synced() {
+        let mut album = SyncedAlbum {
+            id: "a1".to_string(),
+            artist: "Artist".to_string(),
+            album: "Album".to_string(),
+            genre: None,
+            folder: None,
+            track_count: 3,
+            expected_tracks: Some(10),
+            server_tracks: None,
+            synced_tracks: vec!["s1".to_string(), "s2".to_string(), "s3".to_string()],
+            partial: false,
+            synced_at: Utc::now(),
+            tracks: Vec::new(),
+        };
+        let mut manifest = SyncManifest::new("http://server");
+        manifest.add_album(album.clone());
+        assert!(!manifest.is_album_synced("a1"));
+        assert!(album.is_outdated(None));
+
+        // Only some tracks were picked, so a new song on the server doesn't matter
+        album.expected_tracks = Some(3);
+        album.partial = true;
+        assert!(!album.is_outdated(Some(11)));
+        album.partial = false;
+        assert!(album.is_outdated(Some(11)));
+
+        // Older manifests didn't record the expected count
+        album.expected_tracks = None;
+        manifest.add_album(album);
+        assert!(manifest.is_album_synced("a1"));
+    }
+
+    #[test]
+    fn test_failed_items_clear_once_synced() {
+        let mut manifest = SyncManifest::new("http://server");
+        assert!(!manifest.to_json().unwrap().contains("failed"));
+
+        let failed_playlist: Playlist =
+            serde_json::from_value(serde_json::json!({ "id": "p1", "name": "Mix" })).unwrap();
+        manifest.add_failed_playlist(&failed_playlist);
+        manifest.add_failed_playlist(&failed_playlist);
+        assert_eq!(manifest.failed.playlists.len(), 1);
+
+        let saved: SyncManifest = serde_json::from_str(&manifest.to_json().unwrap()).unwrap();
+        assert_eq!(saved.failed.playlists, [failed_playlist]);
+
+        manifest.add_playlist(playlist(10, Some(10)));
+        assert!(manifest.failed.is_empty());
+    }
+}
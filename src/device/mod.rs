@@ -1,10 +1,13 @@
 //! Device detection and storage module
 
+pub mod backend;
 pub mod config;
 pub mod detection;
+pub mod fs_limits;
+pub mod layout;
 pub mod manifest;
 pub mod storage;
 
 pub use detection::{Device, DeviceDetector, UnmountedDevice};
-pub use manifest::{SyncManifest, SyncedAlbum, SyncedPlaylist};
+pub use manifest::{SyncManifest, SyncedAlbum, SyncedPlaylist, TrackSignature};
 pub use storage::DeviceStorage;
@@ -1,21 +1,264 @@
 //! Device storage operations
 
 use anyhow::{Context, Result};
-use std::path::PathBuf;
-use tokio::fs;
-use tracing::debug;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, warn};
 
+use super::backend::{FsBackend, StorageBackend};
+use super::fs_limits;
+use super::layout::{AlbumFolder, LayoutTemplate, TrackName};
+use super::manifest::{SyncManifest, SyncedAlbum, MANIFEST_FILE};
+use crate::utils::m3u::{M3uEncoding, M3uTrack, PlaylistFormat};
 use crate::utils::sanitize_filename;
 
+/// Folder playlists are written to, below the device root
+pub const PLAYLISTS_DIR: &str = "Playlists";
+
+/// Times a track is written before a size mismatch fails it
+const TRACK_WRITE_ATTEMPTS: usize = 2;
+
+/// Extensions of the audio files nutune writes
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "flac", "ogg", "oga", "opus", "m4a", "aac", "wav", "wma", "aiff"];
+
+/// Whether a path looks like an audio file, by extension
+pub fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| AUDIO_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+/// What's in the folders nutune syncs into, matched against a manifest
+#[derive(Debug, Default)]
+pub struct SyncedTree {
+    /// Album and playlist folders the manifest knows about
+    pub synced: Vec<PathBuf>,
+    /// Files and folders no synced album or playlist accounts for
+    pub orphans: Vec<Orphan>,
+}
+
+/// A file or folder on the device that isn't part of any synced album or playlist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Orphan {
+    pub path: PathBuf,
+    pub is_dir: bool,
+}
+
+/// Default playlist track filename format (without extension)
+pub const DEFAULT_PLAYLIST_TRACK_FORMAT: &str = "{artist} - {title}";
+
 /// Manages file operations on a device
 pub struct DeviceStorage {
     root: PathBuf,
+    backend: Arc<dyn StorageBackend>,
+    /// Layout for album tracks, if not the built-in one
+    layout: Option<LayoutTemplate>,
+    /// Keep names and paths within FAT limits
+    fat_limits: bool,
+    /// Shortened paths handed out so far, with the paths they were shortened from
+    shortened: Mutex<HashMap<PathBuf, PathBuf>>,
+    /// Filename format for playlist tracks, see `playlist_track_filename`
+    playlist_track_format: String,
+    /// Byte layout of written M3U files
+    m3u_encoding: M3uEncoding,
+    /// Write `#EXTINF` lines in M3U files
+    extended_m3u: bool,
+    /// Format of the playlist file written to each playlist folder
+    playlist_format: PlaylistFormat,
+    /// Permission bits for written files, if not left to the umask
+    file_mode: Option<u32>,
+    /// Permission bits for created directories, if not left to the umask
+    dir_mode: Option<u32>,
 }
 
 impl DeviceStorage {
     /// Create a new storage manager for a device
     pub fn new(mount_point: PathBuf) -> Self {
-        Self { root: mount_point }
+        Self::with_backend(mount_point, Arc::new(FsBackend))
+    }
+
+    /// Create a storage manager that performs its I/O through `backend`
+    pub fn with_backend(mount_point: PathBuf, backend: Arc<dyn StorageBackend>) -> Self {
+        Self {
+            root: mount_point,
+            backend,
+            layout: None,
+            fat_limits: false,
+            shortened: Mutex::new(HashMap::new()),
+            playlist_track_format: DEFAULT_PLAYLIST_TRACK_FORMAT.to_string(),
+            m3u_encoding: M3uEncoding::default(),
+            extended_m3u: true,
+            playlist_format: PlaylistFormat::default(),
+            file_mode: None,
+            dir_mode: None,
+        }
+    }
+
+    /// Set the layout album tracks are written with
+    pub fn set_layout(&mut self, layout: LayoutTemplate) {
+        self.layout = Some(layout);
+    }
+
+    /// Layout for an album, falling back to the built-in one for its genre grouping
+    fn layout(&self, genre: Option<&str>) -> &LayoutTemplate {
+        self.layout
+            .as_ref()
+            .unwrap_or_else(|| LayoutTemplate::builtin(genre.is_some()))
+    }
+
+    /// Apply the name and path limits of the device's filesystem (e.g. `vfat`)
+    pub fn set_fs_type(&mut self, fs_type: &str) {
+        self.fat_limits = fs_limits::has_fat_limits(fs_type);
+    }
+
+    /// Shorten a path relative to the root, numbering it if another path was shortened to the same one
+    ///
+    /// `fit` shortens `original` with a suffix such as ` (2)`.
+    fn shorten(&self, original: PathBuf, fit: impl Fn(&str) -> PathBuf) -> PathBuf {
+        let mut shortened = self.shortened.lock().unwrap();
+        for n in 1.. {
+            let suffix = if n == 1 { String::new() } else { format!(" ({})", n) };
+            let path = fit(&suffix);
+            if path == original {
+                return path;
+            }
+            match shortened.get(&path) {
+                Some(owner) if *owner != original => continue,
+                _ => {
+                    debug!("Shortened {} to {}", original.display(), path.display());
+                    shortened.insert(path.clone(), original);
+                    return path;
+                }
+            }
+        }
+        unreachable!("suffixes run out")
+    }
+
+    /// Fit a folder path relative to the root within the filesystem's limits
+    fn fit_dir(&self, dir: PathBuf) -> PathBuf {
+        if !self.fat_limits {
+            return dir;
+        }
+        self.shorten(dir.clone(), |suffix| fs_limits::fit_dir(&dir, suffix))
+    }
+
+    /// Fit a file's path below a fitted folder within the filesystem's limits
+    fn fit_file(&self, dir: &Path, rest: PathBuf) -> PathBuf {
+        if !self.fat_limits {
+            return dir.join(rest);
+        }
+        self.shorten(dir.join(&rest), |suffix| dir.join(fs_limits::fit_below(dir, &rest, suffix)))
+    }
+
+    /// Set the filename format used for playlist tracks
+    pub fn set_playlist_track_format(&mut self, format: &str) {
+        self.playlist_track_format = format.to_string();
+    }
+
+    /// Set the line endings and BOM used for M3U files
+    pub fn set_m3u_encoding(&mut self, encoding: M3uEncoding) {
+        self.m3u_encoding = encoding;
+    }
+
+    /// Set whether M3U files get `#EXTINF` lines or list bare filenames
+    pub fn set_extended_m3u(&mut self, extended: bool) {
+        self.extended_m3u = extended;
+    }
+
+    /// Set the format playlist files are written in
+    pub fn set_playlist_format(&mut self, format: PlaylistFormat) {
+        self.playlist_format = format;
+    }
+
+    /// Set the permission bits applied to written files and created directories
+    pub fn set_modes(&mut self, file_mode: Option<u32>, dir_mode: Option<u32>) {
+        self.file_mode = file_mode;
+        self.dir_mode = dir_mode;
+    }
+
+    /// Create a directory and any missing parents, applying the directory mode to those created
+    async fn create_dir_all(&self, path: &Path) -> Result<()> {
+        let Some(mode) = self.dir_mode else {
+            return self.backend.create_dir_all(path).await;
+        };
+
+        let mut created = Vec::new();
+        for dir in path.ancestors() {
+            if self.backend.exists(dir).await {
+                break;
+            }
+            created.push(dir);
+        }
+
+        self.backend.create_dir_all(path).await?;
+        for dir in created.into_iter().rev() {
+            self.backend.set_mode(dir, mode)?;
+        }
+        Ok(())
+    }
+
+    /// Write a file, applying the file mode
+    async fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.backend.write(path, data).await?;
+        if let Some(mode) = self.file_mode {
+            self.backend.set_mode(path, mode)?;
+        }
+        Ok(())
+    }
+
+    /// Write a track and check its size on the device, writing it once more if it's wrong
+    ///
+    /// A file that still has the wrong size is deleted, so a failing card
+    /// doesn't leave a broken track behind that the manifest calls synced.
+    async fn write_track(&self, path: &Path, data: &[u8]) -> Result<()> {
+        let expected = data.len() as u64;
+        let mut actual = 0;
+        for attempt in 1..=TRACK_WRITE_ATTEMPTS {
+            self
+                .write(path, data)
+                .await
+                .context("Failed to write track file")?;
+            actual = self.backend.file_size(path).await?;
+            if actual == expected {
+                return Ok(());
+            }
+
+            warn!(
+                "{} has {} bytes on the device instead of {} (attempt {}/{})",
+                path.display(),
+                actual,
+                expected,
+                attempt,
+                TRACK_WRITE_ATTEMPTS
+            );
+            if let Err(e) = self.backend.remove_file(path).await {
+                warn!("Failed to delete {}: {:#}", path.display(), e);
+            }
+        }
+
+        anyhow::bail!(
+            "Wrote {} bytes to {} but the device has {}; the card may be failing",
+            expected,
+            path.display(),
+            actual
+        )
+    }
+
+    /// Full path of a file recorded relative to the root, as in track signatures
+    pub fn device_path(&self, relative: &str) -> PathBuf {
+        self.root.join(relative)
+    }
+
+    /// Path of a file on the device relative to its root, with `/` separators
+    pub fn relative_path(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let parts: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        Some(parts.join("/"))
     }
 
     /// Get path to Artists directory
@@ -25,16 +268,48 @@ impl DeviceStorage {
 
     /// Get path to Playlists directory
     pub fn playlists_dir(&self) -> PathBuf {
-        self.root.join("Playlists")
+        self.root.join(PLAYLISTS_DIR)
+    }
+
+    /// Get the folder path for a playlist
+    pub fn playlist_dir(&self, name: &str) -> PathBuf {
+        self.root.join(self.playlist_folder(name))
+    }
+
+    /// Playlist folder relative to the root
+    fn playlist_folder(&self, name: &str) -> PathBuf {
+        self.fit_dir(Path::new(PLAYLISTS_DIR).join(sanitize_filename(name)))
+    }
+
+    /// Get path to Genres directory (used when albums are grouped by genre)
+    pub fn genres_dir(&self) -> PathBuf {
+        self.root.join("Genres")
+    }
+
+    /// Get the path of an album folder
+    ///
+    /// Without a configured layout, albums live at `Artists/<artist>/<album>`,
+    /// or at `Genres/<genre>/<artist>/<album>` when a genre folder is given.
+    pub fn album_dir(&self, album: &AlbumFolder) -> PathBuf {
+        self.root.join(self.fit_dir(self.layout(album.genre).album_dir(album)))
+    }
+
+    /// Get the folder a synced album was written to
+    pub fn synced_album_dir(&self, synced: &SyncedAlbum) -> PathBuf {
+        self.root.join(synced.dir())
     }
 
     /// Create the base directory structure
     pub async fn init(&self) -> Result<()> {
-        fs::create_dir_all(self.artists_dir())
-            .await
-            .context("Failed to create Artists directory")?;
+        if self.layout.is_none() {
+            self
+                .create_dir_all(&self.artists_dir())
+                .await
+                .context("Failed to create Artists directory")?;
+        }
 
-        fs::create_dir_all(self.playlists_dir())
+        self
+            .create_dir_all(&self.playlists_dir())
             .await
             .context("Failed to create Playlists directory")?;
 
@@ -42,27 +317,23 @@ impl DeviceStorage {
         Ok(())
     }
 
-    /// Create artist/album folder structure and return the album path
-    pub async fn create_album_folder(&self, artist: &str, album: &str) -> Result<PathBuf> {
-        let artist_safe = sanitize_filename(artist);
-        let album_safe = sanitize_filename(album);
-
-        let album_path = self.artists_dir().join(&artist_safe).join(&album_safe);
-
-        fs::create_dir_all(&album_path)
+    /// Create an album folder (and its parents) if it doesn't exist
+    async fn create_album_folder(&self, album_path: &Path) -> Result<()> {
+        self
+            .create_dir_all(album_path)
             .await
             .context("Failed to create album directory")?;
 
         debug!("Created album folder: {}", album_path.display());
-        Ok(album_path)
+        Ok(())
     }
 
     /// Create playlist folder and return the path
     pub async fn create_playlist_folder(&self, name: &str) -> Result<PathBuf> {
-        let name_safe = sanitize_filename(name);
-        let playlist_path = self.playlists_dir().join(&name_safe);
+        let playlist_path = self.playlist_dir(name);
 
-        fs::create_dir_all(&playlist_path)
+        self
+            .create_dir_all(&playlist_path)
             .await
             .context("Failed to create playlist directory")?;
 
@@ -70,38 +341,117 @@ impl DeviceStorage {
         Ok(playlist_path)
     }
 
-    /// Write a track file to an album folder
+    /// Get the path a track of an album is written to
+    pub fn album_track_path(&self, album: &AlbumFolder, track: &TrackName, extension: &str) -> PathBuf {
+        let layout = self.layout(album.genre);
+        let dir = self.fit_dir(layout.album_dir(album));
+        self.root.join(self.fit_file(&dir, layout.track_subpath(album, track, extension)))
+    }
+
+    /// Whether a track file is on the device
+    pub async fn track_exists(&self, path: &Path) -> bool {
+        self.backend.exists(path).await
+    }
+
+    /// Write a track file to its place in the layout
     ///
     /// Returns the full path of the written file
     pub async fn write_album_track(
         &self,
-        artist: &str,
-        album: &str,
-        track_number: u32,
-        title: &str,
+        album: &AlbumFolder<'_>,
+        track: &TrackName<'_>,
         extension: &str,
         data: &[u8],
     ) -> Result<PathBuf> {
-        let album_path = self.create_album_folder(artist, album).await?;
-
-        let title_safe = sanitize_filename(title);
-        let filename = format!("{:02} - {}.{}", track_number, title_safe, extension);
-        let file_path = album_path.join(&filename);
+        let file_path = self.album_track_path(album, track, extension);
+        if let Some(parent) = file_path.parent() {
+            self.create_album_folder(parent).await?;
+        }
 
-        fs::write(&file_path, data)
-            .await
-            .context("Failed to write track file")?;
+        self.write_track(&file_path, data).await?;
 
         debug!("Wrote track: {}", file_path.display());
         Ok(file_path)
     }
 
+    /// Get the filename a track of a playlist is written to
+    pub fn playlist_track_file(
+        &self,
+        playlist_name: &str,
+        position: usize,
+        total: usize,
+        artist: &str,
+        title: &str,
+        extension: &str,
+    ) -> String {
+        let filename = format!(
+            "{}.{}",
+            playlist_track_filename(&self.playlist_track_format, position, total, artist, title),
+            extension
+        );
+        let folder = self.playlist_folder(playlist_name);
+        self
+            .fit_file(&folder, PathBuf::from(filename))
+            .file_name()
+            .map(|f| f.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Put a copy of a file already on the device at `dest`, creating parent folders
+    ///
+    /// The copy is a hard link where the filesystem supports them, so it
+    /// takes no extra space; on FAT (or if linking fails) the data is copied.
+    pub async fn link_or_copy(&self, original: &Path, dest: &Path) -> Result<()> {
+        if original == dest {
+            return Ok(());
+        }
+        if let Some(parent) = dest.parent() {
+            self
+                .create_dir_all(parent)
+                .await
+                .context("Failed to create directory")?;
+        }
+        if self.backend.exists(dest).await {
+            self.backend.remove_file(dest).await?;
+        }
+
+        if !self.fat_limits {
+            match self.backend.hard_link(original, dest).await {
+                Ok(()) => {
+                    debug!("Linked {} to {}", dest.display(), original.display());
+                    return Ok(());
+                }
+                Err(e) => debug!("{:#}; copying instead", e),
+            }
+        }
+
+        let data = self.backend.read(original).await?;
+        self.write_track(dest, &data).await?;
+        debug!("Copied {} to {}", original.display(), dest.display());
+        Ok(())
+    }
+
+    /// Move a file already on the device to `dest`, creating parent folders
+    ///
+    /// Like [`Self::link_or_copy`], with the original removed afterwards.
+    pub async fn move_file(&self, original: &Path, dest: &Path) -> Result<()> {
+        if original == dest {
+            return Ok(());
+        }
+        self.link_or_copy(original, dest).await?;
+        self.backend.remove_file(original).await
+    }
+
     /// Write a track file to a playlist folder
     ///
-    /// Returns the filename (not full path) for use in M3U
+    /// `position` is the track's 1-based place in a playlist of `total`
+    /// tracks. Returns the filename (not full path) for use in M3U
+    #[allow(clippy::too_many_arguments)]
     pub async fn write_playlist_track(
         &self,
         playlist_name: &str,
+        position: usize,
+        total: usize,
         artist: &str,
         title: &str,
         extension: &str,
@@ -109,71 +459,169 @@ impl DeviceStorage {
     ) -> Result<String> {
         let playlist_path = self.create_playlist_folder(playlist_name).await?;
 
-        let artist_safe = sanitize_filename(artist);
-        let title_safe = sanitize_filename(title);
-        let filename = format!("{} - {}.{}", artist_safe, title_safe, extension);
+        let filename = self.playlist_track_file(playlist_name, position, total, artist, title, extension);
         let file_path = playlist_path.join(&filename);
 
-        fs::write(&file_path, data)
-            .await
-            .context("Failed to write playlist track")?;
+        self.write_track(&file_path, data).await?;
 
         debug!("Wrote playlist track: {}", file_path.display());
         Ok(filename)
     }
 
-    /// Write cover art to an album folder
-    pub async fn write_cover_art(
-        &self,
-        artist: &str,
-        album: &str,
-        data: &[u8],
-    ) -> Result<PathBuf> {
-        let album_path = self.create_album_folder(artist, album).await?;
-        let cover_path = album_path.join("cover.jpg");
+    /// List the audio files in an album folder, in filename order
+    ///
+    /// Cover art, notes and chapter sidecars are skipped.
+    pub async fn list_album_tracks(&self, album_path: &Path) -> Result<Vec<PathBuf>> {
+        let files = self.backend.list_files(album_path).await?;
+        Ok(files.into_iter().filter(|f| is_audio_file(f)).collect())
+    }
+
+    /// Read a file at a path relative to the device root
+    pub async fn read_file(&self, relative: &Path) -> Result<Vec<u8>> {
+        self.backend.read(&self.root.join(relative)).await
+    }
 
-        fs::write(&cover_path, data)
+    /// Write a file at a path relative to the device root, creating parent folders
+    pub async fn write_file(&self, relative: &Path, data: &[u8]) -> Result<PathBuf> {
+        let file_path = self.root.join(relative);
+        if let Some(parent) = file_path.parent() {
+            self
+                .create_dir_all(parent)
+                .await
+                .context("Failed to create directory")?;
+        }
+
+        self
+            .write(&file_path, data)
             .await
-            .context("Failed to write cover art")?;
+            .context("Failed to write file")?;
+
+        debug!("Wrote file: {}", file_path.display());
+        Ok(file_path)
+    }
 
-        debug!("Wrote cover art: {}", cover_path.display());
-        Ok(cover_path)
+    /// Set a written file's modification time
+    pub fn set_file_mtime(&self, path: &Path, time: DateTime<Utc>) -> Result<()> {
+        self.backend.set_mtime(path, time)
     }
 
-    /// Generate and write an M3U playlist file
-    pub async fn write_m3u(&self, playlist_name: &str, tracks: &[String]) -> Result<PathBuf> {
+    /// Write cover art to an album folder under each of the given file names
+    pub async fn write_cover_art(&self, album_path: &Path, data: &[u8], file_names: &[String]) -> Result<Vec<PathBuf>> {
+        self.create_album_folder(album_path).await?;
+
+        let mut paths = Vec::with_capacity(file_names.len());
+        for file_name in file_names {
+            let cover_path = album_path.join(file_name);
+            self
+                .write(&cover_path, data)
+                .await
+                .with_context(|| format!("Failed to write cover art to {}", file_name))?;
+
+            debug!("Wrote cover art: {}", cover_path.display());
+            paths.push(cover_path);
+        }
+        Ok(paths)
+    }
+
+    /// Write album notes to notes.txt in the album folder
+    pub async fn write_album_notes(&self, album_path: &Path, notes: &str) -> Result<PathBuf> {
+        self.create_album_folder(album_path).await?;
+        let notes_path = album_path.join("notes.txt");
+
+        self
+            .write(&notes_path, notes.as_bytes())
+            .await
+            .context("Failed to write album notes")?;
+
+        debug!("Wrote album notes: {}", notes_path.display());
+        Ok(notes_path)
+    }
+
+    /// Write a sidecar file next to a track, e.g. `01 - Title.cue`
+    pub async fn write_sidecar(&self, track_path: &Path, extension: &str, content: &str) -> Result<PathBuf> {
+        let sidecar_path = track_path.with_extension(extension);
+
+        self
+            .write(&sidecar_path, content.as_bytes())
+            .await
+            .context("Failed to write sidecar file")?;
+
+        debug!("Wrote sidecar: {}", sidecar_path.display());
+        Ok(sidecar_path)
+    }
+
+    /// Generate and write the playlist file, in the configured format
+    pub async fn write_playlist_file(&self, playlist_name: &str, tracks: &[M3uTrack]) -> Result<PathBuf> {
         let playlist_path = self.create_playlist_folder(playlist_name).await?;
-        let m3u_path = playlist_path.join("playlist.m3u");
+        let file_path = playlist_path.join(self.playlist_format.file_name());
 
-        let content = crate::utils::generate_m3u(tracks);
+        let content = match self.playlist_format {
+            PlaylistFormat::M3u | PlaylistFormat::M3u8 => {
+                crate::utils::generate_m3u(tracks, self.m3u_encoding, self.extended_m3u)
+            }
+            PlaylistFormat::Pls => crate::utils::generate_pls(tracks, self.m3u_encoding),
+        };
 
-        fs::write(&m3u_path, content)
+        self
+            .write(&file_path, &content)
             .await
-            .context("Failed to write M3U file")?;
+            .context("Failed to write playlist file")?;
 
-        debug!("Wrote M3U: {} ({} tracks)", m3u_path.display(), tracks.len());
-        Ok(m3u_path)
+        debug!("Wrote playlist file: {} ({} tracks)", file_path.display(), tracks.len());
+        Ok(file_path)
+    }
+
+    /// Delete a playlist file written in `format`, if it's there
+    pub async fn delete_playlist_file(&self, playlist_name: &str, format: PlaylistFormat) -> Result<()> {
+        let file_path = self.playlist_dir(playlist_name).join(format.file_name());
+        if self.backend.exists(&file_path).await {
+            self.backend
+                .remove_file(&file_path)
+                .await
+                .context("Failed to delete playlist file")?;
+            debug!("Deleted playlist file: {}", file_path.display());
+        }
+        Ok(())
+    }
+
+    /// Delete one track of an album, along with its chapter sidecar
+    ///
+    /// Files that aren't there are skipped.
+    pub async fn delete_track(&self, path: &Path) -> Result<()> {
+        for file in [path.to_path_buf(), path.with_extension("cue")] {
+            if self.backend.exists(&file).await {
+                self.backend.remove_file(&file).await?;
+                debug!("Deleted track file: {}", file.display());
+            }
+        }
+        Ok(())
     }
 
     /// Delete an album folder and all its contents
-    pub async fn delete_album(&self, artist: &str, album: &str) -> Result<()> {
-        let artist_safe = sanitize_filename(artist);
-        let album_safe = sanitize_filename(album);
-        let album_path = self.artists_dir().join(&artist_safe).join(&album_safe);
+    ///
+    /// Parent folders left empty (the artist, genre, ...) are removed too,
+    /// up to the top-level `Artists` and `Genres` folders.
+    pub async fn delete_album(&self, album_path: &Path) -> Result<()> {
+        if album_path == self.root || !album_path.starts_with(&self.root) {
+            anyhow::bail!("Refusing to delete {}: not an album folder", album_path.display());
+        }
 
-        if album_path.exists() {
-            fs::remove_dir_all(&album_path)
+        if self.backend.exists(album_path).await {
+            self.backend
+                .remove_dir_all(album_path)
                 .await
                 .context("Failed to delete album directory")?;
             debug!("Deleted album folder: {}", album_path.display());
 
-            // Clean up empty artist folder if no albums remain
-            let artist_path = self.artists_dir().join(&artist_safe);
-            if let Ok(mut entries) = fs::read_dir(&artist_path).await
-                && entries.next_entry().await?.is_none()
-            {
-                let _ = fs::remove_dir(&artist_path).await;
-                debug!("Cleaned up empty artist folder: {}", artist_path.display());
+            let (artists, genres) = (self.artists_dir(), self.genres_dir());
+            for parent in album_path.ancestors().skip(1) {
+                if parent == self.root || parent == artists || parent == genres {
+                    break;
+                }
+                if !self.backend.remove_dir_if_empty(parent).await? {
+                    break;
+                }
+                debug!("Cleaned up empty folder: {}", parent.display());
             }
         }
 
@@ -182,11 +630,11 @@ impl DeviceStorage {
 
     /// Delete a playlist folder and all its contents
     pub async fn delete_playlist(&self, name: &str) -> Result<()> {
-        let name_safe = sanitize_filename(name);
-        let playlist_path = self.playlists_dir().join(&name_safe);
+        let playlist_path = self.playlist_dir(name);
 
-        if playlist_path.exists() {
-            fs::remove_dir_all(&playlist_path)
+        if self.backend.exists(&playlist_path).await {
+            self.backend
+                .remove_dir_all(&playlist_path)
                 .await
                 .context("Failed to delete playlist directory")?;
             debug!("Deleted playlist folder: {}", playlist_path.display());
@@ -194,4 +642,427 @@ impl DeviceStorage {
 
         Ok(())
     }
+
+    /// Walk `Artists/`, `Genres/` and `Playlists/`, matching their folders to the manifest
+    ///
+    /// Folders that hold a synced album or playlist (or lead to one, for
+    /// deeper layouts) are kept; anything else in these folders is an orphan.
+    /// What's inside a synced folder isn't looked at.
+    pub async fn walk_synced_tree(&self, manifest: &SyncManifest) -> Result<SyncedTree> {
+        let known: BTreeSet<PathBuf> = manifest
+            .synced_albums
+            .iter()
+            .map(|a| self.synced_album_dir(a))
+            .chain(manifest.synced_playlists.iter().map(|p| self.root.join(p.dir())))
+            .collect();
+
+        let mut tree = SyncedTree::default();
+        let mut pending = vec![self.artists_dir(), self.genres_dir(), self.root.join(PLAYLISTS_DIR)];
+        while let Some(dir) = pending.pop() {
+            if !self.backend.exists(&dir).await {
+                continue;
+            }
+
+            for path in self.backend.list_files(&dir).await? {
+                tree.orphans.push(Orphan { path, is_dir: false });
+            }
+            for path in self.backend.list_dirs(&dir).await? {
+                if known.contains(&path) {
+                    tree.synced.push(path);
+                } else if known.iter().any(|k| k.starts_with(&path)) {
+                    pending.push(path);
+                } else {
+                    tree.orphans.push(Orphan { path, is_dir: true });
+                }
+            }
+        }
+
+        tree.synced.sort();
+        tree.orphans.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(tree)
+    }
+
+    /// Delete a file or folder found by [`Self::walk_synced_tree`]
+    pub async fn delete_orphan(&self, orphan: &Orphan) -> Result<()> {
+        if orphan.path == self.root || !orphan.path.starts_with(&self.root) {
+            anyhow::bail!("Refusing to delete {}: not on the device", orphan.path.display());
+        }
+
+        if orphan.is_dir {
+            self.backend.remove_dir_all(&orphan.path).await?;
+        } else {
+            self.backend.remove_file(&orphan.path).await?;
+        }
+        debug!("Deleted orphan: {}", orphan.path.display());
+        Ok(())
+    }
+
+    /// Save the sync manifest to the device root
+    pub async fn save_manifest(&self, manifest: &SyncManifest) -> Result<()> {
+        let manifest_path = self.root.join(MANIFEST_FILE);
+        let content = manifest.to_json()?;
+
+        self
+            .write(&manifest_path, content.as_bytes())
+            .await
+            .context("Failed to write manifest file")?;
+
+        debug!("Saved manifest to {}", manifest_path.display());
+        Ok(())
+    }
+}
+
+/// Render a playlist track filename (without extension) from a format
+///
+/// Supports `{n}` (playlist position, zero-padded so names sort in playlist
+/// order), `{artist}` and `{title}`.
+pub fn playlist_track_filename(
+    format: &str,
+    position: usize,
+    total: usize,
+    artist: &str,
+    title: &str,
+) -> String {
+    let width = total.to_string().len().max(2);
+    format
+        .replace("{n}", &format!("{:0width$}", position, width = width))
+        .replace("{artist}", &sanitize_filename(artist))
+        .replace("{title}", &sanitize_filename(title))
+}
+
+/// Check a playlist track filename format before syncing with it
+pub fn validate_playlist_track_format(format: &str) -> Result<(), String> {
+    if !format.contains("{title}") {
+        return Err("format must contain {title}".to_string());
+    }
+    if format.contains(['/', '\\']) {
+        return Err("format must not contain path separators".to_string());
+    }
+    Ok(())
+}
+
+/// Pick the folder name used to group an album by genre
+///
+/// Servers may report several genres in one string (e.g. "Jazz; Fusion"), in
+/// which case the first one wins. Albums without a genre go to "Unknown Genre".
+pub fn genre_folder_name(genre: Option<&str>) -> String {
+    genre
+        .and_then(|g| g.split([';', ',', '/']).map(str::trim).find(|g| !g.is_empty()))
+        .unwrap_or("Unknown Genre")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::backend::MemoryBackend;
+    use crate::device::SyncedPlaylist;
+
+    #[test]
+    fn test_genre_folder_name_single() {
+        assert_eq!(genre_folder_name(Some("Jazz")), "Jazz");
+    }
+
+    #[test]
+    fn test_genre_folder_name_multiple() {
+        assert_eq!(genre_folder_name(Some("Jazz; Fusion")), "Jazz");
+        assert_eq!(genre_folder_name(Some(" ; Rock/Pop")), "Rock");
+    }
+
+    #[test]
+    fn test_genre_folder_name_missing() {
+        assert_eq!(genre_folder_name(None), "Unknown Genre");
+        assert_eq!(genre_folder_name(Some("  ")), "Unknown Genre");
+    }
+
+    fn folder<'a>(genre: Option<&'a str>, album: &'a str) -> AlbumFolder<'a> {
+        AlbumFolder {
+            genre,
+            artist: "Artist",
+            album,
+            year: Some(2001),
+        }
+    }
+
+    fn track(track: u32, title: &str) -> TrackName<'_> {
+        TrackName { disc: None, track, title }
+    }
+
+    #[test]
+    fn test_album_dir_layouts() {
+        let mut storage = DeviceStorage::new(PathBuf::from("/dev/null"));
+        assert_eq!(
+            storage.album_dir(&folder(None, "Album")),
+            PathBuf::from("/dev/null/Artists/Artist/Album")
+        );
+        assert_eq!(
+            storage.album_dir(&folder(Some("Jazz"), "Album")),
+            PathBuf::from("/dev/null/Genres/Jazz/Artist/Album")
+        );
+
+        storage.set_layout(LayoutTemplate::parse("Music/{artist} - {album} ({year})/{title}").unwrap());
+        assert_eq!(
+            storage.album_dir(&folder(Some("Jazz"), "Album")),
+            PathBuf::from("/dev/null/Music/Artist - Album (2001)")
+        );
+    }
+
+    #[test]
+    fn test_playlist_track_filename() {
+        assert_eq!(
+            playlist_track_filename(DEFAULT_PLAYLIST_TRACK_FORMAT, 3, 12, "AC/DC", "Song"),
+            "AC⧸DC - Song"
+        );
+        assert_eq!(
+            playlist_track_filename("{n} - {artist} - {title}", 3, 12, "Artist", "Song"),
+            "03 - Artist - Song"
+        );
+        assert_eq!(
+            playlist_track_filename("{n} {title}", 7, 150, "Artist", "Song"),
+            "007 Song"
+        );
+    }
+
+    #[test]
+    fn test_validate_playlist_track_format() {
+        assert!(validate_playlist_track_format("{n} - {artist} - {title}").is_ok());
+        assert!(validate_playlist_track_format("{n} - {artist}").is_err());
+        assert!(validate_playlist_track_format("{artist}/{title}").is_err());
+    }
+
+    fn memory_storage() -> (DeviceStorage, Arc<MemoryBackend>) {
+        let backend = Arc::new(MemoryBackend::default());
+        let storage = DeviceStorage::with_backend(PathBuf::from("/device"), backend.clone());
+        (storage, backend)
+    }
+
+    #[tokio::test]
+    async fn test_write_album_track_in_memory() {
+        let (storage, backend) = memory_storage();
+        let path = storage
+            .write_album_track(&folder(None, "Album"), &track(3, "Song"), "mp3", b"audio")
+            .await
+            .unwrap();
+
+        assert_eq!(path, PathBuf::from("/device/Artists/Artist/Album/03 - Song.mp3"));
+        assert_eq!(backend.file(&path).as_deref(), Some(&b"audio"[..]));
+
+        let time = Utc::now();
+        storage.set_file_mtime(&path, time).unwrap();
+        assert_eq!(backend.mtime(&path), Some(time));
+    }
+
+    #[tokio::test]
+    async fn test_walk_synced_tree_finds_orphans() {
+        let (storage, backend) = memory_storage();
+        storage.init().await.unwrap();
+        let synced_track = storage
+            .write_album_track(&folder(None, "Kept"), &track(1, "Song"), "mp3", b"audio")
+            .await
+            .unwrap();
+        storage
+            .write_album_track(&folder(None, "Stray"), &track(1, "Song"), "mp3", b"audio")
+            .await
+            .unwrap();
+        storage.write_file(Path::new("Artists/Other/Album/01 - Song.mp3"), b"audio").await.unwrap();
+        storage.write_file(Path::new("Artists/notes.txt"), b"notes").await.unwrap();
+        storage
+            .write_playlist_track("Mix", 1, 1, "Artist", "Song", "mp3", b"audio")
+            .await
+            .unwrap();
+        storage
+            .write_playlist_track("Old Mix", 1, 1, "Artist", "Song", "mp3", b"audio")
+            .await
+            .unwrap();
+
+        let mut manifest = SyncManifest::new("http://server");
+        manifest.add_album(SyncedAlbum {
+            id: "a1".to_string(),
+            artist: "Artist".to_string(),
+            album: "Kept".to_string(),
+            genre: None,
+            folder: Some("Artists/Artist/Kept".to_string()),
+            track_count: 1,
+            expected_tracks: Some(1),
+            server_tracks: None,
+            synced_tracks: vec!["s1".to_string()],
+            partial: false,
+            synced_at: Utc::now(),
+            tracks: Vec::new(),
+        });
+        manifest.add_playlist(SyncedPlaylist {
+            id: "p1".to_string(),
+            name: "Mix".to_string(),
+            track_count: 1,
+            expected_tracks: Some(1),
+            server_tracks: None,
+            changed: None,
+            song_ids: vec!["s1".to_string()],
+            synced_at: Utc::now(),
+            tracks: Vec::new(),
+            format: PlaylistFormat::default(),
+        });
+
+        let tree = storage.walk_synced_tree(&manifest).await.unwrap();
+        assert_eq!(
+            tree.synced,
+            [PathBuf::from("/device/Artists/Artist/Kept"), PathBuf::from("/device/Playlists/Mix")]
+        );
+        let orphan = |path: &str, is_dir| Orphan {
+            path: PathBuf::from(path),
+            is_dir,
+        };
+        assert_eq!(
+            tree.orphans,
+            [
+                orphan("/device/Artists/Artist/Stray", true),
+                orphan("/device/Artists/Other", true),
+                orphan("/device/Artists/notes.txt", false),
+                orphan("/device/Playlists/Old Mix", true),
+            ]
+        );
+
+        for orphan in &tree.orphans {
+            storage.delete_orphan(orphan).await.unwrap();
+        }
+        assert!(backend.file(&synced_track).is_some());
+        assert!(!backend.has_dir(Path::new("/device/Artists/Other")));
+        assert!(storage.walk_synced_tree(&manifest).await.unwrap().orphans.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_short_track_writes_are_retried_then_deleted() {
+        let (storage, backend) = memory_storage();
+        let album = folder(None, "Album");
+
+        // One short write is written again
+        backend.cut_short_writes(1);
+        let path = storage
+            .write_album_track(&album, &track(1, "Song"), "mp3", b"audio")
+            .await
+            .unwrap();
+        assert_eq!(backend.file(&path).as_deref(), Some(&b"audio"[..]));
+
+        // A track that keeps coming out short fails, and isn't left behind
+        backend.cut_short_writes(2);
+        assert!(storage
+            .write_album_track(&album, &track(2, "Other"), "mp3", b"audio")
+            .await
+            .is_err());
+        let failed = storage.album_track_path(&album, &track(2, "Other"), "mp3");
+        assert!(backend.file(&failed).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_modes_apply_to_written_files_and_created_dirs() {
+        let (mut storage, backend) = memory_storage();
+        storage.init().await.unwrap();
+        storage.set_modes(Some(0o664), Some(0o775));
+
+        let path = storage
+            .write_album_track(&folder(None, "Album"), &track(1, "Song"), "mp3", b"audio")
+            .await
+            .unwrap();
+
+        assert_eq!(backend.mode(&path), Some(0o664));
+        assert_eq!(backend.mode(Path::new("/device/Artists/Artist/Album")), Some(0o775));
+        assert_eq!(backend.mode(Path::new("/device/Artists/Artist")), Some(0o775));
+        // Directories that already existed are left alone
+        assert_eq!(backend.mode(Path::new("/device/Artists")), None);
+    }
+
+    #[tokio::test]
+    async fn test_list_album_tracks_skips_sidecars() {
+        let (storage, _backend) = memory_storage();
+        storage
+            .write_album_track(&folder(None, "Album"), &track(2, "B"), "flac", b"b")
+            .await
+            .unwrap();
+        let path = storage
+            .write_album_track(&folder(None, "Album"), &track(1, "A"), "mp3", b"a")
+            .await
+            .unwrap();
+        storage.write_sidecar(&path, "cue", "FILE").await.unwrap();
+        let album_path = storage.album_dir(&folder(None, "Album"));
+        storage.write_cover_art(&album_path, b"jpg", &["cover.jpg".to_string()]).await.unwrap();
+
+        let tracks = storage.list_album_tracks(&album_path).await.unwrap();
+        let names: Vec<_> = tracks.iter().map(|t| t.file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(names, ["01 - A.mp3", "02 - B.flac"]);
+    }
+
+    #[tokio::test]
+    async fn test_delete_album_cleans_up_empty_folders() {
+        let (storage, backend) = memory_storage();
+        storage.init().await.unwrap();
+        for album in ["One", "Two"] {
+            storage
+                .write_album_track(&folder(Some("Jazz"), album), &track(1, "A"), "mp3", b"a")
+                .await
+                .unwrap();
+        }
+
+        let one = storage.album_dir(&folder(Some("Jazz"), "One"));
+        storage.delete_album(&one).await.unwrap();
+        assert!(!backend.has_dir(&one));
+        assert!(backend.has_dir(Path::new("/device/Genres/Jazz/Artist")));
+
+        storage.delete_album(&storage.album_dir(&folder(Some("Jazz"), "Two"))).await.unwrap();
+        assert!(!backend.has_dir(Path::new("/device/Genres/Jazz/Artist")));
+        assert!(!backend.has_dir(Path::new("/device/Genres/Jazz")));
+        assert!(backend.has_dir(&storage.genres_dir()));
+
+        assert!(storage.delete_album(Path::new("/device")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_custom_layout_writes_disc_folders() {
+        let (mut storage, backend) = memory_storage();
+        storage.set_layout(LayoutTemplate::parse("{artist}/{album}/Disc {disc}/{track} {title}").unwrap());
+        storage.init().await.unwrap();
+        assert!(!backend.has_dir(&storage.artists_dir()));
+
+        let album = folder(None, "Album");
+        let path = storage
+            .write_album_track(&album, &TrackName { disc: Some(2), track: 1, title: "A" }, "mp3", b"a")
+            .await
+            .unwrap();
+        assert_eq!(path, PathBuf::from("/device/Artist/Album/Disc 2/01 A.mp3"));
+
+        storage.delete_album(&storage.album_dir(&album)).await.unwrap();
+        assert!(!backend.has_dir(Path::new("/device/Artist")));
+        assert!(backend.has_dir(Path::new("/device")));
+    }
+
+    #[test]
+    fn test_fat_limits_number_colliding_names() {
+        let mut storage = DeviceStorage::new(PathBuf::from("/device"));
+        storage.set_fs_type("vfat");
+        let long = "Long ".repeat(60);
+        let (first, second) = (format!("{}One", long), format!("{}Two", long));
+
+        let one = storage.album_dir(&folder(None, &first));
+        let two = storage.album_dir(&folder(None, &second));
+        assert_ne!(one, two);
+        assert!(two.to_str().unwrap().ends_with(" (2)"));
+        // The same album keeps its name
+        assert_eq!(storage.album_dir(&folder(None, &first)), one);
+
+        let path = storage.album_track_path(&folder(None, &second), &track(12, &long), "flac");
+        assert!(path.starts_with(&two));
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("12 - Long"));
+        assert!(name.ends_with(".flac"));
+    }
+
+    #[tokio::test]
+    async fn test_save_manifest_in_memory() {
+        let (storage, backend) = memory_storage();
+        storage.init().await.unwrap();
+        storage.save_manifest(&SyncManifest::new("http://server")).await.unwrap();
+
+        let saved = backend.file(&Path::new("/device").join(MANIFEST_FILE)).unwrap();
+        let manifest: SyncManifest = serde_json::from_slice(&saved).unwrap();
+        assert_eq!(manifest.subsonic_url, "http://server");
+    }
 }
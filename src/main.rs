@@ -5,6 +5,7 @@ use clap::Parser;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 mod cli;
+mod config;
 mod device;
 mod subsonic;
 mod sync;
@@ -19,7 +20,8 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
 
     // Initialize logging with TUI-aware conditional layer
-    // When TUI mode is active, stderr output is suppressed to prevent display corruption
+    // When TUI mode is active, stderr output is suppressed to prevent display corruption.
+    // Logs never go to stdout, which `browse --json` keeps for its output.
     let filter = if cli.verbose {
         "nutune=debug,reqwest=debug"
     } else {
@@ -29,40 +31,169 @@ async fn main() -> Result<()> {
     tracing_subscriber::registry()
         .with(tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| filter.into()))
         .with(ConditionalStderrLayer::new(
-            tracing_subscriber::fmt::layer().with_target(false)
+            tracing_subscriber::fmt::layer().with_target(false).with_writer(std::io::stderr)
         ))
         .init();
 
+    if let Some(path) = cli.credentials_file {
+        cli::AuthManager::use_credentials_file(path);
+    }
+    if cli.insecure {
+        cli::AuthManager::allow_insecure();
+    }
+    if let Some(seconds) = cli.timeout {
+        cli::AuthManager::use_timeout(std::time::Duration::from_secs(seconds));
+    }
+
     match cli.command {
         // Default: launch TUI browser when no command is specified
         None => {
-            cli::commands::browse(false, false).await?;
+            cli::commands::browse(false, false, false, 1).await?;
         }
         Some(Commands::Auth {
             url,
             username,
             password,
+            api_path,
+            auth_mode,
             force,
         }) => {
-            cli::commands::auth(url, username, password, force).await?;
+            cli::commands::auth(url, username, password, api_path, auth_mode, force).await?;
         }
-        Some(Commands::Devices { detailed }) => {
-            cli::commands::devices(detailed).await?;
+        Some(Commands::Devices { detailed, min_free }) => {
+            cli::commands::devices(detailed, min_free).await?;
         }
-        Some(Commands::Browse { artists, playlists }) => {
-            cli::commands::browse(artists, playlists).await?;
+        Some(Commands::Browse { json: true, albums, .. }) => {
+            cli::commands::browse_json(albums).await?;
+        }
+        Some(Commands::Browse {
+            artists,
+            playlists,
+            resume,
+            album_parallel,
+            ..
+        }) => {
+            cli::commands::browse(artists, playlists, resume, album_parallel).await?;
+        }
+        Some(Commands::Sync {
+            device,
+            parallel,
+            max_connections,
+            covers_only: true,
+            options,
+            ..
+        }) => {
+            let result = cli::commands::sync_covers(device, parallel, max_connections, options.into_options()).await?;
+            if result.albums_failed > 0 {
+                std::process::exit(cli::commands::EXIT_SYNC_FAILURES);
+            }
         }
         Some(Commands::Sync {
             device,
             dry_run,
             parallel,
-            no_playlists,
-            playlists_only,
+            max_connections,
+            selection,
+            options,
+            ..
+        }) => {
+            let result = cli::commands::sync_to_device(
+                device,
+                dry_run,
+                parallel,
+                max_connections,
+                selection,
+                options.into_options(),
+            )
+            .await?;
+            if result.has_failures() {
+                std::process::exit(cli::commands::EXIT_SYNC_FAILURES);
+            }
+        }
+        Some(Commands::Prepare {
+            out,
+            parallel,
+            max_connections,
+            selection,
+            options,
+        }) => {
+            let result = cli::commands::prepare(
+                out,
+                parallel,
+                max_connections,
+                selection,
+                options.into_options(),
+            )
+            .await?;
+            if result.has_failures() {
+                std::process::exit(cli::commands::EXIT_SYNC_FAILURES);
+            }
+        }
+        Some(Commands::Retry {
+            device,
+            parallel,
+            max_connections,
+            options,
+        }) => {
+            let result = cli::commands::retry(device, parallel, max_connections, options.into_options()).await?;
+            if result.has_failures() {
+                std::process::exit(cli::commands::EXIT_SYNC_FAILURES);
+            }
+        }
+        Some(Commands::Apply { device, from }) => {
+            cli::commands::apply(device, from).await?;
+        }
+        Some(Commands::Status {
+            device,
+            all,
+            summary,
+            limit,
         }) => {
-            cli::commands::sync_to_device(device, dry_run, parallel, no_playlists, playlists_only).await?;
+            cli::commands::status(device, all, summary, limit).await?;
+        }
+        Some(Commands::Verify {
+            device,
+            full,
+            probe,
+            repair,
+        }) => {
+            let report = cli::commands::verify(device, full, probe, repair).await?;
+            if report.has_issues() && !repair {
+                std::process::exit(cli::commands::EXIT_SYNC_FAILURES);
+            }
+        }
+        Some(Commands::Unsync {
+            device,
+            album_ids,
+            playlist_ids,
+            all,
+            dry_run,
+        }) => {
+            let failed = cli::commands::unsync(device, album_ids, playlist_ids, all, dry_run).await?;
+            if failed > 0 {
+                std::process::exit(cli::commands::EXIT_SYNC_FAILURES);
+            }
+        }
+        Some(Commands::Clean { device, yes, dry_run }) => {
+            let failed = cli::commands::clean(device, yes, dry_run).await?;
+            if failed > 0 {
+                std::process::exit(cli::commands::EXIT_SYNC_FAILURES);
+            }
+        }
+        Some(Commands::Manifest { device, json }) => {
+            cli::commands::manifest(device, json).await?;
+        }
+        Some(Commands::Search { query, limit }) => {
+            cli::commands::search(query, limit).await?;
+        }
+        Some(Commands::Whatsnew { device }) => {
+            cli::commands::whatsnew(device).await?;
+        }
+        Some(Commands::DefaultDevice { device, clear }) => {
+            cli::commands::default_device(device, clear)?;
         }
-        Some(Commands::Status { device }) => {
-            cli::commands::status(device).await?;
+        Some(Commands::ImportSelection { csv, merge }) => {
+            cli::commands::import_selection(csv, merge).await?;
         }
         Some(Commands::Completion { shell }) => {
             cli::commands::completion(shell);
@@ -1,7 +1,8 @@
 //! Subsonic API authentication
 //!
 //! Generates authentication tokens using the MD5(password + salt) scheme
-//! as specified by Subsonic API version 1.13.0+
+//! as specified by Subsonic API version 1.13.0+, or hex-encoded passwords
+//! for servers (and accounts, e.g. LDAP) that can't check tokens
 
 use md5::{Digest, Md5};
 use rand::Rng;
@@ -12,27 +13,63 @@ pub const API_VERSION: &str = "1.16.1";
 /// Client identifier
 pub const CLIENT_NAME: &str = "nutune";
 
+/// How the password is sent with each request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum AuthMode {
+    /// Use a token, falling back to the password if the server rejects tokens
+    #[default]
+    Auto,
+    /// MD5 token of the password and a random salt
+    Token,
+    /// The hex-encoded password, readable by anyone who can see the request
+    Plaintext,
+}
+
+impl AuthMode {
+    /// Name used on the command line and in stored credentials
+    pub fn name(self) -> &'static str {
+        match self {
+            AuthMode::Auto => "auto",
+            AuthMode::Token => "token",
+            AuthMode::Plaintext => "plaintext",
+        }
+    }
+
+    /// Parse a name written by `name`
+    pub fn from_name(name: &str) -> Option<Self> {
+        <Self as clap::ValueEnum>::from_str(name.trim(), true).ok()
+    }
+}
+
 /// Generate authentication parameters for Subsonic API requests
 ///
 /// Returns a vector of (key, value) pairs to include in the request URL:
 /// - u: username
-/// - t: token (MD5 hash of password + salt)
-/// - s: random salt
+/// - t: token (MD5 hash of password + salt), or p: `enc:` and the hex-encoded
+///   password with `AuthMode::Plaintext`
+/// - s: random salt (token auth only)
 /// - v: API version
 /// - c: client identifier
 /// - f: response format (json)
-pub fn generate_auth_params(username: &str, password: &str) -> Vec<(String, String)> {
-    let salt = generate_salt();
-    let token = generate_token(password, &salt);
-
-    vec![
-        ("u".to_string(), username.to_string()),
-        ("t".to_string(), token),
-        ("s".to_string(), salt),
+///
+/// `AuthMode::Auto` sends a token; falling back is up to the caller.
+pub fn generate_auth_params(username: &str, password: &str, mode: AuthMode) -> Vec<(String, String)> {
+    let mut params = vec![("u".to_string(), username.to_string())];
+
+    if mode == AuthMode::Plaintext {
+        params.push(("p".to_string(), format!("enc:{}", hex_encode(password))));
+    } else {
+        let salt = generate_salt();
+        params.push(("t".to_string(), generate_token(password, &salt)));
+        params.push(("s".to_string(), salt));
+    }
+
+    params.extend([
         ("v".to_string(), API_VERSION.to_string()),
         ("c".to_string(), CLIENT_NAME.to_string()),
         ("f".to_string(), "json".to_string()),
-    ]
+    ]);
+    params
 }
 
 /// Generate a random salt string (16 alphanumeric characters)
@@ -52,6 +89,11 @@ fn generate_token(password: &str, salt: &str) -> String {
     format!("{:x}", hasher.finalize())
 }
 
+/// Lowercase hex of the UTF-8 bytes of `s`
+fn hex_encode(s: &str) -> String {
+    s.bytes().map(|b| format!("{:02x}", b)).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -72,7 +114,7 @@ mod tests {
 
     #[test]
     fn test_auth_params_contains_all_fields() {
-        let params = generate_auth_params("user", "pass");
+        let params = generate_auth_params("user", "pass", AuthMode::Auto);
         let keys: Vec<_> = params.iter().map(|(k, _)| k.as_str()).collect();
         assert!(keys.contains(&"u"));
         assert!(keys.contains(&"t"));
@@ -81,4 +123,13 @@ mod tests {
         assert!(keys.contains(&"c"));
         assert!(keys.contains(&"f"));
     }
+
+    #[test]
+    fn test_plaintext_auth_params() {
+        let params = generate_auth_params("user", "pä$s", AuthMode::Plaintext);
+        assert!(params.contains(&("p".to_string(), "enc:70c3a42473".to_string())));
+        assert!(!params.iter().any(|(k, _)| k == "t" || k == "s"));
+        assert_eq!(AuthMode::from_name("Plaintext"), Some(AuthMode::Plaintext));
+        assert_eq!(AuthMode::from_name(AuthMode::Token.name()), Some(AuthMode::Token));
+    }
 }
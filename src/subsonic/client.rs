@@ -1,83 +1,488 @@
 //! Subsonic API HTTP client
 
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use reqwest::Client;
-use tracing::debug;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{debug, warn};
 
-use super::auth::generate_auth_params;
+use super::auth::{AuthMode, generate_auth_params};
 use super::models::*;
 
+/// Path the Subsonic REST API is served under, relative to the server URL
+pub const DEFAULT_API_PATH: &str = "/rest";
+
+/// Albums requested per getAlbumList2 page (the API allows up to 500)
+const ALBUM_LIST_PAGE_SIZE: u32 = 100;
+
+/// Results of each type requested per search3 call; some servers cap counts at 50
+const SEARCH_PAGE_SIZE: u32 = 50;
+
+/// Time allowed to establish a connection to the server
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time allowed for an API request by default, and for a download to go without
+/// receiving any data
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Attempts made to reach the server in `ping` before giving up
+const PING_ATTEMPTS: u32 = 3;
+
+/// Delay before the first ping retry, doubled for each further retry
+const PING_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Why a ping failed, used to tell the user what to check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PingFailure {
+    /// Nothing is listening at the host and port
+    ConnectionRefused,
+    /// The host name could not be resolved
+    Dns,
+    /// The TLS handshake failed (bad certificate, or not an HTTPS server)
+    Tls,
+    /// The server didn't respond in time
+    Timeout,
+    /// Any other connection problem
+    Connection,
+    /// HTTP 401/403, usually a proxy in front of the server
+    HttpUnauthorized(u16),
+    /// HTTP 404, usually a wrong URL or API path
+    HttpNotFound,
+    /// Any other 4xx response
+    HttpClient(u16),
+    /// A 5xx response
+    HttpServer(u16),
+    /// The response wasn't a Subsonic API response
+    NotSubsonic,
+    /// Subsonic error 40
+    WrongCredentials,
+    /// Subsonic error 41
+    TokenAuthUnsupported,
+    /// Subsonic errors 20 and 30
+    IncompatibleVersion,
+    /// Any other Subsonic error code
+    Subsonic(i32),
+}
+
+impl PingFailure {
+    /// Classify a failed HTTP request
+    fn from_request_error(err: &reqwest::Error) -> Self {
+        if err.is_timeout() {
+            return Self::Timeout;
+        }
+
+        let mut source: Option<&(dyn std::error::Error + 'static)> = Some(err);
+        while let Some(e) = source {
+            if let Some(io) = e.downcast_ref::<std::io::Error>()
+                && io.kind() == std::io::ErrorKind::ConnectionRefused
+            {
+                return Self::ConnectionRefused;
+            }
+            let message = e.to_string().to_lowercase();
+            if message.contains("dns error") || message.contains("failed to lookup address") {
+                return Self::Dns;
+            }
+            if message.contains("tls") || message.contains("ssl") || message.contains("certificate") {
+                return Self::Tls;
+            }
+            source = e.source();
+        }
+
+        Self::Connection
+    }
+
+    /// Classify a non-success HTTP status
+    fn from_status(status: u16) -> Self {
+        match status {
+            401 | 403 => Self::HttpUnauthorized(status),
+            404 => Self::HttpNotFound,
+            500.. => Self::HttpServer(status),
+            _ => Self::HttpClient(status),
+        }
+    }
+
+    /// Classify a Subsonic API error code
+    fn from_subsonic_code(code: i32) -> Self {
+        match code {
+            40 => Self::WrongCredentials,
+            41 => Self::TokenAuthUnsupported,
+            20 | 30 => Self::IncompatibleVersion,
+            _ => Self::Subsonic(code),
+        }
+    }
+
+    /// Whether retrying might succeed
+    fn is_transient(self) -> bool {
+        matches!(self, Self::Timeout | Self::Connection | Self::HttpServer(_))
+    }
+
+    /// What went wrong and what to check, for a server at `server`
+    pub fn message(self, server: &str) -> String {
+        match self {
+            Self::ConnectionRefused => format!(
+                "Connection refused by {}: check the URL and port, and that the server is running",
+                server
+            ),
+            Self::Dns => format!("Could not resolve the host of {}: check the URL for typos", server),
+            Self::Tls => format!(
+                "TLS handshake with {} failed: check the server's certificate, or use http:// if it doesn't serve HTTPS. \
+                 For a self-signed certificate you trust, pass --insecure; this turns off certificate checks, so \
+                 anyone between you and the server could read your password and music",
+                server
+            ),
+            Self::Timeout => format!("Timed out waiting for {}: check the URL and your network", server),
+            Self::Connection => format!("Could not connect to {}: check the URL and your network", server),
+            Self::HttpUnauthorized(status) => format!(
+                "Server returned {} at {}: a proxy in front of the server may be blocking the request",
+                status, server
+            ),
+            Self::HttpNotFound => format!(
+                "Server returned 404 at {}: check the URL, and --api-path if the API is served elsewhere",
+                server
+            ),
+            Self::HttpClient(status) => format!("Server returned {} at {}: check the URL", status, server),
+            Self::HttpServer(status) => format!(
+                "Server returned {} at {}: the server is having problems, try again later",
+                status, server
+            ),
+            Self::NotSubsonic => format!(
+                "{} did not answer like a Subsonic server: check the URL, and --api-path if the API is served elsewhere",
+                server
+            ),
+            Self::WrongCredentials => "Wrong username or password".to_string(),
+            Self::TokenAuthUnsupported => {
+                "The server does not support token authentication for this user (e.g. LDAP accounts): \
+                 use --auth-mode auto or plaintext"
+                    .to_string()
+            }
+            Self::IncompatibleVersion => {
+                "The server's Subsonic API version is incompatible with nutune".to_string()
+            }
+            Self::Subsonic(code) => format!("The server rejected the login (Subsonic error {})", code),
+        }
+    }
+}
+
+/// Normalize an API path prefix to a leading slash and no trailing slash
+///
+/// An empty path (or "/") means the API is served at the server root.
+pub fn normalize_api_path(path: &str) -> String {
+    let trimmed = path.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", trimmed)
+    }
+}
+
 /// HTTP client for Subsonic REST API
+///
+/// Clones share the underlying reqwest connection pool, so a run should build
+/// one client with `new` and clone it (or share it in an `Arc`) everywhere else.
 #[derive(Clone)]
 pub struct SubsonicClient {
     base_url: String,
+    api_path: String,
     username: String,
     password: String,
+    auth_mode: AuthMode,
     http_client: Client,
+    /// What `http_client` was built with
+    http_options: HttpOptions,
+    /// Bounds concurrent requests (and so open connections) when set
+    connection_limit: Option<Arc<Semaphore>>,
+}
+
+/// Settings the reqwest client is built with; changing one rebuilds it
+#[derive(Debug, Clone, Copy)]
+struct HttpOptions {
+    max_idle_per_host: Option<usize>,
+    /// Accept self-signed and otherwise invalid TLS certificates
+    accept_invalid_certs: bool,
+    /// Time allowed for a whole API request, and between reads of a download
+    timeout: Duration,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            max_idle_per_host: None,
+            accept_invalid_certs: false,
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+        }
+    }
 }
 
 impl SubsonicClient {
-    /// Create a new Subsonic client
+    /// Create a new Subsonic client with its own connection pool
     pub fn new(base_url: &str, username: &str, password: &str) -> Result<Self> {
         let base_url = base_url.trim_end_matches('/').to_string();
 
-        let http_client = Client::builder()
-            .user_agent("nutune/0.1.0")
-            .build()
-            .context("Failed to create HTTP client")?;
-
         Ok(Self {
             base_url,
+            api_path: DEFAULT_API_PATH.to_string(),
             username: username.to_string(),
             password: password.to_string(),
-            http_client,
+            auth_mode: AuthMode::default(),
+            http_client: Self::build_http_client(HttpOptions::default())?,
+            http_options: HttpOptions::default(),
+            connection_limit: None,
         })
     }
 
+    /// Accept invalid TLS certificates, e.g. a self-signed one on a LAN server
+    ///
+    /// This turns off certificate checks entirely, so the connection is no
+    /// longer protected against interception.
+    pub fn with_insecure(mut self, insecure: bool) -> Result<Self> {
+        if insecure != self.http_options.accept_invalid_certs {
+            self.http_options.accept_invalid_certs = insecure;
+            self.http_client = Self::build_http_client(self.http_options)?;
+        }
+        Ok(self)
+    }
+
+    /// Give up on API requests that take longer than `timeout` in total
+    ///
+    /// Downloads may take as long as they need, but fail (and are retried)
+    /// once no data has arrived for `timeout`, so a stalled connection
+    /// doesn't hold up the rest of the album.
+    pub fn with_timeout(mut self, timeout: Duration) -> Result<Self> {
+        if timeout != self.http_options.timeout {
+            self.http_options.timeout = timeout;
+            self.http_client = Self::build_http_client(self.http_options)?;
+        }
+        Ok(self)
+    }
+
+    /// Limit the number of simultaneous connections to the server
+    ///
+    /// Requests beyond the limit wait for a free slot, and at most `max` idle
+    /// connections are kept in the pool. This is separate from download
+    /// parallelism, which only controls how many tasks are in flight.
+    pub fn with_max_connections(mut self, max: usize) -> Result<Self> {
+        let max = max.max(1);
+        self.http_options.max_idle_per_host = Some(max);
+        self.http_client = Self::build_http_client(self.http_options)?;
+        self.connection_limit = Some(Arc::new(Semaphore::new(max)));
+        Ok(self)
+    }
+
+    fn build_http_client(options: HttpOptions) -> Result<Client> {
+        let mut builder = Client::builder()
+            .user_agent("nutune/0.1.0")
+            .connect_timeout(CONNECT_TIMEOUT)
+            .read_timeout(options.timeout)
+            .danger_accept_invalid_certs(options.accept_invalid_certs);
+        if let Some(max) = options.max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        builder.build().context("Failed to create HTTP client")
+    }
+
+    /// Start a GET request for an API call, limited to the total timeout
+    fn api_request(&self, url: &str) -> reqwest::RequestBuilder {
+        self.http_client.get(url).timeout(self.http_options.timeout)
+    }
+
+    /// Wait for a connection slot if connections are limited
+    ///
+    /// The permit must be held until the response body has been read.
+    async fn acquire_connection(&self) -> Option<OwnedSemaphorePermit> {
+        let limit = self.connection_limit.clone()?;
+        limit.acquire_owned().await.ok()
+    }
+
+    /// Serve API requests from a different path prefix (e.g. "/music/rest")
+    pub fn with_api_path(mut self, api_path: &str) -> Self {
+        self.api_path = normalize_api_path(api_path);
+        self
+    }
+
+    /// Send the password as `mode` says; `AuthMode::Auto` sends a token
+    ///
+    /// Only `negotiate_auth_mode` falls back from a token to the password.
+    pub fn with_auth_mode(mut self, mode: AuthMode) -> Self {
+        self.auth_mode = mode;
+        self
+    }
+
     /// Build URL with authentication parameters
     fn build_url(&self, endpoint: &str) -> String {
-        let params = generate_auth_params(&self.username, &self.password);
+        self.build_url_as(endpoint, self.auth_mode)
+    }
+
+    /// Build URL with authentication parameters for the given auth mode
+    fn build_url_as(&self, endpoint: &str, mode: AuthMode) -> String {
+        let params = generate_auth_params(&self.username, &self.password, mode);
         let query: String = params
             .iter()
             .map(|(k, v)| format!("{}={}", k, urlencoding::encode(v)))
             .collect::<Vec<_>>()
             .join("&");
 
-        format!("{}/rest/{}?{}", self.base_url, endpoint, query)
+        format!("{}{}/{}?{}", self.base_url, self.api_path, endpoint, query)
+    }
+
+    /// Test connection to Subsonic server, returning what it reports about itself
+    ///
+    /// Transient failures (connection problems, timeouts, 5xx responses) are
+    /// retried a few times. The returned error leads with a `PingFailure`
+    /// message saying what went wrong and what to check.
+    pub async fn ping(&self) -> Result<ServerInfo> {
+        self.ping_as(self.auth_mode).await.map_err(|failure| self.ping_error(failure))
     }
 
-    /// Test connection to Subsonic server
-    pub async fn ping(&self) -> Result<bool> {
-        let url = self.build_url("ping");
-        debug!("Pinging Subsonic server: {}", url);
+    /// Ping the server and return the auth mode it accepted
+    ///
+    /// With `AuthMode::Auto`, a token the server rejects with error 41 (token
+    /// auth unsupported, e.g. for LDAP accounts) is retried with the
+    /// hex-encoded password. Other modes are only checked.
+    pub async fn negotiate_auth_mode(&self) -> Result<AuthMode> {
+        let mode = match self.auth_mode {
+            AuthMode::Auto => AuthMode::Token,
+            mode => return self.ping().await.map(|_| mode),
+        };
 
-        let response: SubsonicResponse<()> = self
-            .http_client
-            .get(&url)
+        match self.ping_as(mode).await {
+            Ok(_) => Ok(mode),
+            Err((PingFailure::TokenAuthUnsupported, _)) => {
+                debug!("Server does not support token auth, retrying with the password");
+                self.ping_as(AuthMode::Plaintext)
+                    .await
+                    .map_err(|failure| self.ping_error(failure))?;
+                if self.base_url.starts_with("http://") {
+                    warn!("The server only accepts plaintext passwords, and is reached over unencrypted HTTP");
+                }
+                Ok(AuthMode::Plaintext)
+            }
+            Err(failure) => Err(self.ping_error(failure)),
+        }
+    }
+
+    /// Ping with the given auth mode, retrying transient failures
+    async fn ping_as(&self, mode: AuthMode) -> std::result::Result<ServerInfo, (PingFailure, anyhow::Error)> {
+        let url = self.build_url_as("ping", mode);
+        debug!("Pinging Subsonic server at {}{}", self.base_url, self.api_path);
+
+        let mut delay = PING_RETRY_DELAY;
+        let mut attempt = 1;
+        loop {
+            match self.try_ping(&url).await {
+                Err((failure, err)) if failure.is_transient() && attempt < PING_ATTEMPTS => {
+                    debug!("Ping attempt {} failed ({:#}), retrying in {:?}", attempt, err, delay);
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Lead a ping error with what went wrong and what to check
+    fn ping_error(&self, (failure, err): (PingFailure, anyhow::Error)) -> anyhow::Error {
+        let server = format!("{}{}", self.base_url, self.api_path);
+        err.context(failure.message(&server))
+    }
+
+    /// Make a single ping request, classifying any failure
+    async fn try_ping(&self, url: &str) -> std::result::Result<ServerInfo, (PingFailure, anyhow::Error)> {
+        let _permit = self.acquire_connection().await;
+        let response = self
+            .api_request(url)
             .send()
             .await
-            .context("Failed to connect to Subsonic server")?
-            .json()
+            .map_err(|e| (PingFailure::from_request_error(&e), e.without_url().into()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err((
+                PingFailure::from_status(status.as_u16()),
+                anyhow::anyhow!("HTTP {}", status),
+            ));
+        }
+
+        let body = response
+            .text()
             .await
-            .context("Failed to parse ping response")?;
+            .map_err(|e| (PingFailure::from_request_error(&e), e.without_url().into()))?;
+        let response: SubsonicResponse<ServerInfo> = serde_json::from_str(&body)
+            .map_err(|e| (PingFailure::NotSubsonic, anyhow::Error::new(e).context("Failed to parse ping response")))?;
 
         if response.subsonic_response.status == "ok" {
-            Ok(true)
+            Ok(response.subsonic_response.data.unwrap_or_default())
         } else if let Some(error) = response.subsonic_response.error {
-            anyhow::bail!("Subsonic error {}: {}", error.code, error.message)
+            Err((
+                PingFailure::from_subsonic_code(error.code),
+                anyhow::anyhow!("Subsonic error {}: {}", error.code, error.message),
+            ))
         } else {
-            anyhow::bail!("Unknown Subsonic error")
+            Err((PingFailure::NotSubsonic, anyhow::anyhow!("Unknown Subsonic error")))
         }
     }
 
+    /// List the OpenSubsonic extensions the server supports
+    pub async fn get_open_subsonic_extensions(&self) -> Result<Vec<OpenSubsonicExtension>> {
+        let url = self.build_url("getOpenSubsonicExtensions");
+        debug!("Fetching OpenSubsonic extensions");
+
+        let _permit = self.acquire_connection().await;
+        let response: SubsonicResponse<OpenSubsonicExtensionsData> = self
+            .api_request(&url)
+            .send()
+            .await
+            .context("Failed to fetch OpenSubsonic extensions")?
+            .error_for_status()
+            .context("Failed to fetch OpenSubsonic extensions")?
+            .json()
+            .await
+            .context("Failed to parse OpenSubsonic extensions response")?;
+
+        self.check_response(&response)?;
+
+        Ok(response
+            .subsonic_response
+            .data
+            .map(|d| d.open_subsonic_extensions)
+            .unwrap_or_default())
+    }
+
+    /// Detect the server's type, version and OpenSubsonic extensions
+    ///
+    /// Servers that aren't OpenSubsonic, or whose extensions endpoint
+    /// fails, are reported with no extensions rather than as an error.
+    pub async fn server_capabilities(&self) -> Result<ServerCapabilities> {
+        let info = self.ping().await?;
+
+        let extensions = if info.open_subsonic {
+            self.get_open_subsonic_extensions().await.unwrap_or_else(|e| {
+                debug!("Could not fetch OpenSubsonic extensions: {:#}", e);
+                Vec::new()
+            })
+        } else {
+            Vec::new()
+        };
+
+        Ok(ServerCapabilities {
+            api_version: info.version,
+            server_type: info.server_type,
+            server_version: info.server_version,
+            open_subsonic: info.open_subsonic,
+            extensions,
+        })
+    }
+
     /// Get all artists in the library
     pub async fn get_artists(&self) -> Result<Vec<Artist>> {
         let url = self.build_url("getArtists");
         debug!("Fetching artists from: {}", url);
 
+        let _permit = self.acquire_connection().await;
         let response: SubsonicResponse<ArtistsData> = self
-            .http_client
-            .get(&url)
+            .api_request(&url)
             .send()
             .await
             .context("Failed to fetch artists")?
@@ -108,9 +513,9 @@ impl SubsonicClient {
         let url = format!("{}&id={}", self.build_url("getArtist"), id);
         debug!("Fetching artist {}: {}", id, url);
 
+        let _permit = self.acquire_connection().await;
         let response: SubsonicResponse<ArtistData> = self
-            .http_client
-            .get(&url)
+            .api_request(&url)
             .send()
             .await
             .context("Failed to fetch artist")?
@@ -132,9 +537,9 @@ impl SubsonicClient {
         let url = format!("{}&id={}", self.build_url("getAlbum"), id);
         debug!("Fetching album {}: {}", id, url);
 
+        let _permit = self.acquire_connection().await;
         let response: SubsonicResponse<AlbumData> = self
-            .http_client
-            .get(&url)
+            .api_request(&url)
             .send()
             .await
             .context("Failed to fetch album")?
@@ -151,14 +556,138 @@ impl SubsonicClient {
             .ok_or_else(|| anyhow::anyhow!("Album not found"))
     }
 
+    /// Get album notes and MusicBrainz ID
+    pub async fn get_album_info2(&self, id: &str) -> Result<AlbumInfo> {
+        let url = format!("{}&id={}", self.build_url("getAlbumInfo2"), id);
+        debug!("Fetching album info {}: {}", id, url);
+
+        let _permit = self.acquire_connection().await;
+        let response: SubsonicResponse<AlbumInfoData> = self
+            .api_request(&url)
+            .send()
+            .await
+            .context("Failed to fetch album info")?
+            .json()
+            .await
+            .context("Failed to parse album info response")?;
+
+        self.check_response(&response)?;
+
+        Ok(response
+            .subsonic_response
+            .data
+            .map(|d| d.album_info)
+            .unwrap_or_default())
+    }
+
+    /// Get the user's bookmarks (saved positions in long tracks)
+    pub async fn get_bookmarks(&self) -> Result<Vec<Bookmark>> {
+        let url = self.build_url("getBookmarks");
+        debug!("Fetching bookmarks from: {}", url);
+
+        let _permit = self.acquire_connection().await;
+        let response: SubsonicResponse<BookmarksData> = self
+            .api_request(&url)
+            .send()
+            .await
+            .context("Failed to fetch bookmarks")?
+            .json()
+            .await
+            .context("Failed to parse bookmarks response")?;
+
+        self.check_response(&response)?;
+
+        let bookmarks = response
+            .subsonic_response
+            .data
+            .map(|d| d.bookmarks.bookmark)
+            .unwrap_or_default();
+
+        debug!("Found {} bookmarks", bookmarks.len());
+        Ok(bookmarks)
+    }
+
+    /// Get the top-level folders of a file-based library (getIndexes)
+    pub async fn get_indexes(&self) -> Result<Vec<IndexEntry>> {
+        let url = self.build_url("getIndexes");
+        debug!("Fetching folder indexes from: {}", url);
+
+        let _permit = self.acquire_connection().await;
+        let response: SubsonicResponse<IndexesData> = self
+            .api_request(&url)
+            .send()
+            .await
+            .context("Failed to fetch folder indexes")?
+            .json()
+            .await
+            .context("Failed to parse folder indexes response")?;
+
+        self.check_response(&response)?;
+
+        let folders: Vec<IndexEntry> = response
+            .subsonic_response
+            .data
+            .map(|d| d.indexes.index.into_iter().flat_map(|idx| idx.artist).collect())
+            .unwrap_or_default();
+
+        debug!("Found {} top-level folders", folders.len());
+        Ok(folders)
+    }
+
+    /// Get a folder's subfolders and songs (getMusicDirectory)
+    pub async fn get_music_directory(&self, id: &str) -> Result<MusicDirectory> {
+        let url = format!("{}&id={}", self.build_url("getMusicDirectory"), id);
+        debug!("Fetching music directory {}: {}", id, url);
+
+        let _permit = self.acquire_connection().await;
+        let response: SubsonicResponse<MusicDirectoryData> = self
+            .api_request(&url)
+            .send()
+            .await
+            .context("Failed to fetch music directory")?
+            .json()
+            .await
+            .context("Failed to parse music directory response")?;
+
+        self.check_response(&response)?;
+
+        response
+            .subsonic_response
+            .data
+            .map(|d| d.directory)
+            .ok_or_else(|| anyhow::anyhow!("Directory not found: {}", id))
+    }
+
+    /// Find the folders at or below `id` that directly contain songs
+    ///
+    /// Each is returned as an album so a whole folder tree can be selected
+    /// and synced one album folder at a time.
+    pub async fn get_folder_albums(&self, id: &str, parent_name: Option<&str>) -> Result<Vec<Album>> {
+        let mut albums = Vec::new();
+        let mut pending = vec![(id.to_string(), parent_name.map(str::to_string))];
+
+        while let Some((dir_id, parent_name)) = pending.pop() {
+            let dir = self.get_music_directory(&dir_id).await?;
+            albums.extend(dir.as_album(parent_name.as_deref()));
+            pending.extend(
+                dir.child
+                    .iter()
+                    .filter(|c| c.is_dir)
+                    .map(|c| (c.song.id.clone(), Some(dir.name.clone()))),
+            );
+        }
+
+        Ok(albums)
+    }
+
     /// Get all playlists
     pub async fn get_playlists(&self) -> Result<Vec<Playlist>> {
         let url = self.build_url("getPlaylists");
         debug!("Fetching playlists from: {}", url);
 
+        let _permit = self.acquire_connection().await;
         let response: SubsonicResponse<PlaylistsData> = self
-            .http_client
-            .get(&url)
+            .api_request(&url)
             .send()
             .await
             .context("Failed to fetch playlists")?
@@ -178,14 +707,41 @@ impl SubsonicClient {
         Ok(playlists)
     }
 
+    /// Get the artists, albums and songs the user has starred
+    pub async fn get_starred2(&self) -> Result<Starred> {
+        let url = self.build_url("getStarred2");
+        debug!("Fetching starred items");
+
+        let _permit = self.acquire_connection().await;
+        let response: SubsonicResponse<StarredData> = self
+            .api_request(&url)
+            .send()
+            .await
+            .context("Failed to fetch starred items")?
+            .json()
+            .await
+            .context("Failed to parse starred response")?;
+
+        self.check_response(&response)?;
+
+        let starred = response.subsonic_response.data.map(|d| d.starred).unwrap_or_default();
+        debug!(
+            "Found {} starred artists, {} albums and {} songs",
+            starred.artist.len(),
+            starred.album.len(),
+            starred.song.len()
+        );
+        Ok(starred)
+    }
+
     /// Get playlist details with songs
     pub async fn get_playlist(&self, id: &str) -> Result<PlaylistWithSongs> {
         let url = format!("{}&id={}", self.build_url("getPlaylist"), id);
         debug!("Fetching playlist {}: {}", id, url);
 
+        let _permit = self.acquire_connection().await;
         let response: SubsonicResponse<PlaylistData> = self
-            .http_client
-            .get(&url)
+            .api_request(&url)
             .send()
             .await
             .context("Failed to fetch playlist")?
@@ -202,6 +758,188 @@ impl SubsonicClient {
             .ok_or_else(|| anyhow::anyhow!("Playlist not found"))
     }
 
+    /// Search for artists, albums and songs matching a query (search3)
+    ///
+    /// Returns up to `count` results of each type, skipping the first `offset`.
+    pub async fn search3(&self, query: &str, count: u32, offset: u32) -> Result<SearchResult> {
+        let url = format!(
+            "{}&query={}&artistCount={}&artistOffset={}&albumCount={}&albumOffset={}&songCount={}&songOffset={}",
+            self.build_url("search3"),
+            urlencoding::encode(query),
+            count,
+            offset,
+            count,
+            offset,
+            count,
+            offset
+        );
+        debug!("Searching for '{}': {}", query, url);
+
+        let _permit = self.acquire_connection().await;
+        let response: SubsonicResponse<SearchData> = self
+            .api_request(&url)
+            .send()
+            .await
+            .context("Failed to search")?
+            .json()
+            .await
+            .context("Failed to parse search response")?;
+
+        self.check_response(&response)?;
+
+        Ok(response
+            .subsonic_response
+            .data
+            .map(|d| d.search_result)
+            .unwrap_or_default())
+    }
+
+    /// Search for up to `limit` artists, albums and songs each, paging through search3
+    pub async fn search(&self, query: &str, limit: usize) -> Result<SearchResult> {
+        let mut result = SearchResult::default();
+        let mut offset = 0;
+
+        loop {
+            let page = self.search3(query, SEARCH_PAGE_SIZE, offset).await?;
+            if !append_search_page(&mut result, page, SEARCH_PAGE_SIZE as usize, limit) {
+                return Ok(result);
+            }
+            offset += SEARCH_PAGE_SIZE;
+        }
+    }
+
+    /// Get a page of albums ordered by `list_type` (getAlbumList2)
+    ///
+    /// `list_type` is one of the Subsonic list types, e.g. `newest`,
+    /// `alphabeticalByName` or `recent`.
+    pub async fn get_album_list2(&self, list_type: &str, size: u32, offset: u32) -> Result<Vec<Album>> {
+        let url = format!(
+            "{}&type={}&size={}&offset={}",
+            self.build_url("getAlbumList2"),
+            list_type,
+            size,
+            offset
+        );
+        debug!("Fetching {} albums (offset {}): {}", list_type, offset, url);
+        self.fetch_album_list(&url).await
+    }
+
+    /// Fetch and parse one getAlbumList2 page
+    async fn fetch_album_list(&self, url: &str) -> Result<Vec<Album>> {
+        let _permit = self.acquire_connection().await;
+        let response: SubsonicResponse<AlbumList2Data> = self
+            .api_request(url)
+            .send()
+            .await
+            .context("Failed to fetch album list")?
+            .json()
+            .await
+            .context("Failed to parse album list response")?;
+
+        self.check_response(&response)?;
+
+        Ok(response
+            .subsonic_response
+            .data
+            .map(|d| d.album_list.album)
+            .unwrap_or_default())
+    }
+
+    /// Get every album in the library, sorted by name
+    pub async fn get_all_albums(&self) -> Result<Vec<Album>> {
+        let mut albums = Vec::new();
+        let mut offset = 0;
+
+        loop {
+            let page = self
+                .get_album_list2("alphabeticalByName", ALBUM_LIST_PAGE_SIZE, offset)
+                .await?;
+            let page_len = page.len() as u32;
+            albums.extend(page);
+
+            if page_len < ALBUM_LIST_PAGE_SIZE {
+                break;
+            }
+            offset += page_len;
+        }
+
+        debug!("Found {} albums in the library", albums.len());
+        Ok(albums)
+    }
+
+    /// List the genres in the library
+    pub async fn get_genres(&self) -> Result<Vec<Genre>> {
+        let url = self.build_url("getGenres");
+        debug!("Fetching genres from: {}", url);
+
+        let _permit = self.acquire_connection().await;
+        let response: SubsonicResponse<GenresData> = self
+            .api_request(&url)
+            .send()
+            .await
+            .context("Failed to fetch genres")?
+            .json()
+            .await
+            .context("Failed to parse genres response")?;
+
+        self.check_response(&response)?;
+
+        let genres = response.subsonic_response.data.map(|d| d.genres.genre).unwrap_or_default();
+        debug!("Found {} genres", genres.len());
+        Ok(genres)
+    }
+
+    /// Get every album tagged with `genre` (getAlbumList2 `byGenre`)
+    pub async fn get_albums_by_genre(&self, genre: &str) -> Result<Vec<Album>> {
+        let mut albums = Vec::new();
+        let mut offset = 0;
+
+        loop {
+            let url = format!(
+                "{}&type=byGenre&genre={}&size={}&offset={}",
+                self.build_url("getAlbumList2"),
+                urlencoding::encode(genre),
+                ALBUM_LIST_PAGE_SIZE,
+                offset
+            );
+            debug!("Fetching {} albums (offset {}): {}", genre, offset, url);
+            let page = self.fetch_album_list(&url).await?;
+            let page_len = page.len() as u32;
+            albums.extend(page);
+
+            if page_len < ALBUM_LIST_PAGE_SIZE {
+                break;
+            }
+            offset += page_len;
+        }
+
+        debug!("Found {} albums in genre {}", albums.len(), genre);
+        Ok(albums)
+    }
+
+    /// Get albums added to the server after `cutoff`, newest first
+    ///
+    /// Pages through the `newest` album list until it reaches albums older
+    /// than the cutoff. Albums without a created date are skipped.
+    pub async fn get_albums_added_since(&self, cutoff: DateTime<Utc>) -> Result<Vec<Album>> {
+        let mut albums = Vec::new();
+        let mut offset = 0;
+
+        loop {
+            let page = self.get_album_list2("newest", ALBUM_LIST_PAGE_SIZE, offset).await?;
+            let page_len = page.len() as u32;
+            let reached_cutoff = take_added_since(page, cutoff, &mut albums);
+
+            if reached_cutoff || page_len < ALBUM_LIST_PAGE_SIZE {
+                break;
+            }
+            offset += page_len;
+        }
+
+        debug!("Found {} albums added since {}", albums.len(), cutoff);
+        Ok(albums)
+    }
+
     /// Get download URL for a song (returns URL, doesn't download)
     pub fn get_download_url(&self, id: &str) -> String {
         format!("{}&id={}", self.build_url("download"), id)
@@ -211,12 +949,37 @@ impl SubsonicClient {
     pub async fn download(&self, id: &str) -> Result<bytes::Bytes> {
         let url = self.get_download_url(id);
         debug!("Downloading song {}: {}", id, url);
+        self.fetch_song(&url).await
+    }
 
+    /// Get stream URL for a song, transcoded to `format` and/or capped at `max_bit_rate` kbps
+    pub fn get_stream_url(&self, id: &str, format: Option<&str>, max_bit_rate: Option<u32>) -> String {
+        let mut url = format!("{}&id={}", self.build_url("stream"), id);
+        if let Some(format) = format {
+            url = format!("{}&format={}", url, urlencoding::encode(format));
+        }
+        if let Some(max_bit_rate) = max_bit_rate {
+            url = format!("{}&maxBitRate={}", url, max_bit_rate);
+        }
+        url
+    }
+
+    /// Stream a song as bytes, letting the server transcode it
+    pub async fn stream(&self, id: &str, format: Option<&str>, max_bit_rate: Option<u32>) -> Result<bytes::Bytes> {
+        let url = self.get_stream_url(id, format, max_bit_rate);
+        debug!("Streaming song {}: {}", id, url);
+        self.fetch_song(&url).await
+    }
+
+    /// Fetch song audio from a `download` or `stream` URL
+    async fn fetch_song(&self, url: &str) -> Result<bytes::Bytes> {
+        let _permit = self.acquire_connection().await;
         let response = self
             .http_client
-            .get(&url)
+            .get(url)
             .send()
             .await
+            .and_then(reqwest::Response::error_for_status)
             .context("Failed to download song")?;
 
         // Check if it's an error response (JSON)
@@ -256,9 +1019,9 @@ impl SubsonicClient {
         let url = self.get_cover_art_url(id, size);
         debug!("Fetching cover art {}: {}", id, url);
 
+        let _permit = self.acquire_connection().await;
         let response = self
-            .http_client
-            .get(&url)
+            .api_request(&url)
             .send()
             .await
             .context("Failed to fetch cover art")?;
@@ -277,10 +1040,329 @@ impl SubsonicClient {
     fn check_response<T>(&self, response: &SubsonicResponse<T>) -> Result<()> {
         if response.subsonic_response.status != "ok" {
             if let Some(error) = &response.subsonic_response.error {
-                anyhow::bail!("Subsonic error {}: {}", error.code, error.message);
+                return Err(error.clone().into());
             }
             anyhow::bail!("Unknown Subsonic error");
         }
         Ok(())
     }
 }
+
+/// Move albums added after `cutoff` from a `newest` page into `albums`
+///
+/// Returns whether the page reached albums older than the cutoff, meaning
+/// there's no need to fetch further pages.
+fn take_added_since(page: Vec<Album>, cutoff: DateTime<Utc>, albums: &mut Vec<Album>) -> bool {
+    for album in page {
+        match album.created_at() {
+            Some(created) if created > cutoff => albums.push(album),
+            Some(_) => return true,
+            None => {}
+        }
+    }
+    false
+}
+
+/// Add a search3 page to `result`, keeping at most `limit` of each type
+///
+/// Returns whether another page is needed: some type filled its page and
+/// is still under the limit.
+fn append_search_page(result: &mut SearchResult, page: SearchResult, page_size: usize, limit: usize) -> bool {
+    fn append<T>(all: &mut Vec<T>, page: Vec<T>, page_size: usize, limit: usize) -> bool {
+        let full = page.len() >= page_size;
+        all.extend(page);
+        all.truncate(limit);
+        full && all.len() < limit
+    }
+
+    let more_artists = append(&mut result.artist, page.artist, page_size, limit);
+    let more_albums = append(&mut result.album, page.album, page_size, limit);
+    let more_songs = append(&mut result.song, page.song, page_size, limit);
+    more_artists || more_albums || more_songs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subsonic::mock::{MockReply, mock_server, mock_server_peak, mock_server_with};
+
+    fn album(id: &str, created: Option<&str>) -> Album {
+        Album {
+            id: id.to_string(),
+            name: id.to_string(),
+            artist: None,
+            artist_id: None,
+            cover_art: None,
+            cover_art_ids: Vec::new(),
+            song_count: None,
+            duration: None,
+            year: None,
+            genre: None,
+            created: created.map(str::to_string),
+            source: AlbumSource::Id3,
+        }
+    }
+
+    #[test]
+    fn test_search_pages_until_each_type_is_done() {
+        let mut result = SearchResult::default();
+        let page = |albums: usize| SearchResult {
+            album: (0..albums).map(|i| album(&i.to_string(), None)).collect(),
+            ..Default::default()
+        };
+
+        assert!(append_search_page(&mut result, page(2), 2, 5));
+        assert!(append_search_page(&mut result, page(2), 2, 5));
+        // The limit is reached partway through a full page
+        assert!(!append_search_page(&mut result, page(2), 2, 5));
+        assert_eq!(result.album.len(), 5);
+
+        // A short page means the server has nothing more
+        let mut result = SearchResult::default();
+        assert!(!append_search_page(&mut result, page(1), 2, 5));
+        assert_eq!(result.album.len(), 1);
+    }
+
+    #[test]
+    fn test_take_added_since_stops_at_cutoff() {
+        let cutoff = "2024-01-01T00:00:00Z".parse().unwrap();
+        let mut albums = Vec::new();
+
+        let page = vec![
+            album("new", Some("2024-02-01T00:00:00Z")),
+            album("undated", None),
+            album("old", Some("2023-12-01T00:00:00Z")),
+            album("older", Some("2023-11-01T00:00:00Z")),
+        ];
+        assert!(take_added_since(page, cutoff, &mut albums));
+        assert_eq!(albums.iter().map(|a| a.id.as_str()).collect::<Vec<_>>(), ["new"]);
+
+        let page = vec![album("newer", Some("2024-03-01T00:00:00Z"))];
+        assert!(!take_added_since(page, cutoff, &mut albums));
+        assert_eq!(albums.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_get_all_albums_pages_through_the_library() {
+        let (url, requests) = mock_server_with(|request| {
+            let line = request.lines().next().unwrap_or("");
+            let (start, count) = if line.contains("offset=0 ") {
+                (0, ALBUM_LIST_PAGE_SIZE)
+            } else {
+                (ALBUM_LIST_PAGE_SIZE, 3)
+            };
+            let albums: Vec<_> = (start..start + count)
+                .map(|i| serde_json::json!({ "id": i.to_string(), "name": format!("Album {}", i) }))
+                .collect();
+            MockReply::ok(serde_json::json!({ "albumList2": { "album": albums } }))
+        })
+        .await;
+
+        let client = SubsonicClient::new(&url, "user", "pass").unwrap();
+        let albums = client.get_all_albums().await.unwrap();
+        assert_eq!(albums.len(), ALBUM_LIST_PAGE_SIZE as usize + 3);
+        assert_eq!(albums.last().unwrap().id, (ALBUM_LIST_PAGE_SIZE + 2).to_string());
+        // The short second page ends it
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_normalize_api_path() {
+        assert_eq!(normalize_api_path("/rest"), "/rest");
+        assert_eq!(normalize_api_path("music/rest/"), "/music/rest");
+        assert_eq!(normalize_api_path("/"), "");
+        assert_eq!(normalize_api_path(""), "");
+    }
+
+    #[test]
+    fn test_build_url_uses_api_path() {
+        let client = SubsonicClient::new("https://example.com/", "user", "pass").unwrap();
+        assert!(client.build_url("ping").starts_with("https://example.com/rest/ping?"));
+
+        let client = client.with_api_path("/music/rest/");
+        assert!(client.build_url("ping").starts_with("https://example.com/music/rest/ping?"));
+    }
+
+    #[test]
+    fn test_stream_url_only_sends_given_options() {
+        let client = SubsonicClient::new("https://example.com", "user", "pass").unwrap();
+
+        let url = client.get_stream_url("s1", Some("mp3"), Some(192));
+        assert!(url.starts_with("https://example.com/rest/stream?"));
+        assert!(url.ends_with("&id=s1&format=mp3&maxBitRate=192"));
+
+        assert!(client.get_stream_url("s1", None, None).ends_with("&id=s1"));
+    }
+
+    async fn ping_failure(url: &str) -> String {
+        let client = SubsonicClient::new(url, "user", "pass").unwrap();
+        format!("{:#}", client.ping().await.unwrap_err())
+    }
+
+    #[tokio::test]
+    async fn test_ping_ok() {
+        let (url, _) = mock_server("200 OK", r#"{"subsonic-response":{"status":"ok","version":"1.16.1"}}"#).await;
+        let client = SubsonicClient::new(&url, "user", "pass").unwrap();
+        let info = client.ping().await.unwrap();
+        assert_eq!(info.version.as_deref(), Some("1.16.1"));
+        assert!(!info.open_subsonic);
+    }
+
+    #[tokio::test]
+    async fn test_server_capabilities_reads_extensions() {
+        let (url, _) = mock_server(
+            "200 OK",
+            r#"{"subsonic-response":{"status":"ok","version":"1.16.1","type":"navidrome","serverVersion":"0.53.3","openSubsonic":true,"openSubsonicExtensions":[{"name":"songLyrics","versions":[1]}]}}"#,
+        )
+        .await;
+        let client = SubsonicClient::new(&url, "user", "pass").unwrap();
+        let caps = client.server_capabilities().await.unwrap();
+
+        assert_eq!(caps.server_type.as_deref(), Some("navidrome"));
+        assert_eq!(caps.server_version.as_deref(), Some("0.53.3"));
+        assert!(caps.open_subsonic);
+        assert_eq!(caps.extensions, [OpenSubsonicExtension { name: "songLyrics".to_string(), versions: vec![1] }]);
+    }
+
+    #[tokio::test]
+    async fn test_server_capabilities_with_no_extensions() {
+        let (url, _) = mock_server(
+            "200 OK",
+            r#"{"subsonic-response":{"status":"ok","version":"1.16.1","openSubsonic":true}}"#,
+        )
+        .await;
+        let client = SubsonicClient::new(&url, "user", "pass").unwrap();
+        let caps = client.server_capabilities().await.unwrap();
+        assert!(caps.open_subsonic);
+        assert!(caps.extensions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_ping_connection_refused() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let message = ping_failure(&format!("http://{}", addr)).await;
+        assert!(message.starts_with("Connection refused"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_ping_dns_failure() {
+        let message = ping_failure("http://nutune-ping-test.invalid").await;
+        assert!(message.starts_with("Could not resolve"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_ping_tls_failure() {
+        let (url, _) = mock_server("200 OK", "{}").await;
+        let message = ping_failure(&url.replace("http://", "https://")).await;
+        assert!(message.starts_with("TLS handshake"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_ping_http_errors() {
+        let (url, _) = mock_server("404 Not Found", "").await;
+        assert!(ping_failure(&url).await.contains("returned 404"));
+
+        let (url, requests) = mock_server("500 Internal Server Error", "").await;
+        let message = ping_failure(&url).await;
+        assert!(message.contains("returned 500"), "{}", message);
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), PING_ATTEMPTS as usize);
+    }
+
+    #[tokio::test]
+    async fn test_ping_subsonic_errors() {
+        let (url, requests) = mock_server(
+            "200 OK",
+            r#"{"subsonic-response":{"status":"failed","version":"1.16.1","error":{"code":40,"message":"Wrong username or password"}}}"#,
+        )
+        .await;
+        let message = ping_failure(&url).await;
+        assert!(message.starts_with("Wrong username or password"), "{}", message);
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        let (url, _) = mock_server("200 OK", "<html>Welcome</html>").await;
+        assert!(ping_failure(&url).await.contains("did not answer like a Subsonic server"));
+    }
+
+    #[tokio::test]
+    async fn test_stalled_download_times_out() {
+        // Send the headers, then stall partway through the body
+        let (url, _) = mock_server_with(|_| MockReply {
+            stall_after: Some(7),
+            ..MockReply::new("200 OK", vec![0u8; 1000])
+        })
+        .await;
+
+        let client = SubsonicClient::new(&url, "user", "pass")
+            .unwrap()
+            .with_timeout(Duration::from_millis(200))
+            .unwrap();
+        let started = std::time::Instant::now();
+        assert!(client.download("song").await.is_err());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_slow_download_outlasts_timeout() {
+        // Data keeps arriving, so the download finishes after the timeout has passed
+        let (url, _) = mock_server_with(|_| MockReply {
+            pace: Some(Duration::from_millis(50)),
+            ..MockReply::new("200 OK", vec![0u8; 640])
+        })
+        .await;
+
+        let client = SubsonicClient::new(&url, "user", "pass")
+            .unwrap()
+            .with_timeout(Duration::from_millis(200))
+            .unwrap();
+        let started = std::time::Instant::now();
+        assert_eq!(client.download("song").await.unwrap().len(), 640);
+        assert!(started.elapsed() > Duration::from_millis(200));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_max_connections_caps_requests_in_flight() {
+        // Each download takes ~100ms, so unlimited requests would all overlap
+        let (url, peak) = mock_server_peak(MockReply {
+            pace: Some(Duration::from_millis(20)),
+            ..MockReply::new("200 OK", vec![0u8; 320])
+        })
+        .await;
+
+        let client = SubsonicClient::new(&url, "user", "pass")
+            .unwrap()
+            .with_max_connections(2)
+            .unwrap();
+        let downloads = (0..6).map(|i| {
+            let client = client.clone();
+            tokio::spawn(async move { client.download(&i.to_string()).await })
+        });
+        for download in futures::future::join_all(downloads).await {
+            assert_eq!(download.unwrap().unwrap().len(), 320);
+        }
+        assert_eq!(peak.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_auth_falls_back_to_plaintext() {
+        // Reject tokens with error 41, accept the hex-encoded password
+        let (url, _) = mock_server_with(|request| {
+            let body = if request.contains("p=enc%3A70617373") {
+                r#"{"subsonic-response":{"status":"ok","version":"1.16.1"}}"#
+            } else {
+                r#"{"subsonic-response":{"status":"failed","version":"1.16.1","error":{"code":41,"message":"Token auth unsupported"}}}"#
+            };
+            MockReply::new("200 OK", body)
+        })
+        .await;
+
+        let client = SubsonicClient::new(&url, "user", "pass").unwrap();
+        assert_eq!(client.negotiate_auth_mode().await.unwrap(), AuthMode::Plaintext);
+
+        let token_only = client.clone().with_auth_mode(AuthMode::Token);
+        assert!(token_only.negotiate_auth_mode().await.is_err());
+        assert!(client.with_auth_mode(AuthMode::Plaintext).ping().await.is_ok());
+    }
+}
@@ -0,0 +1,134 @@
+//! Canned Subsonic server for tests
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Response the mock server sends to a request
+#[derive(Debug, Clone)]
+pub struct MockReply {
+    pub status: &'static str,
+    pub body: Vec<u8>,
+    /// Send the body 64 bytes at a time with this pause in between
+    pub pace: Option<Duration>,
+    /// Send only this much of the body, then hold the connection open
+    pub stall_after: Option<usize>,
+}
+
+impl MockReply {
+    pub fn new(status: &'static str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            body: body.into(),
+            pace: None,
+            stall_after: None,
+        }
+    }
+
+    /// A successful Subsonic response with `payload`'s fields added, e.g. `{"playlists": ...}`
+    pub fn ok(payload: serde_json::Value) -> Self {
+        let mut response = serde_json::json!({ "status": "ok", "version": "1.16.1" });
+        if let (Some(response), serde_json::Value::Object(fields)) = (response.as_object_mut(), payload) {
+            response.extend(fields);
+        }
+        Self::new("200 OK", serde_json::json!({ "subsonic-response": response }).to_string())
+    }
+}
+
+/// Serve the same response to every connection, returning the server URL
+/// and a count of requests served
+pub async fn mock_server(status: &'static str, body: &str) -> (String, Arc<AtomicUsize>) {
+    let body = body.to_string();
+    mock_server_with(move |_| MockReply::new(status, body.clone())).await
+}
+
+/// Serve Subsonic API responses by endpoint (e.g. "getPlaylists"); anything
+/// else gets a 404
+pub async fn mock_api(routes: Vec<(&'static str, MockReply)>) -> String {
+    let routes: Vec<(String, MockReply)> = routes
+        .into_iter()
+        .map(|(endpoint, reply)| (format!("/{}?", endpoint), reply))
+        .collect();
+    let (url, _) = mock_server_with(move |request| {
+        let line = request.lines().next().unwrap_or("");
+        match routes.iter().find(|(path, _)| line.contains(path.as_str())) {
+            Some((_, reply)) => reply.clone(),
+            None => MockReply::new("404 Not Found", ""),
+        }
+    })
+    .await;
+    url
+}
+
+/// Answer each request with whatever `respond` makes of the request text
+pub async fn mock_server_with(
+    respond: impl Fn(&str) -> MockReply + Send + Sync + 'static,
+) -> (String, Arc<AtomicUsize>) {
+    let (url, requests, _) = serve(respond).await;
+    (url, requests)
+}
+
+/// Serve `reply` to every connection, returning the server URL and the most
+/// requests that were being answered at once
+pub async fn mock_server_peak(reply: MockReply) -> (String, Arc<AtomicUsize>) {
+    let (url, _, peak) = serve(move |_| reply.clone()).await;
+    (url, peak)
+}
+
+async fn serve(
+    respond: impl Fn(&str) -> MockReply + Send + Sync + 'static,
+) -> (String, Arc<AtomicUsize>, Arc<AtomicUsize>) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let requests = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+    let open = Arc::new(AtomicUsize::new(0));
+    let respond = Arc::new(respond);
+
+    let counter = requests.clone();
+    let most_open = peak.clone();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let respond = respond.clone();
+            let (open, peak) = (open.clone(), most_open.clone());
+            tokio::spawn(async move {
+                let mut buf = [0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                peak.fetch_max(open.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                let reply = respond(&String::from_utf8_lossy(&buf[..n]));
+                let head = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    reply.status,
+                    reply.body.len()
+                );
+                let _ = socket.write_all(head.as_bytes()).await;
+
+                let body = &reply.body[..reply.stall_after.unwrap_or(reply.body.len()).min(reply.body.len())];
+                // The request stops counting as open before its last byte goes
+                // out, so the client can't start another one while it still counts
+                let (body, last) = body.split_at(body.len().saturating_sub(1));
+                match reply.pace {
+                    Some(pause) => {
+                        for chunk in body.chunks(64) {
+                            let _ = socket.write_all(chunk).await;
+                            tokio::time::sleep(pause).await;
+                        }
+                    }
+                    None => {
+                        let _ = socket.write_all(body).await;
+                    }
+                }
+                open.fetch_sub(1, Ordering::SeqCst);
+                let _ = socket.write_all(last).await;
+                if reply.stall_after.is_some() {
+                    tokio::time::sleep(Duration::from_secs(30)).await;
+                }
+                let _ = socket.shutdown().await;
+            });
+        }
+    });
+
+    (format!("http://{}", addr), requests, peak)
+}
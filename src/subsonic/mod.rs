@@ -2,6 +2,8 @@
 
 pub mod auth;
 pub mod client;
+#[cfg(test)]
+pub mod mock;
 pub mod models;
 
 pub use client::SubsonicClient;
@@ -1,6 +1,10 @@
 //! Subsonic API response models
 
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use crate::utils::glob::glob_match;
 
 /// Wrapper for all Subsonic API responses
 #[derive(Debug, Clone, Deserialize)]
@@ -23,6 +27,71 @@ pub struct SubsonicError {
     pub message: String,
 }
 
+impl SubsonicError {
+    /// Error code for a requested item that doesn't exist
+    const NOT_FOUND: i32 = 70;
+
+    /// Whether the requested item doesn't exist on the server
+    pub fn is_not_found(&self) -> bool {
+        self.code == Self::NOT_FOUND
+    }
+}
+
+impl std::fmt::Display for SubsonicError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Subsonic error {}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for SubsonicError {}
+
+/// Server details sent with every response (read from `ping`)
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ServerInfo {
+    /// Subsonic API version, e.g. "1.16.1"
+    pub version: Option<String>,
+    /// Server implementation, e.g. "navidrome" (OpenSubsonic only)
+    #[serde(rename = "type")]
+    pub server_type: Option<String>,
+    /// Server software version (OpenSubsonic only)
+    #[serde(rename = "serverVersion")]
+    pub server_version: Option<String>,
+    /// Whether the server implements OpenSubsonic
+    #[serde(rename = "openSubsonic", default)]
+    pub open_subsonic: bool,
+}
+
+// OpenSubsonic extensions response (getOpenSubsonicExtensions)
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpenSubsonicExtensionsData {
+    #[serde(rename = "openSubsonicExtensions", default)]
+    pub open_subsonic_extensions: Vec<OpenSubsonicExtension>,
+}
+
+/// An OpenSubsonic API extension and the versions the server supports
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpenSubsonicExtension {
+    pub name: String,
+    #[serde(default)]
+    pub versions: Vec<u32>,
+}
+
+/// What the configured server is and supports, detected during `auth`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerCapabilities {
+    /// Subsonic API version
+    pub api_version: Option<String>,
+    /// Server implementation (OpenSubsonic only)
+    pub server_type: Option<String>,
+    /// Server software version (OpenSubsonic only)
+    pub server_version: Option<String>,
+    /// Whether the server implements OpenSubsonic
+    pub open_subsonic: bool,
+    /// OpenSubsonic extensions the server advertises
+    pub extensions: Vec<OpenSubsonicExtension>,
+}
+
 // Artist index response (getArtists)
 #[derive(Debug, Clone, Deserialize)]
 pub struct ArtistsData {
@@ -74,11 +143,196 @@ pub struct Album {
     pub artist_id: Option<String>,
     #[serde(rename = "coverArt")]
     pub cover_art: Option<String>,
+    /// Other artwork besides `coverArt`: the back cover, then disc and booklet scans
+    ///
+    /// Only listed by OpenSubsonic servers that expose extra artwork.
+    #[serde(rename = "coverArtIds", default, skip_serializing_if = "Vec::is_empty")]
+    pub cover_art_ids: Vec<String>,
     #[serde(rename = "songCount")]
     pub song_count: Option<u32>,
     pub duration: Option<u32>,
     pub year: Option<u32>,
     pub genre: Option<String>,
+    /// When the album was added to the server (ISO 8601)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    /// Where the album's tracks come from (never sent by the server)
+    #[serde(default, skip_serializing_if = "AlbumSource::is_id3")]
+    pub source: AlbumSource,
+}
+
+/// How an album's tracks are looked up on the server
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlbumSource {
+    /// An ID3 album (`getAlbum`)
+    #[default]
+    Id3,
+    /// A music folder from a file-based library (`getMusicDirectory`)
+    Folder,
+}
+
+impl AlbumSource {
+    fn is_id3(&self) -> bool {
+        *self == AlbumSource::Id3
+    }
+}
+
+impl Album {
+    /// When the album was added to the server, if known
+    pub fn created_at(&self) -> Option<DateTime<Utc>> {
+        self.created.as_deref().and_then(parse_created)
+    }
+}
+
+// Folder index response (getIndexes)
+#[derive(Debug, Clone, Deserialize)]
+pub struct IndexesData {
+    #[serde(default)]
+    pub indexes: Indexes,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Indexes {
+    #[serde(default)]
+    pub index: Vec<FolderIndex>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FolderIndex {
+    #[serde(default)]
+    pub artist: Vec<IndexEntry>,
+}
+
+/// Top-level folder in a file-based library
+#[derive(Debug, Clone, Deserialize)]
+pub struct IndexEntry {
+    pub id: String,
+    pub name: String,
+}
+
+// Music directory response (getMusicDirectory)
+#[derive(Debug, Clone, Deserialize)]
+pub struct MusicDirectoryData {
+    pub directory: MusicDirectory,
+}
+
+/// A folder in a file-based library with its subfolders and files
+#[derive(Debug, Clone, Deserialize)]
+pub struct MusicDirectory {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub child: Vec<DirectoryChild>,
+}
+
+impl MusicDirectory {
+    /// Treat this folder as an album if it directly contains songs
+    ///
+    /// The artist comes from the songs' tags, falling back to the parent
+    /// folder's name (the usual `Artist/Album` layout).
+    pub fn as_album(&self, parent_name: Option<&str>) -> Option<Album> {
+        let songs: Vec<&Song> = self.child.iter().filter(|c| !c.is_dir).map(|c| &c.song).collect();
+        let first = songs.first()?;
+
+        Some(Album {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            artist: first.artist.clone().or_else(|| parent_name.map(str::to_string)),
+            artist_id: None,
+            cover_art: first.cover_art.clone(),
+            cover_art_ids: Vec::new(),
+            song_count: Some(songs.len() as u32),
+            duration: Some(songs.iter().filter_map(|s| s.duration).sum()),
+            year: None,
+            genre: None,
+            created: None,
+            source: AlbumSource::Folder,
+        })
+    }
+}
+
+/// Entry in a music directory: a subfolder or a song
+#[derive(Debug, Clone, Deserialize)]
+pub struct DirectoryChild {
+    #[serde(rename = "isDir", default)]
+    pub is_dir: bool,
+    #[serde(flatten)]
+    pub song: Song,
+}
+
+// Album list response (getAlbumList2)
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlbumList2Data {
+    #[serde(rename = "albumList2", default)]
+    pub album_list: AlbumList,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AlbumList {
+    #[serde(default)]
+    pub album: Vec<Album>,
+}
+
+// Genres response (getGenres)
+#[derive(Debug, Clone, Deserialize)]
+pub struct GenresData {
+    #[serde(default)]
+    pub genres: GenresList,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct GenresList {
+    #[serde(default)]
+    pub genre: Vec<Genre>,
+}
+
+/// Genre from the library, with how much music is tagged with it
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Genre {
+    /// Genre name, as tagged
+    pub value: String,
+    #[serde(rename = "songCount")]
+    pub song_count: Option<u32>,
+    #[serde(rename = "albumCount")]
+    pub album_count: Option<u32>,
+}
+
+// Album info response (getAlbumInfo2)
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlbumInfoData {
+    #[serde(rename = "albumInfo", default)]
+    pub album_info: AlbumInfo,
+}
+
+/// Extra album information (notes and external IDs)
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AlbumInfo {
+    pub notes: Option<String>,
+    #[serde(rename = "musicBrainzId")]
+    pub music_brainz_id: Option<String>,
+}
+
+// Bookmarks response (getBookmarks)
+#[derive(Debug, Clone, Deserialize)]
+pub struct BookmarksData {
+    #[serde(default)]
+    pub bookmarks: BookmarksList,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BookmarksList {
+    #[serde(default)]
+    pub bookmark: Vec<Bookmark>,
+}
+
+/// A saved play position in a track (used for audiobook/mix chapters)
+#[derive(Debug, Clone, Deserialize)]
+pub struct Bookmark {
+    /// Position in milliseconds
+    pub position: u64,
+    pub comment: Option<String>,
+    pub entry: Song,
 }
 
 // Album with songs response (getAlbum)
@@ -89,6 +343,8 @@ pub struct AlbumData {
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct AlbumWithSongs {
+    #[serde(flatten)]
+    pub info: Album,
     #[serde(default)]
     pub song: Vec<Song>,
 }
@@ -104,10 +360,16 @@ pub struct Song {
     pub artist: Option<String>,
     #[serde(rename = "artistId")]
     pub artist_id: Option<String>,
+    /// Album artist of the song's album (OpenSubsonic)
+    #[serde(rename = "displayAlbumArtist", default, skip_serializing_if = "Option::is_none")]
+    pub display_album_artist: Option<String>,
     pub track: Option<u32>,
     #[serde(rename = "discNumber")]
     pub disc_number: Option<u32>,
     pub duration: Option<u32>,
+    /// Bitrate in kbps
+    #[serde(rename = "bitRate", default, skip_serializing_if = "Option::is_none")]
+    pub bit_rate: Option<u32>,
     pub size: Option<u64>,
     pub suffix: Option<String>,
     #[serde(rename = "contentType")]
@@ -115,6 +377,38 @@ pub struct Song {
     #[serde(rename = "coverArt")]
     pub cover_art: Option<String>,
     pub path: Option<String>,
+    /// When the song was added to the server (ISO 8601)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+}
+
+impl Song {
+    /// Whether the server reports this as a video rather than audio
+    pub fn is_video(&self) -> bool {
+        self.content_type
+            .as_deref()
+            .is_some_and(|t| t.to_ascii_lowercase().starts_with("video/"))
+    }
+
+    /// When the song was added to the server, if known
+    pub fn created_at(&self) -> Option<DateTime<Utc>> {
+        self.created.as_deref().and_then(parse_created)
+    }
+}
+
+/// Parse a Subsonic `created` timestamp
+///
+/// Servers send `xs:dateTime` values, which may or may not carry a timezone;
+/// ones without are taken as UTC.
+fn parse_created(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f")
+                .ok()
+                .map(|dt| dt.and_utc())
+        })
 }
 
 // Playlists response (getPlaylists)
@@ -141,6 +435,17 @@ pub struct Playlist {
     pub public: Option<bool>,
     #[serde(rename = "coverArt")]
     pub cover_art: Option<String>,
+    pub created: Option<String>,
+    /// When the playlist's songs or details last changed on the server (ISO 8601)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changed: Option<String>,
+}
+
+impl Playlist {
+    /// When the playlist was created on the server, if known
+    pub fn created_at(&self) -> Option<DateTime<Utc>> {
+        self.created.as_deref().and_then(parse_created)
+    }
 }
 
 // Playlist with songs response (getPlaylist)
@@ -155,11 +460,63 @@ pub struct PlaylistWithSongs {
     pub songs: Vec<Song>,
 }
 
+// Search response (search3)
+#[derive(Debug, Clone, Deserialize)]
+pub struct SearchData {
+    #[serde(rename = "searchResult3")]
+    pub search_result: SearchResult,
+}
+
+/// Artists, albums and songs matching a search query
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchResult {
+    #[serde(default)]
+    pub artist: Vec<Artist>,
+    #[serde(default)]
+    pub album: Vec<Album>,
+    #[serde(default)]
+    pub song: Vec<Song>,
+}
+
+// Starred items response (getStarred2)
+#[derive(Debug, Clone, Deserialize)]
+pub struct StarredData {
+    #[serde(rename = "starred2", default)]
+    pub starred: Starred,
+}
+
+/// Artists, albums and songs the user has starred
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Starred {
+    #[serde(default)]
+    pub artist: Vec<Artist>,
+    #[serde(default)]
+    pub album: Vec<Album>,
+    #[serde(default)]
+    pub song: Vec<Song>,
+}
+
+impl Starred {
+    /// IDs of the albums of starred songs, leaving out starred albums
+    pub fn song_album_ids(&self) -> BTreeSet<&str> {
+        let starred: HashSet<&str> = self.album.iter().map(|a| a.id.as_str()).collect();
+        self.song
+            .iter()
+            .filter_map(|s| s.album_id.as_deref())
+            .filter(|id| !starred.contains(id))
+            .collect()
+    }
+}
+
 /// Selection of content to sync
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SyncSelection {
     pub albums: Vec<Album>,
     pub playlists: Vec<Playlist>,
+    /// Song IDs picked from albums where only some tracks are wanted, by
+    /// album ID; albums without an entry are synced whole
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tracks: BTreeMap<String, BTreeSet<String>>,
 }
 
 impl SyncSelection {
@@ -178,4 +535,232 @@ impl SyncSelection {
     pub fn playlist_count(&self) -> usize {
         self.playlists.len()
     }
+
+    /// Drop an album and any tracks picked from it
+    pub fn remove_album(&mut self, album_id: &str) {
+        self.albums.retain(|a| a.id != album_id);
+        self.tracks.remove(album_id);
+    }
+
+    /// Keep only albums and playlists whose names match the given globs
+    ///
+    /// An item is kept if it matches any `include` pattern (or there are
+    /// none) and no `exclude` pattern. Albums are matched against both their
+    /// name and "Artist - Album".
+    pub fn filter_by_name(&mut self, include: &[String], exclude: &[String]) {
+        let wanted = |names: &[&str]| {
+            let matches = |pattern: &String| names.iter().any(|n| glob_match(pattern, n));
+            (include.is_empty() || include.iter().any(matches)) && !exclude.iter().any(matches)
+        };
+
+        self.albums.retain(|album| {
+            let artist = album.artist.as_deref().unwrap_or("Unknown Artist");
+            wanted(&[&album.name, &format!("{} - {}", artist, album.name)])
+        });
+        self.playlists.retain(|playlist| wanted(&[&playlist.name]));
+    }
+
+    /// Starred albums whole, and the starred songs of other albums
+    ///
+    /// `song_albums` are the albums listed by `Starred::song_album_ids`;
+    /// starred artists aren't expanded into their albums.
+    pub fn from_starred(starred: Starred, song_albums: Vec<Album>) -> Self {
+        let mut selection = Self {
+            albums: starred.album,
+            ..Default::default()
+        };
+        for album in song_albums {
+            let songs: BTreeSet<String> = starred
+                .song
+                .iter()
+                .filter(|s| s.album_id.as_deref() == Some(album.id.as_str()))
+                .map(|s| s.id.clone())
+                .collect();
+            if !songs.is_empty() && !selection.albums.iter().any(|a| a.id == album.id) {
+                selection.tracks.insert(album.id.clone(), songs);
+                selection.albums.push(album);
+            }
+        }
+        selection
+    }
+
+    /// The albums in `albums` by an artist that has an album in the selection
+    ///
+    /// Artists are matched by ID, or by name when the server gave no ID.
+    pub fn by_selected_artists(&self, albums: Vec<Album>) -> Vec<Album> {
+        let ids: HashSet<&str> = self.albums.iter().filter_map(|a| a.artist_id.as_deref()).collect();
+        let names: HashSet<&str> = self.albums.iter().filter_map(|a| a.artist.as_deref()).collect();
+        albums
+            .into_iter()
+            .filter(|album| match album.artist_id.as_deref() {
+                Some(id) if !ids.is_empty() => ids.contains(id),
+                _ => album.artist.as_deref().is_some_and(|name| names.contains(name)),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_selection_by_name() {
+        let album = |name: &str, artist: &str| Album {
+            id: name.to_string(),
+            name: name.to_string(),
+            artist: Some(artist.to_string()),
+            artist_id: None,
+            cover_art: None,
+            cover_art_ids: Vec::new(),
+            song_count: None,
+            duration: None,
+            year: None,
+            genre: None,
+            created: None,
+            source: AlbumSource::Id3,
+        };
+        let playlist = |name: &str| Playlist {
+            id: name.to_string(),
+            name: name.to_string(),
+            song_count: None,
+            duration: None,
+            owner: None,
+            public: None,
+            cover_art: None,
+            created: None,
+            changed: None,
+        };
+        let mut selection = SyncSelection {
+            albums: vec![
+                album("Live at Leeds", "The Who"),
+                album("Tommy", "The Who"),
+                album("Alive!", "KISS"),
+                album("Live Through This", "Hole"),
+            ],
+            playlists: vec![playlist("Live Favourites"), playlist("Road Trip")],
+            tracks: BTreeMap::new(),
+        };
+
+        selection.filter_by_name(&["*live*".to_string()], &["Hole - *".to_string()]);
+
+        let albums: Vec<&str> = selection.albums.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(albums, ["Live at Leeds", "Alive!"]);
+        let playlists: Vec<&str> = selection.playlists.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(playlists, ["Live Favourites"]);
+    }
+
+    #[test]
+    fn test_albums_by_selected_artists() {
+        let album = |name: &str, artist: &str, artist_id: &str| -> Album {
+            serde_json::from_value(serde_json::json!({
+                "id": name, "name": name, "artist": artist, "artistId": artist_id,
+            }))
+            .unwrap()
+        };
+        let selection = SyncSelection {
+            albums: vec![album("Tommy", "The Who", "ar-1")],
+            ..Default::default()
+        };
+
+        let added = vec![
+            album("Quadrophenia", "The Who", "ar-1"),
+            album("Who's Next", "The Who", "ar-99"),
+            album("Alive!", "KISS", "ar-2"),
+        ];
+        let names: Vec<String> = selection.by_selected_artists(added).into_iter().map(|a| a.name).collect();
+        assert_eq!(names, ["Quadrophenia"]);
+    }
+
+    #[test]
+    fn test_selection_from_starred() {
+        let json = r#"{"starred2":{
+            "album":[{"id":"al-1","name":"Tommy"}],
+            "song":[
+                {"id":"s1","title":"Pinball Wizard","albumId":"al-1"},
+                {"id":"s2","title":"Baba O'Riley","albumId":"al-2"},
+                {"id":"s3","title":"Behind Blue Eyes","albumId":"al-2"}
+            ]
+        }}"#;
+        let starred = serde_json::from_str::<StarredData>(json).unwrap().starred;
+        assert_eq!(starred.song_album_ids().into_iter().collect::<Vec<_>>(), ["al-2"]);
+
+        let album: Album = serde_json::from_str(r#"{"id":"al-2","name":"Who's Next"}"#).unwrap();
+        let selection = SyncSelection::from_starred(starred, vec![album]);
+        let names: Vec<&str> = selection.albums.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["Tommy", "Who's Next"]);
+        assert!(!selection.tracks.contains_key("al-1"));
+        assert_eq!(selection.tracks["al-2"], BTreeSet::from(["s2".to_string(), "s3".to_string()]));
+    }
+
+    #[test]
+    fn test_parse_created_formats() {
+        let with_zone = parse_created("2021-03-04T05:06:07.000Z").unwrap();
+        assert_eq!(with_zone.timestamp(), 1_614_834_367);
+
+        let without_zone = parse_created("2021-03-04T05:06:07").unwrap();
+        assert_eq!(without_zone, with_zone);
+
+        assert!(parse_created("yesterday").is_none());
+    }
+
+    #[test]
+    fn test_parse_music_directory() {
+        let json = r#"{"directory":{"id":"d1","name":"Artist","child":[
+            {"id":"d2","parent":"d1","isDir":true,"title":"Album"},
+            {"id":"s1","parent":"d1","isDir":false,"title":"Loose Track","suffix":"flac","duration":200}
+        ]}}"#;
+        let data: MusicDirectoryData = serde_json::from_str(json).unwrap();
+        let dir = data.directory;
+        assert_eq!(dir.name, "Artist");
+        assert!(dir.child[0].is_dir);
+        assert_eq!(dir.child[0].song.title, "Album");
+        assert!(!dir.child[1].is_dir);
+        assert_eq!(dir.child[1].song.duration, Some(200));
+    }
+
+    #[test]
+    fn test_music_directory_as_album() {
+        let json = r#"{"directory":{"id":"d2","name":"Album","child":[
+            {"id":"s1","isDir":false,"title":"One","duration":100},
+            {"id":"s2","isDir":false,"title":"Two","duration":50},
+            {"id":"d3","isDir":true,"title":"Bonus"}
+        ]}}"#;
+        let data: MusicDirectoryData = serde_json::from_str(json).unwrap();
+        let album = data.directory.as_album(Some("Artist")).unwrap();
+        assert_eq!(album.artist.as_deref(), Some("Artist"));
+        assert_eq!(album.song_count, Some(2));
+        assert_eq!(album.duration, Some(150));
+        assert_eq!(album.source, AlbumSource::Folder);
+
+        let empty = r#"{"directory":{"id":"d1","name":"Artist","child":[
+            {"id":"d2","isDir":true,"title":"Album"}
+        ]}}"#;
+        let data: MusicDirectoryData = serde_json::from_str(empty).unwrap();
+        assert!(data.directory.as_album(None).is_none());
+    }
+
+    #[test]
+    fn test_album_source_defaults_to_id3() {
+        let album: Album = serde_json::from_str(r#"{"id":"a1","name":"Album"}"#).unwrap();
+        assert_eq!(album.source, AlbumSource::Id3);
+        assert!(!serde_json::to_string(&album).unwrap().contains("source"));
+    }
+
+    #[test]
+    fn test_parse_bookmarks() {
+        let json = r#"{"bookmarks":{"bookmark":[
+            {"position":90500,"username":"me","comment":"Part 2",
+             "created":"2021-03-04T05:06:07Z","changed":"2021-03-04T05:06:07Z",
+             "entry":{"id":"s1","title":"Book"}}
+        ]}}"#;
+        let data: BookmarksData = serde_json::from_str(json).unwrap();
+        let bookmark = &data.bookmarks.bookmark[0];
+        assert_eq!(bookmark.position, 90_500);
+        assert_eq!(bookmark.comment.as_deref(), Some("Part 2"));
+        assert_eq!(bookmark.entry.id, "s1");
+
+        let empty: BookmarksData = serde_json::from_str(r#"{"bookmarks":{}}"#).unwrap();
+        assert!(empty.bookmarks.bookmark.is_empty());
+    }
 }
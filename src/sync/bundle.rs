@@ -0,0 +1,167 @@
+//! Applying prepared bundles to devices
+//!
+//! `nutune prepare` runs a normal sync against a plain directory, so a
+//! bundle has the device layout plus a manifest. Applying it copies the
+//! files across and merges the bundle's manifest into the device's.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use crate::device::manifest::MANIFEST_FILE;
+use crate::device::{DeviceStorage, SyncManifest};
+
+/// Result of applying a bundle
+#[derive(Debug, Default)]
+pub struct ApplyResult {
+    pub files_copied: usize,
+    pub bytes_copied: u64,
+    pub albums: usize,
+    pub playlists: usize,
+}
+
+/// Copy a prepared bundle onto a device and record it in `manifest`
+///
+/// `manifest` is the device's existing manifest (or a fresh one); it is
+/// saved to the device once every file has been copied, so an interrupted
+/// apply leaves the device's sync history untouched.
+pub async fn apply_bundle(
+    bundle: &Path,
+    storage: &DeviceStorage,
+    manifest: &mut SyncManifest,
+) -> Result<ApplyResult> {
+    let bundle_manifest = SyncManifest::load(bundle)?.ok_or_else(|| {
+        anyhow::anyhow!(
+            "{} is not a nutune bundle (no {} found). Create one with 'nutune prepare --out <DIR>'.",
+            bundle.display(),
+            MANIFEST_FILE
+        )
+    })?;
+
+    let mut result = ApplyResult {
+        albums: bundle_manifest.synced_albums.len(),
+        playlists: bundle_manifest.synced_playlists.len(),
+        ..Default::default()
+    };
+
+    storage.init().await?;
+
+    for relative in bundle_files(bundle).await? {
+        let source = bundle.join(&relative);
+        let data = tokio::fs::read(&source)
+            .await
+            .with_context(|| format!("Failed to read {}", source.display()))?;
+
+        let target = storage.write_file(&relative, &data).await?;
+
+        // Carry over mtimes so --preserve-dates survives the copy
+        if let Ok(modified) = std::fs::metadata(&source).and_then(|m| m.modified()) {
+            storage.set_file_mtime(&target, DateTime::<Utc>::from(modified))?;
+        }
+
+        result.files_copied += 1;
+        result.bytes_copied += data.len() as u64;
+    }
+
+    manifest.merge(&bundle_manifest);
+    storage.save_manifest(manifest).await?;
+
+    Ok(result)
+}
+
+/// List the files in a bundle relative to its root, excluding the manifest
+async fn bundle_files(bundle: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(dir) = pending.pop() {
+        let mut entries = tokio::fs::read_dir(bundle.join(&dir))
+            .await
+            .with_context(|| format!("Failed to read {}", bundle.join(&dir).display()))?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let relative = dir.join(entry.file_name());
+            if entry.file_type().await?.is_dir() {
+                pending.push(relative);
+            } else if relative != Path::new(MANIFEST_FILE) {
+                files.push(relative);
+            }
+        }
+    }
+
+    files.sort();
+    debug!("Found {} file(s) in bundle {}", files.len(), bundle.display());
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::SyncedAlbum;
+    use crate::device::backend::MemoryBackend;
+    use std::sync::Arc;
+
+    fn synced_album(id: &str) -> SyncedAlbum {
+        SyncedAlbum {
+            id: id.to_string(),
+            artist: "Artist".to_string(),
+            album: format!("Album {}", id),
+            genre: None,
+            folder: None,
+            track_count: 1,
+            expected_tracks: None,
+            server_tracks: None,
+            synced_tracks: Vec::new(),
+            partial: false,
+            synced_at: Utc::now(),
+            tracks: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_apply_bundle_copies_files_and_merges_manifest() {
+        let bundle = std::env::temp_dir().join(format!("nutune_bundle_test_{}", std::process::id()));
+        let album_dir = bundle.join("Artists/Artist/Album 2");
+        std::fs::create_dir_all(&album_dir).unwrap();
+        std::fs::write(album_dir.join("01 - Song.mp3"), b"audio").unwrap();
+
+        let mut bundle_manifest = SyncManifest::new("http://server");
+        bundle_manifest.add_album(synced_album("2"));
+        std::fs::write(bundle.join(MANIFEST_FILE), bundle_manifest.to_json().unwrap()).unwrap();
+
+        let backend = Arc::new(MemoryBackend::default());
+        let storage = DeviceStorage::with_backend(PathBuf::from("/device"), backend.clone());
+        let mut manifest = SyncManifest::new("http://server");
+        manifest.add_album(synced_album("1"));
+
+        let result = apply_bundle(&bundle, &storage, &mut manifest).await;
+        std::fs::remove_dir_all(&bundle).unwrap();
+        let result = result.unwrap();
+
+        assert_eq!(result.files_copied, 1);
+        assert_eq!(result.bytes_copied, 5);
+        assert_eq!(result.albums, 1);
+
+        let track = Path::new("/device/Artists/Artist/Album 2/01 - Song.mp3");
+        assert_eq!(backend.file(track).as_deref(), Some(&b"audio"[..]));
+        assert!(backend.mtime(track).is_some());
+
+        assert!(manifest.is_album_synced("1"));
+        assert!(manifest.is_album_synced("2"));
+        let saved = backend.file(&Path::new("/device").join(MANIFEST_FILE)).unwrap();
+        let saved: SyncManifest = serde_json::from_slice(&saved).unwrap();
+        assert_eq!(saved.synced_albums.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_apply_rejects_directory_without_manifest() {
+        let storage = DeviceStorage::with_backend(PathBuf::from("/device"), Arc::new(MemoryBackend::default()));
+        let mut manifest = SyncManifest::new("http://server");
+
+        let err = apply_bundle(&std::env::temp_dir().join("nutune_no_such_bundle"), &storage, &mut manifest)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not a nutune bundle"));
+    }
+}
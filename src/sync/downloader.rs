@@ -3,8 +3,9 @@
 use anyhow::Result;
 use futures::stream::{self, StreamExt};
 use indicatif::{ProgressBar, ProgressStyle};
-use std::sync::Arc;
-use tracing::debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
 
 use crate::subsonic::{Song, SubsonicClient};
 
@@ -13,37 +14,290 @@ use crate::subsonic::{Song, SubsonicClient};
 pub struct DownloadTask {
     /// Song to download
     pub song: Song,
-    /// Destination info (artist, album, track_number for naming)
+    /// Artist the track is written under
     pub artist: String,
-    pub album: String,
 }
 
 /// Result of a download
+///
+/// When the song was transcoded, `song.suffix` is the format actually
+/// downloaded, so tracks are written with a matching extension.
 pub struct DownloadResult {
     pub song: Song,
     pub data: bytes::Bytes,
     pub artist: String,
-    pub album: String,
+}
+
+/// Transcoding asked of the server instead of downloading original files
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Transcode {
+    /// Target format (`mp3`, `opus`, ...); the server's default if unset
+    pub format: Option<String>,
+    /// Maximum bitrate in kbps
+    pub max_bitrate: Option<u32>,
+}
+
+impl Transcode {
+    /// File extension for a song streamed with these options
+    ///
+    /// Without a format the server picks one, and leaves songs already
+    /// under the bitrate cap as they are, so it is read from the data.
+    fn extension(&self, song: &Song, data: &[u8]) -> String {
+        self.format
+            .clone()
+            .filter(|format| format != "raw")
+            .or_else(|| sniff_extension(data).map(str::to_string))
+            .or_else(|| song.suffix.clone())
+            .unwrap_or_else(|| "mp3".to_string())
+    }
+}
+
+/// Guess an audio file's extension from its first bytes
+fn sniff_extension(data: &[u8]) -> Option<&'static str> {
+    match data {
+        [b'f', b'L', b'a', b'C', ..] => Some("flac"),
+        [b'O', b'g', b'g', b'S', ..] if data.get(28..36) == Some(b"OpusHead") => Some("opus"),
+        [b'O', b'g', b'g', b'S', ..] => Some("ogg"),
+        [b'I', b'D', b'3', ..] => Some("mp3"),
+        // MPEG frame sync: layer bits set for MP3, clear for ADTS AAC
+        [0xFF, second, ..] if second & 0xE0 == 0xE0 && second & 0x06 != 0 => Some("mp3"),
+        [0xFF, second, ..] if second & 0xF6 == 0xF0 => Some("aac"),
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => Some("m4a"),
+        [b'R', b'I', b'F', b'F', ..] => Some("wav"),
+        _ => None,
+    }
+}
+
+/// Retries per song when none are configured
+pub const DEFAULT_RETRIES: usize = 3;
+
+/// Delay before the first retry of a failed download; doubles with each attempt
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Download a song, retrying network failures with exponential backoff
+///
+/// The song is tried `retries + 1` times in all, waiting `base_delay` before
+/// the first retry. 5xx and 429 responses (e.g. a reverse proxy's 502) are
+/// retried like network failures; other HTTP errors and errors reported by
+/// the server itself (missing song, no permission) are not. With `transcode` the song is fetched from
+/// the `stream` endpoint instead; otherwise a download that doesn't match the
+/// song's size is cut short, and is retried like a network failure.
+pub async fn download_with_retry(
+    client: &SubsonicClient,
+    song: &Song,
+    retries: usize,
+    base_delay: Duration,
+    transcode: Option<&Transcode>,
+) -> Result<bytes::Bytes> {
+    let attempts = retries + 1;
+    let mut delay = base_delay;
+    let mut attempt = 1;
+    let id = &song.id;
+    loop {
+        let result = match transcode {
+            Some(t) => client.stream(id, t.format.as_deref(), t.max_bitrate).await,
+            None => client.download(id).await.and_then(|data| check_size(song, data)),
+        };
+        match result {
+            Ok(data) => return Ok(data),
+            Err(e) if attempt < attempts && (is_transient(&e) || e.is::<SizeMismatch>()) => {
+                warn!("Download of {} failed (attempt {}/{}), retrying in {:?}: {:#}", id, attempt, attempts, delay, e);
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// A download that doesn't have the size the server reported for the song
+#[derive(Debug)]
+pub struct SizeMismatch {
+    pub expected: u64,
+    pub actual: u64,
+}
+
+impl std::fmt::Display for SizeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "downloaded {} bytes, but the server reported {}", self.actual, self.expected)
+    }
+}
+
+impl std::error::Error for SizeMismatch {}
+
+/// Check an original (not transcoded) download against the song's reported size
+fn check_size(song: &Song, data: bytes::Bytes) -> Result<bytes::Bytes> {
+    match song.size {
+        Some(expected) if expected != data.len() as u64 => Err(SizeMismatch {
+            expected,
+            actual: data.len() as u64,
+        }
+        .into()),
+        _ => Ok(data),
+    }
+}
+
+/// Whether a failed request is worth retrying: a network error, or an HTTP
+/// status saying the server is overloaded or briefly unavailable
+fn is_transient(e: &anyhow::Error) -> bool {
+    e.chain()
+        .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        .any(|error| match error.status() {
+            Some(status) => status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS,
+            None => true,
+        })
+}
+
+/// Download a task's song, setting its suffix to the transcoded format if any
+pub async fn download_task(
+    client: &SubsonicClient,
+    task: DownloadTask,
+    retries: usize,
+    base_delay: Duration,
+    transcode: Option<&Transcode>,
+) -> Result<DownloadResult> {
+    let mut song = task.song;
+    let data = download_with_retry(client, &song, retries, base_delay, transcode).await?;
+    if let Some(transcode) = transcode {
+        song.suffix = Some(transcode.extension(&song, &data));
+    }
+
+    Ok(DownloadResult {
+        song,
+        data,
+        artist: task.artist,
+    })
+}
+
+/// Concurrency `--auto-parallel` starts at
+const AUTO_PARALLEL_START: usize = 2;
+
+/// Throughput change (as a fraction) that counts as better or worse
+const THROUGHPUT_MARGIN: f64 = 0.05;
+
+/// Adjusts download concurrency between batches from measured throughput
+///
+/// Concurrency goes up by one while throughput keeps improving, down by one
+/// when it drops, and is halved when downloads fail.
+#[derive(Debug)]
+pub struct ConcurrencyController {
+    current: usize,
+    max: usize,
+    /// Bytes per second of the previous batch
+    last_throughput: Option<f64>,
+}
+
+impl ConcurrencyController {
+    pub fn new(max: usize) -> Self {
+        let max = max.max(1);
+        Self {
+            current: AUTO_PARALLEL_START.min(max),
+            max,
+            last_throughput: None,
+        }
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Record a finished batch and return the concurrency for the next one
+    pub fn record(&mut self, bytes: u64, elapsed: Duration, failures: usize) -> usize {
+        let previous = self.current;
+
+        if failures > 0 {
+            self.current = (self.current / 2).max(1);
+            self.last_throughput = None;
+        } else if elapsed > Duration::ZERO {
+            let throughput = bytes as f64 / elapsed.as_secs_f64();
+            match self.last_throughput {
+                Some(last) if throughput < last * (1.0 - THROUGHPUT_MARGIN) => {
+                    self.current = (self.current - 1).max(1);
+                }
+                Some(last) if throughput <= last * (1.0 + THROUGHPUT_MARGIN) => {}
+                _ => self.current = (self.current + 1).min(self.max),
+            }
+            self.last_throughput = Some(throughput);
+        }
+
+        if self.current != previous {
+            debug!("Download concurrency {} -> {}", previous, self.current);
+        }
+        self.current
+    }
 }
 
 /// Parallel downloader with progress tracking
 pub struct Downloader {
     client: Arc<SubsonicClient>,
     parallel: usize,
+    /// Retries per song after a network failure (0 means no retries)
+    retries: usize,
+    /// Delay before a song's first retry, doubling with each one
+    base_delay: Duration,
+    /// Adapts `parallel` to measured throughput under `--auto-parallel`
+    auto: Option<Mutex<ConcurrencyController>>,
+    /// Stream songs transcoded instead of downloading the originals
+    transcode: Option<Transcode>,
 }
 
 impl Downloader {
-    /// Create a new downloader
-    pub fn new(client: SubsonicClient, parallel: usize) -> Self {
+    /// Create a new downloader sharing the caller's client (and connection pool)
+    pub fn new(client: Arc<SubsonicClient>, parallel: usize) -> Self {
         Self {
-            client: Arc::new(client),
+            client,
             parallel,
+            retries: DEFAULT_RETRIES,
+            base_delay: RETRY_BASE_DELAY,
+            auto: None,
+            transcode: None,
+        }
+    }
+
+    /// Tune concurrency automatically, up to `max` downloads at once
+    pub fn set_auto_parallel(&mut self, max: usize) {
+        self.auto = Some(Mutex::new(ConcurrencyController::new(max)));
+    }
+
+    /// Number of songs to download at once for the next batch
+    pub fn concurrency(&self) -> usize {
+        match &self.auto {
+            Some(controller) => controller.lock().unwrap().current(),
+            None => self.parallel,
+        }
+    }
+
+    /// Report a finished batch so auto-tuning can adjust concurrency
+    pub fn record_batch(&self, bytes: u64, elapsed: Duration, failures: usize) {
+        if let Some(controller) = &self.auto {
+            controller.lock().unwrap().record(bytes, elapsed, failures);
         }
     }
 
-    /// Get a clone of the client Arc for parallel operations
-    pub fn client_arc(&self) -> Arc<SubsonicClient> {
-        self.client.clone()
+    /// Retry each song up to `retries` times before giving up on it
+    pub fn set_retries(&mut self, retries: usize) {
+        self.retries = retries;
+    }
+
+    /// Retries per song
+    pub fn retries(&self) -> usize {
+        self.retries
+    }
+
+    /// Delay before a song's first retry
+    pub fn base_delay(&self) -> Duration {
+        self.base_delay
+    }
+
+    /// Have the server transcode songs instead of downloading the originals
+    pub fn set_transcode(&mut self, transcode: Option<Transcode>) {
+        self.transcode = transcode;
+    }
+
+    /// Transcoding asked of the server, if any
+    pub fn transcode(&self) -> Option<Transcode> {
+        self.transcode.clone()
     }
 
     /// Download multiple songs in parallel with progress
@@ -62,24 +316,18 @@ impl Downloader {
         );
 
         let client = self.client.clone();
+        let (retries, base_delay) = (self.retries, self.base_delay);
+        let transcode = self.transcode.as_ref();
+        let started = Instant::now();
         let results: Vec<Result<DownloadResult>> = stream::iter(tasks)
             .map(|task| {
                 let client = client.clone();
                 async move {
-                    let title = task.song.title.clone();
-                    debug!("Downloading: {}", title);
-
-                    let data = client.download(&task.song.id).await?;
-
-                    Ok(DownloadResult {
-                        song: task.song,
-                        data,
-                        artist: task.artist,
-                        album: task.album,
-                    })
+                    debug!("Downloading: {}", task.song.title);
+                    download_task(&client, task, retries, base_delay, transcode).await
                 }
             })
-            .buffer_unordered(self.parallel)
+            .buffer_unordered(self.concurrency())
             .inspect(|result| {
                 progress.inc(1);
                 if let Ok(r) = result {
@@ -102,23 +350,144 @@ impl Downloader {
             }
         }
 
+        let bytes = successful.iter().map(|r| r.data.len() as u64).sum();
+        self.record_batch(bytes, started.elapsed(), total - successful.len());
+
         Ok(successful)
     }
 
     /// Download a single song
     pub async fn download_one(&self, task: DownloadTask) -> Result<DownloadResult> {
-        let data = self.client.download(&task.song.id).await?;
+        download_task(&self.client, task, self.retries, self.base_delay, self.transcode.as_ref()).await
+    }
+
+    /// Download cover art, asking the server to scale it to `size` pixels
+    pub async fn download_cover_art(&self, id: &str, size: u32) -> Result<bytes::Bytes> {
+        self.client.get_cover_art(id, Some(size)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subsonic::mock::{MockReply, mock_server_with};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn test_controller_ramps_up_and_backs_off() {
+        let mut controller = ConcurrencyController::new(4);
+        assert_eq!(controller.current(), 2);
+
+        // Improving throughput ramps up, capped at the maximum
+        assert_eq!(controller.record(1_000, SECOND, 0), 3);
+        assert_eq!(controller.record(2_000, SECOND, 0), 4);
+        assert_eq!(controller.record(3_000, SECOND, 0), 4);
+
+        // Flat throughput holds, a drop steps down
+        assert_eq!(controller.record(3_050, SECOND, 0), 4);
+        assert_eq!(controller.record(2_000, SECOND, 0), 3);
+
+        // Failures halve it, never below one
+        assert_eq!(controller.record(2_000, SECOND, 1), 1);
+        assert_eq!(controller.record(0, SECOND, 3), 1);
+    }
+
+    #[test]
+    fn test_download_must_match_reported_size() {
+        let song: Song = serde_json::from_str(r#"{"id":"1","title":"Song","size":5}"#).unwrap();
+        assert!(check_size(&song, bytes::Bytes::from_static(b"audio")).is_ok());
+
+        let error = check_size(&song, bytes::Bytes::from_static(b"aud")).unwrap_err();
+        assert!(error.is::<SizeMismatch>());
+
+        // Songs without a reported size are taken as they come
+        let no_size: Song = serde_json::from_str(r#"{"id":"1","title":"Song"}"#).unwrap();
+        assert!(check_size(&no_size, bytes::Bytes::from_static(b"aud")).is_ok());
+    }
 
-        Ok(DownloadResult {
-            song: task.song,
-            data,
-            artist: task.artist,
-            album: task.album,
+    #[tokio::test]
+    async fn test_download_retries_server_errors() {
+        let attempts = AtomicUsize::new(0);
+        let (url, requests) = mock_server_with(move |_| match attempts.fetch_add(1, Ordering::SeqCst) {
+            0 => MockReply::new("503 Service Unavailable", "<html>Service Unavailable</html>"),
+            1 => MockReply::new("429 Too Many Requests", "slow down"),
+            _ => MockReply::new("200 OK", "audio"),
         })
+        .await;
+        let client = SubsonicClient::new(&url, "user", "pass").unwrap();
+        let song: Song = serde_json::from_str(r#"{"id":"1","title":"Song","size":5}"#).unwrap();
+
+        let data = download_with_retry(&client, &song, 3, Duration::from_millis(1), None).await.unwrap();
+        assert_eq!(&data[..], b"audio");
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_transcoded_download_never_takes_an_error_page() {
+        let (url, requests) = mock_server_with(|_| MockReply::new("502 Bad Gateway", "<html>Bad Gateway</html>")).await;
+        let client = SubsonicClient::new(&url, "user", "pass").unwrap();
+        let song: Song = serde_json::from_str(r#"{"id":"1","title":"Song"}"#).unwrap();
+        let transcode = Transcode {
+            format: Some("mp3".to_string()),
+            max_bitrate: None,
+        };
+
+        let result = download_with_retry(&client, &song, 2, Duration::from_millis(1), Some(&transcode)).await;
+        assert!(result.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_download_gives_up_on_client_errors() {
+        let not_found = serde_json::json!({
+            "subsonic-response": {
+                "status": "failed",
+                "version": "1.16.1",
+                "error": { "code": 70, "message": "Song not found" }
+            }
+        });
+        let (url, requests) = mock_server_with(move |_| MockReply::new("200 OK", not_found.to_string())).await;
+        let client = SubsonicClient::new(&url, "user", "pass").unwrap();
+        let song: Song = serde_json::from_str(r#"{"id":"1","title":"Song"}"#).unwrap();
+
+        let error = download_with_retry(&client, &song, 3, Duration::from_millis(1), None).await.unwrap_err();
+        assert!(error.to_string().contains("Song not found"));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // A plain 404 is not worth retrying either
+        let (url, requests) = mock_server_with(|_| MockReply::new("404 Not Found", "")).await;
+        let client = SubsonicClient::new(&url, "user", "pass").unwrap();
+        assert!(download_with_retry(&client, &song, 3, Duration::from_millis(1), None).await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
     }
 
-    /// Download cover art
-    pub async fn download_cover_art(&self, id: &str) -> Result<bytes::Bytes> {
-        self.client.get_cover_art(id, Some(500)).await
+    #[test]
+    fn test_transcoded_extension() {
+        let song: Song = serde_json::from_str(r#"{"id":"1","title":"Song","suffix":"flac"}"#).unwrap();
+        let mp3 = b"ID3\x04\x00rest of the file";
+
+        let to_opus = Transcode {
+            format: Some("opus".to_string()),
+            max_bitrate: None,
+        };
+        assert_eq!(to_opus.extension(&song, mp3), "opus");
+
+        // The server picks the format when only the bitrate is capped
+        let capped = Transcode {
+            format: None,
+            max_bitrate: Some(192),
+        };
+        assert_eq!(capped.extension(&song, mp3), "mp3");
+        assert_eq!(capped.extension(&song, b"fLaC\x00\x00"), "flac");
+        assert_eq!(capped.extension(&song, b"unknown"), "flac");
+
+        let mut ogg = b"OggS".to_vec();
+        ogg.resize(28, 0);
+        ogg.extend_from_slice(b"OpusHead");
+        assert_eq!(capped.extension(&song, &ogg), "opus");
+        assert_eq!(sniff_extension(&[0xFF, 0xF1, 0x50]), Some("aac"));
+        assert_eq!(sniff_extension(&[0xFF, 0xFB, 0x90]), Some("mp3"));
     }
 }
@@ -2,27 +2,42 @@
 
 use anyhow::Result;
 use bytes::Bytes;
-use chrono::Utc;
+use chrono::{DateTime, Local, Utc};
+use futures::future;
 use futures::stream::{self, StreamExt};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tracing::{debug, info, warn};
 
-use crate::device::{DeviceStorage, SyncManifest, SyncedAlbum, SyncedPlaylist};
-use crate::subsonic::{Album, Playlist, SubsonicClient, SyncSelection};
-use crate::sync::downloader::{DownloadTask, DownloadResult, Downloader};
-use crate::sync::pipeline::{DownloadedTrack, PipelineConfig, process_tracks_parallel};
-use crate::utils::cover_art;
+use crate::device::layout::{AlbumFolder, LayoutTemplate, TrackName};
+use crate::device::storage::genre_folder_name;
+use crate::device::{DeviceStorage, SyncManifest, SyncedAlbum, SyncedPlaylist, TrackSignature};
+use crate::subsonic::{Album, AlbumSource, Playlist, PlaylistWithSongs, Song, SubsonicClient, SyncSelection};
+use crate::sync::downloader::{download_task, DownloadTask, DownloadResult, Downloader, Transcode, DEFAULT_RETRIES};
+use crate::sync::pipeline::{DownloadedTrack, PipelineConfig, ProcessedTrack, process_tracks_parallel};
+use crate::utils::cover_art::{self, CoverArtConfig, CoverFormat, CoverPicture, TrackMetadata};
+use crate::utils::m3u::{M3uEncoding, M3uTrack, PlaylistFormat};
+use crate::utils::size::estimated_size;
+use crate::utils::{generate_cue, notes_to_text, sanitize_filename, Chapter};
 
 /// Progress updates sent during sync
 #[derive(Debug, Clone)]
 pub enum SyncProgress {
     /// Starting sync
+    ///
+    /// Totals only count items that will actually be downloaded; items
+    /// already on the device are reported as skipped and not counted.
     Started {
         total_albums: usize,
         total_playlists: usize,
+        already_synced: usize,
+        /// Expected download size, when the free space check sized the selection
+        estimated_bytes: Option<u64>,
     },
     /// Starting an album
     AlbumStarted {
@@ -30,16 +45,28 @@ pub enum SyncProgress {
         album: String,
         track_count: usize,
     },
-    /// A track was downloaded
+    /// A track was downloaded; `track_num` counts the tracks done so far
     TrackCompleted {
         track_num: usize,
         total_tracks: usize,
     },
+    /// A track finished downloading, before tagging and writing
+    BytesProgress {
+        bytes: u64,
+    },
     /// An album finished
     AlbumCompleted {
         artist: String,
         album: String,
     },
+    /// The first album of the run finished with every track written
+    ///
+    /// Only sent with `SyncOptions::first_complete`; from here the device
+    /// has something playable even if the sync is cut short.
+    FirstAlbumPlayable {
+        artist: String,
+        album: String,
+    },
     /// An album was skipped (already synced)
     AlbumSkipped {
         artist: String,
@@ -62,6 +89,28 @@ pub enum SyncProgress {
     Error {
         message: String,
     },
+    /// The selection won't fit on the device; nothing was deleted or downloaded
+    InsufficientSpace {
+        required: u64,
+        available: u64,
+    },
+    /// An album failed to sync
+    AlbumFailed {
+        album_id: String,
+        artist: String,
+        album: String,
+        message: String,
+    },
+    /// A playlist failed to sync
+    PlaylistFailed {
+        playlist_id: String,
+        name: String,
+        message: String,
+    },
+    /// Some tracks kept their original audio because cover art couldn't be embedded
+    CoverEmbedFallbacks {
+        tracks: usize,
+    },
     /// Sync complete
     Complete {
         albums_synced: usize,
@@ -108,9 +157,250 @@ pub struct DeletionSelection {
 }
 
 impl DeletionSelection {
+    /// Items in the manifest that are no longer in the selection
+    ///
+    /// Only albums are considered if `albums` is set, and likewise for
+    /// playlists, so a sync limited to one kind leaves the other alone.
+    pub fn deselected(manifest: &SyncManifest, selection: &SyncSelection, albums: bool, playlists: bool) -> Self {
+        let mut deletions = Self::default();
+
+        if albums {
+            for synced in &manifest.synced_albums {
+                if !selection.albums.iter().any(|a| a.id == synced.id) {
+                    deletions
+                        .albums
+                        .push((synced.id.clone(), synced.artist.clone(), synced.album.clone()));
+                }
+            }
+        }
+
+        if playlists {
+            for synced in &manifest.synced_playlists {
+                if !selection.playlists.iter().any(|p| p.id == synced.id) {
+                    deletions.playlists.push((synced.id.clone(), synced.name.clone()));
+                }
+            }
+        }
+
+        deletions
+    }
+
     pub fn is_empty(&self) -> bool {
         self.albums.is_empty() && self.playlists.is_empty()
     }
+
+    /// Number of albums and playlists to delete
+    pub fn len(&self) -> usize {
+        self.albums.len() + self.playlists.len()
+    }
+}
+
+/// What to do when an album, playlist or track fails to sync
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OnError {
+    /// Log the failure and carry on with the rest of the sync
+    #[default]
+    Skip,
+    /// Abort the whole sync at the first failure
+    Stop,
+    /// Retry failed downloads with backoff, then carry on (at least 3 times, whatever `--retries` says)
+    Retry,
+}
+
+impl OnError {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Skip => "skip",
+            Self::Stop => "stop",
+            Self::Retry => "retry",
+        }
+    }
+}
+
+/// Which artist name places album folders and names playlist tracks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ArtistSource {
+    /// The album artist: the album's artist, then the track's album artist, then the track artist
+    Album,
+    /// The track artist: the track's artist, then its album artist, then the album's artist
+    Track,
+}
+
+impl ArtistSource {
+    /// Pick an artist name in this source's fallback order
+    pub fn pick(self, album_artist: Option<&str>, song: Option<&Song>) -> String {
+        let song_artist = song.and_then(|s| s.artist.as_deref());
+        let song_album_artist = song.and_then(|s| s.display_album_artist.as_deref());
+
+        let order = match self {
+            Self::Album => [album_artist, song_album_artist, song_artist],
+            Self::Track => [song_artist, song_album_artist, album_artist],
+        };
+        order
+            .into_iter()
+            .flatten()
+            .find(|name| !name.trim().is_empty())
+            .unwrap_or("Unknown Artist")
+            .to_string()
+    }
+}
+
+/// Least retries per track under `--on-error retry`
+const ON_ERROR_RETRIES: usize = 3;
+
+/// Most downloads `--auto-parallel` ramps up to
+pub const AUTO_PARALLEL_MAX: usize = 16;
+
+/// Artist folder for an album with tracks by several artists and no album artist
+const VARIOUS_ARTISTS: &str = "Various Artists";
+
+/// Plain-text log of the last sync, written to the device root with `--device-log`
+const SYNC_LOG_FILE: &str = ".nutune-sync.log";
+
+/// Least time between manifest saves while an album's tracks are written
+const PROGRESS_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Space to keep free beyond a sync's estimated size, for covers, playlists and the manifest
+const FREE_SPACE_MARGIN: u64 = 64 * 1024 * 1024;
+
+/// A selection estimated not to fit in the device's free space
+///
+/// `required` includes [`FREE_SPACE_MARGIN`]; `available` includes the
+/// space the sync's deletions will free.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InsufficientSpace {
+    pub required: u64,
+    pub available: u64,
+}
+
+impl std::fmt::Display for InsufficientSpace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const MB: f64 = 1_048_576.0;
+        write!(
+            f,
+            "Not enough free space on the device: the sync needs about {:.1} MB but only {:.1} MB is available ({:.1} MB short). Deselect something or free up space on the device.",
+            self.required as f64 / MB,
+            self.available as f64 / MB,
+            self.required.saturating_sub(self.available) as f64 / MB
+        )
+    }
+}
+
+impl std::error::Error for InsufficientSpace {}
+
+/// Options controlling how content is laid out and written on the device
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    /// Group album folders by genre (`Genres/<genre>/<artist>/<album>`)
+    pub genre_folders: bool,
+    /// Layout for album tracks, replacing the built-in one (and `genre_folders`)
+    pub layout: Option<LayoutTemplate>,
+    /// Size (in pixels) to request cover art at from the server
+    ///
+    /// Defaults to the processed cover size so we don't download more than we keep.
+    pub cover_fetch_size: Option<u32>,
+    /// Treat a failure to embed cover art as an error instead of keeping the original audio
+    pub strict_cover: bool,
+    /// Write the server's album notes to notes.txt in each album folder
+    pub write_album_notes: bool,
+    /// Image format for embedded and standalone covers
+    pub cover_format: CoverFormat,
+    /// Largest width or height covers are resized to, in pixels
+    pub cover_size: Option<u32>,
+    /// JPEG quality (1-100) covers are encoded at
+    pub cover_quality: Option<u8>,
+    /// Names for the cover file in each album folder, without the extension
+    /// (`cover` if empty)
+    pub cover_names: Vec<String>,
+    /// Local images to use instead of the server's cover art, by album ID
+    pub cover_overrides: HashMap<String, PathBuf>,
+    /// Set each track's mtime to when it was added to the server
+    pub preserve_dates: bool,
+    /// Write a .cue chapter sidecar for tracks with server bookmarks
+    pub with_chapters: bool,
+    /// Filename format for playlist tracks (`{n}`, `{artist}`, `{title}`)
+    pub playlist_track_format: Option<String>,
+    /// Line endings and BOM for M3U files
+    pub m3u_encoding: M3uEncoding,
+    /// List bare filenames in M3U files, without `#EXTINF` lines
+    pub simple_m3u: bool,
+    /// Format of the playlist file written to each playlist folder
+    pub playlist_format: PlaylistFormat,
+    /// Sync the smallest albums first and report when the first one is complete
+    pub first_complete: bool,
+    /// How failures are handled
+    pub on_error: OnError,
+    /// Artist name to use everywhere; by default albums use the album artist
+    /// and playlist tracks the track artist
+    pub artist_source: Option<ArtistSource>,
+    /// Tune download concurrency from measured throughput
+    pub auto_parallel: bool,
+    /// Also embed an album's back cover and media/booklet scans, when the server lists them
+    pub all_art: bool,
+    /// Ignore the device's manifest and don't save one: everything is synced, nothing is recorded
+    pub no_manifest: bool,
+    /// Re-embed cover art during a cover repair even into tracks that already have acceptable art
+    pub force_cover: bool,
+    /// Overwrite title, artist, album, numbering, year and genre tags with the
+    /// server's instead of only filling in empty ones
+    pub force_tags: bool,
+    /// Directory for cover embedding temp files (the system temp directory if unset)
+    pub temp_dir: Option<PathBuf>,
+    /// Write a plain-text log of the sync to `.nutune-sync.log` on the device
+    pub device_log: bool,
+    /// Skip tracks the server reports below this bitrate (kbps)
+    ///
+    /// Compared with the original's bitrate even when transcoding, since a
+    /// transcode can't make up for a low-bitrate source.
+    pub min_bitrate: Option<u32>,
+    /// Sync video files (`video/*` content types) instead of skipping them
+    pub include_video: bool,
+    /// Permission bits for written files (Unix; the umask decides if unset)
+    pub file_mode: Option<u32>,
+    /// Permission bits for created directories (Unix; the umask decides if unset)
+    pub dir_mode: Option<u32>,
+    /// Have the server transcode tracks instead of downloading the original files
+    pub transcode: Option<Transcode>,
+    /// Retries per track after a network failure ([`DEFAULT_RETRIES`] if unset)
+    pub retries: Option<usize>,
+    /// Albums downloaded at once by [`SyncEngine::sync_with_progress`] (at
+    /// least 1), sharing the download parallelism between them
+    pub album_parallel: usize,
+    /// Download a song in several selected albums/playlists once, and link
+    /// (or copy) its other places on the device to the first file
+    pub dedup: bool,
+    /// Sync even if the selection looks too big for the device's free space
+    pub ignore_free_space: bool,
+}
+
+impl SyncOptions {
+    /// How covers are processed, with the defaults filling anything unset
+    pub fn cover_config(&self) -> CoverArtConfig {
+        let defaults = CoverArtConfig::default();
+        CoverArtConfig {
+            max_size: self.cover_size.unwrap_or(defaults.max_size),
+            quality: self.cover_quality.unwrap_or(defaults.quality),
+            format: self.cover_format,
+            ..defaults
+        }
+    }
+
+    /// Cover settings under which tracks that already have an acceptable cover keep it
+    ///
+    /// `None` when covers are replaced anyway, with `--force-cover` or a cover
+    /// override for `album_id`.
+    pub fn keep_cover(&self, album_id: Option<&str>) -> Option<CoverArtConfig> {
+        let overridden = album_id.is_some_and(|id| self.cover_overrides.contains_key(id));
+        (!self.force_cover && !overridden).then(|| self.cover_config())
+    }
+
+    /// Format the server transcodes tracks to, or `None` when the originals are copied
+    pub fn transcode_format(&self) -> Option<&str> {
+        self.transcode
+            .as_ref()
+            .and_then(|t| t.format.as_deref())
+            .filter(|format| *format != "raw")
+    }
 }
 
 /// Result of a sync operation
@@ -120,23 +410,165 @@ pub struct SyncResult {
     pub playlists_synced: usize,
     pub tracks_downloaded: usize,
     pub bytes_downloaded: u64,
+    /// Tracks written without embedded cover art because embedding failed
+    pub cover_fallbacks: usize,
+    /// Albums that failed to sync (kept as failed for `nutune retry`)
+    pub albums_failed: usize,
+    /// Playlists that failed to sync (kept as failed for `nutune retry`)
+    pub playlists_failed: usize,
+    /// Tracks that failed to download within otherwise synced albums/playlists
+    pub tracks_failed: usize,
+    /// Tracks left out for being below `--min-bitrate`, as "Item: Title (N kbps)"
+    pub low_bitrate_skipped: Vec<String>,
+    /// Videos left out of albums and playlists, as "Item: Title (content type)"
+    pub videos_skipped: Vec<String>,
+    /// Albums deleted from the device for being deselected
+    pub albums_deleted: usize,
+    /// Playlists deleted from the device for being deselected
+    pub playlists_deleted: usize,
+    /// Deselected albums and playlists that could not be deleted
+    pub deletions_failed: usize,
+}
+
+impl SyncResult {
+    /// Whether any album, playlist, track or deletion failed
+    pub fn has_failures(&self) -> bool {
+        self.albums_failed > 0
+            || self.playlists_failed > 0
+            || self.tracks_failed > 0
+            || self.deletions_failed > 0
+    }
+}
+
+/// Result of re-embedding cover art into synced albums
+#[derive(Debug, Default)]
+pub struct CoverRepairResult {
+    pub albums_updated: usize,
+    pub tracks_updated: usize,
+    /// Albums whose cover art couldn't be fetched, left untouched
+    pub albums_without_cover: usize,
+    /// Tracks left alone because they already had acceptable cover art
+    pub tracks_kept: usize,
+    /// Tracks whose cover art couldn't be embedded (audio left as it was)
+    pub cover_fallbacks: usize,
+    pub albums_failed: usize,
+}
+
+/// What writing one album or playlist to the device produced
+///
+/// The manifest entry is handed back rather than recorded in place, so the
+/// manifest is only ever mutated by the sync loop between items (apart from
+/// an album's progress while its tracks are written, see
+/// `SyncEngine::save_album_progress`).
+struct ItemOutcome<T> {
+    tracks: usize,
+    bytes: u64,
+    /// Entry to record in the manifest, or `None` if the item was already synced
+    entry: Option<T>,
+}
+
+/// Songs dropped from an album or playlist before downloading
+#[derive(Debug, Default)]
+struct SkippedSongs {
+    videos: Vec<String>,
+    low_bitrate: Vec<String>,
+}
+
+/// An album downloaded into memory, waiting for its turn to be written to the device
+///
+/// Under `--album-parallel` several albums download at once, but each is
+/// written (and recorded in the manifest) by itself, so the device and the
+/// engine's state are only ever changed by one album at a time.
+struct DownloadedAlbum {
+    skipped: SkippedSongs,
+    /// `None` if every song of the album was skipped
+    tracks: Option<AlbumTracks>,
+}
+
+/// The tracks of a [`DownloadedAlbum`] and what's needed to write them
+struct AlbumTracks {
+    artist: String,
+    compilation: bool,
+    genre: Option<String>,
+    /// Manifest entry, holding the tracks resumed so far
+    entry: SyncedAlbum,
+    /// Songs an earlier sync wrote that are no longer picked, to remove from the device
+    unpicked: Vec<Song>,
+    /// Songs this sync already wrote elsewhere, to link rather than download (`--dedup`)
+    duplicates: Vec<(Song, WrittenTrack)>,
+    cover: Option<Vec<u8>>,
+    processed: Vec<ProcessedTrack>,
+    downloaded_bytes: u64,
+    elapsed: Duration,
+    /// Tracks that failed to download
+    failures: usize,
+}
+
+/// A track written earlier in the current sync, that `--dedup` links later places of its song to
+#[derive(Debug, Clone)]
+struct WrittenTrack {
+    path: PathBuf,
+    signature: Option<TrackSignature>,
+}
+
+impl WrittenTrack {
+    /// Extension of the written file, which can differ from the song's after transcoding
+    fn extension(&self) -> String {
+        self.path
+            .extension()
+            .map(|e| e.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "mp3".to_string())
+    }
+}
+
+impl<T> ItemOutcome<T> {
+    fn skipped() -> Self {
+        Self {
+            tracks: 0,
+            bytes: 0,
+            entry: None,
+        }
+    }
 }
 
 /// Sync engine that coordinates downloading and writing to device
 pub struct SyncEngine {
-    client: SubsonicClient,
+    /// Shared by the downloader and all parallel tasks so every request
+    /// (metadata, tracks and cover art) goes through one connection pool
+    client: Arc<SubsonicClient>,
     storage: DeviceStorage,
+    /// Updated and saved by the sync loop after each album or playlist
     manifest: SyncManifest,
     downloader: Downloader,
-    device_path: PathBuf,
     pipeline_config: PipelineConfig,
+    options: SyncOptions,
+    /// Tracks written without embedded cover art during the current sync
+    cover_fallbacks: usize,
+    /// Tracks that failed to download during the current sync
+    tracks_failed: usize,
+    /// Tracks skipped for their bitrate during the current sync
+    low_bitrate_skipped: Vec<String>,
+    /// Videos skipped during the current sync
+    videos_skipped: Vec<String>,
+    /// Chapter markers from the server's bookmarks, by song ID
+    chapters: HashMap<String, Vec<Chapter>>,
+    /// Lines for the device log of the current sync (`--device-log`)
+    log_lines: Vec<String>,
+    /// Free space on the device, checked against the selection before syncing
+    free_space: Option<u64>,
+    /// Album songs fetched by the free space check, by album ID, so the sync doesn't fetch them again
+    sized_albums: Mutex<HashMap<String, Vec<Song>>>,
+    /// Playlists fetched by the free space check, by playlist ID
+    sized_playlists: Mutex<HashMap<String, PlaylistWithSongs>>,
+    /// Songs picked from partly selected albums in the current sync, by album ID
+    picked_tracks: BTreeMap<String, BTreeSet<String>>,
+    /// Where each song was first written in the current sync, by song ID (`--dedup`)
+    written_tracks: HashMap<String, WrittenTrack>,
 }
 
 impl SyncEngine {
     /// Create a new sync engine
     pub fn new(client: SubsonicClient, device_path: PathBuf, parallel: usize) -> Result<Self> {
-        let storage = DeviceStorage::new(device_path.clone());
-
         // Load or create manifest
         let manifest = SyncManifest::load(&device_path)?
             .unwrap_or_else(|| {
@@ -144,7 +576,21 @@ impl SyncEngine {
                 SyncManifest::new("unknown")
             });
 
-        let downloader = Downloader::new(client.clone(), parallel);
+        let storage = DeviceStorage::new(device_path);
+        Ok(Self::with_storage(client, storage, manifest, parallel))
+    }
+
+    /// Create a sync engine that writes through the given storage
+    ///
+    /// Lets tests run the engine against an in-memory backend.
+    pub fn with_storage(
+        client: SubsonicClient,
+        storage: DeviceStorage,
+        manifest: SyncManifest,
+        parallel: usize,
+    ) -> Self {
+        let client = Arc::new(client);
+        let downloader = Downloader::new(Arc::clone(&client), parallel);
 
         // Configure pipeline with download parallelism from param, processing at half
         let pipeline_config = PipelineConfig {
@@ -152,223 +598,1526 @@ impl SyncEngine {
             processing_parallelism: (parallel / 2).max(1),
         };
 
-        Ok(Self {
+        Self {
             client,
             storage,
             manifest,
             downloader,
-            device_path,
             pipeline_config,
-        })
+            options: SyncOptions::default(),
+            cover_fallbacks: 0,
+            tracks_failed: 0,
+            low_bitrate_skipped: Vec::new(),
+            videos_skipped: Vec::new(),
+            chapters: HashMap::new(),
+            log_lines: Vec::new(),
+            free_space: None,
+            sized_albums: Mutex::default(),
+            sized_playlists: Mutex::default(),
+            picked_tracks: BTreeMap::new(),
+            written_tracks: HashMap::new(),
+        }
     }
 
-    /// Execute sync based on selection
-    pub async fn sync(&mut self, selection: &SyncSelection) -> Result<SyncResult> {
-        let mut result = SyncResult::default();
+    /// Refuse to start a sync that won't fit in `bytes` of free space
+    pub fn with_free_space(mut self, bytes: u64) -> Self {
+        self.free_space = Some(bytes);
+        self
+    }
 
-        // Initialize storage directories
-        self.storage.init().await?;
+    /// Keep file names and paths within the limits of the device's filesystem (e.g. `vfat`)
+    pub fn with_fs_type(mut self, fs_type: &str) -> Self {
+        self.storage.set_fs_type(fs_type);
+        self
+    }
 
-        // Set up progress display
-        let multi = MultiProgress::new();
+    /// Set the options used for this sync
+    pub fn with_options(mut self, options: SyncOptions) -> Self {
+        if let Some(format) = &options.playlist_track_format {
+            self.storage.set_playlist_track_format(format);
+        }
+        self.storage.set_m3u_encoding(options.m3u_encoding);
+        self.storage.set_extended_m3u(!options.simple_m3u);
+        self.storage.set_playlist_format(options.playlist_format);
+        self.storage.set_modes(options.file_mode, options.dir_mode);
+        if let Some(layout) = &options.layout {
+            self.storage.set_layout(layout.clone());
+        }
+        let retries = options.retries.unwrap_or(DEFAULT_RETRIES);
+        self.downloader.set_retries(if options.on_error == OnError::Retry {
+            retries.max(ON_ERROR_RETRIES)
+        } else {
+            retries
+        });
+        self.downloader.set_transcode(options.transcode.clone());
+        if options.no_manifest {
+            // Start from nothing so no item counts as already synced
+            self.manifest = SyncManifest::new(&self.manifest.subsonic_url);
+        }
+        if options.auto_parallel {
+            self.downloader.set_auto_parallel(AUTO_PARALLEL_MAX);
+            self.pipeline_config.processing_parallelism =
+                std::thread::available_parallelism().map(|n| n.get()).unwrap_or(2);
+        }
+        self.options = options;
+        self
+    }
 
-        // Sync albums
-        for album in &selection.albums {
-            let spinner = multi.add(ProgressBar::new_spinner());
-            spinner.set_style(
-                ProgressStyle::default_spinner()
-                    .template("{spinner:.green} {msg}")
-                    .unwrap(),
-            );
+    /// Albums to download at once, no more than the download parallelism
+    fn album_parallelism(&self) -> usize {
+        self.options
+            .album_parallel
+            .clamp(1, self.download_parallelism().max(1))
+    }
 
-            match self.sync_album(album, &multi).await {
-                Ok((tracks, bytes)) => {
-                    result.albums_synced += 1;
-                    result.tracks_downloaded += tracks;
-                    result.bytes_downloaded += bytes;
-                    spinner.finish_with_message(format!(
-                        "Album synced: {} - {}",
-                        album.artist.as_deref().unwrap_or("Unknown"),
-                        album.name
-                    ));
-                }
-                Err(e) => {
-                    spinner.finish_with_message(format!("Failed: {} - {}", album.name, e));
-                    tracing::error!("Failed to sync album {}: {}", album.name, e);
-                }
-            }
+    /// Songs to download at once for the next album or playlist
+    fn download_parallelism(&self) -> usize {
+        if self.options.auto_parallel {
+            self.downloader.concurrency()
+        } else {
+            self.pipeline_config.download_parallelism
         }
+    }
 
-        // Sync playlists
-        for playlist in &selection.playlists {
-            let spinner = multi.add(ProgressBar::new_spinner());
-            spinner.set_style(
-                ProgressStyle::default_spinner()
-                    .template("{spinner:.green} {msg}")
-                    .unwrap(),
-            );
+    /// Record tracks of an item that failed to download
+    ///
+    /// Under `--on-error stop` this fails the item, which then stops the sync.
+    fn record_track_failures(&mut self, failures: usize, item: &str) -> Result<()> {
+        if failures > 0 && self.options.on_error == OnError::Stop {
+            anyhow::bail!("{} track(s) of {} failed to download", failures, item);
+        }
+        self.tracks_failed += failures;
+        Ok(())
+    }
 
-            match self.sync_playlist(playlist, &multi).await {
-                Ok((tracks, bytes)) => {
-                    result.playlists_synced += 1;
-                    result.tracks_downloaded += tracks;
-                    result.bytes_downloaded += bytes;
-                    spinner.finish_with_message(format!("Playlist synced: {}", playlist.name));
-                }
-                Err(e) => {
-                    spinner.finish_with_message(format!("Failed: {} - {}", playlist.name, e));
-                    tracing::error!("Failed to sync playlist {}: {}", playlist.name, e);
-                }
-            }
+    /// Stop the sync after a failed item if the error policy says to
+    async fn stop_on_error(&self, error: anyhow::Error) -> Result<()> {
+        if self.options.on_error != OnError::Stop {
+            return Ok(());
         }
+        // Keep what finished before the failure
+        self.save_manifest().await?;
+        Err(error.context("Sync stopped at the first failure (--on-error stop)"))
+    }
 
-        // Save manifest
-        self.manifest.save(&self.device_path)?;
+    /// Size to request cover art at from the server
+    fn cover_fetch_size(&self) -> u32 {
+        self.options
+            .cover_fetch_size
+            .unwrap_or_else(|| self.options.cover_config().max_size)
+    }
 
-        Ok(result)
+    /// Add a timestamped line to the device log, if it's enabled
+    fn log_event(&mut self, message: impl Into<String>) {
+        if self.options.device_log {
+            self.log_lines
+                .push(format!("{} {}", Local::now().format("%Y-%m-%d %H:%M:%S"), message.into()));
+        }
     }
 
-    /// Delete items that are no longer selected
-    pub async fn delete_deselected(
-        &mut self,
-        deletions: &DeletionSelection,
-        progress_tx: &mpsc::Sender<SyncProgress>,
-    ) -> Result<(usize, usize)> {
-        let mut albums_deleted = 0;
-        let mut playlists_deleted = 0;
+    /// Write the collected log lines to the device, replacing the last sync's log
+    ///
+    /// A failure here only warns: the log is a convenience, not sync state.
+    async fn write_device_log(&self) {
+        if !self.options.device_log {
+            return;
+        }
+        let mut contents = self.log_lines.join("\n");
+        contents.push('\n');
+        if let Err(e) = self.storage.write_file(Path::new(SYNC_LOG_FILE), contents.as_bytes()).await {
+            warn!("Failed to write {}: {:#}", SYNC_LOG_FILE, e);
+        }
+    }
 
-        if deletions.is_empty() {
-            return Ok((0, 0));
+    /// Directory cover embedding writes its temp files to
+    fn temp_dir(&self) -> PathBuf {
+        self.options.temp_dir.clone().unwrap_or_else(std::env::temp_dir)
+    }
+
+    /// File names to write album covers to, e.g. `cover.jpg` and `folder.jpg`
+    fn cover_file_names(&self) -> Vec<String> {
+        let extension = self.options.cover_format.extension();
+        if self.options.cover_names.is_empty() {
+            return vec![format!("cover.{}", extension)];
         }
+        self.options
+            .cover_names
+            .iter()
+            .map(|name| format!("{}.{}", name, extension))
+            .collect()
+    }
 
-        // Send start event
-        let _ = progress_tx.send(SyncProgress::DeletionStarted {
-            albums_to_delete: deletions.albums.len(),
-            playlists_to_delete: deletions.playlists.len(),
-        }).await;
+    /// Resize and re-encode downloaded cover art in the configured format
+    fn process_cover(&self, data: &[u8]) -> Result<Vec<u8>> {
+        cover_art::process_cover_art(data, &self.options.cover_config())
+    }
 
-        // Delete albums
-        for (album_id, artist, album) in &deletions.albums {
-            match self.storage.delete_album(artist, album).await {
-                Ok(()) => {
-                    self.manifest.remove_album(album_id);
-                    albums_deleted += 1;
-                    let _ = progress_tx.send(SyncProgress::AlbumDeleted {
-                        artist: artist.clone(),
-                        album: album.clone(),
-                    }).await;
+    /// Get processed cover art for an album
+    ///
+    /// Uses a local override image if one was given for the album, otherwise
+    /// downloads the server's cover art.
+    async fn album_cover(&self, album: &Album) -> Option<Vec<u8>> {
+        let data = if let Some(path) = self.options.cover_overrides.get(&album.id) {
+            match tokio::fs::read(path).await {
+                Ok(data) => {
+                    debug!("Using cover override {} for {}", path.display(), album.name);
+                    data
                 }
                 Err(e) => {
-                    let _ = progress_tx.send(SyncProgress::AlbumDeleteFailed {
-                        artist: artist.clone(),
-                        album: album.clone(),
-                        error: e.to_string(),
-                    }).await;
+                    warn!("Failed to read cover override {}: {}", path.display(), e);
+                    return None;
                 }
             }
-        }
-
-        // Delete playlists
-        for (playlist_id, name) in &deletions.playlists {
-            match self.storage.delete_playlist(name).await {
-                Ok(()) => {
-                    self.manifest.remove_playlist(playlist_id);
-                    playlists_deleted += 1;
-                    let _ = progress_tx.send(SyncProgress::PlaylistDeleted {
-                        name: name.clone(),
-                    }).await;
-                }
+        } else {
+            let cover_id = album.cover_art.as_ref()?;
+            match self.downloader.download_cover_art(cover_id, self.cover_fetch_size()).await {
+                Ok(data) => data.to_vec(),
                 Err(e) => {
-                    let _ = progress_tx.send(SyncProgress::PlaylistDeleteFailed {
-                        name: name.clone(),
-                        error: e.to_string(),
-                    }).await;
+                    warn!("Failed to download cover art: {}", e);
+                    return None;
                 }
             }
+        };
+
+        match self.process_cover(&data) {
+            Ok(processed) => Some(processed),
+            Err(e) => {
+                warn!("Failed to process cover art: {}", e);
+                None
+            }
         }
+    }
 
-        Ok((albums_deleted, playlists_deleted))
+    /// Pictures to embed in an album's tracks
+    ///
+    /// The processed front cover, plus under `--all-art` the album's other
+    /// artwork. Artwork that fails to download or process is left out.
+    async fn album_pictures(&self, album: &Album, front: Option<&[u8]>) -> Vec<CoverPicture> {
+        let mut pictures: Vec<CoverPicture> = front.map(|data| CoverPicture::front(data.to_vec())).into_iter().collect();
+        if !self.options.all_art {
+            return pictures;
+        }
+
+        let extra = album
+            .cover_art_ids
+            .iter()
+            .filter(|id| album.cover_art.as_ref() != Some(*id));
+        for (index, cover_id) in extra.enumerate() {
+            let processed = match self.downloader.download_cover_art(cover_id, self.cover_fetch_size()).await {
+                Ok(data) => self.process_cover(&data),
+                Err(e) => Err(e),
+            };
+            match processed {
+                Ok(data) => pictures.push(CoverPicture {
+                    kind: cover_art::extra_picture_type(index),
+                    data,
+                }),
+                Err(e) => warn!("Failed to get artwork {} for {}: {}", cover_id, album.name, e),
+            }
+        }
+        pictures
     }
 
-    /// Execute sync with progress updates sent to a channel (for TUI)
-    pub async fn sync_with_progress(
-        &mut self,
-        selection: &SyncSelection,
-        deletions: &DeletionSelection,
-        progress_tx: mpsc::Sender<SyncProgress>,
-    ) -> Result<SyncResult> {
-        let mut result = SyncResult::default();
+    /// Backdate a written track to when it was added to the server (best-effort)
+    fn preserve_date(&self, path: &Path, created: Option<DateTime<Utc>>) {
+        if !self.options.preserve_dates {
+            return;
+        }
 
-        // Initialize storage directories
-        self.storage.init().await?;
+        let Some(created) = created else {
+            debug!("No created date for {}", path.display());
+            return;
+        };
 
-        // Phase 1: Delete deselected items first
-        let (albums_deleted, playlists_deleted) = self.delete_deselected(deletions, &progress_tx).await?;
+        if let Err(e) = self.storage.set_file_mtime(path, created) {
+            warn!("{:#}", e);
+        }
+    }
 
-        // Send start event for downloads
-        let _ = progress_tx.send(SyncProgress::Started {
-            total_albums: selection.albums.len(),
-            total_playlists: selection.playlists.len(),
-        }).await;
+    /// Record tracks whose cover art couldn't be embedded
+    ///
+    /// In strict mode this is an error, so the item is not marked as synced.
+    fn record_cover_failures(&mut self, failures: usize, item: &str) -> Result<()> {
+        if failures == 0 {
+            return Ok(());
+        }
 
-        // Sync albums
-        for album in &selection.albums {
-            let artist = album.artist.as_deref().unwrap_or("Unknown Artist").to_string();
+        if self.options.strict_cover {
+            anyhow::bail!(
+                "Cover art could not be embedded in {} track(s) of {}",
+                failures,
+                item
+            );
+        }
 
-            match self.sync_album_with_progress(album, &progress_tx).await {
-                Ok((tracks, bytes)) => {
-                    if tracks > 0 {
-                        result.albums_synced += 1;
-                        result.tracks_downloaded += tracks;
-                        result.bytes_downloaded += bytes;
-                        let _ = progress_tx.send(SyncProgress::AlbumCompleted {
-                            artist: artist.clone(),
-                            album: album.name.clone(),
-                        }).await;
-                    } else {
-                        let _ = progress_tx.send(SyncProgress::AlbumSkipped {
-                            artist: artist.clone(),
-                            album: album.name.clone(),
-                        }).await;
-                    }
-                }
-                Err(e) => {
-                    let _ = progress_tx.send(SyncProgress::Error {
-                        message: format!("Album {} - {}: {}", artist, album.name, e),
-                    }).await;
-                    tracing::error!("Failed to sync album {}: {}", album.name, e);
-                }
+        self.cover_fallbacks += failures;
+        Ok(())
+    }
+
+    /// Write album notes from getAlbumInfo2 next to the tracks (best-effort)
+    async fn write_album_notes(&self, album: &Album, album_path: &Path) {
+        if !self.options.write_album_notes {
+            return;
+        }
+
+        let artist = album.artist.as_deref().unwrap_or("Unknown Artist");
+        let info = match self.client.get_album_info2(&album.id).await {
+            Ok(info) => info,
+            Err(e) => {
+                debug!("No album info for {} - {}: {}", artist, album.name, e);
+                return;
             }
+        };
+
+        let mut notes = info.notes.as_deref().map(notes_to_text).unwrap_or_default();
+        if notes.is_empty() {
+            debug!("Server has no notes for {} - {}", artist, album.name);
+            return;
         }
 
-        // Sync playlists
-        for playlist in &selection.playlists {
-            match self.sync_playlist_with_progress(playlist, &progress_tx).await {
-                Ok((tracks, bytes)) => {
-                    if tracks > 0 {
-                        result.playlists_synced += 1;
-                        result.tracks_downloaded += tracks;
-                        result.bytes_downloaded += bytes;
-                        let _ = progress_tx.send(SyncProgress::PlaylistCompleted {
-                            name: playlist.name.clone(),
-                        }).await;
-                    } else {
-                        let _ = progress_tx.send(SyncProgress::PlaylistSkipped {
+        if let Some(mbid) = info.music_brainz_id.filter(|id| !id.is_empty()) {
+            notes.push_str(&format!("\n\nMusicBrainz ID: {}", mbid));
+        }
+        notes.push('\n');
+
+        if let Err(e) = self.storage.write_album_notes(album_path, &notes).await {
+            warn!("Failed to write notes.txt: {}", e);
+        }
+    }
+
+    /// Fetch bookmarks to use as chapter markers (best-effort)
+    async fn load_chapters(&mut self) {
+        self.chapters.clear();
+        if !self.options.with_chapters {
+            return;
+        }
+
+        let bookmarks = match self.client.get_bookmarks().await {
+            Ok(bookmarks) => bookmarks,
+            Err(e) => {
+                warn!("Failed to fetch bookmarks, skipping chapters: {}", e);
+                return;
+            }
+        };
+
+        for bookmark in bookmarks {
+            self.chapters
+                .entry(bookmark.entry.id)
+                .or_default()
+                .push(Chapter {
+                    start_ms: bookmark.position,
+                    title: bookmark
+                        .comment
+                        .filter(|c| !c.is_empty())
+                        .unwrap_or_else(|| "Bookmark".to_string()),
+                });
+        }
+        debug!("Loaded chapters for {} tracks", self.chapters.len());
+    }
+
+    /// Write a .cue chapter sidecar next to a track that has bookmarks (best-effort)
+    async fn write_chapters(&self, path: &Path, song: &Song) {
+        let Some(chapters) = self.chapters.get(&song.id) else {
+            return;
+        };
+
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let performer = song.artist.as_deref().unwrap_or("Unknown Artist");
+        let cue = generate_cue(performer, &song.title, &file_name, chapters);
+        if let Err(e) = self.storage.write_sidecar(path, "cue", &cue).await {
+            warn!("Failed to write chapters for {}: {}", song.title, e);
+        }
+    }
+
+    /// Drop videos (unless `--include-video`) and songs below `--min-bitrate`,
+    /// remembering them for the sync report
+    ///
+    /// Songs the server reports no bitrate for are kept. Returns `None` when
+    /// every song was dropped, so the item can be skipped entirely.
+    fn filter_songs(&mut self, songs: Vec<Song>, item: &str) -> Option<Vec<Song>> {
+        let (kept, skipped) = self.screen_songs(songs, item);
+        self.record_skipped(skipped);
+        kept
+    }
+
+    /// [`Self::filter_songs`] without recording what was dropped, which is returned instead
+    fn screen_songs(&self, songs: Vec<Song>, item: &str) -> (Option<Vec<Song>>, SkippedSongs) {
+        let had_songs = !songs.is_empty();
+        let mut kept = Vec::with_capacity(songs.len());
+        let mut skipped = SkippedSongs::default();
+
+        for song in songs {
+            if song.is_video() && !self.options.include_video {
+                let content_type = song.content_type.as_deref().unwrap_or_default();
+                warn!("Skipping video {} ({}); use --include-video to sync it", song.title, content_type);
+                skipped
+                    .videos
+                    .push(format!("{}: {} ({})", item, song.title, content_type));
+                continue;
+            }
+
+            if let (Some(min), Some(rate)) = (self.options.min_bitrate, song.bit_rate)
+                && rate < min
+            {
+                info!("Skipping {} ({} kbps, below {} kbps)", song.title, rate, min);
+                skipped
+                    .low_bitrate
+                    .push(format!("{}: {} ({} kbps)", item, song.title, rate));
+                continue;
+            }
+
+            kept.push(song);
+        }
+
+        let kept = if kept.is_empty() && had_songs { None } else { Some(kept) };
+        (kept, skipped)
+    }
+
+    /// Remember songs dropped by [`Self::screen_songs`] for the sync report
+    fn record_skipped(&mut self, skipped: SkippedSongs) {
+        self.videos_skipped.extend(skipped.videos);
+        self.low_bitrate_skipped.extend(skipped.low_bitrate);
+    }
+
+    /// Fetch the songs of an album, or the files of a music folder
+    async fn album_songs(&self, album: &Album) -> Result<Vec<Song>> {
+        if let Some(songs) = self.sized_albums.lock().unwrap().remove(&album.id) {
+            return Ok(songs);
+        }
+        match album.source {
+            AlbumSource::Id3 => Ok(self.client.get_album(&album.id).await?.song),
+            AlbumSource::Folder => Ok(self
+                .client
+                .get_music_directory(&album.id)
+                .await?
+                .child
+                .into_iter()
+                .filter(|c| !c.is_dir)
+                .map(|c| c.song)
+                .collect()),
+        }
+    }
+
+    /// Fetch a playlist with its songs
+    async fn playlist_details(&self, playlist: &Playlist) -> Result<PlaylistWithSongs> {
+        if let Some(details) = self.sized_playlists.lock().unwrap().remove(&playlist.id) {
+            return Ok(details);
+        }
+        self.client.get_playlist(&playlist.id).await
+    }
+
+    /// Check the selection fits on the device before anything is deleted or downloaded
+    ///
+    /// Only runs when the free space is known. Sizes are what the server
+    /// reports for each song (see [`Self::estimated_track_size`]); albums and
+    /// playlists that can't be fetched are left out here and fail in the sync
+    /// itself. What was fetched is kept for the sync. Returns the estimated
+    /// download size when the check ran.
+    async fn check_free_space(&self, selection: &SyncSelection, deletions: &DeletionSelection) -> Result<Option<u64>> {
+        self.sized_albums.lock().unwrap().clear();
+        self.sized_playlists.lock().unwrap().clear();
+        let Some(free_space) = self.free_space else {
+            return Ok(None);
+        };
+
+        let mut required = FREE_SPACE_MARGIN;
+        for album in selection.albums.iter().filter(|a| !self.is_album_synced(a)) {
+            match self.album_songs(album).await {
+                Ok(songs) => {
+                    required += songs
+                        .iter()
+                        .filter(|s| self.is_track_picked(&album.id, &s.id))
+                        .map(|s| self.estimated_track_size(s))
+                        .sum::<u64>();
+                    self.sized_albums.lock().unwrap().insert(album.id.clone(), songs);
+                }
+                Err(e) => debug!("Couldn't size album {}: {:#}", album.name, e),
+            }
+        }
+        for playlist in selection.playlists.iter().filter(|p| !self.is_playlist_synced(p)) {
+            match self.playlist_details(playlist).await {
+                Ok(details) => {
+                    required += details.songs.iter().map(|s| self.estimated_track_size(s)).sum::<u64>();
+                    self.sized_playlists.lock().unwrap().insert(playlist.id.clone(), details);
+                }
+                Err(e) => debug!("Couldn't size playlist {}: {:#}", playlist.name, e),
+            }
+        }
+
+        let available = free_space + self.freed_by(deletions);
+        debug!("Sync needs about {} bytes, {} available", required, available);
+        if required > available {
+            let error = InsufficientSpace { required, available };
+            if !self.options.ignore_free_space {
+                return Err(error.into());
+            }
+            warn!("{}; syncing anyway", error);
+        }
+        Ok(Some(required.saturating_sub(FREE_SPACE_MARGIN)))
+    }
+
+    /// Bytes a track is expected to take on the device
+    ///
+    /// With a transcode bitrate the server's size is replaced by bitrate ×
+    /// duration. Without a target format the server only re-encodes songs
+    /// above the bitrate, so the smaller of the two is used. Transcoding with
+    /// no bitrate keeps the original size, as the server's default is unknown.
+    fn estimated_track_size(&self, song: &Song) -> u64 {
+        let size = song.size.unwrap_or_else(|| estimated_size(song.duration.unwrap_or(0)));
+        let Some(transcode) = &self.options.transcode else {
+            return size;
+        };
+        let reformats = transcode.format.as_deref().is_some_and(|format| format != "raw");
+        match (transcode.max_bitrate, song.duration) {
+            // kbps to bytes per second is * 1000 / 8
+            (Some(kbps), Some(secs)) if reformats => u64::from(secs) * u64::from(kbps) * 125,
+            (Some(kbps), Some(secs)) if transcode.format.is_none() => size.min(u64::from(secs) * u64::from(kbps) * 125),
+            _ => size,
+        }
+    }
+
+    /// Bytes the deletions will free, from the track sizes in the manifest
+    fn freed_by(&self, deletions: &DeletionSelection) -> u64 {
+        let albums = deletions
+            .albums
+            .iter()
+            .filter_map(|(id, _, _)| self.manifest.album(id))
+            .flat_map(|a| &a.tracks);
+        let playlists = deletions
+            .playlists
+            .iter()
+            .filter_map(|(id, _)| self.manifest.synced_playlists.iter().find(|p| &p.id == id))
+            .flat_map(|p| &p.tracks);
+        albums.chain(playlists).map(|t| t.size).sum()
+    }
+
+    /// Albums in the order they should be synced
+    fn album_order<'a>(&self, albums: &'a [Album]) -> Vec<&'a Album> {
+        if self.options.first_complete {
+            smallest_first(albums)
+        } else {
+            albums.iter().collect()
+        }
+    }
+
+    /// Signature of a track just written to `path`, for the manifest
+    fn track_signature(&self, path: &Path, data: &[u8]) -> Option<TrackSignature> {
+        self.storage.relative_path(path).map(|p| TrackSignature::new(p, data))
+    }
+
+    /// Where a song was already written in this sync, if `--dedup` is on
+    fn written_copy(&self, song_id: &str) -> Option<WrittenTrack> {
+        if !self.options.dedup {
+            return None;
+        }
+        self.written_tracks.get(song_id).cloned()
+    }
+
+    /// Remember where a song was first written, for `--dedup`
+    fn remember_written(&mut self, song_id: &str, path: &Path, signature: Option<&TrackSignature>) {
+        if !self.options.dedup {
+            return;
+        }
+        self.written_tracks
+            .entry(song_id.to_string())
+            .or_insert_with(|| WrittenTrack {
+                path: path.to_path_buf(),
+                signature: signature.cloned(),
+            });
+    }
+
+    /// Link (or copy) an already written song to `dest`, returning its signature there
+    async fn link_written(&self, written: &WrittenTrack, dest: &Path) -> Result<Option<TrackSignature>> {
+        self.storage.link_or_copy(&written.path, dest).await?;
+        let relative = self.storage.relative_path(dest);
+        Ok(written
+            .signature
+            .clone()
+            .zip(relative)
+            .map(|(signature, path)| TrackSignature { path, ..signature }))
+    }
+
+    /// Link a song of an album to where this sync already wrote it (`--dedup`)
+    async fn link_album_duplicate(
+        &self,
+        entry: &mut SyncedAlbum,
+        folder: &AlbumFolder<'_>,
+        song: &Song,
+        written: &WrittenTrack,
+        compilation: bool,
+    ) -> Result<()> {
+        let track = TrackName {
+            disc: song.disc_number,
+            track: song.track.unwrap_or(1),
+            title: &album_track_title(song, compilation),
+        };
+        let path = self.storage.album_track_path(folder, &track, &written.extension());
+        entry.tracks.extend(self.link_written(written, &path).await?);
+        self.write_chapters(&path, song).await;
+
+        debug!("Linked {} to {}", song.title, written.path.display());
+        entry.track_count += 1;
+        entry.synced_tracks.push(song.id.clone());
+        Ok(())
+    }
+
+    /// Move the tracks an earlier sync of a playlist left on the device to
+    /// their places in the playlist as it is now, so only new songs download
+    ///
+    /// Returns the filename and signature of each kept track by position. A
+    /// track is kept when its song is still in the playlist and its file is
+    /// still there, named for the song: older manifests didn't list the
+    /// tracks in song order, so the name is what ties the two together.
+    async fn keep_playlist_tracks(
+        &mut self,
+        playlist: &Playlist,
+        songs: &[Song],
+    ) -> Result<BTreeMap<usize, (String, TrackSignature)>> {
+        let Some(previous) = self.manifest.synced_playlists.iter().find(|p| p.id == playlist.id) else {
+            return Ok(BTreeMap::new());
+        };
+        let mut on_device: HashMap<String, TrackSignature> = HashMap::new();
+        for (song_id, track) in previous.song_ids.iter().zip(&previous.tracks) {
+            let Some(song) = songs.iter().find(|s| &s.id == song_id) else {
+                continue;
+            };
+            let named_for_song = Path::new(&track.path)
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().contains(&sanitize_filename(&song.title)));
+            if named_for_song && self.storage.track_exists(&self.storage.device_path(&track.path)).await {
+                on_device.entry(song_id.clone()).or_insert_with(|| track.clone());
+            }
+        }
+
+        // Where each kept track goes
+        let dir = self.storage.playlist_dir(&playlist.name);
+        let mut moves = Vec::new();
+        for (index, song) in songs.iter().enumerate() {
+            let Some(track) = on_device.remove(&song.id) else {
+                continue;
+            };
+            let extension = Path::new(&track.path)
+                .extension()
+                .map(|e| e.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "mp3".to_string());
+            let filename = self.storage.playlist_track_file(
+                &playlist.name,
+                index + 1,
+                songs.len(),
+                &self.playlist_track_artist(song),
+                &song.title,
+                &extension,
+            );
+            let from = self.storage.device_path(&track.path);
+            moves.push((index + 1, song, track, from, dir.join(&filename), filename));
+        }
+
+        // A track can't take the place of another kept one before that has
+        // moved on, so those go by way of a temporary name, and last
+        let sources: HashSet<PathBuf> = moves.iter().map(|m| m.3.clone()).collect();
+        let (mut last, first): (Vec<_>, Vec<_>) = moves
+            .into_iter()
+            .partition(|(_, _, _, from, to, _)| from != to && sources.contains(to));
+        for (_, _, _, from, _, filename) in &mut last {
+            let temp = from.with_file_name(format!(".{}.moving", filename));
+            self.storage.move_file(from, &temp).await?;
+            *from = temp;
+        }
+
+        let mut kept = BTreeMap::new();
+        for (position, song, track, from, to, filename) in first.into_iter().chain(last) {
+            self.storage.move_file(&from, &to).await?;
+            let signature = TrackSignature {
+                path: self.storage.relative_path(&to).unwrap_or(track.path),
+                ..track
+            };
+            self.remember_written(&song.id, &to, Some(&signature));
+            kept.insert(position, (filename, signature));
+        }
+        if !kept.is_empty() {
+            info!(
+                "Keeping {} of {} track(s) of {} already on the device",
+                kept.len(),
+                songs.len(),
+                playlist.name
+            );
+        }
+        Ok(kept)
+    }
+
+    /// Delete the tracks an earlier sync of an album or playlist wrote that aren't among its `tracks` now
+    ///
+    /// Those are the songs the server dropped since (or tracks that moved).
+    async fn remove_stale_tracks(&self, previous: &[TrackSignature], tracks: &[TrackSignature]) {
+        for track in previous.iter().filter(|t| !tracks.iter().any(|n| n.path == t.path)) {
+            let path = self.storage.device_path(&track.path);
+            if !self.storage.track_exists(&path).await {
+                continue;
+            }
+            match self.storage.delete_track(&path).await {
+                Ok(()) => info!("Removed stale track: {}", path.display()),
+                Err(e) => warn!("Failed to remove {}: {:#}", path.display(), e),
+            }
+        }
+    }
+
+    /// [`Self::remove_stale_tracks`] for a playlist
+    async fn remove_stale_playlist_tracks(&self, playlist: &Playlist, tracks: &[TrackSignature]) {
+        if let Some(previous) = self.manifest.synced_playlists.iter().find(|p| p.id == playlist.id) {
+            self.remove_stale_tracks(&previous.tracks, tracks).await;
+        }
+    }
+
+    /// [`Self::remove_stale_tracks`] for an album
+    async fn remove_stale_album_tracks(&self, entry: &SyncedAlbum) {
+        if let Some(previous) = self.manifest.album(&entry.id) {
+            self.remove_stale_tracks(&previous.tracks, &entry.tracks).await;
+        }
+    }
+
+    /// Link a playlist track to where this sync already wrote its song (`--dedup`)
+    ///
+    /// Returns the filename for the M3U and the track's signature.
+    async fn link_playlist_duplicate(
+        &self,
+        playlist: &Playlist,
+        position: usize,
+        total: usize,
+        song: &Song,
+        artist: &str,
+        written: &WrittenTrack,
+    ) -> Result<(String, Option<TrackSignature>)> {
+        let filename = self.storage.playlist_track_file(
+            &playlist.name,
+            position,
+            total,
+            artist,
+            &song.title,
+            &written.extension(),
+        );
+        let path = self.storage.playlist_dir(&playlist.name).join(&filename);
+        let signature = self.link_written(written, &path).await?;
+        self.write_chapters(&path, song).await;
+
+        debug!("Linked {} to {}", song.title, written.path.display());
+        Ok((filename, signature))
+    }
+
+    /// Manifest entry to write an album into, keeping tracks from an earlier, incomplete sync
+    ///
+    /// A track is kept when the manifest records its song as written and its
+    /// file is still on the device; everything else is downloaded again.
+    async fn resume_album(
+        &self,
+        album: &Album,
+        folder: &AlbumFolder<'_>,
+        songs: &[Song],
+        compilation: bool,
+    ) -> SyncedAlbum {
+        let mut entry = SyncedAlbum {
+            id: album.id.clone(),
+            artist: folder.artist.to_string(),
+            album: album.name.clone(),
+            genre: folder.genre.map(str::to_string),
+            folder: self.storage.relative_path(&self.storage.album_dir(folder)),
+            track_count: 0,
+            expected_tracks: Some(songs.len() as u32),
+            server_tracks: album.song_count,
+            synced_tracks: Vec::new(),
+            partial: self.picked_tracks.contains_key(&album.id),
+            synced_at: Utc::now(),
+            tracks: Vec::new(),
+        };
+        let Some(previous) = self.manifest.album(&album.id) else {
+            return entry;
+        };
+
+        for song in songs.iter().filter(|s| previous.synced_tracks.contains(&s.id)) {
+            let path = self.album_track_path(folder, song, compilation);
+            if !self.storage.track_exists(&path).await {
+                continue;
+            }
+
+            entry.track_count += 1;
+            entry.synced_tracks.push(song.id.clone());
+            let relative = self.storage.relative_path(&path);
+            entry
+                .tracks
+                .extend(previous.tracks.iter().find(|t| Some(&t.path) == relative.as_ref()).cloned());
+        }
+
+        if entry.track_count > 0 {
+            info!(
+                "Resuming {} - {}: {} of {} track(s) already on the device",
+                folder.artist,
+                album.name,
+                entry.track_count,
+                songs.len()
+            );
+        }
+        entry
+    }
+
+    /// Where a song of an album is (or would be) on the device
+    fn album_track_path(&self, folder: &AlbumFolder<'_>, song: &Song, compilation: bool) -> PathBuf {
+        let track = TrackName {
+            disc: song.disc_number,
+            track: song.track.unwrap_or(1),
+            title: &album_track_title(song, compilation),
+        };
+        self.storage.album_track_path(folder, &track, &self.track_extension(song))
+    }
+
+    /// Whether a song is wanted: its album is selected whole, or the song was picked
+    fn is_track_picked(&self, album_id: &str, song_id: &str) -> bool {
+        self.picked_tracks.get(album_id).is_none_or(|ids| ids.contains(song_id))
+    }
+
+    /// Whether an album is on the device with the tracks selected in the current sync
+    ///
+    /// An album the server now lists a different number of songs for isn't.
+    /// Syncing it again keeps the tracks still on the device, downloads the
+    /// new songs, and removes the tracks of songs the server dropped.
+    fn is_album_synced(&self, album: &Album) -> bool {
+        self.manifest.is_album_synced_with(&album.id, self.picked_tracks.get(&album.id))
+            && !self.manifest.album(&album.id).is_some_and(|a| a.is_outdated(album.song_count))
+    }
+
+    /// Whether a playlist is on the device complete and up to date, with its file in the chosen format
+    fn is_playlist_synced(&self, playlist: &Playlist) -> bool {
+        self.manifest.is_playlist_synced_in(&playlist.id, self.options.playlist_format)
+            && !self
+                .manifest
+                .synced_playlists
+                .iter()
+                .any(|p| p.id == playlist.id && p.is_outdated(playlist))
+    }
+
+    /// Write a playlist's file, removing one an earlier sync wrote in another format
+    async fn write_playlist_file(&self, playlist: &Playlist, tracks: &[M3uTrack]) -> Result<()> {
+        let old_format = self
+            .manifest
+            .synced_playlists
+            .iter()
+            .find(|p| p.id == playlist.id)
+            .map(|p| p.format)
+            .filter(|&format| format != self.options.playlist_format);
+        if let Some(format) = old_format
+            && let Err(e) = self.storage.delete_playlist_file(&playlist.name, format).await
+        {
+            debug!("Failed to delete old {} of {}: {}", format.file_name(), playlist.name, e);
+        }
+
+        self.storage.write_playlist_file(&playlist.name, tracks).await?;
+        Ok(())
+    }
+
+    /// Record an album that failed, with the songs picked from it
+    fn add_failed_album(&mut self, album: &Album) {
+        self.manifest.add_failed_album(album, self.picked_tracks.get(&album.id));
+    }
+
+    /// Split the songs of a partly selected album into the picked ones and
+    /// those an earlier sync wrote that are no longer picked
+    fn pick_album_songs(&self, album: &Album, songs: Vec<Song>) -> (Vec<Song>, Vec<Song>) {
+        let (picked, dropped): (Vec<Song>, Vec<Song>) =
+            songs.into_iter().partition(|s| self.is_track_picked(&album.id, &s.id));
+
+        let unpicked = match self.manifest.album(&album.id) {
+            Some(previous) => dropped
+                .into_iter()
+                .filter(|s| previous.synced_tracks.contains(&s.id))
+                .collect(),
+            None => Vec::new(),
+        };
+        (picked, unpicked)
+    }
+
+    /// Delete songs that are no longer picked from the device, so narrowing
+    /// the pick removes just those files
+    async fn remove_unpicked_songs(&self, folder: &AlbumFolder<'_>, songs: &[Song], compilation: bool) {
+        for song in songs {
+            let path = self.album_track_path(folder, song, compilation);
+            match self.storage.delete_track(&path).await {
+                Ok(()) => info!("Removed deselected track: {}", path.display()),
+                Err(e) => warn!("Failed to remove deselected track {}: {:#}", path.display(), e),
+            }
+        }
+    }
+
+    /// Extension a song is written with, as far as it's known before downloading
+    fn track_extension(&self, song: &Song) -> String {
+        self.options
+            .transcode
+            .as_ref()
+            .and_then(|t| t.format.clone())
+            .filter(|format| format != "raw")
+            .or_else(|| song.suffix.clone())
+            .unwrap_or_else(|| "mp3".to_string())
+    }
+
+    /// Record a track written into an album, saving the manifest now and then
+    ///
+    /// The album is recorded as incomplete until its last track is written,
+    /// so an interrupted sync resumes it instead of starting over. Saves are
+    /// spaced out because the whole manifest is rewritten each time.
+    async fn save_album_progress(&mut self, entry: &SyncedAlbum, last_save: &mut Instant) {
+        if last_save.elapsed() < PROGRESS_SAVE_INTERVAL {
+            return;
+        }
+        self.manifest.add_album(entry.clone());
+        self.save_manifest_progress().await;
+        *last_save = Instant::now();
+    }
+
+    /// Record a synced album in the manifest and save it
+    ///
+    /// Saving after every album means an interrupted sync keeps everything
+    /// that finished. A failed save is only logged here; the final save at
+    /// the end of the sync reports it. An album with tracks missing stays
+    /// unsynced, so the next sync resumes it, and is kept as failed for
+    /// `nutune retry`.
+    async fn record_album(&mut self, album: &Album, entry: Option<SyncedAlbum>) {
+        if let Some(entry) = entry {
+            let complete = entry.is_complete();
+            self.manifest.add_album(entry);
+            if !complete {
+                self.add_failed_album(album);
+            }
+            self.save_manifest_progress().await;
+        }
+    }
+
+    /// Record a synced playlist in the manifest and save it
+    ///
+    /// A playlist with tracks missing is also kept as failed, for `nutune retry`.
+    async fn record_playlist(&mut self, playlist: &Playlist, entry: Option<SyncedPlaylist>) {
+        if let Some(entry) = entry {
+            let complete = entry.is_complete();
+            self.manifest.add_playlist(entry);
+            if !complete {
+                self.manifest.add_failed_playlist(playlist);
+            }
+            self.save_manifest_progress().await;
+        }
+    }
+
+    async fn save_manifest_progress(&self) {
+        if let Err(e) = self.save_manifest().await {
+            warn!("Failed to save manifest: {:#}", e);
+        }
+    }
+
+    /// Save the manifest to the device, unless running with `--no-manifest`
+    async fn save_manifest(&self) -> Result<()> {
+        if self.options.no_manifest {
+            return Ok(());
+        }
+        self.storage.save_manifest(&self.manifest).await
+    }
+
+    /// Artist folder an album is written under, and whether it's a compilation
+    ///
+    /// One name per album (from its first track when needed), so an album
+    /// is never split across artist folders. An album whose tracks have
+    /// several artists goes under its album artist, or "Various Artists"
+    /// without one; if that isn't one of the track artists, it's a
+    /// compilation and its tracks are named with their own artist.
+    fn album_artist(&self, album: &Album, songs: &[Song]) -> (String, bool) {
+        let mut track_artists: Vec<&str> = songs
+            .iter()
+            .filter_map(|s| s.artist.as_deref())
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+            .collect();
+        track_artists.sort_unstable();
+        track_artists.dedup();
+
+        if track_artists.len() <= 1 {
+            let artist = self
+                .options
+                .artist_source
+                .unwrap_or(ArtistSource::Album)
+                .pick(album.artist.as_deref(), songs.first());
+            return (artist, false);
+        }
+
+        let artist = album
+            .artist
+            .as_deref()
+            .into_iter()
+            .chain(songs.iter().filter_map(|s| s.display_album_artist.as_deref()))
+            .map(str::trim)
+            .find(|a| !a.is_empty())
+            .unwrap_or(VARIOUS_ARTISTS);
+        let compilation = !track_artists.contains(&artist);
+        (artist.to_string(), compilation)
+    }
+
+    /// Artist a playlist track is named after
+    fn playlist_track_artist(&self, song: &Song) -> String {
+        self.options
+            .artist_source
+            .unwrap_or(ArtistSource::Track)
+            .pick(None, Some(song))
+    }
+
+    /// Genre folder to write an album under, if grouping by genre
+    fn genre_folder(&self, album: &Album) -> Option<String> {
+        let by_genre = match &self.options.layout {
+            Some(layout) => layout.uses_genre(),
+            None => self.options.genre_folders,
+        };
+        by_genre.then(|| genre_folder_name(album.genre.as_deref()))
+    }
+
+    /// Execute sync based on selection, deleting deselected items first
+    pub async fn sync(&mut self, selection: &SyncSelection, deletions: &DeletionSelection) -> Result<SyncResult> {
+        self.log_lines.clear();
+        self.log_event(format!(
+            "Sync started: {} album(s), {} playlist(s), {} deletion(s)",
+            selection.albums.len(),
+            selection.playlists.len(),
+            deletions.len()
+        ));
+
+        let result = self.sync_items(selection, deletions).await;
+        match &result {
+            Ok(result) => self.log_event(format!(
+                "Sync finished: {} album(s) and {} playlist(s) synced, {} track(s) ({:.1} MB), {} album(s), {} playlist(s) and {} track(s) failed",
+                result.albums_synced,
+                result.playlists_synced,
+                result.tracks_downloaded,
+                result.bytes_downloaded as f64 / 1024.0 / 1024.0,
+                result.albums_failed,
+                result.playlists_failed,
+                result.tracks_failed
+            )),
+            Err(e) => self.log_event(format!("Sync failed: {:#}", e)),
+        }
+        self.write_device_log().await;
+        result
+    }
+
+    /// The CLI sync loop behind [`Self::sync`]
+    async fn sync_items(&mut self, selection: &SyncSelection, deletions: &DeletionSelection) -> Result<SyncResult> {
+        let mut result = SyncResult::default();
+        self.picked_tracks = selection.tracks.clone();
+        self.cover_fallbacks = 0;
+        self.tracks_failed = 0;
+        self.low_bitrate_skipped.clear();
+        self.videos_skipped.clear();
+        self.written_tracks.clear();
+
+        self.check_free_space(selection, deletions).await?;
+        self.manifest.transcode = self.options.transcode_format().map(str::to_string);
+
+        // Initialize storage directories
+        self.storage.init().await?;
+        self.load_chapters().await;
+
+        // Set up progress display
+        let multi = MultiProgress::new();
+
+        if !deletions.is_empty() {
+            let (albums_deleted, playlists_deleted) = self.delete_with_spinner(deletions, &multi).await?;
+            result.albums_deleted = albums_deleted;
+            result.playlists_deleted = playlists_deleted;
+            result.deletions_failed = deletions.len() - albums_deleted - playlists_deleted;
+            self.log_event(format!(
+                "Deleted {} album(s) and {} playlist(s), {} failed",
+                albums_deleted, playlists_deleted, result.deletions_failed
+            ));
+        }
+
+        // Sync albums
+        let mut first_playable_reported = false;
+        for album in self.album_order(&selection.albums) {
+            let spinner = multi.add(ProgressBar::new_spinner());
+            spinner.set_style(
+                ProgressStyle::default_spinner()
+                    .template("{spinner:.green} {msg}")
+                    .unwrap(),
+            );
+
+            let tracks_failed_before = self.tracks_failed;
+            match self.sync_album(album, &multi).await {
+                Ok(ItemOutcome { tracks, bytes, entry }) => {
+                    self.record_album(album, entry).await;
+                    result.albums_synced += 1;
+                    result.tracks_downloaded += tracks;
+                    result.bytes_downloaded += bytes;
+                    spinner.finish_with_message(format!(
+                        "Album synced: {} - {}",
+                        album.artist.as_deref().unwrap_or("Unknown"),
+                        album.name
+                    ));
+                    self.log_event(format!(
+                        "Album synced: {} - {} ({} track(s), {} failed)",
+                        album.artist.as_deref().unwrap_or("Unknown"),
+                        album.name,
+                        tracks,
+                        self.tracks_failed - tracks_failed_before
+                    ));
+
+                    if self.options.first_complete
+                        && !first_playable_reported
+                        && tracks > 0
+                        && self.tracks_failed == tracks_failed_before
+                    {
+                        first_playable_reported = true;
+                        let _ = multi.println(format!(
+                            "First album ready: {} - {} (safe to unplug for partial use)",
+                            album.artist.as_deref().unwrap_or("Unknown"),
+                            album.name
+                        ));
+                    }
+                }
+                Err(e) => {
+                    result.albums_failed += 1;
+                    spinner.finish_with_message(format!("Failed: {} - {}", album.name, e));
+                    tracing::error!("Failed to sync album {}: {}", album.name, e);
+                    self.log_event(format!(
+                        "Album failed: {} - {}: {:#}",
+                        album.artist.as_deref().unwrap_or("Unknown"),
+                        album.name,
+                        e
+                    ));
+                    self.add_failed_album(album);
+                    self.stop_on_error(e).await?;
+                }
+            }
+        }
+
+        // Sync playlists
+        for playlist in &selection.playlists {
+            let spinner = multi.add(ProgressBar::new_spinner());
+            spinner.set_style(
+                ProgressStyle::default_spinner()
+                    .template("{spinner:.green} {msg}")
+                    .unwrap(),
+            );
+
+            let tracks_failed_before = self.tracks_failed;
+            match self.sync_playlist(playlist, &multi).await {
+                Ok(ItemOutcome { tracks, bytes, entry }) => {
+                    self.record_playlist(playlist, entry).await;
+                    result.playlists_synced += 1;
+                    result.tracks_downloaded += tracks;
+                    result.bytes_downloaded += bytes;
+                    spinner.finish_with_message(format!("Playlist synced: {}", playlist.name));
+                    self.log_event(format!(
+                        "Playlist synced: {} ({} track(s), {} failed)",
+                        playlist.name,
+                        tracks,
+                        self.tracks_failed - tracks_failed_before
+                    ));
+                }
+                Err(e) => {
+                    result.playlists_failed += 1;
+                    spinner.finish_with_message(format!("Failed: {} - {}", playlist.name, e));
+                    tracing::error!("Failed to sync playlist {}: {}", playlist.name, e);
+                    self.log_event(format!("Playlist failed: {}: {:#}", playlist.name, e));
+                    self.manifest.add_failed_playlist(playlist);
+                    self.stop_on_error(e).await?;
+                }
+            }
+        }
+
+        // Save manifest
+        self.save_manifest().await?;
+
+        result.cover_fallbacks = self.cover_fallbacks;
+        result.tracks_failed = self.tracks_failed;
+        result.low_bitrate_skipped = std::mem::take(&mut self.low_bitrate_skipped);
+        result.videos_skipped = std::mem::take(&mut self.videos_skipped);
+        Ok(result)
+    }
+
+    /// Run the deletion phase behind a spinner (CLI)
+    async fn delete_with_spinner(
+        &mut self,
+        deletions: &DeletionSelection,
+        multi: &MultiProgress,
+    ) -> Result<(usize, usize)> {
+        let spinner = multi.add(ProgressBar::new_spinner());
+        spinner.set_style(
+            ProgressStyle::default_spinner()
+                .template("{spinner:.red} {msg}")
+                .unwrap(),
+        );
+        spinner.set_message(format!("Deleting {} deselected item(s)...", deletions.len()));
+
+        let (tx, mut rx) = mpsc::channel(16);
+        let delete = async {
+            let tx = tx;
+            self.delete_deselected(deletions, &tx).await
+        };
+        let report = async {
+            while let Some(event) = rx.recv().await {
+                match event {
+                    SyncProgress::AlbumDeleted { artist, album } => {
+                        spinner.set_message(format!("Deleted: {} - {}", artist, album));
+                    }
+                    SyncProgress::PlaylistDeleted { name } => {
+                        spinner.set_message(format!("Deleted playlist: {}", name));
+                    }
+                    SyncProgress::AlbumDeleteFailed { artist, album, error } => {
+                        let _ = multi.println(format!("Failed to delete {} - {}: {}", artist, album, error));
+                    }
+                    SyncProgress::PlaylistDeleteFailed { name, error } => {
+                        let _ = multi.println(format!("Failed to delete playlist {}: {}", name, error));
+                    }
+                    _ => {}
+                }
+            }
+        };
+        let (deleted, ()) = tokio::join!(delete, report);
+        let (albums_deleted, playlists_deleted) = deleted?;
+
+        spinner.finish_with_message(format!(
+            "Deleted {} album(s), {} playlist(s)",
+            albums_deleted, playlists_deleted
+        ));
+        Ok((albums_deleted, playlists_deleted))
+    }
+
+    /// Re-embed cover art into every album in the manifest, without downloading audio
+    ///
+    /// Reads each synced track back from the device, embeds freshly fetched
+    /// cover art and rewrites it, along with the album's cover file.
+    pub async fn sync_covers(&mut self) -> Result<CoverRepairResult> {
+        let mut result = CoverRepairResult::default();
+        self.cover_fallbacks = 0;
+
+        let albums = self.manifest.synced_albums.clone();
+        for synced in &albums {
+            match self.repair_album_cover(synced).await {
+                Ok(Some((updated, kept))) => {
+                    if updated > 0 {
+                        result.albums_updated += 1;
+                    }
+                    result.tracks_updated += updated;
+                    result.tracks_kept += kept;
+                }
+                Ok(None) => result.albums_without_cover += 1,
+                Err(e) => {
+                    warn!("Failed to update cover art for {} - {}: {:#}", synced.artist, synced.album, e);
+                    result.albums_failed += 1;
+                }
+            }
+        }
+
+        self.save_manifest().await?;
+        result.cover_fallbacks = self.cover_fallbacks;
+        Ok(result)
+    }
+
+    /// Re-embed cover art into one synced album's tracks
+    ///
+    /// Tracks that already have acceptable cover art are left alone unless
+    /// `--force-cover` is set or the album has a cover override. Returns the
+    /// number of tracks rewritten and kept, or `None` if there was no cover
+    /// art to embed.
+    async fn repair_album_cover(&mut self, synced: &SyncedAlbum) -> Result<Option<(usize, usize)>> {
+        let item = format!("{} - {}", synced.artist, synced.album);
+        info!("Updating cover art: {}", item);
+
+        // Albums synced before signatures were recorded are found by listing their folder
+        let album_path = self.storage.synced_album_dir(synced);
+        let tracks: Vec<String> = if synced.tracks.is_empty() {
+            self.storage
+                .list_album_tracks(&album_path)
+                .await?
+                .iter()
+                .filter_map(|path| self.storage.relative_path(path))
+                .collect()
+        } else {
+            synced.tracks.iter().map(|t| t.path.clone()).collect()
+        };
+
+        // Check existing art first so albums that are fine cost no server requests or writes
+        let force = self.options.force_cover || self.options.cover_overrides.contains_key(&synced.id);
+        let mut kept_signatures = Vec::with_capacity(tracks.len());
+        let cover_config = self.options.cover_config();
+        for relative in &tracks {
+            let signature = if force {
+                None
+            } else {
+                let data = self.storage.read_file(Path::new(relative)).await?;
+                cover_art::has_acceptable_cover(&data, &cover_config).then(|| TrackSignature::new(relative.clone(), &data))
+            };
+            kept_signatures.push(signature);
+        }
+        let kept = kept_signatures.iter().flatten().count();
+        if kept == tracks.len() {
+            debug!("All tracks of {} already have cover art", item);
+            return Ok(Some((0, kept)));
+        }
+
+        let album = Album {
+            id: synced.id.clone(),
+            name: synced.album.clone(),
+            artist: Some(synced.artist.clone()),
+            artist_id: None,
+            cover_art: Some(self.synced_album_cover_id(synced).await),
+            cover_art_ids: Vec::new(),
+            song_count: Some(synced.track_count),
+            duration: None,
+            year: None,
+            genre: synced.genre.clone(),
+            created: None,
+            source: AlbumSource::Id3,
+        };
+        let Some(cover) = self.album_cover(&album).await else {
+            return Ok(None);
+        };
+        let pictures = [CoverPicture::front(cover.clone())];
+
+        let mut signatures = Vec::with_capacity(tracks.len());
+        let mut updated = 0;
+        for (relative, kept_signature) in tracks.iter().zip(kept_signatures) {
+            if let Some(signature) = kept_signature {
+                signatures.push(signature);
+                continue;
+            }
+
+            let path = Path::new(relative);
+            let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("mp3");
+            let data = self.storage.read_file(path).await?;
+
+            match cover_art::embed_cover_art_in_memory(&data, &pictures, None, None, extension, &self.temp_dir()) {
+                Ok(embedded) => {
+                    self.storage.write_file(path, &embedded).await?;
+                    signatures.push(TrackSignature::new(relative.clone(), &embedded));
+                    updated += 1;
+                }
+                Err(e) => {
+                    warn!("Failed to embed cover art in {}: {}", relative, e);
+                    self.record_cover_failures(1, &item)?;
+                    signatures.push(TrackSignature::new(relative.clone(), &data));
+                }
+            }
+        }
+
+        if let Err(e) = self
+            .storage
+            .write_cover_art(&album_path, &cover, &self.cover_file_names())
+            .await
+        {
+            debug!("Failed to write cover file: {}", e);
+        }
+
+        if let Some(entry) = self.manifest.synced_albums.iter_mut().find(|a| a.id == synced.id) {
+            entry.tracks = signatures;
+        }
+
+        Ok(Some((updated, kept)))
+    }
+
+    /// Cover art ID for an album already on the device
+    ///
+    /// Prefers the ID the server reports for the album's songs, falling back
+    /// to the album ID, which most servers also accept.
+    async fn synced_album_cover_id(&self, synced: &SyncedAlbum) -> String {
+        if self.options.cover_overrides.contains_key(&synced.id) {
+            return synced.id.clone();
+        }
+
+        match self.client.get_album(&synced.id).await {
+            Ok(album) => album
+                .song
+                .into_iter()
+                .find_map(|song| song.cover_art)
+                .unwrap_or_else(|| synced.id.clone()),
+            Err(e) => {
+                debug!("Couldn't look up {} for its cover art ID: {}", synced.id, e);
+                synced.id.clone()
+            }
+        }
+    }
+
+    /// Delete items that are no longer selected
+    pub async fn delete_deselected(
+        &mut self,
+        deletions: &DeletionSelection,
+        progress_tx: &mpsc::Sender<SyncProgress>,
+    ) -> Result<(usize, usize)> {
+        Ok(delete_items(&self.storage, &mut self.manifest, deletions, progress_tx).await)
+    }
+
+    /// Execute sync with progress updates sent to a channel (for TUI)
+    pub async fn sync_with_progress(
+        &mut self,
+        selection: &SyncSelection,
+        deletions: &DeletionSelection,
+        progress_tx: mpsc::Sender<SyncProgress>,
+    ) -> Result<SyncResult> {
+        let mut result = SyncResult::default();
+        self.picked_tracks = selection.tracks.clone();
+        self.cover_fallbacks = 0;
+        self.tracks_failed = 0;
+        self.low_bitrate_skipped.clear();
+        self.videos_skipped.clear();
+        self.written_tracks.clear();
+
+        let estimated_bytes = match self.check_free_space(selection, deletions).await {
+            Ok(estimate) => estimate,
+            Err(e) => {
+                if let Some(&InsufficientSpace { required, available }) = e.downcast_ref() {
+                    let _ = progress_tx.send(SyncProgress::InsufficientSpace { required, available }).await;
+                }
+                return Err(e);
+            }
+        };
+        self.manifest.transcode = self.options.transcode_format().map(str::to_string);
+
+        // Initialize storage directories
+        self.storage.init().await?;
+        self.load_chapters().await;
+
+        // Phase 1: Delete deselected items first
+        let (albums_deleted, playlists_deleted) = self.delete_deselected(deletions, &progress_tx).await?;
+        result.albums_deleted = albums_deleted;
+        result.playlists_deleted = playlists_deleted;
+        result.deletions_failed = deletions.len() - albums_deleted - playlists_deleted;
+
+        // Send start event for downloads, counting only what will really download
+        let albums_to_sync = selection
+            .albums
+            .iter()
+            .filter(|a| !self.is_album_synced(a))
+            .count();
+        let playlists_to_sync = selection
+            .playlists
+            .iter()
+            .filter(|p| !self.is_playlist_synced(p))
+            .count();
+        let _ = progress_tx.send(SyncProgress::Started {
+            total_albums: albums_to_sync,
+            total_playlists: playlists_to_sync,
+            already_synced: (selection.albums.len() - albums_to_sync)
+                + (selection.playlists.len() - playlists_to_sync),
+            estimated_bytes,
+        }).await;
+
+        // Sync albums, downloading up to `album_parallel` at once and writing them one by one
+        let album_parallel = self.album_parallelism();
+        let track_parallel = (self.download_parallelism() / album_parallel).max(1);
+        let mut first_playable_reported = false;
+        let order = self.album_order(&selection.albums);
+        for batch in order.chunks(album_parallel) {
+            let mut to_sync = Vec::with_capacity(batch.len());
+            for &album in batch {
+                if self.is_album_synced(album) {
+                    let _ = progress_tx.send(SyncProgress::AlbumSkipped {
+                        artist: album.artist.as_deref().unwrap_or("Unknown Artist").to_string(),
+                        album: album.name.clone(),
+                    }).await;
+                } else {
+                    to_sync.push(album);
+                }
+            }
+
+            let downloads = future::join_all(
+                to_sync
+                    .iter()
+                    .map(|album| self.download_album(album, track_parallel, &progress_tx)),
+            )
+            .await;
+
+            for (album, download) in to_sync.into_iter().zip(downloads) {
+                let artist = album.artist.as_deref().unwrap_or("Unknown Artist").to_string();
+                let tracks_failed_before = self.tracks_failed;
+                let outcome = match download {
+                    Ok(downloaded) => self.write_album(album, downloaded).await,
+                    Err(e) => Err(e),
+                };
+                match outcome {
+                    Ok(ItemOutcome { tracks, bytes, entry }) => {
+                        self.record_album(album, entry).await;
+                        if tracks > 0 {
+                            result.albums_synced += 1;
+                            result.tracks_downloaded += tracks;
+                            result.bytes_downloaded += bytes;
+                        }
+                        let _ = progress_tx.send(SyncProgress::AlbumCompleted {
+                            artist: artist.clone(),
+                            album: album.name.clone(),
+                        }).await;
+
+                        if self.options.first_complete
+                            && !first_playable_reported
+                            && tracks > 0
+                            && self.tracks_failed == tracks_failed_before
+                        {
+                            first_playable_reported = true;
+                            let _ = progress_tx.send(SyncProgress::FirstAlbumPlayable {
+                                artist: artist.clone(),
+                                album: album.name.clone(),
+                            }).await;
+                        }
+                    }
+                    Err(e) => {
+                        result.albums_failed += 1;
+                        let _ = progress_tx.send(SyncProgress::AlbumFailed {
+                            album_id: album.id.clone(),
+                            artist: artist.clone(),
+                            album: album.name.clone(),
+                            message: e.to_string(),
+                        }).await;
+                        tracing::error!("Failed to sync album {}: {}", album.name, e);
+                        self.add_failed_album(album);
+                        self.stop_on_error(e).await?;
+                    }
+                }
+            }
+        }
+
+        // Sync playlists
+        for playlist in &selection.playlists {
+            match self.sync_playlist_with_progress(playlist, &progress_tx).await {
+                Ok(ItemOutcome { tracks, bytes, entry }) => {
+                    self.record_playlist(playlist, entry).await;
+                    if tracks > 0 {
+                        result.playlists_synced += 1;
+                        result.tracks_downloaded += tracks;
+                        result.bytes_downloaded += bytes;
+                        let _ = progress_tx.send(SyncProgress::PlaylistCompleted {
+                            name: playlist.name.clone(),
+                        }).await;
+                    } else {
+                        let _ = progress_tx.send(SyncProgress::PlaylistSkipped {
                             name: playlist.name.clone(),
                         }).await;
                     }
                 }
                 Err(e) => {
-                    let _ = progress_tx.send(SyncProgress::Error {
-                        message: format!("Playlist {}: {}", playlist.name, e),
+                    result.playlists_failed += 1;
+                    let _ = progress_tx.send(SyncProgress::PlaylistFailed {
+                        playlist_id: playlist.id.clone(),
+                        name: playlist.name.clone(),
+                        message: e.to_string(),
                     }).await;
                     tracing::error!("Failed to sync playlist {}: {}", playlist.name, e);
+                    self.manifest.add_failed_playlist(playlist);
+                    self.stop_on_error(e).await?;
                 }
             }
         }
 
         // Save manifest
-        self.manifest.save(&self.device_path)?;
+        self.save_manifest().await?;
+
+        result.cover_fallbacks = self.cover_fallbacks;
+        result.tracks_failed = self.tracks_failed;
+        result.low_bitrate_skipped = std::mem::take(&mut self.low_bitrate_skipped);
+        result.videos_skipped = std::mem::take(&mut self.videos_skipped);
+        if result.cover_fallbacks > 0 {
+            let _ = progress_tx.send(SyncProgress::CoverEmbedFallbacks {
+                tracks: result.cover_fallbacks,
+            }).await;
+        }
 
         // Send completion event
         let _ = progress_tx.send(SyncProgress::Complete {
@@ -383,90 +2132,108 @@ impl SyncEngine {
         Ok(result)
     }
 
-    /// Sync a single album with progress reporting (pipelined parallel version)
-    async fn sync_album_with_progress(
-        &mut self,
+    /// Download and tag an album's tracks in memory, for [`Self::write_album`]
+    ///
+    /// Only reads the engine and the device, so under `--album-parallel` several albums
+    /// download at once. `parallelism` is this album's share of the downloads.
+    async fn download_album(
+        &self,
         album: &Album,
+        parallelism: usize,
         progress_tx: &mpsc::Sender<SyncProgress>,
-    ) -> Result<(usize, u64)> {
-        let artist = album.artist.as_deref().unwrap_or("Unknown Artist");
-
-        // Check if already synced
-        if self.manifest.is_album_synced(&album.id) {
-            debug!("Album already synced: {} - {}", artist, album.name);
-            return Ok((0, 0));
-        }
-
-        info!("Syncing album: {} - {}", artist, album.name);
+    ) -> Result<DownloadedAlbum> {
+        info!(
+            "Syncing album: {} - {}",
+            album.artist.as_deref().unwrap_or("Unknown Artist"),
+            album.name
+        );
 
         // Download and process cover art first (cached for all tracks)
-        let processed_cover: Option<Arc<Vec<u8>>> = if let Some(cover_id) = &album.cover_art {
-            match self.downloader.download_cover_art(cover_id).await {
-                Ok(data) => {
-                    // Process cover art once and cache it
-                    match cover_art::process_cover_art(&data) {
-                        Ok(processed) => Some(Arc::new(processed)),
-                        Err(e) => {
-                            warn!("Failed to process cover art: {}", e);
-                            None
-                        }
-                    }
-                }
-                Err(e) => {
-                    warn!("Failed to download cover art: {}", e);
-                    None
-                }
-            }
-        } else {
-            None
-        };
+        let cover = self.album_cover(album).await;
+        let pictures = Arc::new(self.album_pictures(album, cover.as_deref()).await);
 
         // Fetch album details with songs
-        let album_details = self.client.get_album(&album.id).await?;
-        let track_count = album_details.song.len();
+        let songs = self.album_songs(album).await?;
+        let (artist, compilation) = self.album_artist(album, &songs);
+        let (songs, skipped) = self.screen_songs(songs, &format!("{} - {}", artist, album.name));
+        let Some(songs) = songs else {
+            return Ok(DownloadedAlbum {
+                skipped,
+                tracks: None,
+            });
+        };
+
+        // Leave out tracks that weren't picked, and pick up where an interrupted sync of this album left off
+        let genre = self.genre_folder(album);
+        let folder = AlbumFolder {
+            genre: genre.as_deref(),
+            artist: &artist,
+            album: &album.name,
+            year: album.year,
+        };
+        let (songs, unpicked) = self.pick_album_songs(album, songs);
+        let entry = self.resume_album(album, &folder, &songs, compilation).await;
+        let (duplicates, songs): (Vec<_>, Vec<_>) = songs
+            .into_iter()
+            .filter(|s| !entry.synced_tracks.contains(&s.id))
+            .map(|s| (self.written_copy(&s.id), s))
+            .partition(|(written, _)| written.is_some());
+        let duplicates: Vec<(Song, WrittenTrack)> = duplicates
+            .into_iter()
+            .filter_map(|(written, song)| Some((song, written?)))
+            .collect();
+        let songs: Vec<Song> = songs.into_iter().map(|(_, song)| song).collect();
+        let track_count = songs.len();
 
         // Send start event
         let _ = progress_tx
             .send(SyncProgress::AlbumStarted {
-                artist: artist.to_string(),
+                artist: artist.clone(),
                 album: album.name.clone(),
                 track_count,
             })
             .await;
 
         // Create download tasks
-        let tasks: Vec<DownloadTask> = album_details
-            .song
+        let tasks: Vec<DownloadTask> = songs
             .iter()
             .map(|song| DownloadTask {
                 song: song.clone(),
-                artist: artist.to_string(),
-                album: album.name.clone(),
+                artist: artist.clone(),
             })
             .collect();
 
         // Stage 1: Download all tracks in parallel
-        let client = self.downloader.client_arc();
-        let parallelism = self.pipeline_config.download_parallelism;
+        let client = Arc::clone(&self.client);
+        let (retries, base_delay) = (self.downloader.retries(), self.downloader.base_delay());
+        let transcode = self.downloader.transcode();
+        let started = Instant::now();
         let progress_tx_clone = progress_tx.clone();
+        let completed = Arc::new(AtomicUsize::new(0));
 
         let downloads: Vec<DownloadResult> = stream::iter(tasks)
             .map(|task| {
                 let client = client.clone();
+                let transcode = transcode.as_ref();
+                let completed = Arc::clone(&completed);
+                let progress_tx = progress_tx.clone();
                 async move {
-                    let data = client.download(&task.song.id).await?;
-                    Ok::<_, anyhow::Error>(DownloadResult {
-                        song: task.song,
-                        data,
-                        artist: task.artist,
-                        album: task.album,
-                    })
+                    let download = download_task(&client, task, retries, base_delay, transcode).await?;
+                    let track_num = completed.fetch_add(1, Ordering::Relaxed) + 1;
+                    let _ = progress_tx
+                        .send(SyncProgress::TrackCompleted { track_num, total_tracks: track_count })
+                        .await;
+                    Ok::<_, anyhow::Error>(download)
                 }
             })
             .buffer_unordered(parallelism)
             .filter_map(|result| async {
                 match result {
-                    Ok(r) => Some(r),
+                    Ok(r) => {
+                        let bytes = r.data.len() as u64;
+                        let _ = progress_tx_clone.send(SyncProgress::BytesProgress { bytes }).await;
+                        Some(r)
+                    }
                     Err(e) => {
                         warn!("Download failed: {}", e);
                         None
@@ -476,13 +2243,9 @@ impl SyncEngine {
             .collect()
             .await;
 
-        // Send progress event for downloads completion
-        let _ = progress_tx_clone
-            .send(SyncProgress::TrackCompleted {
-                track_num: downloads.len(),
-                total_tracks: track_count,
-            })
-            .await;
+        let downloaded_bytes = downloads.iter().map(|d| d.data.len() as u64).sum();
+        let elapsed = started.elapsed();
+        let failures = track_count - downloads.len();
 
         // Stage 2: Convert to DownloadedTrack for pipeline processing
         let downloaded_tracks: Vec<DownloadedTrack> = downloads
@@ -490,60 +2253,136 @@ impl SyncEngine {
             .map(|dl| DownloadedTrack {
                 song: dl.song.clone(),
                 audio_data: dl.data,
-                artist: dl.artist,
-                album: dl.album,
                 track_number: dl.song.track.unwrap_or(1),
             })
             .collect();
 
         // Stage 3: Process cover art embedding in parallel
-        let processed_tracks = process_tracks_parallel(
+        let processed = process_tracks_parallel(
             downloaded_tracks,
-            processed_cover.clone(),
+            pictures,
+            Arc::new(album.clone()),
+            self.options.force_tags,
+            self.options.keep_cover(Some(&album.id)),
             self.pipeline_config.processing_parallelism,
             None, // Events handled at album level
+            &self.temp_dir(),
         )
         .await;
 
+        Ok(DownloadedAlbum {
+            skipped,
+            tracks: Some(AlbumTracks {
+                artist,
+                compilation,
+                genre,
+                entry,
+                unpicked,
+                duplicates,
+                cover,
+                processed,
+                downloaded_bytes,
+                elapsed,
+                failures,
+            }),
+        })
+    }
+
+    /// Write an album downloaded by [`Self::download_album`] to the device
+    async fn write_album(&mut self, album: &Album, downloaded: DownloadedAlbum) -> Result<ItemOutcome<SyncedAlbum>> {
+        self.record_skipped(downloaded.skipped);
+        let Some(AlbumTracks {
+            artist,
+            compilation,
+            genre,
+            mut entry,
+            unpicked,
+            duplicates,
+            cover,
+            processed,
+            downloaded_bytes,
+            elapsed,
+            failures,
+        }) = downloaded.tracks
+        else {
+            return Ok(ItemOutcome::skipped());
+        };
+
+        self.downloader.record_batch(downloaded_bytes, elapsed, failures);
+        self.record_track_failures(failures, &format!("{} - {}", artist, album.name))?;
+
+        let embed_failures = processed.iter().filter(|t| t.cover_embed_failed).count();
+        self.record_cover_failures(embed_failures, &format!("{} - {}", artist, album.name))?;
+
         // Stage 4: Write tracks to device
+        let folder = AlbumFolder {
+            genre: genre.as_deref(),
+            artist: &artist,
+            album: &album.name,
+            year: album.year,
+        };
+        self.remove_unpicked_songs(&folder, &unpicked, compilation).await;
+        for (song, written) in &duplicates {
+            self.link_album_duplicate(&mut entry, &folder, song, written, compilation).await?;
+        }
+
         let mut total_bytes: u64 = 0;
-        for track in &processed_tracks {
+        let mut last_save = Instant::now();
+        for track in &processed {
+            // Written by an album that downloaded alongside this one
+            if let Some(written) = self.written_copy(&track.song.id) {
+                self.link_album_duplicate(&mut entry, &folder, &track.song, &written, compilation).await?;
+                self.save_album_progress(&entry, &mut last_save).await;
+                continue;
+            }
             let extension = track.song.suffix.as_deref().unwrap_or("mp3");
 
             total_bytes += track.final_audio_data.len() as u64;
 
-            self.storage
+            let path = self
+                .storage
                 .write_album_track(
-                    &track.artist,
-                    &track.album,
-                    track.track_number,
-                    &track.song.title,
+                    &folder,
+                    &TrackName {
+                        disc: track.song.disc_number,
+                        track: track.track_number,
+                        title: &album_track_title(&track.song, compilation),
+                    },
                     extension,
                     &track.final_audio_data,
                 )
                 .await?;
+            let signature = self.track_signature(&path, &track.final_audio_data);
+            self.remember_written(&track.song.id, &path, signature.as_ref());
+            entry.tracks.extend(signature);
+            self.preserve_date(&path, track.song.created_at().or_else(|| album.created_at()));
+            self.write_chapters(&path, &track.song).await;
+
+            entry.track_count += 1;
+            entry.synced_tracks.push(track.song.id.clone());
+            self.save_album_progress(&entry, &mut last_save).await;
         }
 
         // Also save cover art as file (for file browsers/fallback)
-        if let Some(ref cover) = processed_cover
+        let album_path = self.storage.album_dir(&folder);
+        if let Some(ref cover) = cover
             && let Err(e) = self
                 .storage
-                .write_cover_art(artist, &album.name, cover)
+                .write_cover_art(&album_path, cover, &self.cover_file_names())
                 .await
             {
-                debug!("Failed to write cover.jpg: {}", e);
+                debug!("Failed to write cover file: {}", e);
             }
 
-        // Update manifest
-        self.manifest.add_album(SyncedAlbum {
-            id: album.id.clone(),
-            artist: artist.to_string(),
-            album: album.name.clone(),
-            track_count: processed_tracks.len() as u32,
-            synced_at: Utc::now(),
-        });
+        self.write_album_notes(album, &album_path).await;
+        self.remove_stale_album_tracks(&entry).await;
 
-        Ok((processed_tracks.len(), total_bytes))
+        entry.synced_at = Utc::now();
+        Ok(ItemOutcome {
+            tracks: processed.len(),
+            bytes: total_bytes,
+            entry: Some(entry),
+        })
     }
 
     /// Sync a single playlist with progress reporting (pipelined parallel version)
@@ -551,73 +2390,90 @@ impl SyncEngine {
         &mut self,
         playlist: &Playlist,
         progress_tx: &mpsc::Sender<SyncProgress>,
-    ) -> Result<(usize, u64)> {
+    ) -> Result<ItemOutcome<SyncedPlaylist>> {
         // Check if already synced
-        if self.manifest.is_playlist_synced(&playlist.id) {
+        if self.is_playlist_synced(playlist) {
             debug!("Playlist already synced: {}", playlist.name);
-            return Ok((0, 0));
+            return Ok(ItemOutcome::skipped());
         }
 
         info!("Syncing playlist: {}", playlist.name);
 
         // Fetch playlist details with songs
-        let playlist_details = self.client.get_playlist(&playlist.id).await?;
-        let track_count = playlist_details.songs.len();
-
-        // Send start event
-        let _ = progress_tx
-            .send(SyncProgress::PlaylistStarted {
-                name: playlist.name.clone(),
-                track_count,
-            })
-            .await;
+        let playlist_details = self.playlist_details(playlist).await?;
+        let Some(songs) = self.filter_songs(playlist_details.songs, &format!("Playlist: {}", playlist.name)) else {
+            return Ok(ItemOutcome::skipped());
+        };
+        let track_count = songs.len();
+        let kept = self.keep_playlist_tracks(playlist, &songs).await?;
 
-        // Create download tasks with cover art IDs
-        let tasks_with_covers: Vec<(DownloadTask, Option<String>)> = playlist_details
-            .songs
+        // Create download tasks with cover art IDs, setting aside songs this sync already wrote
+        let mut linked = Vec::new();
+        let tasks_with_covers: Vec<(usize, DownloadTask, Option<String>)> = songs
             .iter()
-            .map(|song| {
+            .enumerate()
+            .filter_map(|(index, song)| {
+                if kept.contains_key(&(index + 1)) {
+                    return None;
+                }
+                if let Some(written) = self.written_copy(&song.id) {
+                    linked.push((index + 1, song, written));
+                    return None;
+                }
                 let task = DownloadTask {
                     song: song.clone(),
-                    artist: song
-                        .artist
-                        .clone()
-                        .unwrap_or_else(|| "Unknown Artist".to_string()),
-                    album: playlist.name.clone(),
+                    artist: self.playlist_track_artist(song),
                 };
                 let cover_id = song.cover_art.clone();
-                (task, cover_id)
+                Some((index + 1, task, cover_id))
             })
             .collect();
+        let download_count = tasks_with_covers.len();
+
+        // Send start event
+        let _ = progress_tx
+            .send(SyncProgress::PlaylistStarted {
+                name: playlist.name.clone(),
+                track_count: download_count,
+            })
+            .await;
 
         // Stage 1: Download all tracks and their covers in parallel
-        let client = self.downloader.client_arc();
-        let parallelism = self.pipeline_config.download_parallelism;
+        let client = Arc::clone(&self.client);
+        let parallelism = self.download_parallelism();
+        let (retries, base_delay) = (self.downloader.retries(), self.downloader.base_delay());
+        let transcode = self.downloader.transcode();
+        let started = Instant::now();
+        let cover_size = self.cover_fetch_size();
+        let completed = Arc::new(AtomicUsize::new(0));
 
         // Download struct to hold track + its cover
         struct PlaylistDownload {
+            /// 1-based position in the playlist
+            position: usize,
             download: DownloadResult,
             cover_data: Option<Bytes>,
             cover_id: Option<String>,
         }
 
         let downloads: Vec<PlaylistDownload> = stream::iter(tasks_with_covers)
-            .map(|(task, cover_id)| {
+            .map(|(position, task, cover_id)| {
                 let client = client.clone();
                 let cover_id_clone = cover_id.clone();
+                let transcode = transcode.as_ref();
+                let completed = Arc::clone(&completed);
+                let progress_tx = progress_tx.clone();
                 async move {
                     // Download the track
-                    let data = client.download(&task.song.id).await?;
-                    let download = DownloadResult {
-                        song: task.song,
-                        data,
-                        artist: task.artist,
-                        album: task.album,
-                    };
+                    let download = download_task(&client, task, retries, base_delay, transcode).await?;
+                    let track_num = completed.fetch_add(1, Ordering::Relaxed) + 1;
+                    let _ = progress_tx
+                        .send(SyncProgress::TrackCompleted { track_num, total_tracks: download_count })
+                        .await;
 
                     // Download cover art if available
                     let cover_data = if let Some(ref cid) = cover_id_clone {
-                        match client.get_cover_art(cid, Some(500)).await {
+                        match client.get_cover_art(cid, Some(cover_size)).await {
                             Ok(data) => Some(data),
                             Err(e) => {
                                 debug!("Failed to download cover for playlist track: {}", e);
@@ -629,6 +2485,7 @@ impl SyncEngine {
                     };
 
                     Ok::<_, anyhow::Error>(PlaylistDownload {
+                        position,
                         download,
                         cover_data,
                         cover_id: cover_id_clone,
@@ -638,7 +2495,11 @@ impl SyncEngine {
             .buffer_unordered(parallelism)
             .filter_map(|result| async {
                 match result {
-                    Ok(r) => Some(r),
+                    Ok(r) => {
+                        let bytes = r.download.data.len() as u64;
+                        let _ = progress_tx.send(SyncProgress::BytesProgress { bytes }).await;
+                        Some(r)
+                    }
                     Err(e) => {
                         warn!("Download failed: {}", e);
                         None
@@ -648,26 +2509,23 @@ impl SyncEngine {
             .collect()
             .await;
 
-        // Send progress event for downloads completion
-        let _ = progress_tx
-            .send(SyncProgress::TrackCompleted {
-                track_num: downloads.len(),
-                total_tracks: track_count,
-            })
-            .await;
+        let bytes = downloads.iter().map(|d| d.download.data.len() as u64).sum();
+        self.downloader
+            .record_batch(bytes, started.elapsed(), download_count - downloads.len());
+        self.record_track_failures(download_count - downloads.len(), &format!("playlist {}", playlist.name))?;
 
         // Stage 2: Process covers and embed in parallel
         // Use a cache to avoid reprocessing the same cover for different tracks
-        let mut cover_cache: std::collections::HashMap<String, Arc<Vec<u8>>> =
+        let mut cover_cache: std::collections::HashMap<String, Arc<Vec<CoverPicture>>> =
             std::collections::HashMap::new();
 
         // Pre-process unique covers
         for dl in &downloads {
             if let (Some(cover_id), Some(cover_data)) = (&dl.cover_id, &dl.cover_data)
                 && !cover_cache.contains_key(cover_id) {
-                    match cover_art::process_cover_art(cover_data) {
+                    match self.process_cover(cover_data) {
                         Ok(processed) => {
-                            cover_cache.insert(cover_id.clone(), Arc::new(processed));
+                            cover_cache.insert(cover_id.clone(), Arc::new(vec![CoverPicture::front(processed)]));
                         }
                         Err(e) => {
                             warn!("Failed to process cover {}: {}", cover_id, e);
@@ -677,7 +2535,7 @@ impl SyncEngine {
         }
 
         // Stage 3: Embed covers in parallel using spawn_blocking
-        use crate::sync::pipeline::embed_cover_art_async;
+        use crate::sync::pipeline::{TrackTags, embed_cover_art_async};
         use tokio::sync::Semaphore;
 
         let semaphore = Arc::new(Semaphore::new(self.pipeline_config.processing_parallelism));
@@ -699,24 +2557,37 @@ impl SyncEngine {
             let audio_data = dl.download.data.clone();
             let song = dl.download.song.clone();
             let artist = dl.download.artist.clone();
+            let position = dl.position;
+            let temp_dir = self.temp_dir();
+            let keep_cover = self.options.keep_cover(None);
+            let tags = TrackTags {
+                song: song.clone(),
+                album: None,
+                force: self.options.force_tags,
+            };
 
             let handle = tokio::spawn(async move {
                 let _permit = permit;
 
-                let final_data = if let Some(cover) = processed_cover {
-                    match embed_cover_art_async(audio_data.clone(), cover, extension.clone()).await
-                    {
-                        Ok(data) => data,
-                        Err(e) => {
-                            warn!("Failed to embed cover in {}: {}", song.title, e);
-                            audio_data.to_vec()
-                        }
+                let has_cover = processed_cover.is_some();
+                let pictures = processed_cover.unwrap_or_default();
+                let mut cover_embed_failed = false;
+                let embedded =
+                    embed_cover_art_async(audio_data.clone(), pictures, tags, keep_cover, extension.clone(), temp_dir);
+                let final_data = match embedded.await {
+                    Ok(data) => data,
+                    Err(e) if has_cover => {
+                        warn!("Failed to embed cover in {}: {}", song.title, e);
+                        cover_embed_failed = true;
+                        audio_data.to_vec()
+                    }
+                    Err(e) => {
+                        debug!("Failed to write tags in {}: {}", song.title, e);
+                        audio_data.to_vec()
                     }
-                } else {
-                    audio_data.to_vec()
                 };
 
-                (song, artist, extension, final_data)
+                (position, song, artist, extension, final_data, cover_embed_failed)
             });
 
             embed_handles.push(handle);
@@ -733,41 +2604,100 @@ impl SyncEngine {
             }
         }
 
-        // Stage 4: Write tracks to device
+        let embed_failures = processed_tracks.iter().filter(|t| t.5).count();
+        self.record_cover_failures(embed_failures, &format!("playlist {}", playlist.name))?;
+
+        // Stage 4: Write tracks to device, and link the songs already written
         let mut total_bytes: u64 = 0;
-        let mut track_filenames: Vec<String> = Vec::new();
+        let mut written: BTreeMap<usize, (M3uTrack, String, Option<TrackSignature>)> = BTreeMap::new();
+
+        for (position, (filename, signature)) in kept {
+            let song = &songs[position - 1];
+            let m3u_track = M3uTrack {
+                filename,
+                duration: song.duration,
+                artist: self.playlist_track_artist(song),
+                title: song.title.clone(),
+            };
+            written.insert(position, (m3u_track, song.id.clone(), Some(signature)));
+        }
+
+        for (position, song, written_copy) in linked {
+            let artist = self.playlist_track_artist(song);
+            let (filename, signature) = self
+                .link_playlist_duplicate(playlist, position, track_count, song, &artist, &written_copy)
+                .await?;
+            let m3u_track = M3uTrack {
+                filename,
+                duration: song.duration,
+                artist,
+                title: song.title.clone(),
+            };
+            written.insert(position, (m3u_track, song.id.clone(), signature));
+        }
 
-        for (song, artist, extension, final_data) in &processed_tracks {
+        for (position, song, artist, extension, final_data, _) in &processed_tracks {
             total_bytes += final_data.len() as u64;
 
             let filename = self
                 .storage
                 .write_playlist_track(
                     &playlist.name,
+                    *position,
+                    track_count,
                     artist,
                     &song.title,
                     extension,
                     final_data,
                 )
                 .await?;
+            let path = self.storage.playlist_dir(&playlist.name).join(&filename);
+            let signature = self.track_signature(&path, final_data);
+            self.remember_written(&song.id, &path, signature.as_ref());
+            self.preserve_date(&path, song.created_at());
+            self.write_chapters(&path, song).await;
+
+            let m3u_track = M3uTrack {
+                filename,
+                duration: song.duration,
+                artist: artist.clone(),
+                title: song.title.clone(),
+            };
+            written.insert(*position, (m3u_track, song.id.clone(), signature));
+        }
 
-            track_filenames.push(filename);
+        // Back in playlist order
+        let mut m3u_tracks = Vec::with_capacity(written.len());
+        let mut song_ids = Vec::with_capacity(written.len());
+        let mut signatures = Vec::with_capacity(written.len());
+        for (m3u_track, song_id, signature) in written.into_values() {
+            m3u_tracks.push(m3u_track);
+            song_ids.push(song_id);
+            signatures.extend(signature);
         }
+        self.remove_stale_playlist_tracks(playlist, &signatures).await;
 
-        // Write M3U playlist file
-        self.storage
-            .write_m3u(&playlist.name, &track_filenames)
-            .await?;
+        // Write the playlist file
+        self.write_playlist_file(playlist, &m3u_tracks).await?;
 
-        // Update manifest
-        self.manifest.add_playlist(SyncedPlaylist {
+        let entry = SyncedPlaylist {
             id: playlist.id.clone(),
             name: playlist.name.clone(),
-            track_count: track_filenames.len() as u32,
+            track_count: m3u_tracks.len() as u32,
+            expected_tracks: Some(track_count as u32),
+            server_tracks: playlist.song_count,
+            changed: playlist.changed.clone(),
+            song_ids,
             synced_at: Utc::now(),
-        });
+            tracks: signatures,
+            format: self.options.playlist_format,
+        };
 
-        Ok((track_filenames.len(), total_bytes))
+        Ok(ItemOutcome {
+            tracks: processed_tracks.len(),
+            bytes: total_bytes,
+            entry: Some(entry),
+        })
     }
 
     /// Sync a single album
@@ -775,41 +2705,61 @@ impl SyncEngine {
         &mut self,
         album: &Album,
         multi: &MultiProgress,
-    ) -> Result<(usize, u64)> {
-        let artist = album.artist.as_deref().unwrap_or("Unknown Artist");
+    ) -> Result<ItemOutcome<SyncedAlbum>> {
+        let display_artist = album.artist.as_deref().unwrap_or("Unknown Artist");
 
         // Check if already synced
-        if self.manifest.is_album_synced(&album.id) {
-            debug!("Album already synced: {} - {}", artist, album.name);
-            return Ok((0, 0));
+        if self.is_album_synced(album) {
+            debug!("Album already synced: {} - {}", display_artist, album.name);
+            return Ok(ItemOutcome::skipped());
         }
 
-        info!("Syncing album: {} - {}", artist, album.name);
+        info!("Syncing album: {} - {}", display_artist, album.name);
 
-        // Download cover art first (needed for embedding)
-        let cover_data = if let Some(cover_id) = &album.cover_art {
-            match self.downloader.download_cover_art(cover_id).await {
-                Ok(data) => Some(data),
-                Err(e) => {
-                    warn!("Failed to download cover art: {}", e);
-                    None
-                }
-            }
-        } else {
-            None
-        };
+        // Download and process cover art first (needed for embedding)
+        let cover_data = self.album_cover(album).await;
+        let pictures = self.album_pictures(album, cover_data.as_deref()).await;
 
         // Fetch album details with songs
-        let album_details = self.client.get_album(&album.id).await?;
+        let songs = self.album_songs(album).await?;
+        let (artist, compilation) = self.album_artist(album, &songs);
+        let artist = artist.as_str();
+        let Some(songs) = self.filter_songs(songs, &format!("{} - {}", artist, album.name)) else {
+            return Ok(ItemOutcome::skipped());
+        };
+
+        // Drop tracks that weren't picked, and pick up where an interrupted sync of this album left off
+        let genre = self.genre_folder(album);
+        let folder = AlbumFolder {
+            genre: genre.as_deref(),
+            artist,
+            album: &album.name,
+            year: album.year,
+        };
+        let (songs, unpicked) = self.pick_album_songs(album, songs);
+        self.remove_unpicked_songs(&folder, &unpicked, compilation).await;
+        let mut entry = self.resume_album(album, &folder, &songs, compilation).await;
+        let songs: Vec<Song> = songs
+            .into_iter()
+            .filter(|s| !entry.synced_tracks.contains(&s.id))
+            .collect();
+        let mut rest = Vec::with_capacity(songs.len());
+        for song in songs {
+            match self.written_copy(&song.id) {
+                Some(written) => {
+                    self.link_album_duplicate(&mut entry, &folder, &song, &written, compilation).await?
+                }
+                None => rest.push(song),
+            }
+        }
+        let songs = rest;
 
         // Create download tasks
-        let tasks: Vec<DownloadTask> = album_details
-            .song
+        let tasks: Vec<DownloadTask> = songs
             .iter()
             .map(|song| DownloadTask {
                 song: song.clone(),
                 artist: artist.to_string(),
-                album: album.name.clone(),
             })
             .collect();
 
@@ -818,60 +2768,88 @@ impl SyncEngine {
         // Download tracks
         let progress = multi.add(ProgressBar::new(task_count as u64));
         let downloads = self.downloader.download_batch(tasks, &progress).await?;
+        self.record_track_failures(task_count - downloads.len(), &format!("{} - {}", artist, album.name))?;
 
         let mut total_bytes: u64 = 0;
+        let mut last_save = Instant::now();
 
         // Write tracks to device with embedded cover art
         for download in &downloads {
             let track_num = download.song.track.unwrap_or(1);
             let extension = download.song.suffix.as_deref().unwrap_or("mp3");
 
-            // Embed cover art if available
-            let audio_data = if let Some(ref cover) = cover_data {
-                match cover_art::embed_cover_art_in_memory(&download.data, cover, extension) {
-                    Ok(data) => {
-                        debug!("Embedded cover art in: {}", download.song.title);
-                        data.into()
-                    }
-                    Err(e) => {
-                        warn!("Failed to embed cover art in {}: {}", download.song.title, e);
-                        download.data.clone()
-                    }
+            // Embed cover art if available, and fill in missing tags
+            let metadata = TrackMetadata {
+                song: &download.song,
+                album: Some(album),
+                force: self.options.force_tags,
+            };
+            let audio_data = match cover_art::embed_cover_art_in_memory(
+                &download.data,
+                &pictures,
+                Some(&metadata),
+                self.options.keep_cover(Some(&album.id)).as_ref(),
+                extension,
+                &self.temp_dir(),
+            ) {
+                Ok(data) => {
+                    debug!("Embedded cover art and tags in: {}", download.song.title);
+                    data.into()
+                }
+                Err(e) if !pictures.is_empty() => {
+                    warn!("Failed to embed cover art in {}: {}", download.song.title, e);
+                    self.record_cover_failures(1, &format!("{} - {}", artist, album.name))?;
+                    download.data.clone()
+                }
+                Err(e) => {
+                    debug!("Failed to write tags in {}: {}", download.song.title, e);
+                    download.data.clone()
                 }
-            } else {
-                download.data.clone()
             };
 
             total_bytes += audio_data.len() as u64;
 
-            self.storage
+            let path = self
+                .storage
                 .write_album_track(
-                    &download.artist,
-                    &download.album,
-                    track_num,
-                    &download.song.title,
+                    &folder,
+                    &TrackName {
+                        disc: download.song.disc_number,
+                        track: track_num,
+                        title: &album_track_title(&download.song, compilation),
+                    },
                     extension,
                     &audio_data,
                 )
                 .await?;
+            let signature = self.track_signature(&path, &audio_data);
+            self.remember_written(&download.song.id, &path, signature.as_ref());
+            entry.tracks.extend(signature);
+            self.preserve_date(&path, download.song.created_at().or_else(|| album.created_at()));
+            self.write_chapters(&path, &download.song).await;
+
+            entry.track_count += 1;
+            entry.synced_tracks.push(download.song.id.clone());
+            self.save_album_progress(&entry, &mut last_save).await;
         }
 
         // Also save cover art as file (for file browsers/fallback)
+        let album_path = self.storage.album_dir(&folder);
         if let Some(ref cover) = cover_data
-            && let Err(e) = self.storage.write_cover_art(artist, &album.name, cover).await {
-                debug!("Failed to write cover.jpg: {}", e);
+            && let Err(e) = self.storage.write_cover_art(&album_path, cover, &self.cover_file_names()).await {
+                debug!("Failed to write cover file: {}", e);
             }
 
-        // Update manifest
-        self.manifest.add_album(SyncedAlbum {
-            id: album.id.clone(),
-            artist: artist.to_string(),
-            album: album.name.clone(),
-            track_count: downloads.len() as u32,
-            synced_at: Utc::now(),
-        });
+        self.write_album_notes(album, &album_path).await;
+        self.remove_stale_album_tracks(&entry).await;
+
+        entry.synced_at = Utc::now();
 
-        Ok((downloads.len(), total_bytes))
+        Ok(ItemOutcome {
+            tracks: downloads.len(),
+            bytes: total_bytes,
+            entry: Some(entry),
+        })
     }
 
     /// Sync a single playlist
@@ -879,28 +2857,30 @@ impl SyncEngine {
         &mut self,
         playlist: &Playlist,
         multi: &MultiProgress,
-    ) -> Result<(usize, u64)> {
+    ) -> Result<ItemOutcome<SyncedPlaylist>> {
         // Check if already synced
-        if self.manifest.is_playlist_synced(&playlist.id) {
+        if self.is_playlist_synced(playlist) {
             debug!("Playlist already synced: {}", playlist.name);
-            return Ok((0, 0));
+            return Ok(ItemOutcome::skipped());
         }
 
         info!("Syncing playlist: {}", playlist.name);
 
         // Fetch playlist details with songs
-        let playlist_details = self.client.get_playlist(&playlist.id).await?;
-        let track_count = playlist_details.songs.len();
+        let playlist_details = self.playlist_details(playlist).await?;
+        let Some(songs) = self.filter_songs(playlist_details.songs, &format!("Playlist: {}", playlist.name)) else {
+            return Ok(ItemOutcome::skipped());
+        };
+        let track_count = songs.len();
+        let mut kept = self.keep_playlist_tracks(playlist, &songs).await?;
 
         // Create download tasks with cover art IDs
-        let tasks_with_covers: Vec<(DownloadTask, Option<String>)> = playlist_details
-            .songs
+        let tasks_with_covers: Vec<(DownloadTask, Option<String>)> = songs
             .iter()
             .map(|song| {
                 let task = DownloadTask {
                     song: song.clone(),
-                    artist: song.artist.clone().unwrap_or_else(|| "Unknown Artist".to_string()),
-                    album: playlist.name.clone(),
+                    artist: self.playlist_track_artist(song),
                 };
                 let cover_id = song.cover_art.clone();
                 (task, cover_id)
@@ -916,16 +2896,54 @@ impl SyncEngine {
         );
 
         let mut total_bytes: u64 = 0;
-        let mut track_filenames: Vec<String> = Vec::new();
+        let mut downloaded = 0;
+        let mut m3u_tracks: Vec<M3uTrack> = Vec::new();
+        let mut signatures = Vec::new();
+        let mut song_ids = Vec::new();
 
         // Download and write tracks one by one (to embed cover art per track)
-        for (task, cover_id) in tasks_with_covers {
+        for (index, (task, cover_id)) in tasks_with_covers.into_iter().enumerate() {
+            if let Some((filename, signature)) = kept.remove(&(index + 1)) {
+                signatures.push(signature);
+                m3u_tracks.push(M3uTrack {
+                    filename,
+                    duration: task.song.duration,
+                    artist: task.artist,
+                    title: task.song.title.clone(),
+                });
+                song_ids.push(task.song.id.clone());
+                progress.inc(1);
+                progress.set_message(task.song.title);
+                continue;
+            }
+            if let Some(written) = self.written_copy(&task.song.id) {
+                let (filename, signature) = self
+                    .link_playlist_duplicate(playlist, index + 1, track_count, &task.song, &task.artist, &written)
+                    .await?;
+                signatures.extend(signature);
+                m3u_tracks.push(M3uTrack {
+                    filename,
+                    duration: task.song.duration,
+                    artist: task.artist,
+                    title: task.song.title.clone(),
+                });
+                song_ids.push(task.song.id.clone());
+                progress.inc(1);
+                progress.set_message(task.song.title);
+                continue;
+            }
+
             let download = self.downloader.download_one(task).await?;
+            downloaded += 1;
 
             // Download cover art for this track
             let cover_data = if let Some(ref cid) = cover_id {
-                match self.downloader.download_cover_art(cid).await {
-                    Ok(data) => Some(data),
+                match self.downloader.download_cover_art(cid, self.cover_fetch_size()).await {
+                    Ok(data) => self
+                        .process_cover(&data)
+                        .inspect_err(|e| debug!("Failed to process cover for playlist track: {}", e))
+                        .ok()
+                        .map(CoverPicture::front),
                     Err(e) => {
                         debug!("Failed to download cover for playlist track: {}", e);
                         None
@@ -936,22 +2954,35 @@ impl SyncEngine {
             };
 
             let extension = download.song.suffix.as_deref().unwrap_or("mp3");
-            let artist = download.song.artist.as_deref().unwrap_or("Unknown Artist");
-
-            // Embed cover art if available
-            let audio_data = if let Some(ref cover) = cover_data {
-                match cover_art::embed_cover_art_in_memory(&download.data, cover, extension) {
-                    Ok(data) => {
-                        debug!("Embedded cover art in playlist track: {}", download.song.title);
-                        data.into()
-                    }
-                    Err(e) => {
-                        warn!("Failed to embed cover art in {}: {}", download.song.title, e);
-                        download.data.clone()
-                    }
+            let artist = download.artist.as_str();
+
+            // Embed cover art if available, and fill in missing tags
+            let metadata = TrackMetadata {
+                song: &download.song,
+                album: None,
+                force: self.options.force_tags,
+            };
+            let audio_data = match cover_art::embed_cover_art_in_memory(
+                &download.data,
+                cover_data.as_slice(),
+                Some(&metadata),
+                self.options.keep_cover(None).as_ref(),
+                extension,
+                &self.temp_dir(),
+            ) {
+                Ok(data) => {
+                    debug!("Embedded cover art and tags in playlist track: {}", download.song.title);
+                    data.into()
+                }
+                Err(e) if cover_data.is_some() => {
+                    warn!("Failed to embed cover art in {}: {}", download.song.title, e);
+                    self.record_cover_failures(1, &format!("playlist {}", playlist.name))?;
+                    download.data.clone()
+                }
+                Err(e) => {
+                    debug!("Failed to write tags in {}: {}", download.song.title, e);
+                    download.data.clone()
                 }
-            } else {
-                download.data.clone()
             };
 
             total_bytes += audio_data.len() as u64;
@@ -960,34 +2991,1167 @@ impl SyncEngine {
                 .storage
                 .write_playlist_track(
                     &playlist.name,
+                    index + 1,
+                    track_count,
                     artist,
                     &download.song.title,
                     extension,
                     &audio_data,
                 )
                 .await?;
+            let path = self.storage.playlist_dir(&playlist.name).join(&filename);
+            let signature = self.track_signature(&path, &audio_data);
+            self.remember_written(&download.song.id, &path, signature.as_ref());
+            signatures.extend(signature);
+            self.preserve_date(&path, download.song.created_at());
+            self.write_chapters(&path, &download.song).await;
 
-            track_filenames.push(filename);
+            m3u_tracks.push(M3uTrack {
+                filename,
+                duration: download.song.duration,
+                artist: artist.to_string(),
+                title: download.song.title.clone(),
+            });
+            song_ids.push(download.song.id.clone());
             progress.inc(1);
             progress.set_message(download.song.title.clone());
         }
 
         progress.finish_with_message("Done");
+        self.remove_stale_playlist_tracks(playlist, &signatures).await;
 
-        // Write M3U playlist file
-        self.storage
-            .write_m3u(&playlist.name, &track_filenames)
-            .await?;
+        // Write the playlist file
+        self.write_playlist_file(playlist, &m3u_tracks).await?;
 
-        // Update manifest
-        self.manifest.add_playlist(SyncedPlaylist {
+        let entry = SyncedPlaylist {
             id: playlist.id.clone(),
             name: playlist.name.clone(),
-            track_count: track_filenames.len() as u32,
+            track_count: m3u_tracks.len() as u32,
+            expected_tracks: Some(track_count as u32),
+            server_tracks: playlist.song_count,
+            changed: playlist.changed.clone(),
+            song_ids,
             synced_at: Utc::now(),
-        });
+            tracks: signatures,
+            format: self.options.playlist_format,
+        };
+
+        Ok(ItemOutcome {
+            tracks: downloaded,
+            bytes: total_bytes,
+            entry: Some(entry),
+        })
+    }
+}
+
+/// Order albums smallest first (by track count, then duration)
+///
+/// Albums with unknown sizes go last; ties keep their selection order.
+fn smallest_first(albums: &[Album]) -> Vec<&Album> {
+    let mut ordered: Vec<&Album> = albums.iter().collect();
+    ordered.sort_by_key(|a| {
+        (
+            a.song_count.unwrap_or(u32::MAX),
+            a.duration.unwrap_or(u32::MAX),
+        )
+    });
+    ordered
+}
+
+/// Title an album track's file is named with
+///
+/// Compilation tracks are prefixed with their artist, since the folder
+/// doesn't say who they're by.
+fn album_track_title(song: &Song, compilation: bool) -> String {
+    match song.artist.as_deref() {
+        Some(artist) if compilation && !artist.trim().is_empty() => format!("{} - {}", artist.trim(), song.title),
+        _ => song.title.clone(),
+    }
+}
+
+/// Delete albums and playlists from the device, dropping them from the manifest
+///
+/// Returns how many albums and playlists were deleted; failures are reported
+/// on `progress_tx` and leave the item in the manifest.
+async fn delete_items(
+    storage: &DeviceStorage,
+    manifest: &mut SyncManifest,
+    deletions: &DeletionSelection,
+    progress_tx: &mpsc::Sender<SyncProgress>,
+) -> (usize, usize) {
+    let mut albums_deleted = 0;
+    let mut playlists_deleted = 0;
+
+    if deletions.is_empty() {
+        return (0, 0);
+    }
+
+    // Send start event
+    let _ = progress_tx.send(SyncProgress::DeletionStarted {
+        albums_to_delete: deletions.albums.len(),
+        playlists_to_delete: deletions.playlists.len(),
+    }).await;
+
+    // Delete albums
+    for (album_id, artist, album) in &deletions.albums {
+        let album_path = match manifest.album(album_id) {
+            Some(synced) => storage.synced_album_dir(synced),
+            None => storage.album_dir(&AlbumFolder {
+                genre: None,
+                artist,
+                album,
+                year: None,
+            }),
+        };
+        match storage.delete_album(&album_path).await {
+            Ok(()) => {
+                manifest.remove_album(album_id);
+                albums_deleted += 1;
+                let _ = progress_tx.send(SyncProgress::AlbumDeleted {
+                    artist: artist.clone(),
+                    album: album.clone(),
+                }).await;
+            }
+            Err(e) => {
+                let _ = progress_tx.send(SyncProgress::AlbumDeleteFailed {
+                    artist: artist.clone(),
+                    album: album.clone(),
+                    error: e.to_string(),
+                }).await;
+            }
+        }
+    }
+
+    // Delete playlists
+    for (playlist_id, name) in &deletions.playlists {
+        match storage.delete_playlist(name).await {
+            Ok(()) => {
+                manifest.remove_playlist(playlist_id);
+                playlists_deleted += 1;
+                let _ = progress_tx.send(SyncProgress::PlaylistDeleted {
+                    name: name.clone(),
+                }).await;
+            }
+            Err(e) => {
+                let _ = progress_tx.send(SyncProgress::PlaylistDeleteFailed {
+                    name: name.clone(),
+                    error: e.to_string(),
+                }).await;
+            }
+        }
+    }
+
+    (albums_deleted, playlists_deleted)
+}
 
-        Ok((track_filenames.len(), total_bytes))
+/// Remove albums and playlists from the device and save the manifest
+///
+/// Removed items are also dropped from the failed list, so `nutune retry`
+/// doesn't bring them back. Nothing here talks to the server, so no
+/// [`SyncEngine`] (or credentials) is needed.
+pub async fn unsync(
+    storage: &DeviceStorage,
+    manifest: &mut SyncManifest,
+    deletions: &DeletionSelection,
+    progress_tx: &mpsc::Sender<SyncProgress>,
+) -> Result<(usize, usize)> {
+    let deleted = delete_items(storage, manifest, deletions, progress_tx).await;
+
+    let failed = &mut manifest.failed;
+    for (id, _, _) in &deletions.albums {
+        failed.remove_album(id);
     }
+    failed.playlists.retain(|p| !deletions.playlists.iter().any(|(id, _)| *id == p.id));
+
+    storage.save_manifest(manifest).await?;
+    Ok(deleted)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::backend::MemoryBackend;
+
+    fn synced_album(id: &str, artist: &str, album: &str) -> SyncedAlbum {
+        SyncedAlbum {
+            id: id.to_string(),
+            artist: artist.to_string(),
+            album: album.to_string(),
+            genre: None,
+            folder: None,
+            track_count: 1,
+            expected_tracks: None,
+            server_tracks: None,
+            synced_tracks: Vec::new(),
+            partial: false,
+            synced_at: Utc::now(),
+            tracks: Vec::new(),
+        }
+    }
+
+    fn song(id: &str, track: u32, suffix: &str) -> Song {
+        serde_json::from_value(serde_json::json!({ "id": id, "title": id, "track": track, "suffix": suffix })).unwrap()
+    }
+
+    /// Folder of an album by "Artist"
+    fn folder(album: &str) -> AlbumFolder<'_> {
+        AlbumFolder {
+            genre: None,
+            artist: "Artist",
+            album,
+            year: None,
+        }
+    }
+
+    /// Engine on an empty in-memory device
+    fn engine_with(options: SyncOptions) -> SyncEngine {
+        let client = SubsonicClient::new("http://server", "user", "pass").unwrap();
+        let storage = DeviceStorage::with_backend(PathBuf::from("/device"), Arc::new(MemoryBackend::default()));
+        SyncEngine::with_storage(client, storage, SyncManifest::new("http://server"), 1).with_options(options)
+    }
+
+    /// Write track 1 of an album by "Artist" with the built-in layout
+    async fn write_track(storage: &DeviceStorage, album: &str, title: &str, extension: &str, data: &[u8]) -> PathBuf {
+        let track = TrackName { disc: None, track: 1, title };
+        storage.write_album_track(&folder(album), &track, extension, data).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_delete_deselected_in_memory() {
+        let backend = Arc::new(MemoryBackend::default());
+        let storage = DeviceStorage::with_backend(PathBuf::from("/device"), backend.clone());
+        write_track(&storage, "Kept", "A", "mp3", b"a").await;
+        write_track(&storage, "Gone", "B", "mp3", b"b").await;
+        storage
+            .write_file(Path::new("Music/Artist/Moved/01 - C.mp3"), b"c")
+            .await
+            .unwrap();
+
+        let mut manifest = SyncManifest::new("http://server");
+        manifest.add_album(synced_album("1", "Artist", "Kept"));
+        manifest.add_album(synced_album("2", "Artist", "Gone"));
+        // Synced with a custom layout, so its folder was recorded
+        let mut moved = synced_album("3", "Artist", "Moved");
+        moved.folder = Some("Music/Artist/Moved".to_string());
+        manifest.add_album(moved);
+
+        let client = SubsonicClient::new("http://server", "user", "pass").unwrap();
+        let mut engine = SyncEngine::with_storage(client, storage, manifest, 1);
+
+        let deletions = DeletionSelection {
+            albums: vec![
+                ("2".to_string(), "Artist".to_string(), "Gone".to_string()),
+                ("3".to_string(), "Artist".to_string(), "Moved".to_string()),
+            ],
+            playlists: Vec::new(),
+        };
+        let (tx, mut rx) = mpsc::channel(8);
+        let deleted = engine.delete_deselected(&deletions, &tx).await.unwrap();
+
+        assert_eq!(deleted, (2, 0));
+        assert!(engine.manifest.is_album_synced("1"));
+        assert!(!engine.manifest.is_album_synced("2"));
+        assert!(!backend.has_dir(Path::new("/device/Artists/Artist/Gone")));
+        assert!(backend.has_dir(Path::new("/device/Artists/Artist/Kept")));
+        assert!(!backend.has_dir(Path::new("/device/Music")));
+
+        assert!(matches!(rx.recv().await, Some(SyncProgress::DeletionStarted { .. })));
+        assert!(matches!(rx.recv().await, Some(SyncProgress::AlbumDeleted { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_unsync_saves_manifest_and_clears_failed() {
+        let backend = Arc::new(MemoryBackend::default());
+        let storage = DeviceStorage::with_backend(PathBuf::from("/device"), backend.clone());
+        write_track(&storage, "Gone", "A", "mp3", b"a").await;
+
+        let mut manifest = SyncManifest::new("http://server");
+        manifest.add_album(synced_album("1", "Artist", "Gone"));
+        manifest.add_failed_album(&album("1", None, None), None);
+
+        let deletions = DeletionSelection {
+            albums: vec![("1".to_string(), "Artist".to_string(), "Gone".to_string())],
+            playlists: Vec::new(),
+        };
+        let (tx, _rx) = mpsc::channel(8);
+        assert_eq!(unsync(&storage, &mut manifest, &deletions, &tx).await.unwrap(), (1, 0));
+
+        let saved = backend.file(&Path::new("/device").join(crate::device::manifest::MANIFEST_FILE)).unwrap();
+        let saved: SyncManifest = serde_json::from_slice(&saved).unwrap();
+        assert!(saved.synced_albums.is_empty());
+        assert!(saved.failed.is_empty());
+        assert!(!backend.has_dir(Path::new("/device/Artists/Artist")));
+    }
+
+    #[test]
+    fn test_deselected_respects_scope() {
+        let mut manifest = SyncManifest::new("http://server");
+        manifest.add_album(synced_album("1", "Artist", "Kept"));
+        manifest.add_album(synced_album("2", "Artist", "Gone"));
+        manifest.add_playlist(SyncedPlaylist {
+            id: "p1".to_string(),
+            name: "Mix".to_string(),
+            track_count: 1,
+            expected_tracks: Some(1),
+            server_tracks: None,
+            changed: None,
+            song_ids: vec!["s1".to_string()],
+            synced_at: Utc::now(),
+            tracks: Vec::new(),
+            format: PlaylistFormat::M3u,
+        });
+        let selection = SyncSelection {
+            albums: vec![album("1", None, None)],
+            playlists: Vec::new(),
+            tracks: BTreeMap::new(),
+        };
+
+        let deletions = DeletionSelection::deselected(&manifest, &selection, true, true);
+        assert_eq!(deletions.albums, [("2".to_string(), "Artist".to_string(), "Gone".to_string())]);
+        assert_eq!(deletions.playlists, [("p1".to_string(), "Mix".to_string())]);
+
+        // A sync that skips playlists leaves them on the device
+        let deletions = DeletionSelection::deselected(&manifest, &selection, true, false);
+        assert!(deletions.playlists.is_empty());
+        assert_eq!(deletions.len(), 1);
+    }
+
+    #[test]
+    fn test_artist_source_fallbacks() {
+        let song = |artist: Option<&str>, album_artist: Option<&str>| Song {
+            id: "s1".to_string(),
+            title: "Song".to_string(),
+            album: None,
+            album_id: None,
+            artist: artist.map(str::to_string),
+            artist_id: None,
+            display_album_artist: album_artist.map(str::to_string),
+            track: None,
+            disc_number: None,
+            duration: None,
+            bit_rate: None,
+            size: None,
+            suffix: None,
+            content_type: None,
+            cover_art: None,
+            path: None,
+            created: None,
+        };
+        let guest = song(Some("Guest"), Some("Various Artists"));
+
+        assert_eq!(ArtistSource::Album.pick(Some("Compilation"), Some(&guest)), "Compilation");
+        assert_eq!(ArtistSource::Album.pick(None, Some(&guest)), "Various Artists");
+        assert_eq!(ArtistSource::Album.pick(Some(""), Some(&song(Some("Guest"), None))), "Guest");
+        assert_eq!(ArtistSource::Track.pick(Some("Compilation"), Some(&guest)), "Guest");
+        assert_eq!(ArtistSource::Track.pick(Some("Compilation"), Some(&song(None, None))), "Compilation");
+        assert_eq!(ArtistSource::Track.pick(None, None), "Unknown Artist");
+    }
+
+    #[test]
+    fn test_cover_file_names_follow_format() {
+        let engine = |cover_names: &[&str], cover_format| {
+            engine_with(SyncOptions {
+                cover_names: cover_names.iter().map(|name| name.to_string()).collect(),
+                cover_format,
+                ..Default::default()
+            })
+        };
+
+        assert_eq!(engine(&[], CoverFormat::Jpeg).cover_file_names(), ["cover.jpg"]);
+        assert_eq!(engine(&[], CoverFormat::Png).cover_file_names(), ["cover.png"]);
+        assert_eq!(
+            engine(&["folder", "cover"], CoverFormat::Webp).cover_file_names(),
+            ["folder.webp", "cover.webp"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cover_repair_follows_cover_format() {
+        use crate::subsonic::mock::{MockReply, mock_api};
+
+        let mut image = Vec::new();
+        image::DynamicImage::new_rgb8(16, 16)
+            .write_to(&mut std::io::Cursor::new(&mut image), image::ImageFormat::Png)
+            .unwrap();
+        let png = CoverArtConfig { format: CoverFormat::Png, ..Default::default() };
+        let cover = cover_art::process_cover_art(&image, &png).unwrap();
+        let mut frame = vec![0u8; 417];
+        frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+        let audio = cover_art::embed_cover_art_in_memory(
+            &frame.repeat(8),
+            &[CoverPicture::front(cover)],
+            None,
+            None,
+            "mp3",
+            &std::env::temp_dir(),
+        )
+        .unwrap();
+
+        let url = mock_api(vec![("getCoverArt", MockReply::new("200 OK", image))]).await;
+        let repair = |cover_format| {
+            let audio = audio.clone();
+            let url = url.clone();
+            async move {
+                let backend = Arc::new(MemoryBackend::default());
+                let storage = DeviceStorage::with_backend(PathBuf::from("/device"), backend.clone());
+                write_track(&storage, "Album", "A", "mp3", &audio).await;
+                let mut manifest = SyncManifest::new(&url);
+                let mut album = synced_album("a1", "Artist", "Album");
+                album.tracks = vec![TrackSignature::new("Artists/Artist/Album/01 - A.mp3".to_string(), &audio)];
+                manifest.add_album(album);
+                let client = SubsonicClient::new(&url, "user", "pass").unwrap();
+                let mut engine = SyncEngine::with_storage(client, storage, manifest, 1)
+                    .with_options(SyncOptions { cover_format, ..Default::default() });
+                let result = engine.sync_covers().await.unwrap();
+                let track = backend.file(Path::new("/device/Artists/Artist/Album/01 - A.mp3")).unwrap();
+                (result.tracks_kept, result.tracks_updated, track)
+            }
+        };
+
+        // A PNG cover is what --cover-format png asks for, but not what the JPEG default does
+        let (kept, updated, track) = repair(CoverFormat::Png).await;
+        assert_eq!((kept, updated), (1, 0));
+        assert_eq!(track, audio);
+        let (kept, updated, track) = repair(CoverFormat::Jpeg).await;
+        assert_eq!((kept, updated), (0, 1));
+        assert!(cover_art::has_acceptable_cover(&track, &CoverArtConfig::default()));
+    }
+
+    #[test]
+    fn test_track_failures_follow_error_policy() {
+        let mut skipping = engine_with(SyncOptions {
+            on_error: OnError::Skip,
+            ..Default::default()
+        });
+        skipping.record_track_failures(2, "Artist - Album").unwrap();
+        assert_eq!(skipping.tracks_failed, 2);
+        assert_eq!(skipping.downloader.retries(), DEFAULT_RETRIES);
+
+        let mut stopping = engine_with(SyncOptions {
+            on_error: OnError::Stop,
+            ..Default::default()
+        });
+        stopping.record_track_failures(0, "Artist - Album").unwrap();
+        assert!(stopping.record_track_failures(1, "Artist - Album").is_err());
+
+        // --on-error retry keeps retrying even with --retries 0
+        let no_retries = |on_error| {
+            engine_with(SyncOptions {
+                on_error,
+                retries: Some(0),
+                ..Default::default()
+            })
+        };
+        assert_eq!(no_retries(OnError::Skip).downloader.retries(), 0);
+        assert_eq!(no_retries(OnError::Retry).downloader.retries(), ON_ERROR_RETRIES);
+    }
+
+    #[test]
+    fn test_album_parallelism_stays_within_download_budget() {
+        // Four songs at once
+        let mut engine = engine_with(SyncOptions::default());
+        engine.pipeline_config.download_parallelism = 4;
+
+        for (album_parallel, expected) in [(0, 1), (3, 3), (8, 4)] {
+            engine.options.album_parallel = album_parallel;
+            assert_eq!(engine.album_parallelism(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recorded_album_is_saved_immediately() {
+        let backend = Arc::new(MemoryBackend::default());
+        let storage = DeviceStorage::with_backend(PathBuf::from("/device"), backend.clone());
+        storage.init().await.unwrap();
+        let client = SubsonicClient::new("http://server", "user", "pass").unwrap();
+        let mut engine = SyncEngine::with_storage(client, storage, SyncManifest::new("http://server"), 1);
+
+        engine.record_album(&album("1", None, None), None).await;
+        assert!(backend.file(&Path::new("/device").join(crate::device::manifest::MANIFEST_FILE)).is_none());
+
+        engine.record_album(&album("1", None, None), Some(synced_album("1", "Artist", "Album"))).await;
+        let saved = backend.file(&Path::new("/device").join(crate::device::manifest::MANIFEST_FILE)).unwrap();
+        let saved: SyncManifest = serde_json::from_slice(&saved).unwrap();
+        assert!(saved.is_album_synced("1"));
+
+        // An album with a track that never downloaded is left to retry
+        let mut incomplete = synced_album("2", "Artist", "Other");
+        incomplete.expected_tracks = Some(2);
+        engine.record_album(&album("2", None, None), Some(incomplete)).await;
+        assert!(!engine.manifest.is_album_synced("2"));
+        assert_eq!(engine.manifest.failed.albums[0].id, "2");
+    }
+
+    #[tokio::test]
+    async fn test_no_manifest_ignores_and_keeps_no_sync_state() {
+        let backend = Arc::new(MemoryBackend::default());
+        let storage = DeviceStorage::with_backend(PathBuf::from("/device"), backend.clone());
+        storage.init().await.unwrap();
+        let client = SubsonicClient::new("http://server", "user", "pass").unwrap();
+        let mut manifest = SyncManifest::new("http://server");
+        manifest.add_album(synced_album("1", "Artist", "Album"));
+        let mut engine = SyncEngine::with_storage(client, storage, manifest, 1).with_options(SyncOptions {
+            no_manifest: true,
+            ..Default::default()
+        });
+
+        assert!(!engine.manifest.is_album_synced("1"));
+
+        engine.record_album(&album("2", None, None), Some(synced_album("2", "Artist", "Other"))).await;
+        assert!(engine.manifest.is_album_synced("2"));
+        assert!(backend.file(&Path::new("/device").join(crate::device::manifest::MANIFEST_FILE)).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_device_log_records_the_last_sync() {
+        let backend = Arc::new(MemoryBackend::default());
+        let storage = DeviceStorage::with_backend(PathBuf::from("/device"), backend.clone());
+        write_track(&storage, "Gone", "A", "mp3", b"a").await;
+        let mut manifest = SyncManifest::new("http://server");
+        manifest.add_album(synced_album("1", "Artist", "Gone"));
+        let client = SubsonicClient::new("http://server", "user", "pass").unwrap();
+        let mut engine = SyncEngine::with_storage(client, storage, manifest, 1).with_options(SyncOptions {
+            device_log: true,
+            ..Default::default()
+        });
+
+        let deletions = DeletionSelection {
+            albums: vec![("1".to_string(), "Artist".to_string(), "Gone".to_string())],
+            playlists: Vec::new(),
+        };
+        engine.sync(&SyncSelection::new(), &deletions).await.unwrap();
+        engine.sync(&SyncSelection::new(), &DeletionSelection::default()).await.unwrap();
+
+        // Each sync replaces the previous log
+        let log = backend.file(&Path::new("/device").join(SYNC_LOG_FILE)).unwrap();
+        let log = String::from_utf8(log).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("Sync started: 0 album(s), 0 playlist(s), 0 deletion(s)"));
+        assert!(lines[1].contains("Sync finished: 0 album(s) and 0 playlist(s) synced"));
+    }
+
+    #[tokio::test]
+    async fn test_sync_refuses_to_start_without_space() {
+        let backend = Arc::new(MemoryBackend::default());
+        let storage = DeviceStorage::with_backend(PathBuf::from("/device"), backend.clone());
+        write_track(&storage, "Big", "A", "mp3", b"a").await;
+        let mut manifest = SyncManifest::new("http://server");
+        let mut big = synced_album("1", "Artist", "Big");
+        big.tracks = vec![TrackSignature::new("Artists/Artist/Big/01 - A.mp3".to_string(), b"a")];
+        big.tracks[0].size = FREE_SPACE_MARGIN;
+        manifest.add_album(big);
+        let client = SubsonicClient::new("http://server", "user", "pass").unwrap();
+        let mut engine = SyncEngine::with_storage(client, storage, manifest, 1).with_free_space(1024);
+
+        // Even an empty sync needs the safety margin
+        let (tx, mut rx) = mpsc::channel(8);
+        let err = engine
+            .sync_with_progress(&SyncSelection::new(), &DeletionSelection::default(), tx)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<InsufficientSpace>(),
+            Some(&InsufficientSpace {
+                required: FREE_SPACE_MARGIN,
+                available: 1024
+            })
+        );
+        assert!(err.to_string().contains("64.0 MB but only 0.0 MB is available"));
+        assert!(matches!(rx.recv().await, Some(SyncProgress::InsufficientSpace { .. })));
+        assert!(rx.recv().await.is_none());
+        assert!(backend.has_dir(Path::new("/device/Artists/Artist/Big")));
+
+        // Space freed by deletions counts
+        let deletions = DeletionSelection {
+            albums: vec![("1".to_string(), "Artist".to_string(), "Big".to_string())],
+            playlists: Vec::new(),
+        };
+        engine.sync(&SyncSelection::new(), &deletions).await.unwrap();
+        assert!(!backend.has_dir(Path::new("/device/Artists/Artist/Big")));
+    }
+
+    #[test]
+    fn test_transcoded_tracks_sized_from_bitrate() {
+        let transcoding = |format: Option<&str>, kbps: u32| {
+            engine_with(SyncOptions {
+                transcode: Some(Transcode {
+                    format: format.map(str::to_string),
+                    max_bitrate: Some(kbps),
+                }),
+                ..Default::default()
+            })
+        };
+        // 100 seconds at 128 kbps
+        let song: Song = serde_json::from_value(serde_json::json!({
+            "id": "s1", "title": "s1", "duration": 100, "size": 1_600_000
+        }))
+        .unwrap();
+
+        assert_eq!(engine_with(SyncOptions::default()).estimated_track_size(&song), 1_600_000);
+        assert_eq!(transcoding(Some("opus"), 320).estimated_track_size(&song), 4_000_000);
+        assert_eq!(transcoding(Some("opus"), 96).estimated_track_size(&song), 1_200_000);
+        // Without a format only songs above the bitrate are re-encoded
+        assert_eq!(transcoding(None, 320).estimated_track_size(&song), 1_600_000);
+        assert_eq!(transcoding(None, 96).estimated_track_size(&song), 1_200_000);
+    }
+
+    #[tokio::test]
+    async fn test_free_space_check_fetches_each_item_once() {
+        use crate::subsonic::mock::{MockReply, mock_server_with};
+
+        let fetches = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let requested = fetches.clone();
+        let (url, _) = mock_server_with(move |request| {
+            let line = request.lines().next().unwrap_or("");
+            let song = serde_json::json!([{ "id": "s1", "title": "s1", "artist": "Artist", "size": 4 }]);
+            if line.contains("/getAlbum?") {
+                requested.lock().unwrap().push("getAlbum");
+                return MockReply::ok(serde_json::json!({ "album": { "id": "a1", "name": "a1", "song": song } }));
+            }
+            if line.contains("/getPlaylist?") {
+                requested.lock().unwrap().push("getPlaylist");
+                return MockReply::ok(serde_json::json!({ "playlist": { "id": "p1", "name": "Mix", "entry": song } }));
+            }
+            if line.contains("/download?") {
+                return MockReply::new("200 OK", "data");
+            }
+            MockReply::new("404 Not Found", "")
+        })
+        .await;
+        let client = SubsonicClient::new(&url, "user", "pass").unwrap();
+        let storage = DeviceStorage::with_backend(PathBuf::from("/device"), Arc::new(MemoryBackend::default()));
+        // Far too small, but the check is overridden
+        let mut engine = SyncEngine::with_storage(client, storage, SyncManifest::new(&url), 1)
+            .with_options(SyncOptions {
+                ignore_free_space: true,
+                ..Default::default()
+            })
+            .with_free_space(1024);
+        let selection = SyncSelection {
+            albums: vec![album("a1", Some(1), None)],
+            playlists: vec![serde_json::from_value(serde_json::json!({ "id": "p1", "name": "Mix", "songCount": 1 })).unwrap()],
+            ..Default::default()
+        };
+
+        let (tx, mut rx) = mpsc::channel(64);
+        let result = engine
+            .sync_with_progress(&selection, &DeletionSelection::default(), tx)
+            .await
+            .unwrap();
+        assert_eq!((result.albums_synced, result.playlists_synced), (1, 1));
+        assert!(matches!(
+            rx.recv().await,
+            Some(SyncProgress::Started { estimated_bytes: Some(8), .. })
+        ));
+        assert_eq!(*fetches.lock().unwrap(), ["getAlbum", "getPlaylist"]);
+    }
+
+    #[tokio::test]
+    async fn test_device_log_is_off_by_default() {
+        let backend = Arc::new(MemoryBackend::default());
+        let storage = DeviceStorage::with_backend(PathBuf::from("/device"), backend.clone());
+        let client = SubsonicClient::new("http://server", "user", "pass").unwrap();
+        let mut engine = SyncEngine::with_storage(client, storage, SyncManifest::new("http://server"), 1);
+
+        engine.sync(&SyncSelection::new(), &DeletionSelection::default()).await.unwrap();
+        assert!(backend.file(&Path::new("/device").join(SYNC_LOG_FILE)).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resume_album_keeps_written_tracks() {
+        let songs = [song("s1", 1, "flac"), song("s2", 2, "flac"), song("s3", 3, "flac")];
+
+        let backend = Arc::new(MemoryBackend::default());
+        let storage = DeviceStorage::with_backend(PathBuf::from("/device"), backend.clone());
+        let path = write_track(&storage, "a1", "s1", "flac", b"one").await;
+
+        // s1 and s2 were written, but s2's file has since gone missing
+        let mut partial = synced_album("a1", "Artist", "a1");
+        partial.track_count = 2;
+        partial.expected_tracks = Some(3);
+        partial.synced_tracks = vec!["s1".to_string(), "s2".to_string()];
+        partial.tracks = vec![TrackSignature::new("Artists/Artist/a1/01 - s1.flac".to_string(), b"one")];
+        let mut manifest = SyncManifest::new("http://server");
+        manifest.add_album(partial);
+        assert!(!manifest.is_album_synced("a1"));
+
+        let client = SubsonicClient::new("http://server", "user", "pass").unwrap();
+        let engine = SyncEngine::with_storage(client, storage, manifest, 1);
+        let folder = folder("a1");
+        let entry = engine.resume_album(&album("a1", None, None), &folder, &songs, false).await;
+
+        assert_eq!(entry.synced_tracks, ["s1"]);
+        assert_eq!(entry.track_count, 1);
+        assert_eq!(entry.expected_tracks, Some(3));
+        assert_eq!(entry.tracks.len(), 1);
+        assert_eq!(entry.folder.as_deref(), Some("Artists/Artist/a1"));
+        assert!(backend.file(&path).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_dedup_links_songs_written_earlier_in_the_sync() {
+
+        let backend = Arc::new(MemoryBackend::default());
+        let storage = DeviceStorage::with_backend(PathBuf::from("/device"), backend.clone());
+        let first = write_track(&storage, "Best Of", "s1", "mp3", b"one").await;
+
+        let client = SubsonicClient::new("http://server", "user", "pass").unwrap();
+        let mut engine = SyncEngine::with_storage(client, storage, SyncManifest::new("http://server"), 1)
+            .with_options(SyncOptions {
+                dedup: true,
+                ..SyncOptions::default()
+            });
+        let signature = TrackSignature::new("Artists/Artist/Best Of/01 - s1.mp3".to_string(), b"one");
+        engine.remember_written("s1", &first, Some(&signature));
+
+        let folder = folder("Album");
+        let mut entry = engine.resume_album(&album("a1", None, None), &folder, &[], false).await;
+        assert!(engine.written_copy("s2").is_none());
+        let written = engine.written_copy("s1").unwrap();
+        engine
+            .link_album_duplicate(&mut entry, &folder, &song("s1", 4, "mp3"), &written, false)
+            .await
+            .unwrap();
+
+        // s1 is linked from its first file
+        assert_eq!(entry.synced_tracks, ["s1"]);
+        assert_eq!(entry.tracks.len(), 1);
+        assert_eq!(entry.tracks[0].path, "Artists/Artist/Album/04 - s1.mp3");
+        assert_eq!(entry.tracks[0].sha256, signature.sha256);
+        let linked = PathBuf::from("/device/Artists/Artist/Album/04 - s1.mp3");
+        assert_eq!(backend.file(&linked).as_deref(), Some(&b"one"[..]));
+    }
+
+    #[tokio::test]
+    async fn test_dedup_links_songs_of_albums_downloaded_together() {
+        use crate::subsonic::mock::{MockReply, mock_server_with};
+
+        // Both albums have song s1; each download of it gets different bytes
+        let downloads = AtomicUsize::new(0);
+        let (url, _) = mock_server_with(move |request| {
+            let line = request.lines().next().unwrap_or("");
+            if line.contains("/download?") {
+                let n = downloads.fetch_add(1, Ordering::SeqCst);
+                return MockReply::new("200 OK", format!("audio {}", n));
+            }
+            if !line.contains("/getAlbum?") {
+                return MockReply::new("404 Not Found", "");
+            }
+            let id = if line.contains("id=a2") { "a2" } else { "a1" };
+            MockReply::ok(serde_json::json!({
+                "album": { "id": id, "name": id, "artist": "Artist", "song": [
+                    { "id": "s1", "title": "Shared", "artist": "Artist", "track": 1, "suffix": "mp3" }
+                ] }
+            }))
+        })
+        .await;
+
+        let backend = Arc::new(MemoryBackend::default());
+        let storage = DeviceStorage::with_backend(PathBuf::from("/device"), backend.clone());
+        let client = SubsonicClient::new(&url, "user", "pass").unwrap();
+        let mut engine = SyncEngine::with_storage(client, storage, SyncManifest::new(&url), 2).with_options(SyncOptions {
+            dedup: true,
+            album_parallel: 2,
+            ..SyncOptions::default()
+        });
+        let selection = SyncSelection {
+            albums: vec![album("a1", Some(1), None), album("a2", Some(1), None)],
+            ..Default::default()
+        };
+        let (tx, _rx) = mpsc::channel(64);
+        let result = engine
+            .sync_with_progress(&selection, &DeletionSelection::default(), tx)
+            .await
+            .unwrap();
+        assert_eq!(result.albums_synced, 2);
+
+        // The second album links the first one's file instead of writing its own download
+        let first = backend.file(Path::new("/device/Artists/Artist/a1/01 - Shared.mp3")).unwrap();
+        let second = backend.file(Path::new("/device/Artists/Artist/a2/01 - Shared.mp3")).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(engine.manifest.album("a2").unwrap().synced_tracks, ["s1"]);
+    }
+
+    #[tokio::test]
+    async fn test_unpicked_tracks_are_removed() {
+        let backend = Arc::new(MemoryBackend::default());
+        let storage = DeviceStorage::with_backend(PathBuf::from("/device"), backend.clone());
+        let folder = folder("a1");
+        let kept = write_track(&storage, "a1", "s1", "mp3", b"one").await;
+        let dropped = storage
+            .write_album_track(&folder, &TrackName { disc: None, track: 2, title: "s2" }, "mp3", b"two")
+            .await
+            .unwrap();
+
+        // Synced whole, now only s1 is picked
+        let mut whole = synced_album("a1", "Artist", "a1");
+        whole.track_count = 2;
+        whole.expected_tracks = Some(2);
+        whole.synced_tracks = vec!["s1".to_string(), "s2".to_string()];
+        let mut manifest = SyncManifest::new("http://server");
+        manifest.add_album(whole);
+
+        let client = SubsonicClient::new("http://server", "user", "pass").unwrap();
+        let mut engine = SyncEngine::with_storage(client, storage, manifest, 1);
+        let picked: BTreeSet<String> = ["s1".to_string()].into();
+        engine.picked_tracks.insert("a1".to_string(), picked.clone());
+        let album = album("a1", None, None);
+        assert!(!engine.is_album_synced(&album));
+
+        let (songs, unpicked) = engine.pick_album_songs(&album, vec![song("s1", 1, "mp3"), song("s2", 2, "mp3")]);
+        assert_eq!(songs.len(), 1);
+        assert_eq!(unpicked.len(), 1);
+        assert!(backend.file(&dropped).is_some());
+        engine.remove_unpicked_songs(&folder, &unpicked, false).await;
+        assert!(backend.file(&kept).is_some());
+        assert!(backend.file(&dropped).is_none());
+
+        let entry = engine.resume_album(&album, &folder, &songs, false).await;
+        assert!(entry.partial);
+        assert_eq!(entry.synced_tracks, ["s1"]);
+        engine.manifest.add_album(entry);
+        assert!(engine.is_album_synced(&album));
+        assert!(!engine.manifest.is_album_synced_with("a1", None));
+    }
+
+    #[tokio::test]
+    async fn test_outdated_album_keeps_tracks_on_the_device() {
+        let storage = DeviceStorage::with_backend(PathBuf::from("/device"), Arc::new(MemoryBackend::default()));
+        let folder = folder("a1");
+        write_track(&storage, "a1", "s1", "mp3", b"one").await;
+
+        // Synced when the server listed one song
+        let mut synced = synced_album("a1", "Artist", "a1");
+        synced.expected_tracks = Some(1);
+        synced.server_tracks = Some(1);
+        synced.synced_tracks = vec!["s1".to_string()];
+        let mut manifest = SyncManifest::new("http://server");
+        manifest.add_album(synced);
+
+        let client = SubsonicClient::new("http://server", "user", "pass").unwrap();
+        let engine = SyncEngine::with_storage(client, storage, manifest, 1);
+        assert!(engine.is_album_synced(&album("a1", Some(1), None)));
+
+        // A song was added since, so only that one is left to download
+        let album = album("a1", Some(2), None);
+        assert!(!engine.is_album_synced(&album));
+        let entry = engine.resume_album(&album, &folder, &[song("s1", 1, "mp3"), song("s2", 2, "mp3")], false).await;
+        assert_eq!(entry.synced_tracks, ["s1"]);
+        assert_eq!(entry.server_tracks, Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_outdated_album_drops_songs_the_server_removed() {
+        use crate::subsonic::mock::{MockReply, mock_api};
+
+        let url = mock_api(vec![(
+            "getAlbum",
+            MockReply::ok(serde_json::json!({ "album": { "id": "a1", "name": "a1", "artist": "Artist", "song": [
+                { "id": "s1", "title": "s1", "artist": "Artist", "track": 1, "suffix": "mp3" }
+            ] } })),
+        )])
+        .await;
+        let backend = Arc::new(MemoryBackend::default());
+        let storage = DeviceStorage::with_backend(PathBuf::from("/device"), backend.clone());
+        let folder = folder("a1");
+        let kept = write_track(&storage, "a1", "s1", "mp3", b"one").await;
+        let dropped = storage
+            .write_album_track(&folder, &TrackName { disc: None, track: 2, title: "s2" }, "mp3", b"two")
+            .await
+            .unwrap();
+
+        // Synced when the server listed two songs; now it lists only s1
+        let mut synced = synced_album("a1", "Artist", "a1");
+        synced.track_count = 2;
+        synced.expected_tracks = Some(2);
+        synced.server_tracks = Some(2);
+        synced.synced_tracks = vec!["s1".to_string(), "s2".to_string()];
+        synced.tracks = vec![
+            TrackSignature::new("Artists/Artist/a1/01 - s1.mp3".to_string(), b"one"),
+            TrackSignature::new("Artists/Artist/a1/02 - s2.mp3".to_string(), b"two"),
+        ];
+        let mut manifest = SyncManifest::new(&url);
+        manifest.add_album(synced);
+
+        let client = SubsonicClient::new(&url, "user", "pass").unwrap();
+        let mut engine = SyncEngine::with_storage(client, storage, manifest, 1);
+        let selection = SyncSelection {
+            albums: vec![album("a1", Some(1), None)],
+            ..Default::default()
+        };
+        let (tx, _rx) = mpsc::channel(64);
+        engine
+            .sync_with_progress(&selection, &DeletionSelection::default(), tx)
+            .await
+            .unwrap();
+
+        assert_eq!(backend.file(&kept).as_deref(), Some(&b"one"[..]));
+        assert!(backend.file(&dropped).is_none());
+        let entry = engine.manifest.album("a1").unwrap();
+        assert_eq!(entry.synced_tracks, ["s1"]);
+        assert_eq!(entry.tracks.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_outdated_playlist_downloads_only_new_songs() {
+        use crate::subsonic::mock::{MockReply, mock_server_with};
+
+        // Two songs share a title, and swap places
+        let song = |id: &str, title: &str| serde_json::json!({ "id": id, "title": title, "artist": "Artist", "suffix": "mp3" });
+        let playlist_songs = serde_json::json!([song("s2", "Intro"), song("s1", "Intro"), song("s4", "New")]);
+        let downloads = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let requested = downloads.clone();
+        let (url, _) = mock_server_with(move |request| {
+            let line = request.lines().next().unwrap_or("");
+            if line.contains("/download?") {
+                let id = line.split("id=").nth(1).unwrap_or("").split([' ', '&']).next().unwrap_or("");
+                requested.lock().unwrap().push(id.to_string());
+                return MockReply::new("200 OK", "new");
+            }
+            if line.contains("/getPlaylist?") {
+                return MockReply::ok(serde_json::json!({ "playlist": { "id": "p1", "name": "Mix", "entry": playlist_songs } }));
+            }
+            MockReply::new("404 Not Found", "")
+        })
+        .await;
+
+        let backend = Arc::new(MemoryBackend::default());
+        let mut storage = DeviceStorage::with_backend(PathBuf::from("/device"), backend.clone());
+        storage.set_playlist_track_format("{n} - {title}");
+        let mut tracks = Vec::new();
+        for (position, title, data) in [(1, "Intro", b"one"), (2, "Intro", b"two"), (3, "Gone", b"old")] {
+            let filename = storage
+                .write_playlist_track("Mix", position, 3, "Artist", title, "mp3", data)
+                .await
+                .unwrap();
+            tracks.push(TrackSignature::new(format!("Playlists/Mix/{}", filename), data));
+        }
+        let mut manifest = SyncManifest::new(&url);
+        manifest.add_playlist(SyncedPlaylist {
+            id: "p1".to_string(),
+            name: "Mix".to_string(),
+            track_count: 3,
+            expected_tracks: Some(3),
+            server_tracks: Some(3),
+            changed: Some("2024-05-01T10:00:00Z".to_string()),
+            song_ids: vec!["s1".to_string(), "s2".to_string(), "s3".to_string()],
+            synced_at: Utc::now(),
+            tracks,
+            format: PlaylistFormat::M3u,
+        });
+
+        let client = SubsonicClient::new(&url, "user", "pass").unwrap();
+        let mut engine = SyncEngine::with_storage(client, storage, manifest, 1);
+        let playlist: Playlist = serde_json::from_value(serde_json::json!({
+            "id": "p1", "name": "Mix", "songCount": 3, "changed": "2024-06-01T10:00:00Z"
+        }))
+        .unwrap();
+        assert!(!engine.is_playlist_synced(&playlist));
+        let selection = SyncSelection {
+            playlists: vec![playlist.clone()],
+            ..Default::default()
+        };
+        let (tx, _rx) = mpsc::channel(64);
+        engine
+            .sync_with_progress(&selection, &DeletionSelection::default(), tx)
+            .await
+            .unwrap();
+
+        assert_eq!(*downloads.lock().unwrap(), ["s4"]);
+        let file = |name: &str| backend.file(&Path::new("/device/Playlists/Mix").join(name));
+        assert_eq!(file("01 - Intro.mp3").as_deref(), Some(&b"two"[..]));
+        assert_eq!(file("02 - Intro.mp3").as_deref(), Some(&b"one"[..]));
+        assert_eq!(file("03 - New.mp3").as_deref(), Some(&b"new"[..]));
+        assert!(file("03 - Gone.mp3").is_none());
+
+        let entry = &engine.manifest.synced_playlists[0];
+        assert_eq!(entry.song_ids, ["s2", "s1", "s4"]);
+        assert_eq!(entry.tracks[1].path, "Playlists/Mix/02 - Intro.mp3");
+        assert_eq!(entry.changed.as_deref(), Some("2024-06-01T10:00:00Z"));
+        assert!(engine.is_playlist_synced(&playlist));
+    }
+
+    #[tokio::test]
+    async fn test_playlist_format_change_only_rewrites_the_playlist_file() {
+        use crate::subsonic::mock::{MockReply, mock_server_with};
+
+        let song = |id: &str| serde_json::json!({ "id": id, "title": id, "artist": "Artist", "suffix": "mp3", "duration": 60 });
+        let (url, requests) = mock_server_with(move |request| {
+            let line = request.lines().next().unwrap_or("");
+            if line.contains("/getPlaylist?") {
+                return MockReply::ok(serde_json::json!({ "playlist": { "id": "p1", "name": "Mix", "entry": [song("s1"), song("s2")] } }));
+            }
+            MockReply::new("404 Not Found", "")
+        })
+        .await;
+
+        let backend = Arc::new(MemoryBackend::default());
+        let storage = DeviceStorage::with_backend(PathBuf::from("/device"), backend.clone());
+        let mut tracks = Vec::new();
+        let mut m3u = Vec::new();
+        for (position, id) in [(1, "s1"), (2, "s2")] {
+            let filename = storage
+                .write_playlist_track("Mix", position, 2, "Artist", id, "mp3", id.as_bytes())
+                .await
+                .unwrap();
+            tracks.push(TrackSignature::new(format!("Playlists/Mix/{}", filename), id.as_bytes()));
+            m3u.push(M3uTrack { filename, duration: Some(60), artist: "Artist".to_string(), title: id.to_string() });
+        }
+        storage.write_playlist_file("Mix", &m3u).await.unwrap();
+        let mut manifest = SyncManifest::new(&url);
+        manifest.add_playlist(SyncedPlaylist {
+            id: "p1".to_string(),
+            name: "Mix".to_string(),
+            track_count: 2,
+            expected_tracks: Some(2),
+            server_tracks: Some(2),
+            changed: None,
+            song_ids: vec!["s1".to_string(), "s2".to_string()],
+            synced_at: Utc::now(),
+            tracks,
+            format: PlaylistFormat::M3u,
+        });
+
+        let client = SubsonicClient::new(&url, "user", "pass").unwrap();
+        let mut engine = SyncEngine::with_storage(client, storage, manifest, 1).with_options(SyncOptions {
+            playlist_format: PlaylistFormat::Pls,
+            ..Default::default()
+        });
+        let playlist: Playlist =
+            serde_json::from_value(serde_json::json!({ "id": "p1", "name": "Mix", "songCount": 2 })).unwrap();
+        assert!(!engine.is_playlist_synced(&playlist));
+        let selection = SyncSelection {
+            playlists: vec![playlist.clone()],
+            ..Default::default()
+        };
+        let (tx, _rx) = mpsc::channel(64);
+        let result = engine
+            .sync_with_progress(&selection, &DeletionSelection::default(), tx)
+            .await
+            .unwrap();
+
+        // Only the playlist was fetched; no track was downloaded again
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert_eq!(result.tracks_downloaded, 0);
+        let folder = Path::new("/device/Playlists/Mix");
+        assert!(backend.file(&folder.join("playlist.m3u")).is_none());
+        let pls = String::from_utf8(backend.file(&folder.join("playlist.pls")).unwrap()).unwrap();
+        assert!(pls.contains("NumberOfEntries=2"), "{}", pls);
+        assert_eq!(backend.file(&folder.join("Artist - s2.mp3")).as_deref(), Some(&b"s2"[..]));
+        assert!(engine.is_playlist_synced(&playlist));
+    }
+
+    #[test]
+    fn test_min_bitrate_filters_and_reports_songs() {
+        let song = |title: &str, bit_rate: Option<u32>| {
+            let mut json = serde_json::json!({ "id": title, "title": title });
+            if let Some(rate) = bit_rate {
+                json["bitRate"] = rate.into();
+            }
+            serde_json::from_value::<Song>(json).unwrap()
+        };
+        let mut engine = engine_with(SyncOptions {
+            min_bitrate: Some(256),
+            ..Default::default()
+        });
+
+        let kept = engine
+            .filter_songs(vec![song("Low", Some(128)), song("High", Some(320)), song("Unknown", None)], "A - B")
+            .unwrap();
+        let titles: Vec<&str> = kept.iter().map(|s| s.title.as_str()).collect();
+        assert_eq!(titles, ["High", "Unknown"]);
+        assert_eq!(engine.low_bitrate_skipped, ["A - B: Low (128 kbps)"]);
+
+        // An item with nothing left is skipped rather than synced empty
+        assert!(engine.filter_songs(vec![song("Low", Some(192))], "A - C").is_none());
+        assert!(engine.filter_songs(Vec::new(), "A - D").is_some());
+    }
+
+    #[test]
+    fn test_multi_artist_albums_stay_in_one_folder() {
+        let song = |title: &str, artist: &str| {
+            serde_json::from_value::<Song>(serde_json::json!({ "id": title, "title": title, "artist": artist }))
+                .unwrap()
+        };
+        let engine = engine_with(SyncOptions {
+            artist_source: Some(ArtistSource::Track),
+            ..Default::default()
+        });
+
+        let compilation = vec![song("One", "A"), song("Two", "B")];
+        let mut unnamed = album("1", None, None);
+        assert_eq!(engine.album_artist(&unnamed, &compilation), ("Various Artists".to_string(), true));
+        unnamed.artist = Some("Now 42".to_string());
+        assert_eq!(engine.album_artist(&unnamed, &compilation), ("Now 42".to_string(), true));
+        assert_eq!(album_track_title(&compilation[1], true), "B - Two");
+
+        // Guest spots on an artist's own album don't make it a compilation
+        let mut own = album("2", None, None);
+        own.artist = Some("A".to_string());
+        let guests = vec![song("One", "A"), song("Two", "A feat. B")];
+        assert_eq!(engine.album_artist(&own, &guests), ("A".to_string(), false));
+        assert_eq!(album_track_title(&guests[1], false), "Two");
+
+        // Single-artist albums still follow --artist-source
+        assert_eq!(engine.album_artist(&own, &[song("One", "Solo")]), ("Solo".to_string(), false));
+    }
+
+    #[test]
+    fn test_videos_are_skipped_unless_included() {
+        let song = |title: &str, content_type: &str| {
+            serde_json::from_value::<Song>(serde_json::json!({
+                "id": title,
+                "title": title,
+                "contentType": content_type,
+            }))
+            .unwrap()
+        };
+        let songs = || vec![song("Track", "audio/mpeg"), song("Clip", "video/mp4")];
+        let engine = |include_video| {
+            engine_with(SyncOptions {
+                include_video,
+                ..Default::default()
+            })
+        };
+
+        let mut skipping = engine(false);
+        assert_eq!(skipping.filter_songs(songs(), "Playlist: Mix").unwrap().len(), 1);
+        assert_eq!(skipping.videos_skipped, ["Playlist: Mix: Clip (video/mp4)"]);
+
+        let mut including = engine(true);
+        assert_eq!(including.filter_songs(songs(), "Playlist: Mix").unwrap().len(), 2);
+        assert!(including.videos_skipped.is_empty());
+    }
+
+    fn album(id: &str, song_count: Option<u32>, duration: Option<u32>) -> Album {
+        Album {
+            id: id.to_string(),
+            name: id.to_string(),
+            artist: None,
+            artist_id: None,
+            cover_art: None,
+            cover_art_ids: Vec::new(),
+            song_count,
+            duration,
+            year: None,
+            genre: None,
+            created: None,
+            source: AlbumSource::Id3,
+        }
+    }
+
+    #[test]
+    fn test_smallest_first_orders_by_size() {
+        let albums = vec![
+            album("big", Some(20), Some(4000)),
+            album("unknown", None, None),
+            album("long-ep", Some(4), Some(1800)),
+            album("short-ep", Some(4), Some(900)),
+            album("single", Some(1), Some(200)),
+        ];
+
+        let ids: Vec<&str> = smallest_first(&albums).iter().map(|a| a.id.as_str()).collect();
+        assert_eq!(ids, ["single", "short-ep", "long-ep", "big", "unknown"]);
+    }
+}
@@ -1,7 +1,9 @@
 //! Sync engine module
 
+pub mod bundle;
 pub mod downloader;
 pub mod engine;
 pub mod pipeline;
+pub mod verify;
 
-pub use engine::{DeletionSelection, SyncEngine, SyncProgress};
+pub use engine::{AUTO_PARALLEL_MAX, ArtistSource, CoverRepairResult, DeletionSelection, InsufficientSpace, OnError, SyncEngine, SyncOptions, SyncProgress, SyncResult, unsync};
@@ -2,18 +2,22 @@
 //!
 //! This module implements a pipelined sync architecture where:
 //! - Download stage: Multiple concurrent network downloads
-//! - Process stage: Parallel cover art embedding (CPU-bound via spawn_blocking)
+//! - Process stage: Parallel cover art embedding and tag filling (CPU-bound via spawn_blocking)
 //! - Write stage: Sequential writes to device (I/O bound)
 //!
 //! All stages run concurrently with backpressure via bounded channels.
 
 use anyhow::{Context, Result};
 use bytes::Bytes;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::{mpsc, Semaphore};
 use tracing::{debug, warn};
 
-use crate::subsonic::Song;
+use crate::subsonic::{Album, Song};
+use crate::utils::cover_art::{
+    CoverArtConfig, CoverPicture, TrackMetadata, apply_metadata, cover_tag, is_embedded, set_pictures,
+};
 
 /// Configuration for the sync pipeline
 #[derive(Debug, Clone)]
@@ -38,8 +42,6 @@ impl Default for PipelineConfig {
 pub struct DownloadedTrack {
     pub song: Song,
     pub audio_data: Bytes,
-    pub artist: String,
-    pub album: String,
     pub track_number: u32,
 }
 
@@ -48,9 +50,29 @@ pub struct DownloadedTrack {
 pub struct ProcessedTrack {
     pub song: Song,
     pub final_audio_data: Vec<u8>,
-    pub artist: String,
-    pub album: String,
     pub track_number: u32,
+    /// Cover art was available but could not be embedded (original audio kept)
+    pub cover_embed_failed: bool,
+}
+
+/// Server metadata to fill into a track's tag, owned for the blocking task
+#[derive(Debug, Clone)]
+pub struct TrackTags {
+    pub song: Song,
+    /// The album the track is synced as part of (none for playlist tracks)
+    pub album: Option<Arc<Album>>,
+    /// Overwrite fields the file already has (`--force-tags`)
+    pub force: bool,
+}
+
+impl TrackTags {
+    fn metadata(&self) -> TrackMetadata<'_> {
+        TrackMetadata {
+            song: &self.song,
+            album: self.album.as_deref(),
+            force: self.force,
+        }
+    }
 }
 
 /// Progress event from the pipeline
@@ -60,17 +82,29 @@ pub enum PipelineEvent {
     Processed,
 }
 
-/// Embed cover art into audio data using spawn_blocking (CPU-bound operation)
+/// Embed cover art and fill in tags using spawn_blocking (CPU-bound operation)
 ///
 /// This runs the lofty-based embedding in a blocking thread pool to avoid
-/// blocking the async runtime.
+/// blocking the async runtime. Temp files go in `temp_dir`. With
+/// `keep_cover`, audio that already has an acceptable cover and the tags is
+/// returned unchanged.
 pub async fn embed_cover_art_async(
     audio_data: Bytes,
-    processed_cover: Arc<Vec<u8>>,
+    pictures: Arc<Vec<CoverPicture>>,
+    tags: TrackTags,
+    keep_cover: Option<CoverArtConfig>,
     file_extension: String,
+    temp_dir: PathBuf,
 ) -> Result<Vec<u8>> {
     tokio::task::spawn_blocking(move || {
-        embed_cover_art_sync(&audio_data, &processed_cover, &file_extension)
+        let metadata = tags.metadata();
+        if let Some(config) = &keep_cover
+            && is_embedded(&audio_data, &pictures, Some(&metadata), config)
+        {
+            debug!("Cover art and tags already in place, keeping the file as it is");
+            return Ok(audio_data.to_vec());
+        }
+        embed_cover_art_sync(&audio_data, &pictures, &metadata, &file_extension, &temp_dir)
     })
     .await
     .context("Cover art embedding task panicked")?
@@ -79,11 +113,12 @@ pub async fn embed_cover_art_async(
 /// Synchronous cover art embedding (called from spawn_blocking)
 fn embed_cover_art_sync(
     audio_data: &[u8],
-    processed_cover: &[u8],
+    pictures: &[CoverPicture],
+    metadata: &TrackMetadata,
     file_extension: &str,
+    temp_dir: &Path,
 ) -> Result<Vec<u8>> {
     use lofty::config::WriteOptions;
-    use lofty::picture::{MimeType, Picture, PictureType};
     use lofty::prelude::*;
     use lofty::probe::Probe;
     use std::fs;
@@ -96,7 +131,6 @@ fn embed_cover_art_sync(
         .duration_since(UNIX_EPOCH)
         .map(|d| d.as_nanos())
         .unwrap_or(0);
-    let temp_dir = std::env::temp_dir();
     let temp_path = temp_dir.join(format!(
         "nutune_embed_{}_{}.{}",
         std::process::id(),
@@ -106,8 +140,9 @@ fn embed_cover_art_sync(
 
     // Write audio data to temp file
     {
-        let mut temp_file =
-            fs::File::create(&temp_path).context("Failed to create temp file for cover embedding")?;
+        let mut temp_file = fs::File::create(&temp_path).with_context(|| {
+            format!("Failed to create temp file for cover embedding in {}", temp_dir.display())
+        })?;
         temp_file
             .write_all(audio_data)
             .context("Failed to write audio to temp file")?;
@@ -119,33 +154,12 @@ fn embed_cover_art_sync(
         .read()
         .context("Failed to read temp audio file")?;
 
-    // Create the picture (cover is already processed)
-    let picture = Picture::new_unchecked(
-        PictureType::CoverFront,
-        Some(MimeType::Jpeg),
-        None,
-        processed_cover.to_vec(),
-    );
-
-    // Get or create tag
-    let tag = match tagged_file.primary_tag_mut() {
-        Some(tag) => tag,
-        None => {
-            if let Some(tag) = tagged_file.first_tag_mut() {
-                tag
-            } else {
-                let tag_type = tagged_file.primary_tag_type();
-                tagged_file.insert_tag(lofty::tag::Tag::new(tag_type));
-                tagged_file
-                    .primary_tag_mut()
-                    .context("Failed to create tag")?
-            }
-        }
-    };
+    // Get or create the tag this format's covers belong in
+    let tag = cover_tag(&mut tagged_file)?;
 
-    // Remove existing cover art and add new one
-    tag.remove_picture_type(PictureType::CoverFront);
-    tag.push_picture(picture);
+    // Replace existing cover art with the new pictures (already processed)
+    set_pictures(tag, pictures);
+    apply_metadata(tag, metadata.song, metadata.album, metadata.force);
 
     // Save back to the temp file
     tagged_file
@@ -163,21 +177,31 @@ fn embed_cover_art_sync(
 
 /// Process a batch of downloaded tracks with cover art embedding in parallel
 ///
-/// Takes a list of downloaded tracks and a pre-processed cover, and returns
-/// processed tracks with cover art embedded.
+/// Takes a list of downloaded tracks and pre-processed pictures (empty for
+/// none), and returns processed tracks with the pictures embedded and tags
+/// filled in from `album` (overwritten with `force_tags`). Tracks that
+/// already have an acceptable cover for `keep_cover` and the tags are left
+/// as they are. Embedding temp files go in `temp_dir`.
+#[allow(clippy::too_many_arguments)]
 pub async fn process_tracks_parallel(
     tracks: Vec<DownloadedTrack>,
-    processed_cover: Option<Arc<Vec<u8>>>,
+    pictures: Arc<Vec<CoverPicture>>,
+    album: Arc<Album>,
+    force_tags: bool,
+    keep_cover: Option<CoverArtConfig>,
     parallelism: usize,
     event_tx: Option<mpsc::Sender<PipelineEvent>>,
+    temp_dir: &Path,
 ) -> Vec<ProcessedTrack> {
     let semaphore = Arc::new(Semaphore::new(parallelism));
     let mut handles = Vec::with_capacity(tracks.len());
 
     for track in tracks {
         let permit = semaphore.clone().acquire_owned().await.unwrap();
-        let cover = processed_cover.clone();
+        let pictures = pictures.clone();
+        let album = album.clone();
         let event_tx = event_tx.clone();
+        let temp_dir = temp_dir.to_path_buf();
 
         let handle = tokio::spawn(async move {
             let _permit = permit; // Hold permit until processing completes
@@ -190,19 +214,28 @@ pub async fn process_tracks_parallel(
                 .to_string();
             let title = track.song.title.clone();
 
-            let final_data = if let Some(cover_data) = cover {
-                match embed_cover_art_async(track.audio_data.clone(), cover_data, extension).await {
-                    Ok(data) => {
-                        debug!("Embedded cover art in: {}", title);
-                        data
-                    }
-                    Err(e) => {
-                        warn!("Failed to embed cover art in {}: {}", title, e);
-                        track.audio_data.to_vec()
-                    }
+            let has_pictures = !pictures.is_empty();
+            let tags = TrackTags {
+                song: track.song.clone(),
+                album: Some(album),
+                force: force_tags,
+            };
+            let mut cover_embed_failed = false;
+            let embedded = embed_cover_art_async(track.audio_data.clone(), pictures, tags, keep_cover, extension, temp_dir);
+            let final_data = match embedded.await {
+                Ok(data) => {
+                    debug!("Embedded cover art and tags in: {}", title);
+                    data
+                }
+                Err(e) if has_pictures => {
+                    warn!("Failed to embed cover art in {}: {}", title, e);
+                    cover_embed_failed = true;
+                    track.audio_data.to_vec()
+                }
+                Err(e) => {
+                    debug!("Failed to write tags in {}: {}", title, e);
+                    track.audio_data.to_vec()
                 }
-            } else {
-                track.audio_data.to_vec()
             };
 
             if let Some(tx) = event_tx {
@@ -212,9 +245,8 @@ pub async fn process_tracks_parallel(
             ProcessedTrack {
                 song: track.song,
                 final_audio_data: final_data,
-                artist: track.artist,
-                album: track.album,
                 track_number: track.track_number,
+                cover_embed_failed,
             }
         });
 
@@ -0,0 +1,440 @@
+//! Checking synced tracks against the signatures in the manifest
+//!
+//! Each track is first checked by size and edge CRC32, which reads at most
+//! 128 KiB. The full SHA-256 is only computed when that isn't conclusive:
+//! the file was modified after it was synced (so its middle may have
+//! changed), or a full check was asked for.
+//!
+//! Albums and playlists synced before signatures were recorded can only be
+//! checked for a folder with non-empty audio files in it.
+
+use chrono::{DateTime, Duration, Utc};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::device::storage::is_audio_file;
+use crate::device::{SyncManifest, TrackSignature};
+use crate::utils::signature::{file_edge_crc32, file_sha256};
+
+/// Slack for filesystems with coarse timestamps (FAT rounds to 2 seconds)
+const MTIME_TOLERANCE_SECS: i64 = 5;
+
+/// What is wrong with a synced track
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackProblem {
+    Missing,
+    Empty,
+    SizeMismatch { expected: u64, actual: u64 },
+    ContentMismatch,
+    Unreadable(String),
+    /// The file couldn't be parsed as audio
+    InvalidAudio(String),
+}
+
+impl std::fmt::Display for TrackProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing => write!(f, "missing"),
+            Self::Empty => write!(f, "empty file"),
+            Self::SizeMismatch { expected, actual } => {
+                write!(f, "size is {} bytes, expected {}", actual, expected)
+            }
+            Self::ContentMismatch => write!(f, "content differs from what was written"),
+            Self::Unreadable(e) => write!(f, "unreadable: {}", e),
+            Self::InvalidAudio(e) => write!(f, "not valid audio: {}", e),
+        }
+    }
+}
+
+/// An album or playlist in the manifest, by ID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncedItem {
+    Album(String),
+    Playlist(String),
+}
+
+/// A track that failed verification
+#[derive(Debug, Clone)]
+pub struct TrackIssue {
+    /// Album ("Artist - Album") or playlist ("Playlist: name") the track belongs to
+    pub item: String,
+    pub synced: SyncedItem,
+    /// Track path relative to the device root
+    pub path: String,
+    pub problem: TrackProblem,
+}
+
+/// What is wrong with a synced album or playlist as a whole
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ItemProblem {
+    FolderMissing,
+    PlaylistFileMissing,
+}
+
+impl std::fmt::Display for ItemProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FolderMissing => write!(f, "folder is missing"),
+            Self::PlaylistFileMissing => write!(f, "playlist file is missing"),
+        }
+    }
+}
+
+/// An album or playlist that failed verification
+#[derive(Debug, Clone)]
+pub struct ItemIssue {
+    pub item: String,
+    pub synced: SyncedItem,
+    /// Folder or file path relative to the device root
+    pub path: String,
+    pub problem: ItemProblem,
+}
+
+/// Result of verifying a device
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub tracks_checked: usize,
+    /// Tracks whose whole content was hashed
+    pub full_hashes: usize,
+    /// Albums and playlists synced before signatures were recorded
+    pub unsigned_items: usize,
+    pub issues: Vec<TrackIssue>,
+    pub item_issues: Vec<ItemIssue>,
+}
+
+impl VerifyReport {
+    /// Whether anything is missing or damaged
+    pub fn has_issues(&self) -> bool {
+        !self.issues.is_empty() || !self.item_issues.is_empty()
+    }
+
+    /// Albums and playlists with any problem, each listed once
+    pub fn broken_items(&self) -> Vec<SyncedItem> {
+        let mut broken: Vec<SyncedItem> = Vec::new();
+        let all = self.item_issues.iter().map(|i| &i.synced).chain(self.issues.iter().map(|i| &i.synced));
+        for synced in all {
+            if !broken.contains(synced) {
+                broken.push(synced.clone());
+            }
+        }
+        broken
+    }
+}
+
+/// One album or playlist to verify
+struct Item<'a> {
+    label: String,
+    synced: SyncedItem,
+    dir: PathBuf,
+    synced_at: DateTime<Utc>,
+    tracks: &'a [TrackSignature],
+    /// Name of the playlist file in `dir`, for playlists
+    playlist_file: Option<&'static str>,
+}
+
+/// Check every album and playlist in `manifest` against the files under `root`
+///
+/// With `full`, every track is hashed whole instead of only ambiguous ones.
+/// With `probe`, tracks that pass are also parsed to check they are audio.
+pub fn verify_device(root: &Path, manifest: &SyncManifest, full: bool, probe: bool) -> VerifyReport {
+    let mut report = VerifyReport::default();
+
+    let albums = manifest.synced_albums.iter().map(|a| Item {
+        label: format!("{} - {}", a.artist, a.album),
+        synced: SyncedItem::Album(a.id.clone()),
+        dir: a.dir(),
+        synced_at: a.synced_at,
+        tracks: &a.tracks,
+        playlist_file: None,
+    });
+    let playlists = manifest.synced_playlists.iter().map(|p| Item {
+        label: format!("Playlist: {}", p.name),
+        synced: SyncedItem::Playlist(p.id.clone()),
+        dir: p.dir(),
+        synced_at: p.synced_at,
+        tracks: &p.tracks,
+        playlist_file: Some(p.format.file_name()),
+    });
+
+    for item in albums.chain(playlists) {
+        let item_issue = |path: &Path, problem| ItemIssue {
+            item: item.label.clone(),
+            synced: item.synced.clone(),
+            path: path.to_string_lossy().into_owned(),
+            problem,
+        };
+        if !root.join(&item.dir).is_dir() {
+            report.item_issues.push(item_issue(&item.dir, ItemProblem::FolderMissing));
+            continue;
+        }
+        if let Some(file_name) = item.playlist_file
+            && !root.join(&item.dir).join(file_name).is_file()
+        {
+            report.item_issues.push(item_issue(&item.dir.join(file_name), ItemProblem::PlaylistFileMissing));
+        }
+
+        let mut results = Vec::new();
+        if item.tracks.is_empty() {
+            report.unsigned_items += 1;
+            for path in audio_files(&root.join(&item.dir)) {
+                let result = match std::fs::metadata(&path) {
+                    Ok(meta) if meta.len() == 0 => Err(TrackProblem::Empty),
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(io_problem(e)),
+                };
+                let relative = path.strip_prefix(root).unwrap_or(&path).to_string_lossy().into_owned();
+                results.push((relative, result));
+            }
+        } else {
+            for signature in item.tracks {
+                let (result, hashed) = check_track(root, signature, item.synced_at, full);
+                if hashed {
+                    report.full_hashes += 1;
+                }
+                results.push((signature.path.clone(), result));
+            }
+        }
+
+        for (path, mut result) in results {
+            report.tracks_checked += 1;
+            if probe && result.is_ok() {
+                result = probe_track(&root.join(&path));
+            }
+            if let Err(problem) = result {
+                report.issues.push(TrackIssue {
+                    item: item.label.clone(),
+                    synced: item.synced.clone(),
+                    path,
+                    problem,
+                });
+            }
+        }
+    }
+
+    report
+}
+
+/// Drop the albums and playlists with problems from `manifest`, so the next sync downloads them again
+///
+/// Returns how many were dropped.
+pub fn forget_broken(manifest: &mut SyncManifest, report: &VerifyReport) -> usize {
+    report
+        .broken_items()
+        .iter()
+        .filter(|synced| match synced {
+            SyncedItem::Album(id) => manifest.remove_album(id).is_some(),
+            SyncedItem::Playlist(id) => manifest.remove_playlist(id).is_some(),
+        })
+        .count()
+}
+
+/// Audio files directly in a folder, in filename order
+fn audio_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| entries.flatten().map(|e| e.path()).filter(|p| p.is_file() && is_audio_file(p)).collect())
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// Check a track parses as audio
+fn probe_track(path: &Path) -> Result<(), TrackProblem> {
+    lofty::probe::Probe::open(path)
+        .map_err(|e| TrackProblem::Unreadable(e.to_string()))?
+        .guess_file_type()
+        .map_err(io_problem)?
+        .read()
+        .map(|_| ())
+        .map_err(|e| TrackProblem::InvalidAudio(e.to_string()))
+}
+
+/// Check one track, returning the outcome and whether it was hashed whole
+fn check_track(
+    root: &Path,
+    signature: &TrackSignature,
+    synced_at: DateTime<Utc>,
+    full: bool,
+) -> (Result<(), TrackProblem>, bool) {
+    let path = root.join(&signature.path);
+
+    let (size, crc) = match file_edge_crc32(&path) {
+        Ok(quick) => quick,
+        Err(e) => return (Err(io_problem(e)), false),
+    };
+    if size == 0 && signature.size > 0 {
+        return (Err(TrackProblem::Empty), false);
+    }
+    if size != signature.size {
+        return (
+            Err(TrackProblem::SizeMismatch {
+                expected: signature.size,
+                actual: size,
+            }),
+            false,
+        );
+    }
+    if crc != signature.edge_crc32 {
+        return (Err(TrackProblem::ContentMismatch), false);
+    }
+
+    if !full && !modified_since(&path, synced_at) {
+        return (Ok(()), false);
+    }
+
+    let result = match file_sha256(&path) {
+        Ok(hash) if hash == signature.sha256 => Ok(()),
+        Ok(_) => Err(TrackProblem::ContentMismatch),
+        Err(e) => Err(io_problem(e)),
+    };
+    (result, true)
+}
+
+/// Whether a file was modified after its album or playlist was synced
+fn modified_since(path: &Path, synced_at: DateTime<Utc>) -> bool {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map(|modified| {
+            DateTime::<Utc>::from(modified) > synced_at + Duration::seconds(MTIME_TOLERANCE_SECS)
+        })
+        .unwrap_or(true)
+}
+
+fn io_problem(e: io::Error) -> TrackProblem {
+    if e.kind() == io::ErrorKind::NotFound {
+        TrackProblem::Missing
+    } else {
+        TrackProblem::Unreadable(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::{SyncedAlbum, SyncedPlaylist};
+    use crate::utils::m3u::PlaylistFormat;
+
+    #[test]
+    fn test_verify_device_finds_damaged_tracks() {
+        let root = std::env::temp_dir().join(format!("nutune_verify_test_{}", std::process::id()));
+        let album_dir = root.join("Artists/Artist/Album");
+        std::fs::create_dir_all(&album_dir).unwrap();
+
+        let tracks: Vec<TrackSignature> = ["01 - Good.mp3", "02 - Short.mp3", "03 - Changed.mp3", "04 - Gone.mp3"]
+            .iter()
+            .map(|name| TrackSignature::new(format!("Artists/Artist/Album/{}", name), b"audio data"))
+            .collect();
+        std::fs::write(album_dir.join("01 - Good.mp3"), b"audio data").unwrap();
+        std::fs::write(album_dir.join("02 - Short.mp3"), b"audio").unwrap();
+        std::fs::write(album_dir.join("03 - Changed.mp3"), b"audio DATA").unwrap();
+        std::fs::create_dir_all(root.join("Artists/Artist/Old")).unwrap();
+        std::fs::write(root.join("Artists/Artist/Old/01 - Empty.mp3"), b"").unwrap();
+
+        let mut manifest = SyncManifest::new("http://server");
+        manifest.add_album(SyncedAlbum {
+            id: "1".to_string(),
+            artist: "Artist".to_string(),
+            album: "Album".to_string(),
+            genre: None,
+            folder: None,
+            track_count: 4,
+            expected_tracks: None,
+            server_tracks: None,
+            synced_tracks: Vec::new(),
+            partial: false,
+            synced_at: Utc::now(),
+            tracks,
+        });
+        manifest.add_album(SyncedAlbum {
+            id: "2".to_string(),
+            artist: "Artist".to_string(),
+            album: "Old".to_string(),
+            genre: None,
+            folder: None,
+            track_count: 1,
+            expected_tracks: None,
+            server_tracks: None,
+            synced_tracks: Vec::new(),
+            partial: false,
+            synced_at: Utc::now(),
+            tracks: Vec::new(),
+        });
+
+        let report = verify_device(&root, &manifest, false, false);
+        let full_report = verify_device(&root, &manifest, true, false);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(report.tracks_checked, 5);
+        assert_eq!(report.full_hashes, 0);
+        assert_eq!(report.unsigned_items, 1);
+        let problems: Vec<_> = report.issues.iter().map(|i| i.problem.clone()).collect();
+        assert_eq!(
+            problems,
+            [
+                TrackProblem::SizeMismatch { expected: 10, actual: 5 },
+                TrackProblem::ContentMismatch,
+                TrackProblem::Missing,
+                TrackProblem::Empty,
+            ]
+        );
+        assert!(report.item_issues.is_empty());
+
+        // Only tracks that pass the quick check get hashed
+        assert_eq!(full_report.full_hashes, 1);
+        assert_eq!(full_report.issues.len(), 4);
+    }
+
+    #[test]
+    fn test_missing_folders_and_repair() {
+        let root = std::env::temp_dir().join(format!("nutune_verify_repair_test_{}", std::process::id()));
+        let playlist_dir = root.join("Playlists/Mix");
+        std::fs::create_dir_all(&playlist_dir).unwrap();
+        std::fs::write(playlist_dir.join("01 - Song.mp3"), b"not really audio").unwrap();
+
+        let mut manifest = SyncManifest::new("http://server");
+        manifest.add_album(SyncedAlbum {
+            id: "1".to_string(),
+            artist: "Artist".to_string(),
+            album: "Gone".to_string(),
+            genre: None,
+            folder: Some("Music/Artist/Gone".to_string()),
+            track_count: 1,
+            expected_tracks: None,
+            server_tracks: None,
+            synced_tracks: Vec::new(),
+            partial: false,
+            synced_at: Utc::now(),
+            tracks: Vec::new(),
+        });
+        manifest.add_playlist(SyncedPlaylist {
+            id: "p1".to_string(),
+            name: "Mix".to_string(),
+            track_count: 1,
+            expected_tracks: None,
+            server_tracks: None,
+            changed: None,
+            song_ids: Vec::new(),
+            synced_at: Utc::now(),
+            tracks: vec![TrackSignature::new("Playlists/Mix/01 - Song.mp3".to_string(), b"not really audio")],
+            format: PlaylistFormat::M3u,
+        });
+
+        let report = verify_device(&root, &manifest, false, false);
+        let probed = verify_device(&root, &manifest, false, true);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let problems: Vec<_> = report.item_issues.iter().map(|i| (i.path.as_str(), i.problem.clone())).collect();
+        assert_eq!(
+            problems,
+            [
+                ("Music/Artist/Gone", ItemProblem::FolderMissing),
+                ("Playlists/Mix/playlist.m3u", ItemProblem::PlaylistFileMissing),
+            ]
+        );
+        assert!(report.issues.is_empty());
+        assert!(matches!(probed.issues[0].problem, TrackProblem::InvalidAudio(_)));
+
+        assert_eq!(forget_broken(&mut manifest, &report), 2);
+        assert!(manifest.synced_albums.is_empty());
+        assert!(manifest.synced_playlists.is_empty());
+    }
+}
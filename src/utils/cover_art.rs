@@ -1,39 +1,124 @@
 //! Cover art processing and embedding for portable device compatibility
 //!
-//! Optimized for FiiO Snowsky Echo Mini requirements:
+//! The defaults are optimized for FiiO Snowsky Echo Mini requirements:
 //! - JPEG format with baseline encoding
 //! - Max 300x300 pixels (maximum compatibility)
 //! - Under 200KB file size
 //! - Embedded in audio file metadata
+//!
+//! Size and quality can be raised through [`CoverArtConfig`] for players
+//! that handle bigger art. WebP output is available for players that
+//! support it, trading compatibility for smaller files, and PNG for players
+//! that only show PNG art.
+//!
+//! The same tag write fills in the title, artist, album and numbering from
+//! the server, for files with sparse tags.
 
 use anyhow::{Context, Result};
 use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
 use image::imageops::FilterType;
-use image::{DynamicImage, ImageReader};
+use image::{DynamicImage, ImageEncoder, ImageReader};
 use lofty::config::WriteOptions;
+use lofty::file::{FileType, TaggedFile};
 use lofty::picture::{MimeType, Picture, PictureType};
 use lofty::prelude::*;
 use lofty::probe::Probe;
+use lofty::tag::{Tag, TagType};
+use std::borrow::Cow;
 use std::io::Cursor;
+use std::path::Path;
 use tracing::{debug, warn};
 
-/// Maximum dimension for cover art (width or height)
+use crate::subsonic::{Album, Song};
+
+/// Default maximum dimension for cover art (width or height)
 /// 300px for maximum Echo Mini compatibility (per user reports)
-const MAX_COVER_SIZE: u32 = 300;
+pub const DEFAULT_COVER_SIZE: u32 = 300;
+
+/// Default JPEG quality (0-100) - 75 for smaller file sizes
+pub const DEFAULT_COVER_QUALITY: u8 = 75;
+
+/// Default maximum file size for cover art in bytes (200KB)
+const DEFAULT_COVER_BYTES: usize = 200 * 1024;
+
+/// Lowest quality used when shrinking an oversized JPEG or WebP cover
+const MIN_COVER_QUALITY: u8 = 50;
+
+/// Smallest dimension WebP covers are shrunk to when enforcing the size cap
+const MIN_WEBP_COVER_SIZE: u32 = 100;
 
-/// JPEG quality (0-100) - 75 for smaller file sizes
-const JPEG_QUALITY: u8 = 75;
+/// Image format covers are encoded as
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum CoverFormat {
+    /// Baseline JPEG (widest device support)
+    #[default]
+    Jpeg,
+    /// WebP (smaller files, needs player support)
+    Webp,
+    /// PNG, for players that only show PNG art (larger files)
+    Png,
+}
 
-/// Maximum file size for cover art in bytes (200KB)
-const MAX_COVER_BYTES: usize = 200 * 1024;
+impl CoverFormat {
+    /// File extension for standalone cover files
+    pub fn extension(self) -> &'static str {
+        match self {
+            CoverFormat::Jpeg => "jpg",
+            CoverFormat::Webp => "webp",
+            CoverFormat::Png => "png",
+        }
+    }
+
+    /// MIME type pictures of this format are embedded with
+    pub fn mime_type(self) -> MimeType {
+        match self {
+            CoverFormat::Jpeg => MimeType::Jpeg,
+            CoverFormat::Webp => MimeType::Unknown("image/webp".to_string()),
+            CoverFormat::Png => MimeType::Png,
+        }
+    }
+
+    fn image_format(self) -> image::ImageFormat {
+        match self {
+            CoverFormat::Jpeg => image::ImageFormat::Jpeg,
+            CoverFormat::Webp => image::ImageFormat::WebP,
+            CoverFormat::Png => image::ImageFormat::Png,
+        }
+    }
+}
+
+/// Limits and format covers are processed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoverArtConfig {
+    /// Maximum width or height in pixels
+    pub max_size: u32,
+    /// JPEG or WebP quality (1-100) to start from; lowered while the file is too large
+    pub quality: u8,
+    /// File size covers are shrunk to fit, in bytes
+    pub max_bytes: usize,
+    pub format: CoverFormat,
+}
+
+impl Default for CoverArtConfig {
+    fn default() -> Self {
+        Self {
+            max_size: DEFAULT_COVER_SIZE,
+            quality: DEFAULT_COVER_QUALITY,
+            max_bytes: DEFAULT_COVER_BYTES,
+            format: CoverFormat::default(),
+        }
+    }
+}
 
 /// Process cover art for device compatibility
 ///
 /// - Decodes the image
-/// - Resizes to fit within MAX_COVER_SIZE (500x500)
-/// - Encodes as baseline JPEG
-/// - Reduces quality if file size exceeds MAX_COVER_BYTES
-pub fn process_cover_art(data: &[u8]) -> Result<Vec<u8>> {
+/// - Resizes to fit within `config.max_size`
+/// - Encodes in the configured format
+/// - Lowers the quality of JPEG and WebP output if file size exceeds `config.max_bytes`
+pub fn process_cover_art(data: &[u8], config: &CoverArtConfig) -> Result<Vec<u8>> {
     // Load image
     let img = ImageReader::new(Cursor::new(data))
         .with_guessed_format()
@@ -41,19 +126,46 @@ pub fn process_cover_art(data: &[u8]) -> Result<Vec<u8>> {
         .decode()
         .context("Failed to decode cover art")?;
 
-    // Resize to fit within MAX_COVER_SIZE
-    let img = resize_to_fit(img);
+    let img = resize_to_fit(img, config.max_size);
+
+    match config.format {
+        CoverFormat::Jpeg => encode_jpeg(&img, config.quality, config.max_bytes),
+        CoverFormat::Webp => encode_webp(img, config.quality, config.max_bytes),
+        CoverFormat::Png => encode_png(&img),
+    }
+}
+
+/// Encode as PNG
+///
+/// PNG is lossless and already resized, so there's nothing left to trade
+/// for a smaller file; the size cap doesn't apply.
+fn encode_png(img: &DynamicImage) -> Result<Vec<u8>> {
+    // Covers are 8-bit; this also drops any 16-bit depth from the source
+    let rgb = img.to_rgb8();
+    let mut output = Vec::new();
+    PngEncoder::new(&mut output)
+        .write_image(rgb.as_raw(), rgb.width(), rgb.height(), image::ExtendedColorType::Rgb8)
+        .context("Failed to encode cover art as PNG")?;
 
-    // Encode as baseline JPEG, reducing quality if file is too large
-    let mut quality = JPEG_QUALITY;
+    debug!(
+        "Processed cover art: {}x{} -> {} bytes (PNG)",
+        rgb.width(),
+        rgb.height(),
+        output.len()
+    );
+    Ok(output)
+}
+
+/// Encode as baseline JPEG, reducing quality if file is too large
+fn encode_jpeg(img: &DynamicImage, mut quality: u8, max_bytes: usize) -> Result<Vec<u8>> {
     loop {
         let mut output = Vec::new();
         let mut encoder = JpegEncoder::new_with_quality(&mut output, quality);
         encoder
-            .encode_image(&img)
+            .encode_image(img)
             .context("Failed to encode cover art as JPEG")?;
 
-        if output.len() <= MAX_COVER_BYTES || quality <= 50 {
+        if output.len() <= max_bytes || quality <= MIN_COVER_QUALITY {
             debug!(
                 "Processed cover art: {}x{} -> {} bytes (quality {})",
                 img.width(),
@@ -69,28 +181,110 @@ pub fn process_cover_art(data: &[u8]) -> Result<Vec<u8>> {
             "Cover art too large ({} bytes), reducing quality from {} to {}",
             output.len(),
             quality,
-            quality - 10
+            quality.saturating_sub(10).max(MIN_COVER_QUALITY)
         );
-        quality -= 10;
+        quality = quality.saturating_sub(10).max(MIN_COVER_QUALITY);
     }
 }
 
-/// Resize image to fit within MAX_COVER_SIZE while maintaining aspect ratio
-fn resize_to_fit(img: DynamicImage) -> DynamicImage {
+/// Encode as WebP, reducing quality and then size if the file is too large
+///
+/// The image crate only has a lossless WebP encoder, so quality is applied
+/// by quantizing the colors first (near-lossless WebP): the lower the
+/// quality, the fewer bits per channel are kept and the better the rest
+/// compresses. Below the lowest quality the image is shrunk instead.
+fn encode_webp(mut img: DynamicImage, mut quality: u8, max_bytes: usize) -> Result<Vec<u8>> {
+    loop {
+        // The WebP encoder only accepts 8-bit RGB(A)
+        let mut rgba = img.to_rgba8();
+        quantize(&mut rgba, quality);
+        let mut output = Vec::new();
+        WebPEncoder::new_lossless(&mut output)
+            .encode(rgba.as_raw(), rgba.width(), rgba.height(), image::ExtendedColorType::Rgba8)
+            .context("Failed to encode cover art as WebP")?;
+
+        let (width, height) = (img.width(), img.height());
+        if output.len() <= max_bytes || (quality <= MIN_COVER_QUALITY && width.max(height) <= MIN_WEBP_COVER_SIZE) {
+            debug!(
+                "Processed cover art: {}x{} -> {} bytes (WebP, quality {})",
+                width,
+                height,
+                output.len(),
+                quality
+            );
+            return Ok(output);
+        }
+
+        if quality > MIN_COVER_QUALITY {
+            let lower = quality.saturating_sub(10).max(MIN_COVER_QUALITY);
+            warn!(
+                "Cover art too large ({} bytes), reducing quality from {} to {}",
+                output.len(),
+                quality,
+                lower
+            );
+            quality = lower;
+            continue;
+        }
+
+        let (new_width, new_height) = (width * 4 / 5, height * 4 / 5);
+        warn!(
+            "Cover art too large ({} bytes), shrinking from {}x{} to {}x{}",
+            output.len(),
+            width,
+            height,
+            new_width,
+            new_height
+        );
+        img = img.resize(new_width.max(1), new_height.max(1), FilterType::Lanczos3);
+    }
+}
+
+/// Drop the low bits of each color channel for `quality` (1-100)
+///
+/// Quality 100 keeps every bit; each 15 points below drops one more, down
+/// to 2 bits kept at quality 1. Values are rounded to the nearest level.
+fn quantize(rgba: &mut image::RgbaImage, quality: u8) {
+    let dropped = u32::from(100 - quality.min(100)) / 15;
+    if dropped == 0 {
+        return;
+    }
+    let mask = 0xFFu8 << dropped;
+    let half = 1u16 << (dropped - 1);
+    for pixel in rgba.pixels_mut() {
+        for channel in &mut pixel.0[..3] {
+            *channel = ((u16::from(*channel) + half).min(255) as u8) & mask;
+        }
+    }
+}
+
+/// MIME type of processed cover art, detected from its header
+pub fn cover_mime_type(data: &[u8]) -> MimeType {
+    if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        MimeType::Unknown("image/webp".to_string())
+    } else if data.starts_with(b"\x89PNG") {
+        MimeType::Png
+    } else {
+        MimeType::Jpeg
+    }
+}
+
+/// Resize image to fit within `max_size` while maintaining aspect ratio
+fn resize_to_fit(img: DynamicImage, max_size: u32) -> DynamicImage {
     let (width, height) = (img.width(), img.height());
 
     // Don't resize if already small enough
-    if width <= MAX_COVER_SIZE && height <= MAX_COVER_SIZE {
+    if width <= max_size && height <= max_size {
         return img;
     }
 
     // Calculate new dimensions maintaining aspect ratio
     let (new_width, new_height) = if width > height {
-        let ratio = MAX_COVER_SIZE as f64 / width as f64;
-        (MAX_COVER_SIZE, (height as f64 * ratio) as u32)
+        let ratio = max_size as f64 / width as f64;
+        (max_size, (height as f64 * ratio) as u32)
     } else {
-        let ratio = MAX_COVER_SIZE as f64 / height as f64;
-        ((width as f64 * ratio) as u32, MAX_COVER_SIZE)
+        let ratio = max_size as f64 / height as f64;
+        ((width as f64 * ratio) as u32, max_size)
     };
 
     debug!(
@@ -101,29 +295,243 @@ fn resize_to_fit(img: DynamicImage) -> DynamicImage {
     img.resize(new_width, new_height, FilterType::Lanczos3)
 }
 
-/// Embed cover art into audio data in memory (before writing to disk)
+/// Whether audio data already has a front cover worth keeping
 ///
-/// Returns the modified audio data with embedded cover art.
-/// Uses a temporary file because lofty requires seekable I/O with original data.
+/// The cover must be within the limits covers are processed to (at most
+/// `config.max_size` pixels a side and `config.max_bytes`) and in
+/// `config.format`, so oversized art or art in another format embedded by
+/// other tools still gets replaced.
+pub fn has_acceptable_cover(audio_data: &[u8], config: &CoverArtConfig) -> bool {
+    let Ok(probe) = Probe::new(Cursor::new(audio_data)).guess_file_type() else {
+        return false;
+    };
+    let Ok(tagged_file) = probe.read() else {
+        return false;
+    };
+
+    tagged_file
+        .tags()
+        .iter()
+        .flat_map(|tag| tag.pictures())
+        .filter(|picture| picture.pic_type() == PictureType::CoverFront)
+        .any(|picture| is_acceptable_cover(picture, config))
+}
+
+/// Whether an embedded picture is in the configured format and within the
+/// processed cover limits
+fn is_acceptable_cover(picture: &Picture, config: &CoverArtConfig) -> bool {
+    // Players may pick a decoder by the declared type, so it has to match too
+    let data = picture.data();
+    if picture.mime_type() != Some(&config.format.mime_type())
+        || image::guess_format(data).ok() != Some(config.format.image_format())
+    {
+        return false;
+    }
+    // PNG covers aren't shrunk to the size cap, so only their dimensions count
+    if config.format != CoverFormat::Png && data.len() > config.max_bytes {
+        return false;
+    }
+    ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_dimensions().ok())
+        .is_some_and(|(width, height)| {
+            width > 0 && height > 0 && width <= config.max_size && height <= config.max_size
+        })
+}
+
+/// An already processed image to embed, and what it shows
+#[derive(Debug, Clone)]
+pub struct CoverPicture {
+    pub kind: PictureType,
+    pub data: Vec<u8>,
+}
+
+impl CoverPicture {
+    /// The front cover
+    pub fn front(data: Vec<u8>) -> Self {
+        Self {
+            kind: PictureType::CoverFront,
+            data,
+        }
+    }
+}
+
+/// Picture type for an album's extra artwork, by its position after the front cover
+///
+/// The first extra image is the back cover; the rest (disc and booklet
+/// scans) are embedded as media.
+pub fn extra_picture_type(index: usize) -> PictureType {
+    if index == 0 {
+        PictureType::CoverBack
+    } else {
+        PictureType::Media
+    }
+}
+
+/// Tag type covers are embedded in for a file type
+///
+/// Chosen explicitly rather than using whichever tag a file already has:
+/// a FLAC or Ogg file carrying a stray ID3v2 tag would otherwise get its
+/// cover there, where players don't look.
+pub fn cover_tag_type(file_type: FileType) -> TagType {
+    match file_type {
+        FileType::Mpeg => TagType::Id3v2,
+        // Written as PICTURE blocks in FLAC and METADATA_BLOCK_PICTURE comments in Ogg
+        FileType::Flac | FileType::Vorbis | FileType::Opus | FileType::Speex => TagType::VorbisComments,
+        other => other.primary_tag_type(),
+    }
+}
+
+/// The tag to embed covers in, created if the file doesn't have one yet
+pub fn cover_tag(tagged_file: &mut TaggedFile) -> Result<&mut Tag> {
+    let tag_type = cover_tag_type(tagged_file.file_type());
+    if tagged_file.tag(tag_type).is_none() {
+        tagged_file.insert_tag(Tag::new(tag_type));
+    }
+    tagged_file.tag_mut(tag_type).context("Failed to create tag")
+}
+
+/// Replace the tag's pictures of each type in `pictures` with the new ones
+///
+/// Pictures of other types (e.g. an artist photo) are kept.
+pub fn set_pictures(tag: &mut Tag, pictures: &[CoverPicture]) {
+    for picture in pictures {
+        tag.remove_picture_type(picture.kind);
+    }
+    for picture in pictures {
+        tag.push_picture(Picture::new_unchecked(
+            picture.kind,
+            Some(cover_mime_type(&picture.data)),
+            None,
+            picture.data.clone(),
+        ));
+    }
+}
+
+/// Server metadata for a track, written into the tag alongside the covers
+#[derive(Debug, Clone, Copy)]
+pub struct TrackMetadata<'a> {
+    pub song: &'a Song,
+    /// The album the track is synced as part of (none for playlist tracks)
+    pub album: Option<&'a Album>,
+    /// Overwrite fields the file already has, not just empty ones
+    pub force: bool,
+}
+
+/// Fill in title, artist, album, track, disc, year and genre from the server
+///
+/// Song fields come first, then the album's. Fields the file already has
+/// are kept unless `force` is set.
+pub fn apply_metadata(tag: &mut Tag, song: &Song, album: Option<&Album>, force: bool) {
+    fn is_missing(value: Option<Cow<'_, str>>) -> bool {
+        value.is_none_or(|v| v.trim().is_empty())
+    }
+
+    if force || is_missing(tag.title()) {
+        tag.set_title(song.title.clone());
+    }
+    if let Some(artist) = song.artist.as_ref().or_else(|| album.and_then(|a| a.artist.as_ref()))
+        && (force || is_missing(tag.artist()))
+    {
+        tag.set_artist(artist.clone());
+    }
+    if let Some(name) = song.album.as_ref().or_else(|| album.map(|a| &a.name))
+        && (force || is_missing(tag.album()))
+    {
+        tag.set_album(name.clone());
+    }
+    if let Some(track) = song.track
+        && (force || tag.track().is_none())
+    {
+        tag.set_track(track);
+    }
+    if let Some(disc) = song.disc_number
+        && (force || tag.disk().is_none())
+    {
+        tag.set_disk(disc);
+    }
+    if let Some(year) = album.and_then(|a| a.year)
+        && (force || tag.year().is_none())
+    {
+        tag.set_year(year);
+    }
+    if let Some(genre) = album.and_then(|a| a.genre.as_ref())
+        && (force || is_missing(tag.genre()))
+    {
+        tag.set_genre(genre.clone());
+    }
+}
+
+/// Whether audio already has everything embedding would write into it
+///
+/// That's an acceptable front cover for `config` (see [`has_acceptable_cover`]),
+/// the other pictures as they are, and every tag field `metadata` would set.
+pub fn is_embedded(
+    audio_data: &[u8],
+    pictures: &[CoverPicture],
+    metadata: Option<&TrackMetadata>,
+    config: &CoverArtConfig,
+) -> bool {
+    let Ok(probe) = Probe::new(Cursor::new(audio_data)).guess_file_type() else {
+        return false;
+    };
+    let Ok(tagged_file) = probe.read() else {
+        return false;
+    };
+    let Some(tag) = tagged_file.tag(cover_tag_type(tagged_file.file_type())) else {
+        return false;
+    };
+
+    let has_pictures = pictures.iter().all(|picture| {
+        let mut existing = tag.pictures().iter().filter(|p| p.pic_type() == picture.kind);
+        if picture.kind == PictureType::CoverFront {
+            existing.any(|p| is_acceptable_cover(p, config))
+        } else {
+            existing.any(|p| p.data() == picture.data.as_slice())
+        }
+    });
+    has_pictures
+        && metadata.is_none_or(|metadata| {
+            let mut updated = tag.clone();
+            apply_metadata(&mut updated, metadata.song, metadata.album, metadata.force);
+            updated.item_count() == tag.item_count() && updated.items().all(|item| tag.items().any(|i| i == item))
+        })
+}
+
+/// Embed already processed pictures into audio data in memory (before writing to disk)
+///
+/// Tag fields are filled in from `metadata` in the same write. Returns the
+/// modified audio data, or the original when `keep_cover` is given and the
+/// audio already has an acceptable cover for it and the tags (see
+/// [`is_embedded`]). Uses a temporary file in `temp_dir` because lofty
+/// requires seekable I/O with original data.
 pub fn embed_cover_art_in_memory(
     audio_data: &[u8],
-    cover_data: &[u8],
+    pictures: &[CoverPicture],
+    metadata: Option<&TrackMetadata>,
+    keep_cover: Option<&CoverArtConfig>,
     file_extension: &str,
+    temp_dir: &Path,
 ) -> Result<Vec<u8>> {
     use std::fs;
     use std::io::Write;
 
-    // Process cover art first
-    let processed_cover = process_cover_art(cover_data)?;
+    if let Some(config) = keep_cover
+        && is_embedded(audio_data, pictures, metadata, config)
+    {
+        debug!("Cover art and tags already in place, keeping the file as it is");
+        return Ok(audio_data.to_vec());
+    }
 
     // Create a temp file with the audio data
-    let temp_dir = std::env::temp_dir();
     let temp_path = temp_dir.join(format!("nutune_embed_{}.{}", std::process::id(), file_extension));
 
     // Write audio data to temp file
     {
-        let mut temp_file = fs::File::create(&temp_path)
-            .context("Failed to create temp file for cover embedding")?;
+        let mut temp_file = fs::File::create(&temp_path).with_context(|| {
+            format!("Failed to create temp file for cover embedding in {}", temp_dir.display())
+        })?;
         temp_file.write_all(audio_data)
             .context("Failed to write audio to temp file")?;
     }
@@ -134,33 +542,14 @@ pub fn embed_cover_art_in_memory(
         .read()
         .context("Failed to read temp audio file")?;
 
-    // Create the picture
-    let picture = Picture::new_unchecked(
-        PictureType::CoverFront,
-        Some(MimeType::Jpeg),
-        None,
-        processed_cover,
-    );
+    // Get or create the tag this format's covers belong in
+    let tag = cover_tag(&mut tagged_file)?;
 
-    // Get or create tag
-    let tag = match tagged_file.primary_tag_mut() {
-        Some(tag) => tag,
-        None => {
-            if let Some(tag) = tagged_file.first_tag_mut() {
-                tag
-            } else {
-                let tag_type = tagged_file.primary_tag_type();
-                tagged_file.insert_tag(lofty::tag::Tag::new(tag_type));
-                tagged_file
-                    .primary_tag_mut()
-                    .context("Failed to create tag")?
-            }
-        }
-    };
-
-    // Remove existing cover art and add new one
-    tag.remove_picture_type(PictureType::CoverFront);
-    tag.push_picture(picture);
+    // Replace existing cover art with the new pictures
+    set_pictures(tag, pictures);
+    if let Some(metadata) = metadata {
+        apply_metadata(tag, metadata.song, metadata.album, metadata.force);
+    }
 
     // Save back to the temp file
     tagged_file
@@ -185,7 +574,7 @@ mod tests {
     fn test_resize_small_image() {
         // Create a small test image (100x100)
         let img = DynamicImage::new_rgb8(100, 100);
-        let resized = resize_to_fit(img);
+        let resized = resize_to_fit(img, DEFAULT_COVER_SIZE);
         assert_eq!(resized.width(), 100);
         assert_eq!(resized.height(), 100);
     }
@@ -194,8 +583,339 @@ mod tests {
     fn test_resize_large_image() {
         // Create a large test image (1500x1000)
         let img = DynamicImage::new_rgb8(1500, 1000);
-        let resized = resize_to_fit(img);
-        assert_eq!(resized.width(), MAX_COVER_SIZE);
-        assert!(resized.height() <= MAX_COVER_SIZE);
+        let resized = resize_to_fit(img, DEFAULT_COVER_SIZE);
+        assert_eq!(resized.width(), DEFAULT_COVER_SIZE);
+        assert!(resized.height() <= DEFAULT_COVER_SIZE);
+    }
+
+    /// Encode a noisy test image (compresses poorly, like real artwork)
+    fn noisy_png(width: u32, height: u32) -> Vec<u8> {
+        let img = image::RgbImage::from_fn(width, height, |x, y| {
+            let v = x.wrapping_mul(2_654_435_761).wrapping_add(y.wrapping_mul(40_503)) as u8;
+            image::Rgb([v, v.wrapping_mul(7), v.wrapping_mul(13)])
+        });
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(img)
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn test_webp_output_respects_size_cap() {
+        let config = CoverArtConfig { format: CoverFormat::Webp, ..Default::default() };
+        let processed = process_cover_art(&noisy_png(800, 800), &config).unwrap();
+        assert!(processed.len() <= config.max_bytes);
+        assert_eq!(cover_mime_type(&processed), MimeType::Unknown("image/webp".to_string()));
+
+        let decoded = image::load_from_memory(&processed).unwrap();
+        assert!(decoded.width() <= config.max_size);
+    }
+
+    #[test]
+    fn test_webp_lowers_quality_before_size() {
+        let webp = |quality, max_bytes| {
+            let config = CoverArtConfig { format: CoverFormat::Webp, quality, max_bytes, ..Default::default() };
+            process_cover_art(&noisy_png(800, 800), &config).unwrap()
+        };
+        let lossless = webp(100, usize::MAX);
+        let lossy = webp(50, usize::MAX);
+        assert!(lossy.len() < lossless.len() * 2 / 3);
+
+        // Fits at a lower quality, so it keeps its size
+        let capped = webp(90, lossy.len() + 1024);
+        assert!(capped.len() <= lossy.len() + 1024);
+        let decoded = image::load_from_memory(&capped).unwrap();
+        assert_eq!(decoded.width(), DEFAULT_COVER_SIZE);
+    }
+
+    #[test]
+    fn test_png_output() {
+        let config = CoverArtConfig { format: CoverFormat::Png, ..Default::default() };
+        let processed = process_cover_art(&noisy_png(600, 400), &config).unwrap();
+        assert_eq!(cover_mime_type(&processed), MimeType::Png);
+
+        let decoded = image::load_from_memory(&processed).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (300, 200));
+    }
+
+    #[test]
+    fn test_configured_cover_size() {
+        let config = CoverArtConfig {
+            max_size: 500,
+            quality: 90,
+            max_bytes: usize::MAX,
+            format: CoverFormat::Jpeg,
+        };
+        let processed = process_cover_art(&noisy_png(1000, 800), &config).unwrap();
+        let decoded = image::load_from_memory(&processed).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (500, 400));
+    }
+
+    #[test]
+    fn test_set_pictures_replaces_only_given_types() {
+        let mut tag = Tag::new(lofty::tag::TagType::Id3v2);
+        tag.push_picture(Picture::new_unchecked(PictureType::CoverFront, None, None, b"old".to_vec()));
+        tag.push_picture(Picture::new_unchecked(PictureType::Artist, None, None, b"artist".to_vec()));
+
+        let pictures = [
+            CoverPicture::front(b"front".to_vec()),
+            CoverPicture { kind: extra_picture_type(0), data: b"back".to_vec() },
+            CoverPicture { kind: extra_picture_type(1), data: b"disc".to_vec() },
+            CoverPicture { kind: extra_picture_type(2), data: b"booklet".to_vec() },
+        ];
+        set_pictures(&mut tag, &pictures);
+
+        let kinds: Vec<_> = tag.pictures().iter().map(|p| (p.pic_type(), p.data().to_vec())).collect();
+        assert_eq!(
+            kinds,
+            [
+                (PictureType::Artist, b"artist".to_vec()),
+                (PictureType::CoverFront, b"front".to_vec()),
+                (PictureType::CoverBack, b"back".to_vec()),
+                (PictureType::Media, b"disc".to_vec()),
+                (PictureType::Media, b"booklet".to_vec()),
+            ]
+        );
+    }
+
+    /// An embedded front cover declared as `mime_type`
+    fn front_cover(mime_type: MimeType, data: Vec<u8>) -> Picture {
+        Picture::new_unchecked(PictureType::CoverFront, Some(mime_type), None, data)
+    }
+
+    #[test]
+    fn test_acceptable_cover_limits() {
+        let config = CoverArtConfig { format: CoverFormat::Png, ..Default::default() };
+        let png = |width, height| front_cover(MimeType::Png, noisy_png(width, height));
+        assert!(is_acceptable_cover(&png(DEFAULT_COVER_SIZE, 200), &config));
+        assert!(!is_acceptable_cover(&png(DEFAULT_COVER_SIZE + 1, 200), &config));
+        assert!(!is_acceptable_cover(&front_cover(MimeType::Png, b"not an image".to_vec()), &config));
+        let larger = CoverArtConfig { max_size: 500, ..config };
+        assert!(is_acceptable_cover(&png(DEFAULT_COVER_SIZE + 1, 200), &larger));
+        let tiny_cap = CoverArtConfig { max_bytes: 16, ..config };
+        assert!(is_acceptable_cover(&png(DEFAULT_COVER_SIZE, 200), &tiny_cap));
+    }
+
+    #[test]
+    fn test_acceptable_cover_matches_format() {
+        let jpeg = process_cover_art(&noisy_png(64, 64), &CoverArtConfig::default()).unwrap();
+        let config = CoverArtConfig::default();
+        assert!(is_acceptable_cover(&front_cover(MimeType::Jpeg, jpeg.clone()), &config));
+        // Another format, however it's declared
+        assert!(!is_acceptable_cover(&front_cover(MimeType::Png, noisy_png(64, 64)), &config));
+        assert!(!is_acceptable_cover(&front_cover(MimeType::Jpeg, noisy_png(64, 64)), &config));
+        // The right data declared as something else, or not declared
+        assert!(!is_acceptable_cover(&front_cover(MimeType::Png, jpeg.clone()), &config));
+        let undeclared = Picture::new_unchecked(PictureType::CoverFront, None, None, jpeg.clone());
+        assert!(!is_acceptable_cover(&undeclared, &config));
+
+        let webp = CoverArtConfig { format: CoverFormat::Webp, ..config };
+        assert!(!is_acceptable_cover(&front_cover(MimeType::Jpeg, jpeg), &webp));
+        let processed = process_cover_art(&noisy_png(64, 64), &webp).unwrap();
+        assert!(is_acceptable_cover(&front_cover(CoverFormat::Webp.mime_type(), processed), &webp));
+    }
+
+    /// Minimal MP3: a few silent MPEG-1 Layer III frames (128 kbps, 44.1 kHz)
+    fn sample_mp3() -> Vec<u8> {
+        let mut frame = vec![0u8; 417];
+        frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+        frame.repeat(8)
+    }
+
+    /// Minimal FLAC: the marker, STREAMINFO (44.1 kHz, stereo, 16-bit) and padding
+    fn sample_flac() -> Vec<u8> {
+        let mut flac = b"fLaC".to_vec();
+        flac.extend([0x00, 0, 0, 34]); // STREAMINFO, 34 bytes
+        flac.extend(4096u16.to_be_bytes());
+        flac.extend(4096u16.to_be_bytes());
+        flac.extend([0; 6]); // frame sizes unknown
+        let format: u64 = (44_100 << 44) | (1 << 41) | (15 << 36); // rate, channels - 1, bits - 1, no samples
+        flac.extend(format.to_be_bytes());
+        flac.extend([0; 16]); // MD5
+        flac.extend([0x81, 0, 0, 16]); // last metadata block, PADDING, 16 bytes
+        flac.extend([0; 16]);
+        flac
+    }
+
+    /// ID3v2.4 tag holding only a title, as some taggers prepend to FLAC files
+    fn id3v2_title_tag() -> Vec<u8> {
+        let mut tag = b"ID3\x04\x00\x00".to_vec();
+        tag.extend([0, 0, 0, 12]); // tag size (synchsafe)
+        tag.extend(b"TIT2");
+        tag.extend([0, 0, 0, 2, 0, 0]); // frame size (synchsafe), flags
+        tag.extend([0x03, b'x']); // UTF-8 "x"
+        tag
+    }
+
+    /// CRC used by Ogg pages (polynomial 0x04C11DB7, not reflected)
+    fn ogg_crc(data: &[u8]) -> u32 {
+        let mut crc = 0u32;
+        for &byte in data {
+            crc ^= (byte as u32) << 24;
+            for _ in 0..8 {
+                crc = if crc & 0x8000_0000 != 0 { (crc << 1) ^ 0x04C1_1DB7 } else { crc << 1 };
+            }
+        }
+        crc
+    }
+
+    fn ogg_page(header_type: u8, sequence: u32, packets: &[&[u8]]) -> Vec<u8> {
+        let mut lacing = Vec::new();
+        for packet in packets {
+            lacing.extend(std::iter::repeat_n(255u8, packet.len() / 255));
+            lacing.push((packet.len() % 255) as u8);
+        }
+
+        let mut page = b"OggS".to_vec();
+        page.extend([0, header_type]);
+        page.extend(0u64.to_le_bytes()); // granule position
+        page.extend(1u32.to_le_bytes()); // stream serial
+        page.extend(sequence.to_le_bytes());
+        page.extend(0u32.to_le_bytes()); // CRC, filled in below
+        page.push(lacing.len() as u8);
+        page.extend(lacing);
+        for packet in packets {
+            page.extend_from_slice(packet);
+        }
+        let crc = ogg_crc(&page);
+        page[22..26].copy_from_slice(&crc.to_le_bytes());
+        page
+    }
+
+    /// Minimal Ogg Vorbis: the three header packets and no audio
+    fn sample_ogg() -> Vec<u8> {
+        let mut identification = b"\x01vorbis".to_vec();
+        identification.extend(0u32.to_le_bytes()); // version
+        identification.push(2); // channels
+        identification.extend(44_100u32.to_le_bytes());
+        identification.extend(0i32.to_le_bytes());
+        identification.extend(128_000i32.to_le_bytes());
+        identification.extend(0i32.to_le_bytes());
+        identification.extend([0xB8, 1]); // block sizes 256/2048, framing bit
+
+        let mut comment = b"\x03vorbis".to_vec();
+        comment.extend(6u32.to_le_bytes());
+        comment.extend(b"nutune");
+        comment.extend(0u32.to_le_bytes()); // no comments
+        comment.push(1);
+
+        let setup = b"\x05vorbis\x00".to_vec();
+
+        let mut ogg = ogg_page(0x02, 0, &[&identification]);
+        ogg.extend(ogg_page(0x00, 1, &[&comment, &setup]));
+        ogg
+    }
+
+    #[test]
+    fn test_cover_is_embedded_in_each_format_native_tag() {
+        let cover = process_cover_art(&noisy_png(64, 64), &CoverArtConfig::default()).unwrap();
+        let pictures = [CoverPicture::front(cover.clone())];
+
+        for (audio, extension, file_type, tag_type) in [
+            (sample_mp3(), "mp3", FileType::Mpeg, TagType::Id3v2),
+            (sample_flac(), "flac", FileType::Flac, TagType::VorbisComments),
+            // A stray ID3v2 tag mustn't get the cover instead of the Vorbis comments
+            ([id3v2_title_tag(), sample_flac()].concat(), "flac", FileType::Flac, TagType::VorbisComments),
+            (sample_ogg(), "ogg", FileType::Vorbis, TagType::VorbisComments),
+        ] {
+            let embedded = embed_cover_art_in_memory(&audio, &pictures, None, None, extension, &std::env::temp_dir())
+                .unwrap_or_else(|e| panic!("embedding into {} failed: {:#}", extension, e));
+
+            let tagged_file = Probe::new(Cursor::new(&embedded))
+                .guess_file_type()
+                .unwrap()
+                .read()
+                .unwrap();
+            assert_eq!(tagged_file.file_type(), file_type);
+            let tag = tagged_file
+                .tag(tag_type)
+                .unwrap_or_else(|| panic!("{} has no {:?} tag", extension, tag_type));
+            let front = tag.pictures().iter().find(|p| p.pic_type() == PictureType::CoverFront);
+            assert_eq!(front.map(|p| p.data()), Some(cover.as_slice()), "{}", extension);
+            assert!(has_acceptable_cover(&embedded, &CoverArtConfig::default()), "{}", extension);
+        }
+    }
+
+    fn tagged_song() -> (Song, Album) {
+        let song = serde_json::from_value(serde_json::json!({
+            "id": "s1", "title": "Song", "artist": "Artist", "album": "Album", "track": 3, "discNumber": 2
+        }))
+        .unwrap();
+        let album = serde_json::from_value(serde_json::json!({
+            "id": "a1", "name": "Album", "year": 1997, "genre": "Trip-Hop"
+        }))
+        .unwrap();
+        (song, album)
+    }
+
+    fn read_id3v2(data: &[u8]) -> Tag {
+        let tagged_file = Probe::new(Cursor::new(data)).guess_file_type().unwrap().read().unwrap();
+        tagged_file.tag(TagType::Id3v2).expect("no ID3v2 tag").clone()
+    }
+
+    #[test]
+    fn test_metadata_fills_missing_tags() {
+        // Own directory, so the temp file doesn't clash with other embedding tests
+        let temp_dir = std::env::temp_dir().join(format!("nutune_tags_test_{}", std::process::id()));
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let (song, album) = tagged_song();
+        let metadata = TrackMetadata { song: &song, album: Some(&album), force: false };
+
+        // An untagged MP3 gains every frame
+        let tagged = embed_cover_art_in_memory(&sample_mp3(), &[], Some(&metadata), None, "mp3", &temp_dir).unwrap();
+        let tag = read_id3v2(&tagged);
+        assert_eq!(tag.title().as_deref(), Some("Song"));
+        assert_eq!(tag.artist().as_deref(), Some("Artist"));
+        assert_eq!(tag.album().as_deref(), Some("Album"));
+        assert_eq!(tag.track(), Some(3));
+        assert_eq!(tag.disk(), Some(2));
+        assert_eq!(tag.year(), Some(1997));
+        assert_eq!(tag.genre().as_deref(), Some("Trip-Hop"));
+
+        // An existing title is kept unless forced
+        let audio = [id3v2_title_tag(), sample_mp3()].concat();
+        let kept = embed_cover_art_in_memory(&audio, &[], Some(&metadata), None, "mp3", &temp_dir).unwrap();
+        let tag = read_id3v2(&kept);
+        assert_eq!(tag.title().as_deref(), Some("x"));
+        assert_eq!(tag.artist().as_deref(), Some("Artist"));
+
+        let metadata = TrackMetadata { force: true, ..metadata };
+        let forced = embed_cover_art_in_memory(&audio, &[], Some(&metadata), None, "mp3", &temp_dir).unwrap();
+        assert_eq!(read_id3v2(&forced).title().as_deref(), Some("Song"));
+
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_acceptable_cover_keeps_the_file() {
+        let temp_dir = std::env::temp_dir().join(format!("nutune_keep_cover_test_{}", std::process::id()));
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let config = CoverArtConfig::default();
+        let (song, album) = tagged_song();
+        let metadata = TrackMetadata { song: &song, album: Some(&album), force: false };
+        let cover = |size| [CoverPicture::front(process_cover_art(&noisy_png(size, size), &config).unwrap())];
+        let embed = |audio: &[u8], pictures: &[CoverPicture], keep: Option<&CoverArtConfig>| {
+            embed_cover_art_in_memory(audio, pictures, Some(&metadata), keep, "mp3", &temp_dir).unwrap()
+        };
+        let tagged = embed(&sample_mp3(), &cover(64), None);
+
+        // Another cover from the server doesn't replace an acceptable one
+        assert_eq!(embed(&tagged, &cover(32), Some(&config)), tagged);
+        assert_ne!(embed(&tagged, &cover(32), None), tagged);
+        // Nor does it once the cover is too big, or a tag is missing
+        let smaller = CoverArtConfig { max_size: 48, ..config };
+        assert_ne!(embed(&tagged, &cover(32), Some(&smaller)), tagged);
+        let untagged = embed_cover_art_in_memory(&sample_mp3(), &cover(64), None, None, "mp3", &temp_dir).unwrap();
+        let filled = embed(&untagged, &cover(32), Some(&config));
+        assert_eq!(read_id3v2(&filled).title().as_deref(), Some("Song"));
+
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_jpeg_is_default_format() {
+        let processed = process_cover_art(&noisy_png(64, 64), &CoverArtConfig::default()).unwrap();
+        assert_eq!(cover_mime_type(&processed), MimeType::Jpeg);
     }
 }
@@ -0,0 +1,103 @@
+//! Minimal CSV parsing for selection imports
+
+/// Parse CSV content into rows of fields
+///
+/// Supports quoted fields (with `""` as an escaped quote), commas and newlines
+/// inside quotes, and both LF and CRLF line endings. Blank lines and lines
+/// starting with `#` are skipped. Unquoted fields are trimmed.
+pub fn parse_csv(content: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut was_quoted = false;
+    let mut chars = content.chars().peekable();
+
+    let finish_field = |field: &mut String, row: &mut Vec<String>, was_quoted: &mut bool| {
+        let value = if *was_quoted {
+            field.clone()
+        } else {
+            field.trim().to_string()
+        };
+        row.push(value);
+        field.clear();
+        *was_quoted = false;
+    };
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                in_quotes = true;
+                was_quoted = true;
+            }
+            ',' => finish_field(&mut field, &mut row, &mut was_quoted),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                finish_field(&mut field, &mut row, &mut was_quoted);
+                push_row(&mut rows, std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || was_quoted || !row.is_empty() {
+        finish_field(&mut field, &mut row, &mut was_quoted);
+        push_row(&mut rows, row);
+    }
+
+    rows
+}
+
+/// Add a row unless it is blank or a comment
+fn push_row(rows: &mut Vec<Vec<String>>, row: Vec<String>) {
+    let is_blank = row.iter().all(|f| f.is_empty());
+    let is_comment = row.first().is_some_and(|f| f.starts_with('#'));
+    if !is_blank && !is_comment {
+        rows.push(row);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_simple_rows() {
+        let rows = parse_csv("artist,album\nRadiohead, OK Computer\n");
+        assert_eq!(
+            rows,
+            vec![
+                vec!["artist".to_string(), "album".to_string()],
+                vec!["Radiohead".to_string(), "OK Computer".to_string()],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_quoted_fields() {
+        let rows = parse_csv("\"Crosby, Stills & Nash\",\"Say \"\"Hi\"\"\"\r\n");
+        assert_eq!(
+            rows,
+            vec![vec!["Crosby, Stills & Nash".to_string(), "Say \"Hi\"".to_string()]]
+        );
+    }
+
+    #[test]
+    fn test_skip_blank_and_comment_lines() {
+        let rows = parse_csv("# my list\n\nBjörk,Homogenic");
+        assert_eq!(rows, vec![vec!["Björk".to_string(), "Homogenic".to_string()]]);
+    }
+}
@@ -0,0 +1,90 @@
+//! CUE sheet generation for chapter markers
+
+/// A chapter starting at a position within a track
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    /// Start position in milliseconds
+    pub start_ms: u64,
+    pub title: String,
+}
+
+/// Generate a CUE sheet splitting one audio file into chapters
+///
+/// The first chapter always starts at 00:00:00 (CUE requires it), so a
+/// leading "Start" chapter is added when the earliest marker is later.
+pub fn generate_cue(performer: &str, title: &str, file_name: &str, chapters: &[Chapter]) -> String {
+    let mut chapters = chapters.to_vec();
+    chapters.sort_by_key(|c| c.start_ms);
+    if chapters.first().is_none_or(|c| c.start_ms > 0) {
+        chapters.insert(
+            0,
+            Chapter {
+                start_ms: 0,
+                title: "Start".to_string(),
+            },
+        );
+    }
+
+    let file_type = if file_name.to_lowercase().ends_with(".mp3") {
+        "MP3"
+    } else {
+        "WAVE"
+    };
+
+    let mut content = format!(
+        "PERFORMER \"{}\"\nTITLE \"{}\"\nFILE \"{}\" {}\n",
+        quote(performer),
+        quote(title),
+        quote(file_name),
+        file_type
+    );
+    for (i, chapter) in chapters.iter().enumerate() {
+        content.push_str(&format!(
+            "  TRACK {:02} AUDIO\n    TITLE \"{}\"\n    INDEX 01 {}\n",
+            i + 1,
+            quote(&chapter.title),
+            cue_time(chapter.start_ms)
+        ));
+    }
+    content
+}
+
+/// Format milliseconds as a CUE `MM:SS:FF` timestamp (75 frames per second)
+fn cue_time(ms: u64) -> String {
+    let minutes = ms / 60_000;
+    let seconds = (ms / 1000) % 60;
+    let frames = (ms % 1000) * 75 / 1000;
+    format!("{:02}:{:02}:{:02}", minutes, seconds, frames)
+}
+
+/// CUE strings can't contain double quotes
+fn quote(value: &str) -> String {
+    value.replace('"', "'")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cue_time() {
+        assert_eq!(cue_time(0), "00:00:00");
+        assert_eq!(cue_time(90_500), "01:30:37");
+        assert_eq!(cue_time(3_725_000), "62:05:00");
+    }
+
+    #[test]
+    fn test_generate_cue_adds_start_chapter() {
+        let chapters = vec![Chapter {
+            start_ms: 90_500,
+            title: "Part \"2\"".to_string(),
+        }];
+        let cue = generate_cue("Author", "Book", "01 - Book.mp3", &chapters);
+        assert_eq!(
+            cue,
+            "PERFORMER \"Author\"\nTITLE \"Book\"\nFILE \"01 - Book.mp3\" MP3\n\
+             \x20 TRACK 01 AUDIO\n    TITLE \"Start\"\n    INDEX 01 00:00:00\n\
+             \x20 TRACK 02 AUDIO\n    TITLE \"Part '2'\"\n    INDEX 01 01:30:37\n"
+        );
+    }
+}
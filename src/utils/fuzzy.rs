@@ -0,0 +1,74 @@
+//! Typo-tolerant matching for the browser's search filter
+
+/// Score how well `text` matches `query` as a fuzzy subsequence, ignoring case
+///
+/// Every character of the query (spaces aside) must appear in `text` in
+/// order, so "radiohd" matches "Radiohead" and "ok computr" matches
+/// "OK Computer". Runs of consecutive characters and matches at the start
+/// of a word score higher; gaps between matches cost a little. Returns
+/// `None` if the query isn't a subsequence of the text.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let query: Vec<char> = query
+        .to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let mut score = 0i64;
+    let mut matched = 0;
+    let mut previous: Option<usize> = None;
+
+    for (i, &c) in text.iter().enumerate() {
+        if matched == query.len() {
+            break;
+        }
+        if c != query[matched] {
+            continue;
+        }
+
+        score += 1;
+        if i == 0 || !text[i - 1].is_alphanumeric() {
+            score += 8;
+        }
+        let gap = match previous {
+            Some(p) => i - p - 1,
+            None => i,
+        };
+        if gap == 0 && previous.is_some() {
+            score += 5;
+        } else {
+            score -= gap.min(5) as i64;
+        }
+
+        previous = Some(i);
+        matched += 1;
+    }
+
+    (matched == query.len()).then_some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_score_tolerates_typos() {
+        assert!(fuzzy_score("radiohd", "Radiohead").is_some());
+        assert!(fuzzy_score("ok computr", "OK Computer").is_some());
+        assert!(fuzzy_score("", "Anything").is_some());
+        assert!(fuzzy_score("xyz", "Radiohead").is_none());
+        assert!(fuzzy_score("daeh", "Radiohead").is_none());
+    }
+
+    #[test]
+    fn test_fuzzy_score_ranks_closer_matches_higher() {
+        let exact = fuzzy_score("kid a", "Kid A").unwrap();
+        let spread = fuzzy_score("kid a", "Kicking Daisies Again").unwrap();
+        assert!(exact > spread);
+
+        let word_start = fuzzy_score("comp", "OK Computer").unwrap();
+        let mid_word = fuzzy_score("comp", "Uncompressed").unwrap();
+        assert!(word_start > mid_word);
+    }
+}
@@ -0,0 +1,57 @@
+//! Shell-style wildcard matching for selection filters
+
+/// Whether `text` matches a glob `pattern`, ignoring case
+///
+/// `*` matches any run of characters (including none) and `?` matches a
+/// single character. Everything else matches literally.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` swallow one more character and retry
+                Some((star, start)) => {
+                    p = star + 1;
+                    t = start + 1;
+                    backtrack = Some((star, start + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*Live*", "Alive in Paris"));
+        assert!(glob_match("*live*", "LIVE at Leeds"));
+        assert!(glob_match("Abbey Road", "abbey road"));
+        assert!(glob_match("Disc ?", "Disc 2"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+
+        assert!(!glob_match("Live*", "Alive"));
+        assert!(!glob_match("Disc ?", "Disc 10"));
+        assert!(!glob_match("a*b", "aXbY"));
+    }
+}
@@ -1,35 +1,236 @@
-//! M3U playlist generation
+//! M3U, M3U8 and PLS playlist generation
+
+use serde::{Deserialize, Serialize};
+
+/// File format playlists are written in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistFormat {
+    /// playlist.m3u (extended M3U)
+    #[default]
+    M3u,
+    /// playlist.m3u8: the same content, with the extension that marks it as UTF-8
+    M3u8,
+    /// playlist.pls (INI-style, for older players)
+    Pls,
+}
+
+impl PlaylistFormat {
+    /// Name of the playlist file written to each playlist folder
+    pub fn file_name(self) -> &'static str {
+        match self {
+            PlaylistFormat::M3u => "playlist.m3u",
+            PlaylistFormat::M3u8 => "playlist.m3u8",
+            PlaylistFormat::Pls => "playlist.pls",
+        }
+    }
+
+    pub fn is_m3u(&self) -> bool {
+        *self == PlaylistFormat::M3u
+    }
+}
+
+/// Byte-level layout of M3U files, for players that are picky about it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum M3uEncoding {
+    /// UTF-8 with LF line endings
+    #[default]
+    Utf8,
+    /// UTF-8 with CRLF line endings
+    Utf8Crlf,
+    /// UTF-8 with a byte order mark and LF line endings
+    Utf8Bom,
+    /// UTF-8 with a byte order mark and CRLF line endings (Windows-style)
+    Windows,
+}
+
+impl M3uEncoding {
+    fn line_ending(self) -> &'static str {
+        match self {
+            M3uEncoding::Utf8 | M3uEncoding::Utf8Bom => "\n",
+            M3uEncoding::Utf8Crlf | M3uEncoding::Windows => "\r\n",
+        }
+    }
+
+    fn has_bom(self) -> bool {
+        matches!(self, M3uEncoding::Utf8Bom | M3uEncoding::Windows)
+    }
+}
+
+/// A playlist entry: the track's file and what players show for it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct M3uTrack {
+    pub filename: String,
+    /// Length in seconds, if the server reported one
+    pub duration: Option<u32>,
+    pub artist: String,
+    pub title: String,
+}
+
+impl M3uTrack {
+    /// "Artist - Title" on one line
+    fn display_name(&self) -> String {
+        format!("{} - {}", self.artist, self.title).replace(['\r', '\n'], " ")
+    }
+
+    /// Length in seconds, `-1` if unknown (as both M3U and PLS expect)
+    fn length(&self) -> i64 {
+        self.duration.map_or(-1, i64::from)
+    }
+
+    /// The `#EXTINF` line
+    fn extinf(&self) -> String {
+        format!("#EXTINF:{},{}", self.length(), self.display_name())
+    }
+}
 
 /// Generate an M3U playlist file content
 ///
 /// Uses relative paths (just filenames) for maximum compatibility
-/// with portable devices like FiiO players.
-pub fn generate_m3u(tracks: &[String]) -> String {
-    let mut content = String::from("#EXTM3U\n");
+/// with portable devices like FiiO players. `extended` adds an `#EXTINF`
+/// line with the duration, artist and title before each track.
+pub fn generate_m3u(tracks: &[M3uTrack], encoding: M3uEncoding, extended: bool) -> Vec<u8> {
+    let newline = encoding.line_ending();
+    let mut content = String::new();
+    if encoding.has_bom() {
+        content.push('\u{feff}');
+    }
+    content.push_str("#EXTM3U");
+    content.push_str(newline);
     for track in tracks {
-        content.push_str(track);
-        content.push('\n');
+        if extended {
+            content.push_str(&track.extinf());
+            content.push_str(newline);
+        }
+        content.push_str(&track.filename);
+        content.push_str(newline);
+    }
+    content.into_bytes()
+}
+
+/// Generate a PLS playlist file content
+///
+/// Like M3U files, entries are filenames relative to the playlist.
+pub fn generate_pls(tracks: &[M3uTrack], encoding: M3uEncoding) -> Vec<u8> {
+    let newline = encoding.line_ending();
+    let mut lines = vec!["[playlist]".to_string()];
+    for (index, track) in tracks.iter().enumerate() {
+        let n = index + 1;
+        lines.push(format!("File{}={}", n, track.filename));
+        lines.push(format!("Title{}={}", n, track.display_name()));
+        lines.push(format!("Length{}={}", n, track.length()));
     }
-    content
+    lines.push(format!("NumberOfEntries={}", tracks.len()));
+    lines.push("Version=2".to_string());
+
+    let mut content = String::new();
+    if encoding.has_bom() {
+        content.push('\u{feff}');
+    }
+    for line in lines {
+        content.push_str(&line);
+        content.push_str(newline);
+    }
+    content.into_bytes()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn tracks() -> Vec<M3uTrack> {
+        vec![
+            M3uTrack {
+                filename: "01 - Track One.flac".to_string(),
+                duration: Some(215),
+                artist: "Artist".to_string(),
+                title: "Track One".to_string(),
+            },
+            M3uTrack {
+                filename: "02 - Träck Two.flac".to_string(),
+                duration: None,
+                artist: "Ärtist".to_string(),
+                title: "Träck\nTwo".to_string(),
+            },
+        ]
+    }
+
     #[test]
     fn test_generate_m3u_empty() {
-        let result = generate_m3u(&[]);
-        assert_eq!(result, "#EXTM3U\n");
+        let result = generate_m3u(&[], M3uEncoding::Utf8, true);
+        assert_eq!(result, b"#EXTM3U\n");
     }
 
     #[test]
     fn test_generate_m3u_tracks() {
-        let tracks = vec![
-            "01 - Track One.flac".to_string(),
-            "02 - Track Two.flac".to_string(),
-        ];
-        let result = generate_m3u(&tracks);
-        assert_eq!(result, "#EXTM3U\n01 - Track One.flac\n02 - Track Two.flac\n");
+        let tracks: Vec<M3uTrack> = ["01 - Track One.flac", "02 - Track Two.flac"]
+            .into_iter()
+            .map(|filename| M3uTrack {
+                filename: filename.to_string(),
+                duration: None,
+                artist: String::new(),
+                title: String::new(),
+            })
+            .collect();
+        let result = generate_m3u(&tracks, M3uEncoding::Utf8, false);
+        assert_eq!(result, b"#EXTM3U\n01 - Track One.flac\n02 - Track Two.flac\n");
+    }
+
+    #[test]
+    fn test_generate_m3u_non_ascii_names() {
+        let result = generate_m3u(&tracks(), M3uEncoding::Utf8, false);
+        assert_eq!(result, "#EXTM3U\n01 - Track One.flac\n02 - Träck Two.flac\n".as_bytes());
+    }
+
+    #[test]
+    fn test_generate_extended_m3u() {
+        let result = generate_m3u(&tracks(), M3uEncoding::Utf8Crlf, true);
+        assert_eq!(
+            result,
+            "#EXTM3U\r\n#EXTINF:215,Artist - Track One\r\n01 - Track One.flac\r\n\
+             #EXTINF:-1,Ärtist - Träck Two\r\n02 - Träck Two.flac\r\n"
+                .as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_generate_pls() {
+        let result = generate_pls(&tracks(), M3uEncoding::Utf8);
+        assert_eq!(
+            String::from_utf8(result).unwrap(),
+            "[playlist]\n\
+             File1=01 - Track One.flac\nTitle1=Artist - Track One\nLength1=215\n\
+             File2=02 - Träck Two.flac\nTitle2=Ärtist - Träck Two\nLength2=-1\n\
+             NumberOfEntries=2\nVersion=2\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_m3u_crlf() {
+        let result = generate_m3u(&tracks(), M3uEncoding::Utf8Crlf, false);
+        assert_eq!(
+            result,
+            "#EXTM3U\r\n01 - Track One.flac\r\n02 - Träck Two.flac\r\n".as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_generate_m3u_bom() {
+        let result = generate_m3u(&tracks(), M3uEncoding::Utf8Bom, false);
+        assert_eq!(&result[..3], [0xEF, 0xBB, 0xBF]);
+        assert_eq!(
+            &result[3..],
+            "#EXTM3U\n01 - Track One.flac\n02 - Träck Two.flac\n".as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_generate_m3u_windows() {
+        let result = generate_m3u(&tracks(), M3uEncoding::Windows, false);
+        assert_eq!(&result[..3], [0xEF, 0xBB, 0xBF]);
+        assert_eq!(
+            &result[3..],
+            "#EXTM3U\r\n01 - Track One.flac\r\n02 - Träck Two.flac\r\n".as_bytes()
+        );
     }
 }
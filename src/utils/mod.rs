@@ -1,10 +1,20 @@
 //! Utility functions
 
 pub mod cover_art;
-mod m3u;
+pub mod csv;
+mod cue;
+pub mod fuzzy;
+pub mod glob;
+pub mod m3u;
+mod notes;
 mod sanitize;
+pub mod signature;
+pub mod size;
+pub mod text;
 pub mod tui_log;
 
-pub use m3u::generate_m3u;
+pub use cue::{generate_cue, Chapter};
+pub use m3u::{generate_m3u, generate_pls};
+pub use notes::notes_to_text;
 pub use sanitize::sanitize_filename;
-pub use tui_log::{set_tui_mode, ConditionalStderrLayer};
+pub use tui_log::{is_tui_mode, set_tui_mode, ConditionalStderrLayer};
@@ -0,0 +1,50 @@
+//! Album notes formatting
+
+/// Convert server-provided album notes into plain text
+///
+/// Servers usually pass Last.fm biographies through verbatim, which contain
+/// HTML links and entities. Tags are dropped and common entities decoded.
+pub fn notes_to_text(notes: &str) -> String {
+    let mut text = String::with_capacity(notes.len());
+    let mut in_tag = false;
+
+    for c in notes.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    text.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strips_links() {
+        let notes = "A great record. <a href=\"https://www.last.fm/music/X\">Read more on Last.fm</a>";
+        assert_eq!(notes_to_text(notes), "A great record. Read more on Last.fm");
+    }
+
+    #[test]
+    fn test_decodes_entities() {
+        assert_eq!(notes_to_text("Rock &amp; Roll &quot;live&quot;"), "Rock & Roll \"live\"");
+    }
+
+    #[test]
+    fn test_blank_notes() {
+        assert_eq!(notes_to_text("  <br/> "), "");
+    }
+}
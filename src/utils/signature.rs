@@ -0,0 +1,92 @@
+//! Cheap file signatures for integrity checks
+//!
+//! A quick signature is the file size plus a CRC32 of its first and last
+//! 64 KiB, so checking a track on a slow SD card reads at most 128 KiB.
+//! A SHA-256 of the whole file backs it up when the quick check can't be
+//! trusted.
+
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Bytes hashed from each end of a file for the quick signature
+const EDGE_BYTES: u64 = 64 * 1024;
+
+/// CRC32 of the first and last `EDGE_BYTES` of `data`
+///
+/// Files up to twice that size are hashed whole.
+pub fn edge_crc32(data: &[u8]) -> u32 {
+    let len = data.len() as u64;
+    let mut hasher = crc32fast::Hasher::new();
+    if len <= EDGE_BYTES * 2 {
+        hasher.update(data);
+    } else {
+        hasher.update(&data[..EDGE_BYTES as usize]);
+        hasher.update(&data[(len - EDGE_BYTES) as usize..]);
+    }
+    hasher.finalize()
+}
+
+/// Size and edge CRC32 of a file, reading only its ends
+pub fn file_edge_crc32(path: &Path) -> io::Result<(u64, u32)> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+
+    let mut hasher = crc32fast::Hasher::new();
+    if len <= EDGE_BYTES * 2 {
+        let mut data = Vec::with_capacity(len as usize);
+        file.read_to_end(&mut data)?;
+        hasher.update(&data);
+    } else {
+        let mut buf = vec![0u8; EDGE_BYTES as usize];
+        file.read_exact(&mut buf)?;
+        hasher.update(&buf);
+        file.seek(SeekFrom::End(-(EDGE_BYTES as i64)))?;
+        file.read_exact(&mut buf)?;
+        hasher.update(&buf);
+    }
+
+    Ok((len, hasher.finalize()))
+}
+
+/// Hex SHA-256 of `data`
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Hex SHA-256 of a whole file
+pub fn file_sha256(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_signature_matches_in_memory() {
+        let path = std::env::temp_dir().join(format!("nutune_signature_test_{}", std::process::id()));
+        for size in [0usize, 1000, 200_000] {
+            let data: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+            std::fs::write(&path, &data).unwrap();
+
+            assert_eq!(file_edge_crc32(&path).unwrap(), (size as u64, edge_crc32(&data)));
+            assert_eq!(file_sha256(&path).unwrap(), sha256_hex(&data));
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_edge_crc32_ignores_middle_of_large_files() {
+        let mut data = vec![7u8; 300_000];
+        let before = edge_crc32(&data);
+        data[150_000] = 8;
+        assert_eq!(edge_crc32(&data), before);
+        data[10] = 8;
+        assert_ne!(edge_crc32(&data), before);
+    }
+}
@@ -0,0 +1,61 @@
+//! Human-readable byte sizes
+
+/// Bytes per second of audio assumed when a track's real size isn't known
+///
+/// Roughly a 1000 kbps FLAC stream, so estimates err on the high side for
+/// lossy libraries rather than promising room that isn't there.
+const ESTIMATED_BYTES_PER_SECOND: u64 = 125_000;
+
+/// Parse a size like `500M`, `4G`, `1.5GB` or a plain byte count
+///
+/// Suffixes are binary (K = 1024 bytes) and case-insensitive.
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let upper = value.to_ascii_uppercase();
+    let number = upper
+        .trim_end_matches('B')
+        .trim_end_matches('I');
+    let (number, multiplier) = match number.chars().last() {
+        Some('K') => (&number[..number.len() - 1], 1u64 << 10),
+        Some('M') => (&number[..number.len() - 1], 1 << 20),
+        Some('G') => (&number[..number.len() - 1], 1 << 30),
+        Some('T') => (&number[..number.len() - 1], 1 << 40),
+        _ => (number, 1),
+    };
+
+    let amount: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid size '{}' (expected e.g. 500M, 4G)", value))?;
+    if amount < 0.0 || !amount.is_finite() {
+        return Err(format!("invalid size '{}'", value));
+    }
+
+    Ok((amount * multiplier as f64) as u64)
+}
+
+/// Estimate the on-device size of audio from its duration
+pub fn estimated_size(duration_secs: u32) -> u64 {
+    duration_secs as u64 * ESTIMATED_BYTES_PER_SECOND
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size_suffixes() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("500M"), Ok(500 << 20));
+        assert_eq!(parse_size("4g"), Ok(4 << 30));
+        assert_eq!(parse_size("1.5GB"), Ok(3 << 29));
+        assert_eq!(parse_size("2GiB"), Ok(2 << 30));
+    }
+
+    #[test]
+    fn test_parse_size_invalid() {
+        assert!(parse_size("lots").is_err());
+        assert!(parse_size("-1G").is_err());
+        assert!(parse_size("").is_err());
+    }
+}
@@ -0,0 +1,76 @@
+//! Terminal text layout helpers
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Lay out a row as a left-aligned name followed by right-aligned columns
+///
+/// Each column is right-aligned within its own width so values line up across
+/// rows. The name is padded or truncated (with an ellipsis) to fill the
+/// remaining width. Widths are measured in terminal cells, not bytes.
+pub fn format_row(name: &str, columns: &[(&str, usize)], width: usize) -> String {
+    let columns_width: usize = columns.iter().map(|(_, w)| w + 1).sum();
+    let name_width = width.saturating_sub(columns_width);
+
+    let mut row = truncate_to_width(name, name_width);
+    row.push_str(&" ".repeat(name_width.saturating_sub(row.width())));
+
+    for (text, column_width) in columns {
+        let text = truncate_to_width(text, *column_width);
+        row.push(' ');
+        row.push_str(&" ".repeat(column_width.saturating_sub(text.width())));
+        row.push_str(&text);
+    }
+
+    row
+}
+
+/// Truncate a string to fit in `width` terminal cells, adding an ellipsis if cut
+pub fn truncate_to_width(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+
+    let mut result = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if used + char_width + 1 > width {
+            break;
+        }
+        result.push(c);
+        used += char_width;
+    }
+    if width > 0 {
+        result.push('…');
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_row_aligns_columns() {
+        let row = format_row("[ ] Short", &[("3 tracks", 10)], 30);
+        assert_eq!(row.width(), 30);
+        assert!(row.starts_with("[ ] Short "));
+        assert!(row.ends_with("  3 tracks"));
+    }
+
+    #[test]
+    fn test_format_row_truncates_long_names() {
+        let row = format_row("[ ] A very long album name indeed", &[("12 tracks", 10)], 25);
+        assert_eq!(row.width(), 25);
+        assert!(row.contains('…'));
+        assert!(row.ends_with(" 12 tracks"));
+    }
+
+    #[test]
+    fn test_truncate_wide_characters() {
+        // Each CJK character is two cells wide
+        let truncated = truncate_to_width("東京事変", 5);
+        assert_eq!(truncated, "東京…");
+        assert!(truncated.width() <= 5);
+    }
+}
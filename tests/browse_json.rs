@@ -0,0 +1,49 @@
+//! `browse --json` output, run through the built binary
+
+#[allow(dead_code)]
+#[path = "../src/subsonic/mock.rs"]
+mod mock;
+
+use mock::{MockReply, mock_api};
+use serde_json::json;
+use std::process::Command;
+
+#[tokio::test]
+async fn test_browse_json_prints_only_json_to_stdout() {
+    let url = mock_api(vec![
+        ("ping", MockReply::ok(json!({}))),
+        (
+            "getArtists",
+            MockReply::ok(json!({ "artists": { "index": [{ "artist": [{ "id": "ar1", "name": "Artist" }] }] } })),
+        ),
+        ("getPlaylists", MockReply::ok(json!({ "playlists": { "playlist": [{ "id": "p1", "name": "Mix" }] } }))),
+    ])
+    .await;
+
+    let dir = std::env::temp_dir().join(format!("nutune_browse_json_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let credentials = dir.join("credentials.env");
+    std::fs::write(&credentials, format!("SUBSONIC_URL={}\nSUBSONIC_USER=user\nSUBSONIC_PASS=pass\n", url)).unwrap();
+
+    let home = dir.clone();
+    let output = tokio::task::spawn_blocking(move || {
+        Command::new(env!("CARGO_BIN_EXE_nutune"))
+            .args(["browse", "--json", "--credentials-file"])
+            .arg(&credentials)
+            .env("NUTUNE_CONFIG_DIR", &home)
+            .env("NUTUNE_CACHE_DIR", &home)
+            .env("RUST_LOG", "nutune=debug")
+            .output()
+            .unwrap()
+    })
+    .await
+    .unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    // Logging, such as verifying the credentials, goes to stderr
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Credentials verified"));
+    let export: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(export["artists"][0]["name"], "Artist");
+    assert_eq!(export["playlists"][0]["name"], "Mix");
+}